@@ -0,0 +1,265 @@
+//! `tiny check`: checks files in parallel, printing what each one found in
+//! the order the files were given, then a summary.
+
+extern crate glob;
+
+use clap::ValueEnum;
+use diagnostic::Diagnostic;
+use serde_json::json;
+use source::Source;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::Instant;
+use watch;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
+/// A file to check, or its name and why it can't be checked.
+pub type Input = Result<String, (String, String)>;
+
+/// What checking one file found, with its diagnostics already printed to
+/// `output`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub output: String,
+    pub forms: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    /// The module files the file imported.
+    pub modules: Vec<PathBuf>,
+}
+
+/// Checks the files `paths` name, and whether none had errors. Watching
+/// checks them again whenever they change, and doesn't return.
+pub fn run(paths: &[String], format: MessageFormat, watch: bool) -> bool {
+    let inputs = discover(paths);
+    if !watch {
+        return check_all(&inputs, format).0;
+    }
+    // Only files can be watched, so the rest is reported once.
+    let mut files = vec![];
+    for input in inputs {
+        match input {
+            Ok(file) => files.push(file),
+            Err((name, message)) => eprintln!("{name}: error: {message}"),
+        }
+    }
+    watch::watch(files, |files| {
+        let inputs: Vec<Input> = files.iter().cloned().map(Ok).collect();
+        check_all(&inputs, format).1
+    })
+}
+
+/// Checks `inputs`, printing what they found and a summary. Returns
+/// whether none had errors, and the module files each one imported.
+fn check_all(inputs: &[Input], format: MessageFormat) -> (bool, Vec<Vec<PathBuf>>) {
+    let started = Instant::now();
+    let mut total = Report::default();
+    let mut modules = vec![];
+    each_report(inputs, format, |report| {
+        match format {
+            MessageFormat::Human => eprint!("{}", report.output),
+            MessageFormat::Json => print!("{}", report.output),
+        }
+        total.forms += report.forms;
+        total.errors += report.errors;
+        total.warnings += report.warnings;
+        modules.push(report.modules);
+    });
+    let seconds = started.elapsed().as_secs_f64();
+    match format {
+        MessageFormat::Human => eprintln!(
+            "checked {} ({}) in {seconds:.2}s: {}, {}",
+            plural(inputs.len(), "file"),
+            plural(total.forms, "form"),
+            plural(total.errors, "error"),
+            plural(total.warnings, "warning"),
+        ),
+        MessageFormat::Json => println!(
+            "{}",
+            json!({
+                "reason": "check-finished",
+                "success": total.errors == 0,
+                "files": inputs.len(),
+                "forms": total.forms,
+                "errors": total.errors,
+                "warnings": total.warnings,
+                "seconds": seconds,
+            })
+        ),
+    }
+    (total.errors == 0, modules)
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {noun}"),
+        _ => format!("{count} {noun}s"),
+    }
+}
+
+/// Checks `inputs` on as many threads as there are cores, calling `f` with
+/// their reports in the order of `inputs`.
+pub fn each_report<F: FnMut(Report)>(inputs: &[Input], format: MessageFormat, mut f: F) {
+    // Stdin can only be read once, so it is read before checking starts.
+    let stdin = inputs
+        .iter()
+        .any(|input| input.as_deref() == Ok("-"))
+        .then(|| Source::load("-"));
+    let stdin = Mutex::new(stdin);
+    let next = AtomicUsize::new(0);
+    let threads = std::thread::available_parallelism().map_or(1, usize::from);
+    let (sender, receiver) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..threads.min(inputs.len()) {
+            let (sender, next, stdin) = (sender.clone(), &next, &stdin);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let source = match inputs.get(i) {
+                    Some(Ok(file)) if file == "-" => stdin.lock().unwrap().take().unwrap(),
+                    Some(Ok(file)) => Source::load(file),
+                    Some(Err(err)) => Err(err.clone()),
+                    None => break,
+                };
+                if sender.send((i, check(source, format))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        // Reports arrive in any order, so later ones wait for earlier ones.
+        let mut waiting = BTreeMap::new();
+        let mut printed = 0;
+        for (i, report) in receiver {
+            waiting.insert(i, report);
+            while let Some(report) = waiting.remove(&printed) {
+                f(report);
+                printed += 1;
+            }
+        }
+    });
+}
+
+/// The files `paths` name, without repeats: `-` and files as they are,
+/// the `.tiny` files under directories, sorted, and the matches of glob
+/// patterns. Missing files are kept so reading them reports the error.
+pub fn discover(paths: &[String]) -> Vec<Input> {
+    let mut inputs = vec![];
+    for path in paths {
+        let is_pattern = path != "-" && path.contains(['*', '?', '[']);
+        if !is_pattern {
+            expand(Path::new(path), &mut inputs);
+            continue;
+        }
+        match glob::glob(path) {
+            Ok(matches) => {
+                let start = inputs.len();
+                for entry in matches {
+                    match entry {
+                        Ok(path) => expand(&path, &mut inputs),
+                        Err(err) => inputs.push(Err((
+                            err.path().display().to_string(),
+                            format!("could not read: {}", err.error()),
+                        ))),
+                    }
+                }
+                if inputs.len() == start {
+                    inputs.push(Err((path.clone(), "no files match".into())));
+                }
+            }
+            Err(err) => inputs.push(Err((path.clone(), format!("invalid pattern: {err}")))),
+        }
+    }
+    let mut seen = HashSet::new();
+    inputs.retain(|input| match input {
+        Ok(file) => seen.insert(file.clone()),
+        Err(_) => true,
+    });
+    inputs
+}
+
+fn expand(path: &Path, inputs: &mut Vec<Input>) {
+    if !path.is_dir() {
+        inputs.push(Ok(path.display().to_string()));
+        return;
+    }
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            let message = format!("could not read: {err}");
+            inputs.push(Err((path.display().to_string(), message)));
+            return;
+        }
+    };
+    let mut paths: Vec<_> = (entries.filter_map(Result::ok))
+        .map(|entry| entry.path())
+        .filter(|path| {
+            !path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() || path.extension().is_some_and(|ext| ext == "tiny") {
+            expand(&path, inputs);
+        }
+    }
+}
+
+fn check(source: Result<Source, (String, String)>, format: MessageFormat) -> Report {
+    let source = match source {
+        Ok(source) => source,
+        Err((name, message)) => {
+            let output = match format {
+                MessageFormat::Human => format!("{name}: error: {message}\n"),
+                MessageFormat::Json => {
+                    format!("{}\n", Diagnostic::io(&name, message).to_json(None))
+                }
+            };
+            return Report {
+                output,
+                errors: 1,
+                ..Report::default()
+            };
+        }
+    };
+    let env = source.check_env();
+    let checked = env.check_str(&source.name, &source.content);
+    let warnings = env.take_warnings();
+    let mut output = String::new();
+    match format {
+        MessageFormat::Human => {
+            for warning in &warnings {
+                output.push_str(&source.describe_warning(warning));
+            }
+            for err in &checked.errors {
+                output.push_str(&source.describe(err));
+            }
+        }
+        MessageFormat::Json => {
+            let warnings = warnings.iter().map(Diagnostic::warning);
+            let errors = checked.errors.iter().map(|err| {
+                let content = source.content_of(&err.name).unwrap_or_default();
+                Diagnostic::error(err, &content)
+            });
+            for diagnostic in warnings.chain(errors) {
+                let content = source.content_of(&diagnostic.name);
+                output.push_str(&format!("{}\n", diagnostic.to_json(content.as_deref())));
+            }
+        }
+    }
+    Report {
+        output,
+        forms: checked.forms,
+        errors: checked.errors.len(),
+        warnings: warnings.len(),
+        modules: env.module_files(),
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use check::{discover, each_report, Input, MessageFormat};
+    use std::path::PathBuf;
+
+    /// A directory with `files`, each holding its own name as a string.
+    fn tree(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tiny-check-{name}-{}", std::process::id()));
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, format!("{file:?}")).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = tree(
+            "discover",
+            &[
+                "b.tiny",
+                "a/z.tiny",
+                "a/y.tiny",
+                "a/notes.txt",
+                ".hidden/x.tiny",
+            ],
+        );
+        let root = dir.display().to_string();
+        let found = |paths: &[String]| -> Vec<Input> {
+            (discover(paths).into_iter())
+                .map(|input| input.map(|file| file.replace(&root, "")))
+                .collect()
+        };
+        assert_eq!(
+            found(std::slice::from_ref(&root)),
+            [
+                Ok("/a/y.tiny".into()),
+                Ok("/a/z.tiny".into()),
+                Ok("/b.tiny".into())
+            ]
+        );
+        assert_eq!(
+            found(&[
+                format!("{root}/b.tiny"),
+                format!("{root}/*/z*"),
+                root.clone()
+            ]),
+            [
+                Ok("/b.tiny".into()),
+                Ok("/a/z.tiny".into()),
+                Ok("/a/y.tiny".into())
+            ]
+        );
+        let missing = format!("{root}/none*.tiny");
+        assert_eq!(
+            found(&["-".into(), missing.clone(), format!("{root}/gone.tiny")]),
+            [
+                Ok("-".into()),
+                Err((missing, "no files match".into())),
+                Ok("/gone.tiny".into())
+            ]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reports_keep_input_order() {
+        let files: Vec<String> = (0..40).map(|i| format!("f{i:02}.tiny")).collect();
+        let dir = tree(
+            "order",
+            &files.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+        let mut inputs: Vec<Input> = (files.iter().rev())
+            .map(|file| Ok(dir.join(file).display().to_string()))
+            .collect();
+        inputs.insert(3, Err(("gone.tiny".into(), "could not read".into())));
+        std::fs::write(dir.join("f20.tiny"), "(f").unwrap();
+        let mut reports = vec![];
+        each_report(&inputs, MessageFormat::Human, |report| reports.push(report));
+        assert_eq!(reports.len(), 41);
+        assert_eq!(reports[3].output, "gone.tiny: error: could not read\n");
+        let errors: Vec<usize> = reports.iter().map(|report| report.errors).collect();
+        let mut expected = vec![0; 41];
+        expected[3] = 1;
+        expected[20] = 1;
+        assert_eq!(errors, expected);
+        assert_eq!(reports.iter().map(|report| report.forms).sum::<usize>(), 39);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
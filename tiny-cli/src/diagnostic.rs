@@ -0,0 +1,173 @@
+//! Errors and warnings as diagnostics with a code and possibly a fix, and
+//! their JSON form for `tiny check --message-format=json`.
+
+use serde_json::{json, Value as Json};
+use tiny_library::eval::{ErrorKind, EvalError, Warning};
+use tiny_library::reader::{line_col, ReadErrorKind};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A replacement of the bytes `start..end` that would resolve a diagnostic.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fix {
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+impl Diagnostic {
+    /// The diagnostic of `err`, where `content` is the source of the file
+    /// it is in.
+    pub fn error(err: &EvalError, content: &str) -> Diagnostic {
+        Diagnostic {
+            name: err.name.clone(),
+            start: err.start,
+            end: err.end,
+            severity: Severity::Error,
+            code: code(err.kind),
+            message: err.message.clone(),
+            fix: fix(err, content),
+        }
+    }
+
+    pub fn warning(warning: &Warning) -> Diagnostic {
+        Diagnostic {
+            name: warning.name.clone(),
+            start: warning.start,
+            end: warning.end,
+            severity: Severity::Warning,
+            code: code(warning.kind),
+            message: warning.message.clone(),
+            fix: None,
+        }
+    }
+
+    /// The diagnostic of a file that couldn't be read.
+    pub fn io(name: &str, message: String) -> Diagnostic {
+        Diagnostic {
+            name: name.into(),
+            start: 0,
+            end: 0,
+            severity: Severity::Error,
+            code: "io",
+            message,
+            fix: None,
+        }
+    }
+
+    /// The diagnostic as one JSON object, in the spirit of cargo's
+    /// `--message-format=json`. `content` is the source of the file, for
+    /// line and column numbers: 1-based, with columns counted in
+    /// characters. Without it there is no span.
+    pub fn to_json(&self, content: Option<&str>) -> Json {
+        let span = |start: usize, end: usize| match content {
+            Some(content) => {
+                let (line_start, column_start) = line_col(content, start);
+                let (line_end, column_end) = line_col(content, end);
+                json!({
+                    "byte_start": start,
+                    "byte_end": end,
+                    "line_start": line_start,
+                    "column_start": column_start,
+                    "line_end": line_end,
+                    "column_end": column_end,
+                })
+            }
+            None => Json::Null,
+        };
+        json!({
+            "reason": "diagnostic",
+            "file": self.name,
+            "severity": match self.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            "code": self.code,
+            "message": self.message,
+            "span": span(self.start, self.end),
+            "fix": self.fix.as_ref().map(|fix| json!({
+                "message": fix.message,
+                "span": span(fix.start, fix.end),
+                "replacement": fix.replacement,
+            })),
+        })
+    }
+}
+
+/// A stable name for the kind of problem.
+fn code(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Read(kind) => match kind {
+            ReadErrorKind::Unclosed { .. } => "unclosed-delimiter",
+            ReadErrorKind::UnterminatedString => "unterminated-string",
+            ReadErrorKind::InvalidEscape => "invalid-escape",
+            ReadErrorKind::InvalidRegex => "invalid-regex",
+            ReadErrorKind::InvalidNumber => "invalid-number",
+            ReadErrorKind::MissingForm { .. } => "missing-form",
+            ReadErrorKind::UnexpectedChar => "unexpected-char",
+        },
+        ErrorKind::UnresolvedSymbol => "unresolved-symbol",
+        ErrorKind::UnresolvedModule => "unresolved-module",
+        ErrorKind::UnresolvedNamespace => "unresolved-namespace",
+        ErrorKind::ImportCycle => "import-cycle",
+        ErrorKind::NonExhaustiveMatch => "non-exhaustive-match",
+        ErrorKind::Other => "invalid-form",
+    }
+}
+
+fn fix(err: &EvalError, content: &str) -> Option<Fix> {
+    let insert_at_end = |message: String, text: &str| Fix {
+        message,
+        start: content.len(),
+        end: content.len(),
+        replacement: text.into(),
+    };
+    let delete = |message: String, len: usize| Fix {
+        message,
+        start: err.start,
+        end: err.start + len,
+        replacement: "".into(),
+    };
+    let kind = match err.kind {
+        ErrorKind::Read(kind) => kind,
+        _ => return None,
+    };
+    match kind {
+        ReadErrorKind::Unclosed { close } => Some(insert_at_end(
+            format!("insert `{close}`"),
+            &close.to_string(),
+        )),
+        ReadErrorKind::UnterminatedString => Some(insert_at_end("insert `\"`".into(), "\"")),
+        ReadErrorKind::InvalidEscape => Some(Fix {
+            message: "escape the backslash".into(),
+            start: err.start,
+            end: err.start + 1,
+            replacement: "\\\\".into(),
+        }),
+        ReadErrorKind::MissingForm { len } => {
+            let prefix = content.get(err.start..err.start + len)?;
+            Some(delete(format!("remove `{prefix}`"), len))
+        }
+        ReadErrorKind::UnexpectedChar => {
+            let ch = content.get(err.start..)?.chars().next()?;
+            matches!(ch, ')' | ']' | '}').then(|| delete(format!("remove `{ch}`"), ch.len_utf8()))
+        }
+        ReadErrorKind::InvalidRegex | ReadErrorKind::InvalidNumber => None,
+    }
+}
@@ -0,0 +1,98 @@
+#[cfg(test)]
+mod tests {
+    use diagnostic::{Diagnostic, Fix};
+    use serde_json::json;
+    use tiny_library::eval::Env;
+
+    /// The diagnostics of checking `content`.
+    fn check(content: &str) -> Vec<Diagnostic> {
+        (Env::new().check_str("_test_.tiny", content).errors.iter())
+            .map(|err| Diagnostic::error(err, content))
+            .collect()
+    }
+
+    /// `content` with the fix of its first diagnostic applied.
+    fn fixed(content: &str) -> String {
+        let Fix {
+            start,
+            end,
+            replacement,
+            ..
+        } = check(content).remove(0).fix.unwrap();
+        format!("{}{replacement}{}", &content[..start], &content[end..])
+    }
+
+    #[test]
+    fn test_codes() {
+        let codes = |content: &str| -> Vec<&str> {
+            check(content)
+                .iter()
+                .map(|diagnostic| diagnostic.code)
+                .collect()
+        };
+        assert_eq!(codes("(f"), ["unclosed-delimiter"]);
+        assert_eq!(codes("\"a"), ["unterminated-string"]);
+        assert_eq!(codes("\"\\q\""), ["invalid-escape"]);
+        assert_eq!(codes("1/0"), ["invalid-number"]);
+        assert_eq!(codes("'"), ["missing-form"]);
+        assert_eq!(codes(")"), ["unexpected-char"]);
+        assert_eq!(codes("(g)"), ["unresolved-symbol"]);
+        assert_eq!(codes("(h/g)"), ["unresolved-namespace"]);
+        assert_eq!(codes("(let [x] x)"), ["invalid-form"]);
+        // Codes come from the kind of error, not from what its message says.
+        assert_eq!(
+            codes("(defmacro m [] (throw \"unable to resolve symbol: x\")) (m)"),
+            ["invalid-form"]
+        );
+    }
+
+    #[test]
+    fn test_fixes() {
+        assert_eq!(fixed("(f [1 2\n"), "(f [1 2\n]");
+        assert_eq!(fixed("{:a (inc 1)"), "{:a (inc 1)}");
+        assert_eq!(fixed("(str \"a"), "(str \"a\"");
+        assert_eq!(fixed("\"\\q\""), "\"\\\\q\"");
+        assert_eq!(fixed("(+ 1 2))"), "(+ 1 2)");
+        assert_eq!(fixed("[1 ~@"), "[1 ");
+        assert_eq!(check("(g)")[0].fix, None);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let content = "(def x 1)\n(f \"é\" [1\n";
+        assert_eq!(
+            check(content)[0].to_json(Some(content)),
+            json!({
+                "reason": "diagnostic",
+                "file": "_test_.tiny",
+                "severity": "error",
+                "code": "unclosed-delimiter",
+                "message": "unclosed `[`",
+                "span": {
+                    "byte_start": 18,
+                    "byte_end": 21,
+                    "line_start": 2,
+                    "column_start": 8,
+                    "line_end": 3,
+                    "column_end": 1,
+                },
+                "fix": {
+                    "message": "insert `]`",
+                    "span": {
+                        "byte_start": 21,
+                        "byte_end": 21,
+                        "line_start": 3,
+                        "column_start": 1,
+                        "line_end": 3,
+                        "column_end": 1,
+                    },
+                    "replacement": "]",
+                },
+            })
+        );
+        assert_eq!(
+            Diagnostic::io("gone.tiny", "could not read".into()).to_json(None)["span"],
+            json!(null)
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Line editing for `tiny repl`: completion, highlighting, bracket matching
+//! and continuing unbalanced input on the next line.
+
+use repl;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::path::Path;
+use tiny_library::eval::{Env, SPECIAL_FORMS};
+use tiny_library::reader::{tokens, Token, TokenKind};
+
+const RESET: &str = "\x1b[0m";
+const MATCHING_BRACKET: &str = "\x1b[1;34m";
+
+pub struct TinyHelper {
+    env: Env,
+    /// The keywords typed so far, to complete.
+    keywords: RefCell<BTreeSet<String>>,
+    /// The bracket at or before the cursor, to show its match.
+    bracket: Cell<Option<usize>>,
+}
+
+impl TinyHelper {
+    pub fn new(env: Env) -> TinyHelper {
+        TinyHelper {
+            env,
+            keywords: RefCell::new(BTreeSet::new()),
+            bracket: Cell::new(None),
+        }
+    }
+
+    /// Remembers the keywords of `input` for completion.
+    pub fn remember(&self, input: &str) {
+        let mut keywords = self.keywords.borrow_mut();
+        for token in tokens(input) {
+            if token.kind == TokenKind::Keyword {
+                keywords.insert(input[token.start..token.end].into());
+            }
+        }
+    }
+
+    fn symbols(&self, prefix: &str) -> Vec<Pair> {
+        let mut names = self.env.visible_names();
+        names.extend(SPECIAL_FORMS.iter().map(|name| name.to_string()));
+        names.sort();
+        names.dedup();
+        candidates(names.into_iter().filter(|name| name.starts_with(prefix)))
+    }
+
+    fn keywords(&self, prefix: &str) -> Vec<Pair> {
+        let keywords = self.keywords.borrow();
+        candidates(
+            (keywords.iter())
+                .filter(|keyword| keyword.starts_with(prefix))
+                .cloned(),
+        )
+    }
+}
+
+fn candidates<I: Iterator<Item = String>>(names: I) -> Vec<Pair> {
+    names
+        .map(|name| Pair {
+            display: name.clone(),
+            replacement: name,
+        })
+        .collect()
+}
+
+/// The files and directories whose path starts with `text`, directories
+/// with a trailing `/`. Hidden ones are left out unless `text` names them.
+pub fn complete_path(text: &str) -> Vec<Pair> {
+    let (dir, prefix) = match text.rfind('/') {
+        Some(i) => (&text[..=i], &text[i + 1..]),
+        None => ("", text),
+    };
+    let entries = match std::fs::read_dir(if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    }) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut pairs: Vec<Pair> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(Pair {
+                display: format!("{name}{slash}"),
+                replacement: format!("{dir}{name}{slash}"),
+            })
+        })
+        .collect();
+    pairs.sort_by(|a, b| a.display.cmp(&b.display));
+    pairs
+}
+
+impl Completer for TinyHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let token = tokens(&line[..pos]).pop().filter(|token| token.end == pos);
+        Ok(match token {
+            Some(Token {
+                kind: TokenKind::String,
+                start,
+                end,
+            }) if end - start == 1 || !line[..end].ends_with('"') => {
+                (start + 1, complete_path(&line[start + 1..end]))
+            }
+            Some(Token {
+                kind: TokenKind::Keyword,
+                start,
+                end,
+            }) => (start, self.keywords(&line[start..end])),
+            Some(Token {
+                kind: TokenKind::Symbol,
+                start,
+                end,
+            }) => (start, self.symbols(&line[start..end])),
+            Some(_) => (pos, vec![]),
+            None => (pos, self.symbols("")),
+        })
+    }
+}
+
+impl Hinter for TinyHelper {
+    type Hint = String;
+}
+
+/// The color of a token, if it has one.
+fn color(line: &str, token: &Token) -> Option<&'static str> {
+    match token.kind {
+        TokenKind::Comment => Some("\x1b[90m"),
+        TokenKind::String | TokenKind::Regex => Some("\x1b[32m"),
+        TokenKind::Number => Some("\x1b[36m"),
+        TokenKind::Keyword => Some("\x1b[35m"),
+        TokenKind::Prefix => Some("\x1b[33m"),
+        TokenKind::Error => Some("\x1b[31m"),
+        TokenKind::Symbol => match &line[token.start..token.end] {
+            "nil" | "true" | "false" => Some("\x1b[36m"),
+            name if SPECIAL_FORMS.contains(&name) => Some("\x1b[33m"),
+            _ => None,
+        },
+        TokenKind::Open | TokenKind::Close => None,
+    }
+}
+
+/// The position of the bracket matching the one at `pos`.
+pub fn matching_bracket(tokens: &[Token], pos: usize) -> Option<usize> {
+    let mut open = vec![];
+    let mut pairs = vec![];
+    for token in tokens {
+        match token.kind {
+            TokenKind::Open => open.push(token.start),
+            TokenKind::Close => {
+                if let Some(start) = open.pop() {
+                    pairs.push((start, token.start));
+                }
+            }
+            _ => {}
+        }
+    }
+    (pairs.into_iter()).find_map(|(open, close)| match pos {
+        _ if pos == open => Some(close),
+        _ if pos == close => Some(open),
+        _ => None,
+    })
+}
+
+/// The bracket the cursor at `pos` is on, or else just after.
+fn bracket_at(tokens: &[Token], pos: usize) -> Option<usize> {
+    let is_bracket = |token: &&Token| matches!(token.kind, TokenKind::Open | TokenKind::Close);
+    let at = |pos| {
+        (tokens.iter().filter(is_bracket))
+            .find(|token| token.start == pos)
+            .map(|token| token.start)
+    };
+    at(pos).or_else(|| pos.checked_sub(1).and_then(at))
+}
+
+impl Highlighter for TinyHelper {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        let tokens = tokens(line);
+        let matching = (self.bracket.get()).and_then(|bracket| matching_bracket(&tokens, bracket));
+        let mut highlighted = String::with_capacity(line.len() * 2);
+        let mut last = 0;
+        for token in &tokens {
+            highlighted.push_str(&line[last..token.start]);
+            let text = &line[token.start..token.end];
+            let color = match matching {
+                Some(pos) if pos == token.start => Some(MATCHING_BRACKET),
+                _ => color(line, token),
+            };
+            match color {
+                Some(color) => highlighted.push_str(&format!("{color}{text}{RESET}")),
+                None => highlighted.push_str(text),
+            }
+            last = token.end;
+        }
+        highlighted.push_str(&line[last..]);
+        Cow::Owned(highlighted)
+    }
+
+    fn highlight_char(&self, line: &str, pos: usize, forced: bool) -> bool {
+        // A forced redraw is for accepting the line, which shows no match.
+        self.bracket.set(if forced {
+            None
+        } else {
+            bracket_at(&tokens(line), pos)
+        });
+        // Any edit can change the colors, so always redraw.
+        true
+    }
+}
+
+impl Validator for TinyHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(if repl::is_complete(ctx.input()) {
+            ValidationResult::Valid(None)
+        } else {
+            ValidationResult::Incomplete
+        })
+    }
+}
+
+impl Helper for TinyHelper {}
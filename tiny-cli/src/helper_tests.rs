@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod tests {
+    use helper::{complete_path, matching_bracket, TinyHelper};
+    use rustyline::completion::Completer;
+    use rustyline::highlight::Highlighter;
+    use rustyline::history::DefaultHistory;
+    use rustyline::Context;
+    use tiny_library::eval::Env;
+    use tiny_library::reader::tokens;
+
+    /// The start and replacements of completing `line` at its end.
+    fn complete(helper: &TinyHelper, line: &str) -> (usize, Vec<String>) {
+        let history = DefaultHistory::new();
+        let (start, pairs) = helper
+            .complete(line, line.len(), &Context::new(&history))
+            .unwrap();
+        (
+            start,
+            pairs.into_iter().map(|pair| pair.replacement).collect(),
+        )
+    }
+
+    #[test]
+    fn test_complete_symbols() {
+        let env = Env::new();
+        env.eval_str(
+            "_test_.tiny",
+            "(import [std.string :as s]) (def my-value 1)",
+        )
+        .unwrap();
+        let helper = TinyHelper::new(env);
+        assert_eq!(complete(&helper, "(my-v"), (1, vec!["my-value".into()]));
+        assert_eq!(
+            complete(&helper, "(s/tri"),
+            (
+                1,
+                vec![
+                    "s/trim".into(),
+                    "s/trim-newline".into(),
+                    "s/triml".into(),
+                    "s/trimr".into()
+                ]
+            )
+        );
+        assert_eq!(complete(&helper, "(defm"), (1, vec!["defmacro".into()]));
+        assert!(complete(&helper, "(ma").1.contains(&"map".to_string()));
+        assert_eq!(complete(&helper, "(+ 12").1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_complete_keywords() {
+        let helper = TinyHelper::new(Env::new());
+        helper.remember("{:name \"a\" :age 1 :other 2} ; :comment");
+        assert_eq!(complete(&helper, "(:a"), (1, vec![":age".into()]));
+        assert_eq!(complete(&helper, ":c").1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_complete_paths() {
+        let dir = std::env::temp_dir().join(format!("tiny-complete-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("one.tiny"), "").unwrap();
+        std::fs::write(dir.join("other.txt"), "").unwrap();
+        std::fs::write(dir.join(".hidden"), "").unwrap();
+        let dir = dir.display().to_string();
+        let names = |text: &str| -> Vec<String> {
+            complete_path(text)
+                .into_iter()
+                .map(|pair| pair.display)
+                .collect()
+        };
+        assert_eq!(names(&format!("{dir}/")), ["one.tiny", "other.txt", "sub/"]);
+        assert_eq!(names(&format!("{dir}/o")), ["one.tiny", "other.txt"]);
+        assert_eq!(names(&format!("{dir}/.")), [".hidden"]);
+        let helper = TinyHelper::new(Env::new());
+        let line = format!("(slurp \"{dir}/su");
+        assert_eq!(complete(&helper, &line), (8, vec![format!("{dir}/sub/")]));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let line = "(let [s \"(]\"] {:a (f s)})";
+        let line_tokens = tokens(line);
+        assert_eq!(matching_bracket(&line_tokens, 0), Some(line.len() - 1));
+        assert_eq!(matching_bracket(&line_tokens, 5), Some(12));
+        assert_eq!(matching_bracket(&line_tokens, 12), Some(5));
+        assert_eq!(matching_bracket(&line_tokens, 8), None);
+        assert_eq!(matching_bracket(&tokens("(a [b"), 0), None);
+    }
+
+    #[test]
+    fn test_highlight() {
+        let helper = TinyHelper::new(Env::new());
+        assert_eq!(
+            helper.highlight("(def x :k) ; hi", 0),
+            "(\x1b[33mdef\x1b[0m x \x1b[35m:k\x1b[0m) \x1b[90m; hi\x1b[0m"
+        );
+        helper.highlight_char("(f \"s\" 1)", 0, false);
+        assert_eq!(
+            helper.highlight("(f \"s\" 1)", 0),
+            "(f \x1b[32m\"s\"\x1b[0m \x1b[36m1\x1b[0m\x1b[1;34m)\x1b[0m"
+        );
+    }
+}
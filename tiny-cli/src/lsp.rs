@@ -0,0 +1,765 @@
+//! `tiny lsp`: a language server for editors, speaking the Language Server
+//! Protocol over stdin and stdout. Open documents are checked as they
+//! change, the way `tiny check` checks files.
+
+use diagnostic::{Diagnostic, Severity};
+use serde_json::{json, Value as Json};
+use source::check_env;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use tiny_library::core::PRELUDE;
+use tiny_library::eval::{Env, CORE, SPECIAL_FORMS};
+use tiny_library::fmt;
+use tiny_library::reader::{tokens, Reader, Span, Token, TokenKind};
+use tiny_library::value::Value;
+
+/// The semantic token types, in the order of their indexes in the legend.
+pub const TOKEN_TYPES: &[&str] = &[
+    "comment",
+    "string",
+    "regexp",
+    "number",
+    "keyword",
+    "enumMember",
+    "macro",
+    "function",
+    "operator",
+];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// The code and message of a request that failed.
+type Error = (i64, String);
+
+/// Serves stdin and stdout until the client says to exit.
+pub fn run() -> bool {
+    let stdin = io::stdin();
+    serve(stdin.lock(), io::stdout())
+}
+
+/// Answers the messages read from `input` until `exit`, and whether
+/// `shutdown` came before it, as it should.
+pub fn serve<R: BufRead, W: Write>(mut input: R, output: W) -> bool {
+    let mut server = Server {
+        output,
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+    loop {
+        let body = match read_message(&mut input) {
+            Ok(Some(body)) => body,
+            Ok(None) => return false,
+            Err(err) => {
+                eprintln!("error: could not read message: {err}");
+                return false;
+            }
+        };
+        let message: Json = match serde_json::from_str(&body) {
+            Ok(message) => message,
+            Err(err) => {
+                server.respond(Json::Null, Err((PARSE_ERROR, err.to_string())));
+                continue;
+            }
+        };
+        // Responses to requests the server never makes are ignored.
+        let method = match message["method"].as_str() {
+            Some(method) => method,
+            None => continue,
+        };
+        let params = &message["params"];
+        match message.get("id") {
+            Some(id) => {
+                let result = server.request(method, params);
+                server.respond(id.clone(), result);
+            }
+            None if method == "exit" => return server.shutdown,
+            None => server.notification(method, params),
+        }
+    }
+}
+
+/// Reads the body of one message, framed by a `Content-Length` header.
+/// `None` means the input ended.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match line.trim_end().split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("content-length") => {
+                length = value.trim().parse().ok();
+            }
+            None if line.trim_end().is_empty() && length.is_some() => break,
+            _ => {}
+        }
+    }
+    let mut body = vec![0; length.unwrap_or_default()];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+pub fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+struct Server<W> {
+    output: W,
+    /// The open documents by URI.
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, message: Json) {
+        if let Err(err) = write_message(&mut self.output, &message) {
+            eprintln!("error: could not write message: {err}");
+        }
+    }
+
+    fn respond(&mut self, id: Json, result: Result<Json, Error>) {
+        self.send(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": code, "message": message},
+            }),
+        });
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, Error> {
+        if self.shutdown {
+            return Err((INVALID_REQUEST, "the server is shutting down".into()));
+        }
+        match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/documentSymbol" => Ok(self.document(params)?.symbols()),
+            "textDocument/definition" => {
+                let (document, offset) = self.position(params)?;
+                Ok(document.definition(offset))
+            }
+            "textDocument/hover" => {
+                let (document, offset) = self.position(params)?;
+                Ok(document.hover(offset))
+            }
+            "textDocument/completion" => {
+                let (document, offset) = self.position(params)?;
+                Ok(document.completion(offset))
+            }
+            "textDocument/formatting" => Ok(self.document(params)?.formatting()),
+            "textDocument/semanticTokens/full" => Ok(self.document(params)?.semantic_tokens()),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method: {method}"))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // Documents are synced whole, so the last change is all of it.
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.publish(uri, vec![]);
+                return;
+            }
+            _ => return,
+        };
+        if let Some(text) = text {
+            let (document, diagnostics) = Document::check(uri, text.into());
+            self.documents.insert(uri.into(), document);
+            self.publish(uri, diagnostics);
+        }
+    }
+
+    fn publish(&mut self, uri: &str, diagnostics: Vec<Json>) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": diagnostics},
+        }));
+    }
+
+    fn document(&self, params: &Json) -> Result<&Document, Error> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        self.documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("unknown document: {uri}")))
+    }
+
+    /// The document and the offset of the position of a request.
+    fn position(&self, params: &Json) -> Result<(&Document, usize), Error> {
+        let document = self.document(params)?;
+        let position = &params["position"];
+        let (line, character) = match (position["line"].as_u64(), position["character"].as_u64()) {
+            (Some(line), Some(character)) => (line as usize, character as usize),
+            _ => return Err((INVALID_PARAMS, "expected a position".into())),
+        };
+        Ok((document, Lines::new(&document.text).offset(line, character)))
+    }
+}
+
+fn capabilities() -> Json {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "documentSymbolProvider": true,
+            "definitionProvider": true,
+            "hoverProvider": true,
+            "completionProvider": {"triggerCharacters": ["/"]},
+            "documentFormattingProvider": true,
+            "semanticTokensProvider": {
+                "legend": {"tokenTypes": TOKEN_TYPES, "tokenModifiers": []},
+                "full": true,
+            },
+        },
+        "serverInfo": {"name": "tiny", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+struct Document {
+    uri: String,
+    /// The path of the document, or its URI when it isn't a file.
+    name: String,
+    text: String,
+    /// The environment the document was checked in, which knows what it
+    /// defines and imports.
+    env: Env,
+}
+
+/// Where a name is defined, and the source it is defined in.
+pub struct Found {
+    /// `None` for the names tiny itself defines.
+    pub uri: Option<String>,
+    pub content: String,
+    pub definition: Definition,
+}
+
+impl Document {
+    /// Checks `text`, returning the document and its diagnostics.
+    fn check(uri: &str, text: String) -> (Document, Vec<Json>) {
+        let path = uri_path(uri);
+        let name = path
+            .as_ref()
+            .map_or(uri.to_string(), |path| path.display().to_string());
+        // What the modules it imports print would corrupt the protocol on
+        // stdout.
+        let env = check_env(path.as_deref().and_then(Path::parent));
+        let checked = env.check_str(&name, &text);
+        let warnings = env.take_warnings();
+        let document = Document {
+            uri: uri.into(),
+            name,
+            text,
+            env,
+        };
+        let lines = Lines::new(&document.text);
+        let diagnostics = (warnings.iter().map(Diagnostic::warning))
+            .chain((checked.errors.iter()).map(|err| Diagnostic::error(err, &document.text)))
+            .map(|diagnostic| document.diagnostic(&diagnostic, &lines))
+            .collect();
+        (document, diagnostics)
+    }
+
+    /// `diagnostic` as LSP has it. Ones in modules the document imports are
+    /// put at its start.
+    fn diagnostic(&self, diagnostic: &Diagnostic, lines: &Lines) -> Json {
+        let (range, message) = if diagnostic.name == self.name {
+            let range = lines.range(diagnostic.start, diagnostic.end);
+            (range, diagnostic.message.clone())
+        } else {
+            let message = format!("in {}: {}", diagnostic.name, diagnostic.message);
+            (lines.range(0, 0), message)
+        };
+        json!({
+            "range": range,
+            "severity": match diagnostic.severity {
+                Severity::Error => 1,
+                Severity::Warning => 2,
+            },
+            "code": diagnostic.code,
+            "source": "tiny",
+            "message": message,
+        })
+    }
+
+    fn symbols(&self) -> Json {
+        let lines = Lines::new(&self.text);
+        let symbol = |definition: &Definition| definition.symbol(&lines);
+        Json::Array(definitions(&self.text).iter().map(symbol).collect())
+    }
+
+    fn definition(&self, offset: usize) -> Json {
+        let found = self
+            .symbol_at(offset)
+            .and_then(|token| self.find(&self.text[token.start..token.end]));
+        match found {
+            Some(Found {
+                uri: Some(uri),
+                content,
+                definition,
+            }) => json!({
+                "uri": uri,
+                "range": Lines::new(&content).range(definition.name_start, definition.name_end),
+            }),
+            _ => Json::Null,
+        }
+    }
+
+    fn hover(&self, offset: usize) -> Json {
+        let token = match self.symbol_at(offset) {
+            Some(token) => token,
+            None => return Json::Null,
+        };
+        let symbol = &self.text[token.start..token.end];
+        let found = self.find(symbol);
+        let defined = self.env.lookup(symbol).is_some() || self.env.lookup_macro(symbol).is_some();
+        if !defined && found.is_none() {
+            return Json::Null;
+        }
+        let name = (self.env.resolve(symbol)).unwrap_or_else(|_| symbol.to_string());
+        let definition = found.map(|found| found.definition);
+        let signatures = match definition.as_ref().map(|def| &def.params[..]) {
+            Some(params) if !params.is_empty() => (params.iter())
+                .map(|params| format!("({name} {params})"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => name,
+        };
+        let mut value = format!("```tiny\n{signatures}\n```");
+        if let Some(doc) = (self.env.doc(symbol)).or_else(|| definition.and_then(|def| def.doc)) {
+            value.push_str(&format!("\n\n{doc}"));
+        }
+        json!({
+            "contents": {"kind": "markdown", "value": value},
+            "range": Lines::new(&self.text).range(token.start, token.end),
+        })
+    }
+
+    /// The names visible in the document that start with the part of the
+    /// symbol before `offset`.
+    fn completion(&self, offset: usize) -> Json {
+        let prefix = match self.symbol_at(offset) {
+            Some(token) => &self.text[token.start..offset],
+            None => "",
+        };
+        let items: Vec<Json> = (completions(&self.env, prefix).into_iter())
+            .map(|name| self.completion_item(name))
+            .collect();
+        json!({"isIncomplete": false, "items": items})
+    }
+
+    fn completion_item(&self, name: String) -> Json {
+        let detail = kind_of(&self.env, &name);
+        let kind = match detail {
+            "special form" => 14,
+            "macro" | "function" => 3,
+            _ => 6,
+        };
+        let mut item = json!({"label": name, "kind": kind, "detail": detail});
+        if let Some(doc) = self.env.doc(&name) {
+            item["documentation"] = json!(doc);
+        }
+        item
+    }
+
+    /// An edit replacing the document with it formatted, if that changes
+    /// it.
+    fn formatting(&self) -> Json {
+        match fmt::format(&self.name, &self.text) {
+            Ok(formatted) if formatted == self.text => json!([]),
+            Ok(formatted) => json!([{
+                "range": Lines::new(&self.text).range(0, self.text.len()),
+                "newText": formatted,
+            }]),
+            // Its diagnostics already say why it can't be read.
+            Err(_) => Json::Null,
+        }
+    }
+
+    /// The tokens of the document, each as its line and start relative to
+    /// the previous one, its length and its type.
+    fn semantic_tokens(&self) -> Json {
+        let lines = Lines::new(&self.text);
+        let mut data = vec![];
+        let (mut prev_line, mut prev_start) = (0, 0);
+        for token in tokens(&self.text) {
+            let token_type = match self.token_type(&token) {
+                Some(token_type) => token_type,
+                None => continue,
+            };
+            // Tokens can't span lines, so multiline strings are split.
+            let mut start = token.start;
+            for part in self.text[token.start..token.end].split('\n') {
+                let (line, character) = lines.position(start);
+                let length = utf16_len(part.trim_end_matches('\r'));
+                if length > 0 {
+                    let delta = if line == prev_line {
+                        character - prev_start
+                    } else {
+                        character
+                    };
+                    data.extend([line - prev_line, delta, length, token_type, 0]);
+                    (prev_line, prev_start) = (line, character);
+                }
+                start += part.len() + 1;
+            }
+        }
+        json!({"data": data})
+    }
+
+    fn token_type(&self, token: &Token) -> Option<usize> {
+        let text = &self.text[token.start..token.end];
+        let token_type = match token.kind {
+            TokenKind::Comment => "comment",
+            TokenKind::String => "string",
+            TokenKind::Regex => "regexp",
+            TokenKind::Number => "number",
+            TokenKind::Keyword => "enumMember",
+            TokenKind::Prefix => "operator",
+            TokenKind::Symbol
+                if SPECIAL_FORMS.contains(&text) || matches!(text, "nil" | "true" | "false") =>
+            {
+                "keyword"
+            }
+            TokenKind::Symbol if self.env.lookup_macro(text).is_some() => "macro",
+            TokenKind::Symbol if matches!(self.env.lookup(text), Some(Value::Fn(_))) => "function",
+            _ => return None,
+        };
+        TOKEN_TYPES.iter().position(|&name| name == token_type)
+    }
+
+    /// The symbol at `offset`, or ending there as it does while typing it.
+    fn symbol_at(&self, offset: usize) -> Option<Token> {
+        tokens(&self.text).into_iter().find(|token| {
+            token.kind == TokenKind::Symbol && token.start <= offset && offset <= token.end
+        })
+    }
+
+    fn find(&self, symbol: &str) -> Option<Found> {
+        locate(&self.env, symbol, Some((&self.uri, &self.text)))
+    }
+}
+
+/// The definition of what `symbol` refers to in `env`: in a module it
+/// imports, in tiny's prelude, or in `own`, the URI and text of the source
+/// of its namespace, if it has one.
+pub fn locate(env: &Env, symbol: &str, own: Option<(&str, &str)>) -> Option<Found> {
+    let key = env.resolve(symbol).ok();
+    let (ns, name) = match key.as_deref().and_then(|key| key.split_once('/')) {
+        Some((ns, name)) => (ns.to_string(), name.to_string()),
+        // It may be defined after an error stopped checking.
+        None => (env.ns().to_string(), symbol.to_string()),
+    };
+    let (uri, content) = if ns == env.ns() {
+        let (uri, text) = own?;
+        (Some(uri.to_string()), text.to_string())
+    } else if ns == CORE {
+        (None, PRELUDE.to_string())
+    } else {
+        let path = env.module_path(&ns)?;
+        let content = std::fs::read_to_string(&path).ok()?;
+        (Some(path_uri(&path)), content)
+    };
+    let definition = find(definitions(&content), &name)?;
+    Some(Found {
+        uri,
+        content,
+        definition,
+    })
+}
+
+/// The names visible in `env` that start with `prefix`, in order.
+pub fn completions(env: &Env, prefix: &str) -> Vec<String> {
+    let mut names: BTreeSet<String> = env.visible_names().into_iter().collect();
+    names.extend(SPECIAL_FORMS.iter().map(|name| name.to_string()));
+    (names.into_iter())
+        .filter(|name| name.starts_with(prefix))
+        .collect()
+}
+
+/// What `name` is in `env`: a `special form`, a `macro`, a `function` or
+/// a `value`.
+pub fn kind_of(env: &Env, name: &str) -> &'static str {
+    if SPECIAL_FORMS.contains(&name) {
+        "special form"
+    } else if env.lookup_macro(name).is_some() {
+        "macro"
+    } else if matches!(env.lookup(name), Some(Value::Fn(_))) {
+        "function"
+    } else {
+        "value"
+    }
+}
+
+/// A top-level definition, found by reading rather than evaluating, so
+/// documents with errors have the ones before the first error.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    /// The form that defines it, such as `defn`, or `variant` and `field`
+    /// for the parts of enums and structs.
+    pub head: String,
+    pub start: usize,
+    pub end: usize,
+    pub name_start: usize,
+    pub name_end: usize,
+    pub doc: Option<String>,
+    /// The parameters of each arity of a function or macro, as written.
+    pub params: Vec<String>,
+    pub children: Vec<Definition>,
+}
+
+impl Definition {
+    fn symbol(&self, lines: &Lines) -> Json {
+        let kind = match self.head.as_str() {
+            "defn" | "defn-" | "defmacro" => 12,
+            "defstruct" => 23,
+            "defenum" => 10,
+            "variant" => 22,
+            "field" => 8,
+            _ => 13,
+        };
+        let children: Vec<Json> = self.children.iter().map(|c| c.symbol(lines)).collect();
+        json!({
+            "name": self.name,
+            "detail": self.head,
+            "kind": kind,
+            "range": lines.range(self.start, self.end),
+            "selectionRange": lines.range(self.name_start, self.name_end),
+            "children": children,
+        })
+    }
+}
+
+/// The definitions of `content`, in order.
+pub fn definitions(content: &str) -> Vec<Definition> {
+    let mut reader = Reader::new("", content);
+    let mut definitions = vec![];
+    while let Some(Ok((form, span))) = reader.read_spanned() {
+        definitions.extend(definition(&form, &span, content));
+    }
+    definitions
+}
+
+fn definition(form: &Value, span: &Span, content: &str) -> Option<Definition> {
+    let items = match form {
+        Value::ListParen(items) => items,
+        _ => return None,
+    };
+    let head = match items.first() {
+        Some(Value::Symbol(_, head)) => head.as_str(),
+        _ => return None,
+    };
+    let defines = [
+        "def",
+        "def-",
+        "defn",
+        "defn-",
+        "defmacro",
+        "defstruct",
+        "defenum",
+    ];
+    if !defines.contains(&head) {
+        return None;
+    }
+    let mut definition = named(head, items.get(1)?, span.items.get(1)?)?;
+    (definition.start, definition.end) = (span.start, span.end);
+    definition.doc = match items.get(2) {
+        Some(Value::String(doc)) if items.len() > 3 => Some(doc.clone()),
+        _ => None,
+    };
+    let rest = if definition.doc.is_some() { 3 } else { 2 };
+    let text = |span: &Span| content[span.start..span.end].to_string();
+    match (head, &items[rest.min(items.len())..]) {
+        ("defn" | "defn-" | "defmacro", [Value::ListBracket(_), ..]) => {
+            definition.params = span.items.get(rest).map(text).into_iter().collect();
+        }
+        ("defn" | "defn-" | "defmacro", arities) => {
+            for (i, arity) in arities.iter().enumerate() {
+                if let Value::ListParen(arity) = arity {
+                    if let (Some(Value::ListBracket(_)), Some(span)) =
+                        (arity.first(), span.items.get(rest + i))
+                    {
+                        definition.params.extend(span.items.first().map(text));
+                    }
+                }
+            }
+        }
+        ("defstruct", [Value::ListBracket(fields)]) => {
+            let spans = &span.items.get(rest)?.items;
+            definition.children = (fields.iter().zip(spans))
+                .filter_map(|(field, span)| named("field", field, span))
+                .collect();
+        }
+        ("defstruct" | "defenum", specs) => {
+            let part = if head == "defenum" {
+                "variant"
+            } else {
+                "field"
+            };
+            for (spec, span) in specs.iter().zip(&span.items[rest..]) {
+                let child = match spec {
+                    Value::ListParen(spec) | Value::ListBracket(spec) => spec
+                        .first()
+                        .zip(span.items.first())
+                        .and_then(|(name, name_span)| {
+                            let mut child = named(part, name, name_span)?;
+                            (child.start, child.end) = (span.start, span.end);
+                            Some(child)
+                        }),
+                    name => named(part, name, span),
+                };
+                definition.children.extend(child);
+            }
+        }
+        _ => {}
+    }
+    Some(definition)
+}
+
+/// The definition of the symbol `name` by `head`, spanning just its name.
+fn named(head: &str, name: &Value, span: &Span) -> Option<Definition> {
+    match name {
+        Value::Symbol(_, name) => Some(Definition {
+            name: name.clone(),
+            head: head.into(),
+            start: span.start,
+            end: span.end,
+            name_start: span.start,
+            name_end: span.end,
+            doc: None,
+            params: vec![],
+            children: vec![],
+        }),
+        _ => None,
+    }
+}
+
+/// The definition of global `name`: a definition, an enum variant, or a
+/// struct field for its accessor `Struct-field`.
+fn find(definitions: Vec<Definition>, name: &str) -> Option<Definition> {
+    for definition in definitions {
+        if definition.name == name {
+            return Some(definition);
+        }
+        for child in &definition.children {
+            let defines = match child.head.as_str() {
+                "field" => format!("{}-{}", definition.name, child.name),
+                _ => child.name.clone(),
+            };
+            if defines == name {
+                return Some(child.clone());
+            }
+        }
+    }
+    None
+}
+
+/// Converts between byte offsets and LSP positions, whose lines and
+/// characters count from zero and whose characters are UTF-16 code units.
+pub struct Lines<'a> {
+    text: &'a str,
+    /// The offsets lines start at.
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    pub fn new(text: &'a str) -> Lines<'a> {
+        let starts = (std::iter::once(0))
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Lines { text, starts }
+    }
+
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        (line, utf16_len(&self.text[self.starts[line]..offset]))
+    }
+
+    /// The offset of a position, clamped to its line and to the text.
+    pub fn offset(&self, line: usize, character: usize) -> usize {
+        let start = match self.starts.get(line) {
+            Some(&start) => start,
+            None => return self.text.len(),
+        };
+        let end = self
+            .starts
+            .get(line + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        let mut units = 0;
+        for (i, ch) in self.text[start..end].char_indices() {
+            if units >= character {
+                return start + i;
+            }
+            units += ch.len_utf16();
+        }
+        end
+    }
+
+    pub fn range(&self, start: usize, end: usize) -> Json {
+        let position = |offset| {
+            let (line, character) = self.position(offset);
+            json!({"line": line, "character": character})
+        };
+        json!({"start": position(start), "end": position(end)})
+    }
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+/// The path of a `file:` URI.
+pub fn uri_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?.as_bytes();
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        let escaped = (path[i] == b'%')
+            .then(|| std::str::from_utf8(path.get(i + 1..i + 3)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(path[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+/// The `file:` URI of `path`.
+pub fn path_uri(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.into());
+    let mut uri = String::from("file://");
+    for &byte in path.to_string_lossy().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
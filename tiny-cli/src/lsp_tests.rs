@@ -0,0 +1,504 @@
+#[cfg(test)]
+mod tests {
+    use lsp::{definitions, path_uri, read_message, serve, uri_path, write_message, Lines};
+    use serde_json::{json, Value as Json};
+    use std::io::{self, BufReader, Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::thread::{self, JoinHandle};
+
+    /// The reading end of an in-memory pipe.
+    struct PipeReader {
+        receiver: Receiver<Vec<u8>>,
+        buffer: Vec<u8>,
+        read: usize,
+    }
+
+    impl Read for PipeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.read == self.buffer.len() {
+                match self.receiver.recv() {
+                    Ok(buffer) => (self.buffer, self.read) = (buffer, 0),
+                    Err(_) => return Ok(0),
+                }
+            }
+            let n = buf.len().min(self.buffer.len() - self.read);
+            buf[..n].copy_from_slice(&self.buffer[self.read..self.read + n]);
+            self.read += n;
+            Ok(n)
+        }
+    }
+
+    struct PipeWriter(Sender<Vec<u8>>);
+
+    impl Write for PipeWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .send(buf.to_vec())
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "pipe closed"))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn pipe() -> (PipeWriter, PipeReader) {
+        let (sender, receiver) = mpsc::channel();
+        let reader = PipeReader {
+            receiver,
+            buffer: vec![],
+            read: 0,
+        };
+        (PipeWriter(sender), reader)
+    }
+
+    /// A client of a server running on another thread.
+    struct Client {
+        input: PipeWriter,
+        output: BufReader<PipeReader>,
+        server: JoinHandle<bool>,
+        next_id: u64,
+        /// Notifications that came while waiting for a response.
+        notifications: Vec<Json>,
+    }
+
+    impl Client {
+        /// Starts a server and initializes it.
+        fn start() -> Client {
+            let (input, server_input) = pipe();
+            let (server_output, output) = pipe();
+            let server = thread::spawn(move || serve(BufReader::new(server_input), server_output));
+            let mut client = Client {
+                input,
+                output: BufReader::new(output),
+                server,
+                next_id: 0,
+                notifications: vec![],
+            };
+            client.request("initialize", json!({"capabilities": {}}));
+            client.notify("initialized", json!({}));
+            client
+        }
+
+        fn notify(&mut self, method: &str, params: Json) {
+            let message = json!({"jsonrpc": "2.0", "method": method, "params": params});
+            write_message(&mut self.input, &message).unwrap();
+        }
+
+        /// Sends a request and returns the response.
+        fn request(&mut self, method: &str, params: Json) -> Json {
+            self.next_id += 1;
+            let id = self.next_id;
+            let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+            write_message(&mut self.input, &message).unwrap();
+            loop {
+                let message = self.receive();
+                if message["id"] == id {
+                    return message;
+                }
+                self.notifications.push(message);
+            }
+        }
+
+        fn result(&mut self, method: &str, params: Json) -> Json {
+            self.request(method, params)["result"].clone()
+        }
+
+        fn receive(&mut self) -> Json {
+            let body = read_message(&mut self.output).unwrap().unwrap();
+            serde_json::from_str(&body).unwrap()
+        }
+
+        /// The params of the next notification of `method`.
+        fn notification(&mut self, method: &str) -> Json {
+            if let Some(i) = (self.notifications.iter()).position(|n| n["method"] == method) {
+                return self.notifications.remove(i)["params"].clone();
+            }
+            loop {
+                let message = self.receive();
+                if message["method"] == method {
+                    return message["params"].clone();
+                }
+            }
+        }
+
+        /// Opens a document and returns its diagnostics.
+        fn open(&mut self, uri: &str, text: &str) -> Json {
+            let document = json!({"uri": uri, "languageId": "tiny", "version": 1, "text": text});
+            self.notify("textDocument/didOpen", json!({"textDocument": document}));
+            self.notification("textDocument/publishDiagnostics")["diagnostics"].clone()
+        }
+
+        /// Shuts the server down, returning whether it exited cleanly.
+        fn stop(mut self) -> bool {
+            assert_eq!(self.result("shutdown", Json::Null), Json::Null);
+            self.notify("exit", Json::Null);
+            self.server.join().unwrap()
+        }
+    }
+
+    fn at(uri: &str, line: usize, character: usize) -> Json {
+        json!({
+            "textDocument": {"uri": uri},
+            "position": {"line": line, "character": character},
+        })
+    }
+
+    fn document(uri: &str) -> Json {
+        json!({"textDocument": {"uri": uri}})
+    }
+
+    fn range(start: (usize, usize), end: (usize, usize)) -> Json {
+        json!({
+            "start": {"line": start.0, "character": start.1},
+            "end": {"line": end.0, "character": end.1},
+        })
+    }
+
+    /// A fresh directory for the files of one test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tiny-lsp-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_lifecycle() {
+        let mut client = Client::start();
+        let unknown = client.request("textDocument/rename", json!({}));
+        assert_eq!(unknown["error"]["code"], -32601);
+        let missing = client.request("textDocument/hover", at("file:///nowhere.tiny", 0, 0));
+        assert_eq!(missing["error"]["code"], -32602);
+        assert!(client.stop());
+
+        // Exiting without shutting down first is an error.
+        let client = Client::start();
+        write_message(
+            &mut { client.input },
+            &json!({"jsonrpc": "2.0", "method": "exit"}),
+        )
+        .unwrap();
+        assert!(!client.server.join().unwrap());
+    }
+
+    #[test]
+    fn test_initialize() {
+        let (mut input, server_input) = pipe();
+        let (server_output, output) = pipe();
+        let server = thread::spawn(move || serve(BufReader::new(server_input), server_output));
+        let message = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+        write_message(&mut input, &message).unwrap();
+        let body = read_message(&mut BufReader::new(output)).unwrap().unwrap();
+        let response: Json = serde_json::from_str(&body).unwrap();
+        let capabilities = &response["result"]["capabilities"];
+        assert_eq!(capabilities["textDocumentSync"], 1);
+        assert_eq!(capabilities["definitionProvider"], true);
+        let legend = &capabilities["semanticTokensProvider"]["legend"]["tokenTypes"];
+        assert_eq!(legend[0], "comment");
+        drop(input);
+        assert!(!server.join().unwrap());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut client = Client::start();
+        let uri = "untitled:one";
+        let diagnostics = client.open(uri, "(defn f [x]\n  (+ x y))\n(f 1");
+        assert_eq!(
+            diagnostics,
+            json!([
+                {
+                    "range": range((1, 7), (1, 8)),
+                    "severity": 1,
+                    "code": "unresolved-symbol",
+                    "source": "tiny",
+                    "message": "unable to resolve symbol: y",
+                },
+                {
+                    "range": range((2, 0), (2, 4)),
+                    "severity": 1,
+                    "code": "unclosed-delimiter",
+                    "source": "tiny",
+                    "message": "unclosed `(`",
+                },
+            ])
+        );
+        let change = json!({
+            "textDocument": {"uri": uri, "version": 2},
+            "contentChanges": [{"text": "(defn f [x] x)"}],
+        });
+        client.notify("textDocument/didChange", change);
+        let published = client.notification("textDocument/publishDiagnostics");
+        assert_eq!(published, json!({"uri": uri, "diagnostics": []}));
+        client.notify("textDocument/didClose", document(uri));
+        let published = client.notification("textDocument/publishDiagnostics");
+        assert_eq!(published, json!({"uri": uri, "diagnostics": []}));
+        assert_eq!(
+            client.request("textDocument/hover", at(uri, 0, 7))["error"]["code"],
+            -32602
+        );
+        assert!(client.stop());
+    }
+
+    #[test]
+    fn test_diagnostics_with_side_effects() {
+        let dir = temp_dir("side-effects");
+        let written = dir.join("written.txt");
+        std::fs::write(
+            dir.join("noisy.tiny"),
+            format!(
+                "(import [std.io :as io])\n(println \"loaded\")\n(def x 1)\n(io/spit {:?} \"x\")",
+                written.display().to_string()
+            ),
+        )
+        .unwrap();
+        let mut client = Client::start();
+        let uri = path_uri(&dir.join("main.tiny"));
+        let diagnostics = client.open(&uri, "(import [noisy :as n])\n(inc n/x)");
+        // Checking imports the module without letting it write files.
+        let messages: Vec<&str> = (diagnostics.as_array().unwrap().iter())
+            .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "no such namespace: n".to_string(),
+                format!(
+                    "in {}: spit needs the write capability, which is disabled",
+                    dir.join("noisy.tiny").display()
+                ),
+            ]
+        );
+        assert!(!written.exists());
+        assert!(client.stop());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let mut client = Client::start();
+        let uri = "untitled:symbols";
+        let text = "(def limit 10)\n\
+                    (defn area \"The area.\" [w h] (* w h))\n\
+                    (defstruct Point [x y])\n\
+                    (defenum Shape\n  (Circle Int)\n  Empty)\n\
+                    (println limit)";
+        client.open(uri, text);
+        let symbols = client.result("textDocument/documentSymbol", document(uri));
+        let summary: Vec<(Json, Json, Json, usize)> = (symbols.as_array().unwrap().iter())
+            .map(|symbol| {
+                let children = symbol["children"].as_array().unwrap().len();
+                (
+                    symbol["name"].clone(),
+                    symbol["kind"].clone(),
+                    symbol["detail"].clone(),
+                    children,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (json!("limit"), json!(13), json!("def"), 0),
+                (json!("area"), json!(12), json!("defn"), 0),
+                (json!("Point"), json!(23), json!("defstruct"), 2),
+                (json!("Shape"), json!(10), json!("defenum"), 2),
+            ]
+        );
+        assert_eq!(symbols[1]["range"], range((1, 0), (1, 37)));
+        assert_eq!(symbols[1]["selectionRange"], range((1, 6), (1, 10)));
+        let variant = &symbols[3]["children"][0];
+        assert_eq!(variant["name"], "Circle");
+        assert_eq!(variant["kind"], 22);
+        assert_eq!(variant["range"], range((4, 2), (4, 14)));
+        assert_eq!(variant["selectionRange"], range((4, 3), (4, 9)));
+        assert_eq!(symbols[2]["children"][1]["name"], "y");
+        assert!(client.stop());
+    }
+
+    /// A directory with a module `lib.util` and a file importing it.
+    fn project(name: &str) -> (String, String) {
+        let dir = temp_dir(name);
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        let util = dir.join("lib/util.tiny");
+        std::fs::write(
+            &util,
+            "(defn twice\n  \"Doubles n.\"\n  [n]\n  (* 2 n))\n(defn- hidden [] 1)\n",
+        )
+        .unwrap();
+        (path_uri(&dir.join("main file.tiny")), path_uri(&util))
+    }
+
+    #[test]
+    fn test_definition() {
+        let (uri, util) = project("definition");
+        let mut client = Client::start();
+        let text = "(import [lib.util :as u])\n(defn four [] (u/twice 2))\n(four)";
+        assert_eq!(client.open(&uri, text), json!([]));
+        assert_eq!(
+            client.result("textDocument/definition", at(&uri, 1, 18)),
+            json!({"uri": util, "range": range((0, 6), (0, 11))})
+        );
+        // At the end of the symbol, as after typing it.
+        assert_eq!(
+            client.result("textDocument/definition", at(&uri, 2, 5)),
+            json!({"uri": uri, "range": range((1, 6), (1, 10))})
+        );
+        // Names tiny defines have no file, and `(` isn't a symbol.
+        client.open("untitled:core", "(map inc [1])");
+        let core = client.result("textDocument/definition", at("untitled:core", 0, 2));
+        assert_eq!(core, Json::Null);
+        let open = client.result("textDocument/definition", at(&uri, 2, 0));
+        assert_eq!(open, Json::Null);
+        assert!(client.stop());
+    }
+
+    #[test]
+    fn test_hover() {
+        let (uri, _) = project("hover");
+        let mut client = Client::start();
+        let text = "(import [lib.util :as u])\n(u/twice 2)\n(comp inc)\n(undefined)";
+        client.open(&uri, text);
+        assert_eq!(
+            client.result("textDocument/hover", at(&uri, 1, 3)),
+            json!({
+                "contents": {
+                    "kind": "markdown",
+                    "value": "```tiny\n(lib.util/twice [n])\n```\n\nDoubles n.",
+                },
+                "range": range((1, 1), (1, 8)),
+            })
+        );
+        let comp = client.result("textDocument/hover", at(&uri, 2, 1));
+        let value = comp["contents"]["value"].as_str().unwrap();
+        assert!(
+            value.starts_with("```tiny\n(tiny.core/comp [])\n(tiny.core/comp [f])\n"),
+            "{:?}",
+            value
+        );
+        assert!(value.ends_with("\n\nComposes fs: ((comp f g) x) is (f (g x))."));
+        assert_eq!(
+            client.result("textDocument/hover", at(&uri, 3, 3)),
+            Json::Null
+        );
+        assert!(client.stop());
+    }
+
+    #[test]
+    fn test_completion() {
+        let (uri, _) = project("completion");
+        let mut client = Client::start();
+        let text = "(import [lib.util :as u])\n(defn twirl [] 1)\n(u/ tw)";
+        client.open(&uri, text);
+        let labels = |completion: &Json| -> Vec<Json> {
+            (completion["items"].as_array().unwrap().iter())
+                .map(|item| item["label"].clone())
+                .collect()
+        };
+        let aliased = client.result("textDocument/completion", at(&uri, 2, 3));
+        assert_eq!(labels(&aliased), vec![json!("u/twice")]);
+        assert_eq!(aliased["items"][0]["detail"], "function");
+        assert_eq!(aliased["items"][0]["documentation"], "Doubles n.");
+        let own = client.result("textDocument/completion", at(&uri, 2, 6));
+        assert_eq!(labels(&own), vec![json!("twirl")]);
+        let all = client.result("textDocument/completion", at(&uri, 2, 0));
+        let all = labels(&all);
+        assert!(all.contains(&json!("if")) && all.contains(&json!("map")));
+        let items = client.result("textDocument/completion", at(&uri, 2, 0))["items"].clone();
+        let when = (items.as_array().unwrap().iter())
+            .find(|item| item["label"] == "when-not")
+            .unwrap();
+        assert_eq!(
+            (&when["kind"], &when["detail"]),
+            (&json!(3), &json!("macro"))
+        );
+        assert!(client.stop());
+    }
+
+    #[test]
+    fn test_formatting() {
+        let mut client = Client::start();
+        client.open("untitled:fmt", "(defn f [x]\n(inc x))");
+        assert_eq!(
+            client.result("textDocument/formatting", document("untitled:fmt")),
+            json!([{
+                "range": range((0, 0), (1, 8)),
+                "newText": "(defn f [x]\n  (inc x))\n",
+            }])
+        );
+        client.open("untitled:formatted", "(inc 1)\n");
+        let formatted = client.result("textDocument/formatting", document("untitled:formatted"));
+        assert_eq!(formatted, json!([]));
+        client.open("untitled:broken", "(inc 1");
+        let broken = client.result("textDocument/formatting", document("untitled:broken"));
+        assert_eq!(broken, Json::Null);
+        assert!(client.stop());
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let mut client = Client::start();
+        let text = "(defn f [x] ; note\n  (when x \"a\nb\" :k 'y 1.5 #\"r\" nil))";
+        client.open("untitled:tokens", text);
+        let tokens = client.result(
+            "textDocument/semanticTokens/full",
+            document("untitled:tokens"),
+        );
+        // Lines and starts are relative to the previous token.
+        #[rustfmt::skip]
+        let expected = json!([
+            0, 1, 4, 4, 0,  // defn
+            0, 5, 1, 7, 0,  // f
+            0, 6, 6, 0, 0,  // ; note
+            1, 3, 4, 4, 0,  // when
+            0, 7, 2, 1, 0,  // "a
+            1, 0, 2, 1, 0,  // b"
+            0, 3, 2, 5, 0,  // :k
+            0, 3, 1, 8, 0,  // '
+            0, 3, 3, 3, 0,  // 1.5
+            0, 4, 4, 2, 0,  // #"r"
+            0, 5, 3, 4, 0,  // nil
+        ]);
+        assert_eq!(tokens["data"], expected);
+        assert!(client.stop());
+    }
+
+    #[test]
+    fn test_definitions() {
+        let text = "(defn- add\n  ([a] a)\n  ([a b] (+ a b)))\n(defstruct P (x Int) y)";
+        let found = definitions(text);
+        assert_eq!(
+            (found[0].name.as_str(), found[0].head.as_str()),
+            ("add", "defn-")
+        );
+        assert_eq!(found[0].params, vec!["[a]", "[a b]"]);
+        let fields: Vec<_> = (found[1].children.iter())
+            .map(|field| (field.name.as_str(), field.start, field.name_start))
+            .collect();
+        assert_eq!(fields, vec![("x", 53, 54), ("y", 61, 61)]);
+        assert_eq!(definitions("(def ok 1) (def"), definitions("(def ok 1)"));
+    }
+
+    #[test]
+    fn test_lines() {
+        let text = "a\n😀b\r\nc";
+        let lines = Lines::new(text);
+        assert_eq!(lines.position(0), (0, 0));
+        assert_eq!(lines.position(6), (1, 2));
+        assert_eq!(lines.position(text.len()), (2, 1));
+        assert_eq!(lines.offset(1, 2), 6);
+        assert_eq!(lines.offset(1, 99), 8);
+        assert_eq!(lines.offset(9, 0), text.len());
+    }
+
+    #[test]
+    fn test_uris() {
+        let path = Path::new("/tmp/a b/ü.tiny");
+        let uri = path_uri(path);
+        assert_eq!(uri, "file:///tmp/a%20b/%C3%BC.tiny");
+        assert_eq!(uri_path(&uri).unwrap(), path);
+        assert_eq!(uri_path("untitled:1"), None);
+    }
+}
@@ -1,77 +1,346 @@
+extern crate clap;
 extern crate rustyline;
+extern crate serde_json;
 extern crate tiny_library;
 
-use rustyline::{error::ReadlineError, DefaultEditor};
-use std::io::Read;
-use tiny_library::reader::Reader;
+mod check;
+mod diagnostic;
+mod helper;
+mod lsp;
+mod nrepl;
+mod repl;
+mod source;
+mod watch;
 
-fn main() {
-    let args: Vec<String> = std::env::args().skip(1).collect();
-    if args.len() > 1 {
-        process_files(&args);
+#[cfg(test)]
+mod check_tests;
+#[cfg(test)]
+mod diagnostic_tests;
+#[cfg(test)]
+mod helper_tests;
+#[cfg(test)]
+mod lsp_tests;
+#[cfg(test)]
+mod nrepl_tests;
+#[cfg(test)]
+mod repl_tests;
+#[cfg(test)]
+mod watch_tests;
+
+use check::MessageFormat;
+use clap::{Parser, Subcommand};
+use source::{new_env, Source};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tiny_library::codec;
+use tiny_library::eval::{self, Env};
+use tiny_library::fmt;
+use tiny_library::reader::{Reader, Span};
+use tiny_library::value::Value;
+
+#[derive(Parser)]
+#[command(name = "tiny", version, about = "Runs and checks tiny programs")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Evaluates files, then calls their `main` function if they define one
+    Run {
+        /// Runs files again when they or the modules they import change
+        #[arg(long)]
+        watch: bool,
+        /// Source files, `.tinyc` compiled files, or `-` for stdin
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Reports read errors and unresolved symbols without running anything
+    Check {
+        /// How to print diagnostics: `json` prints one JSON object per line
+        #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat,
+        /// Checks files again when they or the modules they import change
+        #[arg(long)]
+        watch: bool,
+        /// Source files, directories to find `.tiny` files in, glob
+        /// patterns, or `-` for stdin
+        #[arg(required = true)]
+        paths: Vec<String>,
+    },
+    /// Starts an interactive session
+    Repl,
+    /// Prints the forms read from files
+    Dump {
+        /// Source files, or `-` for stdin
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Formats files in place, or prints stdin formatted
+    Fmt {
+        /// Lists the files that would change instead of changing them
+        #[arg(long)]
+        check: bool,
+        /// Source files, or `-` for stdin
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Evaluates files and runs the tests they define with `deftest`
+    Test {
+        /// Source files, or `-` for stdin
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Runs a language server for editors over stdin and stdout
+    Lsp,
+    /// Runs a server editors evaluate code in, over TCP or a Unix socket
+    Nrepl {
+        /// The port to listen on, on localhost; any free one if it is 0
+        #[arg(long, default_value_t = 0, conflicts_with = "socket")]
+        port: u16,
+        /// The path of a Unix socket to listen on instead
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Writes the forms of each file to a `.tinyc` file next to it, or
+    /// those of stdin to stdout
+    Compile {
+        /// Source files, or `-` for stdin
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+}
+
+fn main() -> ExitCode {
+    let command = Cli::parse().command;
+    // Commands evaluate on a thread with room for deeply nested calls.
+    let ok = std::thread::Builder::new()
+        .stack_size(eval::STACK_SIZE)
+        .spawn(move || run(command))
+        .expect("failed to start the main thread")
+        .join()
+        .unwrap_or(false);
+    if ok {
+        ExitCode::SUCCESS
     } else {
-        run_stdin();
-        // inter();
+        ExitCode::FAILURE
     }
 }
 
-fn process_files(args: &Vec<String>) {
-    for file in args {
-        let content = std::fs::read_to_string(file).unwrap();
-        process(file, &content, true);
+/// Runs `command`, returning whether it succeeded.
+fn run(command: Command) -> bool {
+    match command {
+        Command::Run {
+            watch: false,
+            files,
+        } => all(&files, |file| run_file(file).0),
+        Command::Run { watch: true, files } => watch::watch(files, |files| {
+            files.iter().map(|file| run_file(file).1).collect()
+        }),
+        Command::Check {
+            message_format,
+            watch,
+            paths,
+        } => check::run(&paths, message_format, watch),
+        Command::Repl => {
+            repl::run(new_env(None));
+            true
+        }
+        Command::Dump { files } => all(&files, dump_file),
+        Command::Fmt { check, files } => all(&files, |file| fmt_file(file, check)),
+        Command::Test { files } => all(&files, test_file),
+        Command::Lsp => lsp::run(),
+        Command::Nrepl { port, socket } => nrepl::run(port, socket),
+        Command::Compile { files } => all(&files, compile_file),
     }
 }
 
-#[allow(dead_code)]
-fn run_stdin() {
-    let mut content = String::new();
-    std::io::stdin()
-        .read_to_string(&mut content)
-        .expect("error readin stdin");
-    process("_stdin_.tiny", &content, false);
+/// Calls `f` on every file, even after one fails, and whether all succeeded.
+fn all<F: Fn(&str) -> bool>(files: &[String], f: F) -> bool {
+    let results: Vec<bool> = files.iter().map(|file| f(file)).collect();
+    results.into_iter().all(|ok| ok)
 }
 
-#[allow(dead_code)]
-fn inter() {
-    let mut rl = DefaultEditor::new().unwrap();
-    if rl.load_history("./history.txt").is_err() {
-        println!("No previous history.");
+/// Runs `file`, returning whether it succeeded and the module files it
+/// imported.
+fn run_file(file: &str) -> (bool, Vec<PathBuf>) {
+    if Path::new(file)
+        .extension()
+        .is_some_and(|ext| ext == "tinyc")
+    {
+        return run_compiled(file);
     }
-    loop {
-        match rl.readline(">>> ") {
-            Ok(line) => {
-                process("_repl_.tiny", &line, false);
-                rl.add_history_entry(line.as_str()).unwrap();
+    let source = match Source::read(file) {
+        Some(source) => source,
+        None => return (false, vec![]),
+    };
+    let env = source.env();
+    let ok = source.eval(&env) && run_main(&source, &env);
+    (ok, env.module_files())
+}
+
+fn run_compiled(file: &str) -> (bool, Vec<PathBuf>) {
+    let forms = std::fs::read(file)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| codec::decode(&bytes).map_err(|err| format!("{err:?}")));
+    let forms = match forms {
+        Ok(forms) => forms,
+        Err(err) => {
+            eprintln!("{file}: error: could not load: {err}");
+            return (false, vec![]);
+        }
+    };
+    // Compiled forms have no spans, so errors can only name the file.
+    let source = Source {
+        name: file.into(),
+        content: String::new(),
+    };
+    let env = new_env(Path::new(file).parent());
+    for form in forms {
+        if let Err(err) = env.eval_form(file, &form, &Span::default()) {
+            source.report(&err);
+            return (false, env.module_files());
+        }
+    }
+    (run_main(&source, &env), env.module_files())
+}
+
+fn run_main(source: &Source, env: &Env) -> bool {
+    match env.lookup("main") {
+        Some(main) => match env.apply(&main, vec![]) {
+            Ok(_) => true,
+            Err(err) => {
+                source.report(&err);
+                false
             }
-            Err(ReadlineError::Eof) => break,
-            Err(ReadlineError::Interrupted) => {}
+        },
+        None => true,
+    }
+}
+
+fn dump_file(file: &str) -> bool {
+    let source = match Source::read(file) {
+        Some(source) => source,
+        None => return false,
+    };
+    let mut reader = Reader::new(&source.name, &source.content);
+    while let Some(form) = reader.read() {
+        match form {
+            Ok(form) => form.dump(""),
             Err(err) => {
-                eprintln!("Error: {err}");
-                break;
+                source.report(&err.into());
+                return false;
             }
-        };
+        }
     }
-    rl.save_history("./history.txt").unwrap();
+    true
 }
 
-fn process(name: &str, content: &str, verbose: bool) {
-    let mut reader = Reader::new(name, content);
-    if verbose {
-        eprintln!("Compiling {}", reader.name);
+fn fmt_file(file: &str, check: bool) -> bool {
+    let source = match Source::read(file) {
+        Some(source) => source,
+        None => return false,
+    };
+    let formatted = match fmt::format(&source.name, &source.content) {
+        Ok(formatted) => formatted,
+        Err(err) => {
+            source.report(&err.into());
+            return false;
+        }
+    };
+    if check {
+        if formatted != source.content {
+            eprintln!("{}: would reformat", source.name);
+        }
+        formatted == source.content
+    } else if file == "-" {
+        print!("{formatted}");
+        true
+    } else if formatted == source.content {
+        true
+    } else {
+        match std::fs::write(file, formatted) {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("{file}: error: could not write: {err}");
+                false
+            }
+        }
+    }
+}
+
+fn test_file(file: &str) -> bool {
+    let source = match Source::read(file) {
+        Some(source) => source,
+        None => return false,
+    };
+    let env = source.env();
+    if !source.eval(&env) {
+        return false;
     }
-    let dump = matches!(std::env::var("DUMP"), Ok(val) if val == "1");
-    loop {
-        match reader.read() {
-            Some(Ok(form)) => {
-                if dump {
-                    form.dump("")
-                }
+    let tests = match env.lookup("*tests*") {
+        Some(Value::Atom(tests)) => tests.deref(),
+        _ => Value::Nil,
+    };
+    let tests = match tests {
+        Value::ListBracket(tests) => tests,
+        _ => vec![],
+    };
+    let mut failed = 0;
+    for test in &tests {
+        if let Value::ListBracket(pair) = test {
+            if let Err(err) = env.apply(&pair[1], vec![]) {
+                eprintln!("FAIL {}", pair[0]);
+                source.report(&err);
+                failed += 1;
             }
-            Some(Err(err)) => {
-                println!("Error: {:?}", err);
-                break;
+        }
+    }
+    println!(
+        "{}: {} passed, {failed} failed",
+        source.name,
+        tests.len() - failed
+    );
+    failed == 0
+}
+
+fn compile_file(file: &str) -> bool {
+    let source = match Source::read(file) {
+        Some(source) => source,
+        None => return false,
+    };
+    let mut reader = Reader::new(&source.name, &source.content);
+    let mut forms = vec![];
+    while let Some(form) = reader.read() {
+        match form {
+            Ok(form) => forms.push(form),
+            Err(err) => {
+                source.report(&err.into());
+                return false;
             }
-            None => break,
+        }
+    }
+    let bytes = match codec::encode(&forms) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("{}: error: could not compile: {err:?}", source.name);
+            return false;
+        }
+    };
+    let written = if file == "-" {
+        std::io::stdout().write_all(&bytes)
+    } else {
+        let output = Path::new(file).with_extension("tinyc");
+        eprintln!("Compiling {} to {}", file, output.display());
+        std::fs::write(&output, bytes)
+    };
+    match written {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("{}: error: could not compile: {err}", source.name);
+            false
         }
     }
 }
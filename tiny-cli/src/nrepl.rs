@@ -0,0 +1,387 @@
+//! `tiny nrepl`: a server editors evaluate code in, over TCP on localhost
+//! or a Unix socket, in the spirit of Clojure's nREPL.
+//!
+//! Messages are JSON objects, one per line. A request names its `op` and
+//! usually has an `id` and a `session`, which the responses to it repeat;
+//! the last response has a `status` including `done`. Requests without a
+//! session use one of their connection's own, which closes with it.
+//!
+//! - `describe`: `ops`, the ops below, and `versions`.
+//! - `clone`: `new-session`, a session with definitions of its own, kept
+//!   until it is closed.
+//! - `close`: closes `session`, with status `session-closed`.
+//! - `eval`: evaluates `code`, read as the file `file` if it is given.
+//!   What it prints is sent as it is printed, in `out` and `err`, and the
+//!   result of each form in `value`, with the `ns` it was evaluated in. An
+//!   error is sent in `err`, then its message in `ex` with status
+//!   `eval-error`, and no more forms are evaluated. Evals in a session run
+//!   one after another.
+//! - `load-file`: evaluates `file`, the contents of the file at
+//!   `file-path`, as `eval` does. Modules are then imported from its
+//!   directory too.
+//! - `complete`: `completions`, the names starting with `prefix`, each a
+//!   `candidate` and its `type`: `special form`, `macro`, `function` or
+//!   `value`.
+//! - `lookup`: `info` about `sym`: its `name`, `ns` and `doc`, the
+//!   `arglists` of functions and macros, and the `file` and `line` of ones
+//!   defined in modules. Status `no-info` if it isn't defined.
+//! - `interrupt`: stops the eval running in `session`, or only the one with
+//!   id `interrupt-id` if it is given, which then ends with status
+//!   `interrupted`. Status `session-idle` if none is running.
+//!
+//! Unknown ops get status `unknown-op`, sessions that don't exist
+//! `unknown-session` and lines that aren't JSON objects `invalid-request`,
+//! along with `error`.
+
+use lsp;
+use repl::{self, Repl};
+use serde_json::{json, Value as Json};
+use source::{new_env, Source};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tiny_library::eval::{self, EvalError};
+use tiny_library::io::OutputStream;
+use tiny_library::reader::line_col;
+
+pub const OPS: &[&str] = &[
+    "clone",
+    "close",
+    "complete",
+    "describe",
+    "eval",
+    "interrupt",
+    "load-file",
+    "lookup",
+];
+
+/// Where responses to a connection are written.
+type Sink = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Listens on `port` of localhost, or on the Unix socket `socket`, and
+/// serves every connection until killed.
+pub fn run(port: u16, socket: Option<PathBuf>) -> bool {
+    let server = Server::default();
+    match socket {
+        Some(path) => server.listen_unix(&path),
+        None => match TcpListener::bind(("127.0.0.1", port)) {
+            Ok(listener) => {
+                if let Ok(address) = listener.local_addr() {
+                    listening(&address.to_string());
+                }
+                server.listen_tcp(listener)
+            }
+            Err(err) => {
+                eprintln!("error: could not listen on port {port}: {err}");
+                false
+            }
+        },
+    }
+}
+
+/// Says where the server listens, on stdout for whoever started it.
+fn listening(address: &str) {
+    println!("nrepl server listening on {address}");
+    let _ = io::stdout().flush();
+}
+
+/// The sessions of every connection.
+#[derive(Clone, Default)]
+pub struct Server {
+    sessions: Arc<Mutex<HashMap<String, Arc<Session>>>>,
+    next_session: Arc<AtomicUsize>,
+}
+
+impl Server {
+    pub fn listen_tcp(&self, listener: TcpListener) -> bool {
+        for stream in listener.incoming() {
+            self.accept(stream, TcpStream::try_clone);
+        }
+        true
+    }
+
+    #[cfg(unix)]
+    fn listen_unix(&self, path: &Path) -> bool {
+        let listener = match UnixListener::bind(path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                eprintln!("error: could not listen on {}: {err}", path.display());
+                return false;
+            }
+        };
+        listening(&path.display().to_string());
+        for stream in listener.incoming() {
+            self.accept(stream, UnixStream::try_clone);
+        }
+        true
+    }
+
+    #[cfg(not(unix))]
+    fn listen_unix(&self, _: &Path) -> bool {
+        eprintln!("error: Unix sockets aren't supported here, use --port");
+        false
+    }
+
+    /// Serves a connection on its own thread.
+    fn accept<S: Read + Write + Send + 'static>(
+        &self,
+        stream: io::Result<S>,
+        try_clone: fn(&S) -> io::Result<S>,
+    ) {
+        let streams = stream.and_then(|stream| Ok((try_clone(&stream)?, stream)));
+        let (input, output) = match streams {
+            Ok(streams) => streams,
+            Err(err) => {
+                eprintln!("warning: could not accept a connection: {err}");
+                return;
+            }
+        };
+        let server = self.clone();
+        std::thread::spawn(move || server.serve(input, output));
+    }
+
+    /// Answers the requests read from `input` until it ends.
+    pub fn serve<R: Read, W: Write + Send + 'static>(&self, input: R, output: W) {
+        let sink: Sink = Arc::new(Mutex::new(Box::new(output)));
+        let mut own = None;
+        for line in BufReader::new(input).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(request @ Json::Object(_)) => self.request(&request, &sink, &mut own),
+                _ => Reply::new(&sink, Json::Null, None).send(json!({
+                    "err": format!("invalid request: {line}\n"),
+                    "status": ["done", "error", "invalid-request"],
+                })),
+            }
+        }
+        if let Some(id) = own {
+            self.sessions.lock().unwrap().remove(&id);
+        }
+    }
+
+    fn new_session(&self) -> String {
+        let id = format!(
+            "session-{}",
+            self.next_session.fetch_add(1, Ordering::Relaxed)
+        );
+        let session = Session {
+            repl: Repl::new(new_env(None)),
+            evaluating: Mutex::new(()),
+            running: Mutex::new(None),
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.insert(id.clone(), Arc::new(session));
+        id
+    }
+
+    /// Answers `request`, where `own` is the session of its connection.
+    fn request(&self, request: &Json, sink: &Sink, own: &mut Option<String>) {
+        let op = request["op"].as_str().unwrap_or_default();
+        let id = match request["session"].as_str() {
+            Some(id) => id.to_string(),
+            None => own.get_or_insert_with(|| self.new_session()).clone(),
+        };
+        let reply = Reply::new(sink, request["id"].clone(), Some(id.clone()));
+        let ops: serde_json::Map<String, Json> =
+            (OPS.iter()).map(|op| (op.to_string(), json!({}))).collect();
+        match op {
+            "describe" => {
+                return reply.send(json!({
+                    "ops": ops,
+                    "versions": {"tiny": env!("CARGO_PKG_VERSION")},
+                    "status": ["done"],
+                }))
+            }
+            "clone" => {
+                let session = self.new_session();
+                return reply.send(json!({"new-session": session, "status": ["done"]}));
+            }
+            _ => {}
+        }
+        let session = self.sessions.lock().unwrap().get(&id).cloned();
+        let session = match session {
+            Some(session) if ops.contains_key(op) => session,
+            Some(_) => return reply.done(&["error", "unknown-op"]),
+            None => return reply.done(&["error", "unknown-session"]),
+        };
+        match op {
+            "close" => {
+                self.sessions.lock().unwrap().remove(&id);
+                reply.done(&["session-closed"]);
+            }
+            "eval" => {
+                let code = request["code"].as_str().unwrap_or_default().to_string();
+                let name = request["file"].as_str().unwrap_or(repl::NAME).to_string();
+                spawn_eval(move || session.eval(&name, &code, &reply));
+            }
+            "load-file" => {
+                let code = request["file"].as_str().unwrap_or_default().to_string();
+                let path = request["file-path"].as_str().map(PathBuf::from);
+                if let Some(dir) = path.as_deref().and_then(Path::parent) {
+                    session.repl.env.add_source_root(dir);
+                }
+                let name = path.map_or(repl::NAME.into(), |path| path.display().to_string());
+                spawn_eval(move || session.eval(&name, &code, &reply));
+            }
+            "complete" => session.complete(request["prefix"].as_str().unwrap_or_default(), &reply),
+            "lookup" => session.lookup(request["sym"].as_str().unwrap_or_default(), &reply),
+            "interrupt" => session.interrupt(request.get("interrupt-id"), &reply),
+            _ => unreachable!("unknown ops are answered above"),
+        }
+    }
+}
+
+/// Runs `f` on a thread of its own with a stack deep enough for
+/// `eval::MAX_DEPTH` nested calls, so the session survives deep recursion.
+fn spawn_eval<F: FnOnce() + Send + 'static>(f: F) {
+    std::thread::Builder::new()
+        .name("tiny-eval".into())
+        .stack_size(eval::STACK_SIZE)
+        .spawn(f)
+        .expect("failed to start an eval thread");
+}
+
+struct Session {
+    repl: Repl,
+    /// Held while evaluating, so evals run one after another.
+    evaluating: Mutex<()>,
+    /// The id of the eval running, if any.
+    running: Mutex<Option<Json>>,
+}
+
+impl Session {
+    fn eval(&self, name: &str, code: &str, reply: &Reply) {
+        let _evaluating = self.evaluating.lock().unwrap();
+        let env = &self.repl.env;
+        // Resumed before it is running, so an interrupt for it isn't lost.
+        env.resume();
+        *self.running.lock().unwrap() = Some(reply.id.clone());
+        let printed = reply.clone();
+        env.set_output(Some(Arc::new(move |stream, text: &str| {
+            printed.send(match stream {
+                OutputStream::Out => json!({"out": text}),
+                OutputStream::Err => json!({"err": text}),
+            })
+        })));
+        let source = Source {
+            name: name.into(),
+            content: code.into(),
+        };
+        let mut failed: Option<EvalError> = None;
+        self.repl.eval_each(name, code, |result| {
+            for warning in env.take_warnings() {
+                reply.send(json!({"err": source.describe_warning(&warning)}));
+            }
+            match result {
+                Ok(value) => reply.send(json!({"value": value.to_string(), "ns": env.ns()})),
+                Err(err) => failed = Some(err),
+            }
+        });
+        *self.running.lock().unwrap() = None;
+        match failed {
+            Some(_) if env.is_interrupted() => reply.send(json!({"status": ["interrupted"]})),
+            Some(err) => {
+                reply.send(json!({"err": source.describe(&err)}));
+                reply.send(json!({"ex": err.message, "status": ["eval-error"]}));
+            }
+            None => {}
+        }
+        reply.done(&[]);
+    }
+
+    fn complete(&self, prefix: &str, reply: &Reply) {
+        let env = &self.repl.env;
+        let completions: Vec<Json> = (lsp::completions(env, prefix).into_iter())
+            .map(|name| json!({"candidate": name, "type": lsp::kind_of(env, &name)}))
+            .collect();
+        reply.send(json!({"completions": completions, "status": ["done"]}));
+    }
+
+    fn lookup(&self, symbol: &str, reply: &Reply) {
+        let env = &self.repl.env;
+        let defined = env.lookup(symbol).is_some() || env.lookup_macro(symbol).is_some();
+        let key = match env.resolve(symbol) {
+            Ok(key) if defined => key,
+            _ => return reply.done(&["no-info"]),
+        };
+        let (ns, name) = key.split_once('/').unwrap_or(("", &key));
+        let mut info = json!({"name": name, "ns": ns});
+        let found = lsp::locate(env, symbol, None);
+        let doc = env
+            .doc(symbol)
+            .or_else(|| found.as_ref()?.definition.doc.clone());
+        if let Some(doc) = doc {
+            info["doc"] = json!(doc);
+        }
+        if let Some(found) = found {
+            if !found.definition.params.is_empty() {
+                info["arglists"] = json!(found.definition.params);
+            }
+            if let Some(uri) = found.uri {
+                info["file"] = json!(uri);
+                info["line"] = json!(line_col(&found.content, found.definition.name_start).0);
+            }
+        }
+        reply.send(json!({"info": info, "status": ["done"]}));
+    }
+
+    fn interrupt(&self, id: Option<&Json>, reply: &Reply) {
+        let running = self.running.lock().unwrap();
+        match &*running {
+            Some(running) if id.is_none_or(|id| id == running) => {
+                self.repl.env.interrupt();
+                reply.done(&[]);
+            }
+            _ => reply.done(&["session-idle"]),
+        }
+    }
+}
+
+/// Sends the responses to one request, each with its `id` and `session`.
+#[derive(Clone)]
+struct Reply {
+    sink: Sink,
+    id: Json,
+    session: Option<String>,
+}
+
+impl Reply {
+    fn new(sink: &Sink, id: Json, session: Option<String>) -> Reply {
+        Reply {
+            sink: sink.clone(),
+            id,
+            session,
+        }
+    }
+
+    fn send(&self, mut message: Json) {
+        if !self.id.is_null() {
+            message["id"] = self.id.clone();
+        }
+        if let Some(session) = &self.session {
+            message["session"] = json!(session);
+        }
+        let mut sink = self.sink.lock().unwrap();
+        // Once the client is gone, what it asked for no longer matters.
+        let _ = writeln!(sink, "{message}").and_then(|()| sink.flush());
+    }
+
+    /// Sends the last response, with status `done` and `status`.
+    fn done(&self, status: &[&str]) {
+        let status: Vec<&str> = std::iter::once("done")
+            .chain(status.iter().copied())
+            .collect();
+        self.send(json!({"status": status}));
+    }
+}
@@ -0,0 +1,328 @@
+#[cfg(test)]
+mod tests {
+    use nrepl::{Server, OPS};
+    use serde_json::{json, Value as Json};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    /// A client of a server listening on a free port of localhost.
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+        /// Responses read while waiting for those to another request.
+        pending: Vec<Json>,
+    }
+
+    impl Client {
+        fn start() -> Client {
+            Client::connect(&Server::default())
+        }
+
+        fn connect(server: &Server) -> Client {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap();
+            let server = server.clone();
+            std::thread::spawn(move || server.listen_tcp(listener));
+            let writer = TcpStream::connect(address).unwrap();
+            Client {
+                reader: BufReader::new(writer.try_clone().unwrap()),
+                writer,
+                pending: vec![],
+            }
+        }
+
+        fn send(&mut self, request: Json) {
+            writeln!(self.writer, "{request}").unwrap();
+        }
+
+        fn receive(&mut self) -> Json {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+
+        /// The responses to request `id`, up to the one with status `done`,
+        /// without their `id` and `session`.
+        fn responses(&mut self, id: &str) -> Vec<Json> {
+            let mut responses = vec![];
+            loop {
+                let message = match self.pending.iter().position(|m| m["id"] == id) {
+                    Some(i) => self.pending.remove(i),
+                    None => self.receive(),
+                };
+                if message["id"] != id {
+                    self.pending.push(message);
+                    continue;
+                }
+                let done = (message["status"].as_array())
+                    .is_some_and(|status| status.contains(&json!("done")));
+                let mut message = message;
+                let fields = message.as_object_mut().unwrap();
+                fields.remove("id");
+                fields.remove("session");
+                responses.push(message);
+                if done {
+                    return responses;
+                }
+            }
+        }
+
+        fn request(&mut self, mut request: Json) -> Vec<Json> {
+            let id = request["op"].as_str().unwrap().to_string();
+            request["id"] = json!(id);
+            self.send(request);
+            self.responses(&id)
+        }
+
+        fn eval(&mut self, session: &str, code: &str) -> Vec<Json> {
+            self.request(json!({"op": "eval", "session": session, "code": code}))
+        }
+
+        /// Interrupts eval `id` once it has started.
+        fn interrupt(&mut self, session: &str, id: &str) {
+            loop {
+                let interrupt = json!({"op": "interrupt", "session": session, "interrupt-id": id});
+                if self.request(interrupt) == vec![json!({"status": ["done"]})] {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+
+        fn clone_session(&mut self) -> String {
+            let responses = self.request(json!({"op": "clone"}));
+            responses[0]["new-session"].as_str().unwrap().to_string()
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut client = Client::start();
+        let responses = client.request(json!({"op": "describe"}));
+        let ops = responses[0]["ops"].as_object().unwrap();
+        assert_eq!(ops.keys().collect::<Vec<_>>(), OPS.to_vec());
+        assert_eq!(responses[0]["versions"]["tiny"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(responses[0]["status"], json!(["done"]));
+    }
+
+    #[test]
+    fn test_eval_streams_output() {
+        let mut client = Client::start();
+        let session = client.clone_session();
+        assert_eq!(
+            client.eval(
+                &session,
+                "(println \"hi\") (+ 1 2) (do (print \"x\") \"s\")"
+            ),
+            vec![
+                json!({"out": "hi\n"}),
+                json!({"value": "nil", "ns": "user"}),
+                json!({"value": "3", "ns": "user"}),
+                json!({"out": "x"}),
+                json!({"value": "\"s\"", "ns": "user"}),
+                json!({"status": ["done"]}),
+            ]
+        );
+        assert_eq!(
+            client.eval(&session, "(inc 1)\n(+ 1 :a)\n(println \"never\")"),
+            vec![
+                json!({"value": "2", "ns": "user"}),
+                json!({"err": "_repl_.tiny:2:1: error: :a is not a number\n"}),
+                json!({"ex": ":a is not a number", "status": ["eval-error"]}),
+                json!({"status": ["done"]}),
+            ]
+        );
+        let responses = client.request(json!({
+            "op": "eval",
+            "session": session,
+            "code": "*1\n(missing)",
+            "file": "scratch.tiny",
+        }));
+        assert_eq!(responses[0], json!({"value": "2", "ns": "user"}));
+        assert_eq!(
+            responses[1],
+            json!({"err": "scratch.tiny:2:2: error: unable to resolve symbol: missing\n"})
+        );
+    }
+
+    #[test]
+    fn test_sessions_are_isolated() {
+        let server = Server::default();
+        let mut client = Client::connect(&server);
+        let (one, two) = (client.clone_session(), client.clone_session());
+        assert_ne!(one, two);
+        client.eval(&one, "(def x 1)");
+        assert_eq!(client.eval(&one, "x")[0]["value"], "1");
+        assert_eq!(
+            client.eval(&two, "x")[1]["ex"],
+            "unable to resolve symbol: x"
+        );
+
+        // Requests without a session use one of their connection's own.
+        let responses = client.request(json!({"op": "eval", "code": "(def y 2)"}));
+        assert_eq!(responses[0]["value"], "user/y");
+        let mut other = Client::connect(&server);
+        let responses = other.request(json!({"op": "eval", "code": "y"}));
+        assert_eq!(responses[1]["ex"], "unable to resolve symbol: y");
+        // Sessions can be shared by id, until they are closed.
+        assert_eq!(other.eval(&one, "(inc x)")[0]["value"], "2");
+        let closed = other.request(json!({"op": "close", "session": one}));
+        assert_eq!(closed, vec![json!({"status": ["done", "session-closed"]})]);
+        assert_eq!(
+            client.eval(&one, "x"),
+            vec![json!({"status": ["done", "error", "unknown-session"]})]
+        );
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut client = Client::start();
+        let session = client.clone_session();
+        let idle = client.request(json!({"op": "interrupt", "session": session}));
+        assert_eq!(idle, vec![json!({"status": ["done", "session-idle"]})]);
+        client.send(json!({
+            "op": "eval",
+            "id": "spin",
+            "session": session,
+            "code": "(defn spin [n] (recur (inc n))) (spin 0)",
+        }));
+        client.interrupt(&session, "spin");
+        assert_eq!(
+            client.responses("spin"),
+            vec![
+                json!({"value": "user/spin", "ns": "user"}),
+                json!({"status": ["interrupted"]}),
+                json!({"status": ["done"]}),
+            ]
+        );
+        assert_eq!(client.eval(&session, "(inc 1)")[0]["value"], "2");
+    }
+
+    #[test]
+    fn test_interrupt_channel_waits() {
+        let mut client = Client::start();
+        let session = client.clone_session();
+        for (id, code) in [
+            ("take", "(<! (chan))"),
+            ("timeout", "(<! (timeout 600000))"),
+        ] {
+            client.send(json!({"op": "eval", "id": id, "session": session, "code": code}));
+            client.interrupt(&session, id);
+            assert_eq!(
+                client.responses(id),
+                vec![
+                    json!({"status": ["interrupted"]}),
+                    json!({"status": ["done"]}),
+                ]
+            );
+        }
+        assert_eq!(client.eval(&session, "(inc 1)")[0]["value"], "2");
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        let mut client = Client::start();
+        let session = client.clone_session();
+        client.eval(&session, "(defn f [n] (if (= n 0) 0 (+ 1 (f (- n 1)))))");
+        assert_eq!(client.eval(&session, "(f 500)")[0]["value"], "500");
+        // Deeper recursion fails with an error instead of aborting.
+        // Its trace collapses the recursive calls.
+        let responses = client.eval(&session, "(f 100000)");
+        assert_eq!(
+            responses[0]["err"],
+            "_repl_.tiny:1:11: error: stack depth exceeded\n  at f (_repl_.tiny:1:11)\n  ... 1999 more calls to f\n  at f (_repl_.tiny:1:1)\n"
+        );
+        assert_eq!(
+            responses[1],
+            json!({"ex": "stack depth exceeded", "status": ["eval-error"]})
+        );
+        assert_eq!(client.eval(&session, "(f 1500)")[0]["value"], "1500");
+    }
+
+    #[test]
+    fn test_complete_and_lookup() {
+        let mut client = Client::start();
+        let session = client.clone_session();
+        client.eval(&session, "(defn my-fn \"Mine.\" [a] a) (def my-val 1)");
+        let completions =
+            client.request(json!({"op": "complete", "session": session, "prefix": "my-"}));
+        assert_eq!(
+            completions,
+            vec![json!({
+                "completions": [
+                    {"candidate": "my-fn", "type": "function"},
+                    {"candidate": "my-val", "type": "value"},
+                ],
+                "status": ["done"],
+            })]
+        );
+        let lookup = |client: &mut Client, sym: &str| {
+            client.request(json!({"op": "lookup", "session": session, "sym": sym}))
+        };
+        assert_eq!(
+            lookup(&mut client, "my-fn"),
+            vec![json!({
+                "info": {"name": "my-fn", "ns": "user", "doc": "Mine."},
+                "status": ["done"],
+            })]
+        );
+        let comp = lookup(&mut client, "comp");
+        assert_eq!(comp[0]["info"]["ns"], "tiny.core");
+        assert_eq!(
+            comp[0]["info"]["arglists"],
+            json!(["[]", "[f]", "[f & fs]"])
+        );
+        assert_eq!(
+            lookup(&mut client, "nope"),
+            vec![json!({"status": ["done", "no-info"]})]
+        );
+    }
+
+    #[test]
+    fn test_load_file() {
+        let dir = std::env::temp_dir().join(format!("tiny-nrepl-load-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(
+            dir.join("lib/util.tiny"),
+            "(defn twice\n  \"Doubles n.\"\n  [n]\n  (* 2 n))\n",
+        )
+        .unwrap();
+        let main = dir.join("main.tiny");
+        let mut client = Client::start();
+        let session = client.clone_session();
+        let responses = client.request(json!({
+            "op": "load-file",
+            "session": session,
+            "file": "(import [lib.util :as u])\n(u/twice 21)",
+            "file-path": main.display().to_string(),
+        }));
+        assert_eq!(responses[1], json!({"value": "42", "ns": "user"}));
+        let lookup = client.request(json!({"op": "lookup", "session": session, "sym": "u/twice"}));
+        let info = &lookup[0]["info"];
+        assert_eq!(
+            (&info["name"], &info["ns"]),
+            (&json!("twice"), &json!("lib.util"))
+        );
+        assert_eq!(
+            (&info["doc"], &info["arglists"]),
+            (&json!("Doubles n."), &json!(["[n]"]))
+        );
+        assert!(info["file"].as_str().unwrap().ends_with("/lib/util.tiny"));
+        assert_eq!(info["line"], 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut client = Client::start();
+        assert_eq!(
+            client.request(json!({"op": "bogus"})),
+            vec![json!({"status": ["done", "error", "unknown-op"]})]
+        );
+        client.send(json!([1]));
+        assert_eq!(
+            client.receive(),
+            json!({"err": "invalid request: [1]\n", "status": ["done", "error", "invalid-request"]})
+        );
+    }
+}
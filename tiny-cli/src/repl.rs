@@ -0,0 +1,171 @@
+//! `tiny repl`: reads forms from the terminal, evaluates them and prints
+//! the results.
+
+use helper::TinyHelper;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{CompletionType, Config, Editor};
+use source::Source;
+use std::path::PathBuf;
+use tiny_library::eval::{Env, EvalError};
+use tiny_library::reader::Reader;
+use tiny_library::value::Value;
+
+/// The name of what is typed at the prompt, in errors.
+pub const NAME: &str = "_repl_.tiny";
+
+/// An environment evaluating input and remembering the last results in
+/// `*1`, `*2` and `*3` and the last error in `*e`.
+pub struct Repl {
+    pub env: Env,
+}
+
+impl Repl {
+    pub fn new(env: Env) -> Repl {
+        for name in ["*1", "*2", "*3", "*e"] {
+            env.define(name, Value::Nil);
+        }
+        Repl { env }
+    }
+
+    /// Evaluates the forms of `input` in order, up to the first error.
+    pub fn eval(&self, input: &str) -> Vec<Result<Value, EvalError>> {
+        let mut results = vec![];
+        self.eval_each(NAME, input, |result| results.push(result));
+        results
+    }
+
+    /// Evaluates the forms of `input`, read as file `name`, calling `f`
+    /// with the result of each as it comes, up to the first error.
+    pub fn eval_each<F: FnMut(Result<Value, EvalError>)>(&self, name: &str, input: &str, mut f: F) {
+        let mut reader = Reader::new(name, input);
+        while let Some(form) = reader.read_spanned() {
+            let result = form
+                .map_err(EvalError::from)
+                .and_then(|(form, span)| self.env.eval_form(name, &form, &span));
+            match &result {
+                Ok(value) => self.push(value.clone()),
+                Err(err) => self.env.define("*e", err.to_value()),
+            }
+            let failed = result.is_err();
+            f(result);
+            if failed {
+                break;
+            }
+        }
+    }
+
+    fn push(&self, value: Value) {
+        let lookup = |name| self.env.lookup(name).unwrap_or(Value::Nil);
+        self.env.define("*3", lookup("*2"));
+        self.env.define("*2", lookup("*1"));
+        self.env.define("*1", value);
+    }
+
+    fn prompt(&self) -> String {
+        format!("{}=> ", self.env.ns())
+    }
+}
+
+/// Whether `input` has no unclosed brackets or strings, so it can be
+/// evaluated rather than continued on the next line.
+pub fn is_complete(input: &str) -> bool {
+    let mut reader = Reader::new(NAME, input);
+    loop {
+        match reader.read() {
+            Some(Ok(_)) => {}
+            Some(Err(err)) => return !err.is_unterminated(),
+            None => return true,
+        }
+    }
+}
+
+/// Where history is kept: `tiny/history` under `$XDG_DATA_HOME`, or
+/// under `~/.local/share` if that is unset.
+fn history_path() -> Option<PathBuf> {
+    let data = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(data.join("tiny").join("history"))
+}
+
+pub fn run(env: Env) {
+    let repl = Repl::new(env);
+    let config = Config::builder()
+        .completion_type(CompletionType::List)
+        .build();
+    let mut rl: Editor<TinyHelper, DefaultHistory> = match Editor::with_config(config) {
+        Ok(rl) => rl,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            return;
+        }
+    };
+    let helper = TinyHelper::new(repl.env.clone());
+    let history = history_path();
+    if let Some(path) = &history {
+        // There is no history the first time.
+        let _ = rl.load_history(path);
+        for entry in rl.history().iter() {
+            helper.remember(entry);
+        }
+    }
+    rl.set_helper(Some(helper));
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() {
+            repl.prompt()
+        } else {
+            format!("{:>width$}", "... ", width = repl.prompt().len())
+        };
+        match rl.readline(&prompt) {
+            Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+                if !is_complete(&input) {
+                    continue;
+                }
+                let entry = std::mem::take(&mut input);
+                if entry.trim().is_empty() {
+                    continue;
+                }
+                let _ = rl.add_history_entry(entry.trim_end());
+                if let Some(helper) = rl.helper() {
+                    helper.remember(&entry);
+                }
+                print_results(&repl, &entry);
+            }
+            Err(ReadlineError::Interrupted) => input.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Error: {err}");
+                break;
+            }
+        }
+    }
+    if let Some(path) = &history {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(err) = rl.save_history(path) {
+            eprintln!("could not save history to {}: {err}", path.display());
+        }
+    }
+}
+
+fn print_results(repl: &Repl, input: &str) {
+    let source = Source {
+        name: NAME.into(),
+        content: input.into(),
+    };
+    for result in repl.eval(input) {
+        for warning in repl.env.take_warnings() {
+            source.warn(&warning);
+        }
+        match result {
+            Ok(value) => println!("{value}"),
+            Err(err) => source.report(&err),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use repl::{is_complete, Repl};
+    use tiny_library::eval::Env;
+
+    /// Evaluates `input` and prints each result, or the error message.
+    fn show(repl: &Repl, input: &str) -> Vec<String> {
+        (repl.eval(input).into_iter())
+            .map(|result| match result {
+                Ok(value) => value.to_string(),
+                Err(err) => format!("error: {}", err.message),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_is_complete() {
+        assert!(is_complete(""));
+        assert!(is_complete("(+ 1 2)"));
+        assert!(is_complete("(+ 1 2)\n(- 3 1)\n"));
+        assert!(!is_complete("(defn f [x]\n"));
+        assert!(!is_complete("{:a [1 2"));
+        assert!(!is_complete("(println \"a\n"));
+        assert!(!is_complete("'"));
+        assert!(!is_complete("(str #\"a"));
+        // Errors more input can't fix are evaluated to report them.
+        assert!(is_complete("(+ 1 2))"));
+        assert!(is_complete("(\"\\q\""));
+    }
+
+    #[test]
+    fn test_eval_prints_canonically() {
+        let repl = Repl::new(Env::new());
+        assert_eq!(
+            show(&repl, "(def x 1) \"a\\nb\" {:a [1 2/4]} (map inc [1 2])"),
+            ["user/x", "\"a\\nb\"", "{:a [1 1/2]}", "(2 3)"]
+        );
+        assert_eq!(
+            show(&repl, "(+ x 1) (/ 1 0) (+ x 2)"),
+            ["2", "error: divide by zero"]
+        );
+    }
+
+    #[test]
+    fn test_result_vars() {
+        let repl = Repl::new(Env::new());
+        assert_eq!(show(&repl, "[*1 *2 *3 *e]"), ["[nil nil nil nil]"]);
+        show(&repl, "1 2 3");
+        assert_eq!(show(&repl, "[*1 *2 *3]"), ["[3 2 1]"]);
+        assert_eq!(show(&repl, "*1"), ["[3 2 1]"]);
+        show(&repl, "(throw (ex-info \"boom\" {:a 1}))");
+        assert_eq!(
+            show(&repl, "[(ex-message *e) (ex-data *e)]"),
+            ["[\"boom\" {:a 1}]"]
+        );
+        show(&repl, "(undefined-fn)");
+        assert_eq!(
+            show(&repl, "(ex-message *e)"),
+            ["\"unable to resolve symbol: undefined-fn\""]
+        );
+    }
+}
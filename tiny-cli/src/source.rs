@@ -0,0 +1,160 @@
+//! Reading source files, or stdin, and reporting their errors.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use tiny_library::eval::{Env, EvalError, Warning};
+use tiny_library::io::Capabilities;
+use tiny_library::reader::{line_col, Reader};
+
+/// The name of what is read from stdin, in errors.
+pub const STDIN_NAME: &str = "_stdin_.tiny";
+
+/// A source file, or stdin when it is `-`.
+pub struct Source {
+    pub name: String,
+    pub content: String,
+}
+
+impl Source {
+    /// Reads `file`, or returns its name and why it couldn't be read.
+    pub fn load(file: &str) -> Result<Source, (String, String)> {
+        let (name, content) = if file == "-" {
+            let mut content = String::new();
+            let read = std::io::stdin().read_to_string(&mut content);
+            (STDIN_NAME.to_string(), read.map(|_| content))
+        } else {
+            (file.to_string(), std::fs::read_to_string(file))
+        };
+        match content {
+            Ok(content) => Ok(Source { name, content }),
+            Err(err) => Err((name, format!("could not read: {err}"))),
+        }
+    }
+
+    pub fn read(file: &str) -> Option<Source> {
+        match Source::load(file) {
+            Ok(source) => Some(source),
+            Err((name, message)) => {
+                eprintln!("{name}: error: {message}");
+                None
+            }
+        }
+    }
+
+    /// An environment importing from the directory of the file.
+    pub fn env(&self) -> Env {
+        new_env(self.dir())
+    }
+
+    /// An environment to check the file in, importing from its directory.
+    pub fn check_env(&self) -> Env {
+        check_env(self.dir())
+    }
+
+    fn dir(&self) -> Option<&Path> {
+        if self.name == STDIN_NAME {
+            None
+        } else {
+            Path::new(&self.name).parent()
+        }
+    }
+
+    /// The content of file `name`, which may be a module this source
+    /// imported.
+    pub fn content_of(&self, name: &str) -> Option<String> {
+        if name == self.name {
+            Some(self.content.clone())
+        } else {
+            std::fs::read_to_string(name).ok()
+        }
+    }
+
+    /// `name:line:col` of `start` in file `name`.
+    pub fn position(&self, name: &str, start: usize) -> String {
+        position(name, self.content_of(name).as_deref(), start)
+    }
+
+    pub fn report(&self, err: &EvalError) {
+        eprint!("{}", self.describe(err));
+    }
+
+    pub fn warn(&self, warning: &Warning) {
+        eprint!("{}", self.describe_warning(warning));
+    }
+
+    /// The error and its trace, reading each file in it once.
+    pub fn describe(&self, err: &EvalError) -> String {
+        let mut contents = HashMap::new();
+        err.describe(|name, start| {
+            let content =
+                (contents.entry(name.to_string())).or_insert_with(|| self.content_of(name));
+            position(name, content.as_deref(), start)
+        })
+    }
+
+    pub fn describe_warning(&self, warning: &Warning) -> String {
+        format!(
+            "{}: warning: {}\n",
+            self.position(&warning.name, warning.start),
+            warning.message
+        )
+    }
+
+    /// Evaluates the forms in order, stopping at the first error.
+    pub fn eval(&self, env: &Env) -> bool {
+        let mut reader = Reader::new(&self.name, &self.content);
+        while let Some(form) = reader.read_spanned() {
+            let result = form
+                .map_err(EvalError::from)
+                .and_then(|(form, span)| env.eval_form(&self.name, &form, &span));
+            for warning in env.take_warnings() {
+                self.warn(&warning);
+            }
+            if let Err(err) = result {
+                self.report(&err);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// An environment importing modules from `TINY_PATH` and then the
+/// directory of the file being run.
+pub fn new_env(dir: Option<&Path>) -> Env {
+    let env = Env::new();
+    if let Some(paths) = std::env::var_os("TINY_PATH") {
+        for root in std::env::split_paths(&paths) {
+            env.add_source_root(root);
+        }
+    }
+    match dir {
+        Some(dir) if dir.as_os_str().is_empty() => env.add_source_root("."),
+        Some(dir) => env.add_source_root(dir),
+        None => env.add_source_root("."),
+    }
+    env
+}
+
+/// An environment like `new_env`'s for checking files, which only
+/// evaluates the modules they import: what those print is discarded, and
+/// they can't touch files, environment variables or processes.
+pub fn check_env(dir: Option<&Path>) -> Env {
+    let env = new_env(dir);
+    env.set_output(Some(Arc::new(|_, _: &str| {})));
+    env.set_capabilities(Capabilities::NONE);
+    env
+}
+
+/// `name:line:col` of `start` in `content`, or `name:start` without it.
+fn position(name: &str, content: Option<&str>, start: usize) -> String {
+    match content {
+        Some(content) => {
+            let (line, col) = line_col(content, start);
+            format!("{name}:{line}:{col}")
+        }
+        None => format!("{name}:{start}"),
+    }
+}
@@ -0,0 +1,197 @@
+//! `--watch`: runs files again when they or the modules they import
+//! change. Changes are noticed with inotify, or by polling where that isn't
+//! available or when `TINY_WATCH=poll` is set.
+
+extern crate notify;
+
+use self::notify::{
+    Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+/// How long changes must stop for before running again, so saving several
+/// files at once runs once.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Clears the terminal and moves the cursor to the top.
+const CLEAR: &str = "\x1b[2J\x1b[H";
+
+type Events = Sender<notify::Result<Event>>;
+
+/// Calls `run` with `files`, then again with the ones affected each time
+/// they or the modules they import change. `run` returns the module files
+/// each file it was given imported. Only returns if watching fails.
+pub fn watch<F: FnMut(&[String]) -> Vec<Vec<PathBuf>>>(files: Vec<String>, mut run: F) -> bool {
+    if files.iter().any(|file| file == "-") {
+        eprintln!("error: can't watch stdin");
+        return false;
+    }
+    let (sender, receiver) = mpsc::channel();
+    let mut watched = match Watched::new(sender) {
+        Some(watched) => watched,
+        None => return false,
+    };
+    let mut dependencies: Vec<HashSet<PathBuf>> = (files.iter().zip(run(&files)))
+        .map(|(file, modules)| dependencies_of(file, modules))
+        .collect();
+    loop {
+        for path in dependencies.iter().flatten() {
+            match path.parent() {
+                Some(dir) if dir.is_dir() => watched.add(dir),
+                _ => {}
+            }
+        }
+        eprintln!(
+            "watching {} for changes, press Ctrl-C to stop",
+            match files.len() {
+                1 => "1 file".to_string(),
+                n => format!("{n} files"),
+            }
+        );
+        let affected = loop {
+            let changed = match changes(&receiver) {
+                Some(changed) => changed,
+                None => return false,
+            };
+            let affected = affected(&dependencies, &changed);
+            if !affected.is_empty() {
+                break affected;
+            }
+        };
+        print!("{CLEAR}");
+        let _ = std::io::stdout().flush();
+        let names: Vec<String> = affected.iter().map(|&i| files[i].clone()).collect();
+        for (&i, modules) in affected.iter().zip(run(&names)) {
+            dependencies[i] = dependencies_of(&files[i], modules);
+        }
+    }
+}
+
+/// The paths whose changes affect `file`: itself and the modules it
+/// imported.
+pub fn dependencies_of(file: &str, modules: Vec<PathBuf>) -> HashSet<PathBuf> {
+    (std::iter::once(PathBuf::from(file)).chain(modules))
+        .map(|path| canonical(&path))
+        .collect()
+}
+
+/// The indexes of the files whose dependencies include a changed path.
+pub fn affected(dependencies: &[HashSet<PathBuf>], changed: &HashSet<PathBuf>) -> Vec<usize> {
+    (0..dependencies.len())
+        .filter(|&i| !dependencies[i].is_disjoint(changed))
+        .collect()
+}
+
+/// `path` made absolute with symlinks resolved, as the watcher reports it,
+/// even when the file was removed.
+pub fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    let parent = match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+        Some(parent) => parent,
+        None => return path.into(),
+    };
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => path.into(),
+    }
+}
+
+/// Waits for a change, then for changes to stop, returning the paths that
+/// changed. `None` means the watcher stopped.
+fn changes(receiver: &Receiver<notify::Result<Event>>) -> Option<HashSet<PathBuf>> {
+    let mut changed = HashSet::new();
+    let mut record = |event: notify::Result<Event>| match event {
+        // Reading files to run them is an access, which must not count.
+        Ok(event) => {
+            if matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                changed.extend(event.paths.iter().map(|path| canonical(path)));
+            }
+        }
+        Err(err) => eprintln!("warning: watching failed: {err}"),
+    };
+    record(receiver.recv().ok()?);
+    while let Ok(event) = receiver.recv_timeout(DEBOUNCE) {
+        record(event);
+    }
+    Some(changed)
+}
+
+/// The directories being watched. Directories rather than files are
+/// watched, since editors often save by replacing the file.
+struct Watched {
+    watcher: Box<dyn Watcher>,
+    polling: bool,
+    dirs: HashSet<PathBuf>,
+    sender: Events,
+}
+
+impl Watched {
+    fn new(sender: Events) -> Option<Watched> {
+        let poll = std::env::var("TINY_WATCH").is_ok_and(|value| value == "poll");
+        let watcher = if poll {
+            None
+        } else {
+            match RecommendedWatcher::new(sender.clone(), Config::default()) {
+                Ok(watcher) => Some(watcher),
+                Err(err) => {
+                    eprintln!("warning: polling for changes, since watching failed: {err}");
+                    None
+                }
+            }
+        };
+        let (watcher, polling): (Box<dyn Watcher>, bool) = match watcher {
+            Some(watcher) => (Box::new(watcher), false),
+            None => (Box::new(poll_watcher(&sender)?), true),
+        };
+        Some(Watched {
+            watcher,
+            polling,
+            dirs: HashSet::new(),
+            sender,
+        })
+    }
+
+    fn add(&mut self, dir: &Path) {
+        if !self.dirs.insert(dir.into()) {
+            return;
+        }
+        let err = match self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+            Ok(()) => return,
+            Err(err) => err,
+        };
+        if self.polling {
+            eprintln!("warning: could not watch {}: {err}", dir.display());
+            return;
+        }
+        // Such as when inotify runs out of watches.
+        eprintln!("warning: polling for changes, since watching failed: {err}");
+        if let Some(watcher) = poll_watcher(&self.sender) {
+            self.watcher = Box::new(watcher);
+            self.polling = true;
+            for dir in std::mem::take(&mut self.dirs) {
+                self.add(&dir);
+            }
+        }
+    }
+}
+
+fn poll_watcher(sender: &Events) -> Option<PollWatcher> {
+    let config = Config::default().with_poll_interval(POLL_INTERVAL);
+    match PollWatcher::new(sender.clone(), config) {
+        Ok(watcher) => Some(watcher),
+        Err(err) => {
+            eprintln!("error: could not watch for changes: {err}");
+            None
+        }
+    }
+}
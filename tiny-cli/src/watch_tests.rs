@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+    use watch::{affected, canonical, dependencies_of};
+
+    #[test]
+    fn test_canonical() {
+        let dir = std::env::temp_dir().join(format!("tiny-watch-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.tiny"), "").unwrap();
+        let dir = dir.canonicalize().unwrap();
+        assert_eq!(canonical(&dir.join("sub/../a.tiny")), dir.join("a.tiny"));
+        assert_eq!(
+            canonical(&dir.join("sub/../gone.tiny")),
+            dir.join("gone.tiny")
+        );
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(canonical("gone.tiny".as_ref()), cwd.join("gone.tiny"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_affected() {
+        let paths = |names: &[&str]| -> HashSet<PathBuf> {
+            names
+                .iter()
+                .map(|name| PathBuf::from(format!("/p/{name}")))
+                .collect()
+        };
+        let dependencies = [
+            paths(&["main.tiny", "lib.tiny", "util.tiny"]),
+            paths(&["other.tiny"]),
+            paths(&["test.tiny", "util.tiny"]),
+        ];
+        assert_eq!(affected(&dependencies, &paths(&["util.tiny"])), [0, 2]);
+        assert_eq!(
+            affected(&dependencies, &paths(&["other.tiny", "x.tiny"])),
+            [1]
+        );
+        assert_eq!(
+            affected(&dependencies, &paths(&["readme.md"])),
+            Vec::<usize>::new()
+        );
+        let file = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(
+            dependencies_of("main.tiny", vec!["/p/lib.tiny".into()]),
+            HashSet::from([file.join("main.tiny"), "/p/lib.tiny".into()])
+        );
+    }
+}
@@ -0,0 +1,361 @@
+extern crate serde_json;
+
+use serde_json::{json, Value as Json};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Runs `tiny` with `args` and `stdin`, returning the exit code, stdout
+/// and stderr.
+fn tiny(args: &[&str], stdin: &str) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tiny"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// A fresh directory for the files of one test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tiny-cli-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_run() {
+    let (code, out, _) = tiny(
+        &["run", "-"],
+        "(println \"top\") (defn main [] (println \"main\"))",
+    );
+    assert_eq!((code, out.as_str()), (0, "top\nmain\n"));
+    let (code, out, err) = tiny(&["run", "-"], "(println 1)\n(+ 1 :a)\n(println 2)");
+    assert_eq!((code, out.as_str()), (1, "1\n"));
+    assert_eq!(err, "_stdin_.tiny:2:1: error: :a is not a number\n");
+}
+
+#[test]
+fn test_run_single_file_and_missing_file() {
+    let dir = temp_dir("run");
+    let file = dir.join("one.tiny");
+    std::fs::write(&file, "(defn main [] (println \"one\"))").unwrap();
+    let missing = dir.join("missing.tiny");
+    let (code, out, err) = tiny(
+        &["run", missing.to_str().unwrap(), file.to_str().unwrap()],
+        "",
+    );
+    assert_eq!((code, out.as_str()), (1, "one\n"));
+    assert!(err.starts_with(&format!("{}: error: could not read", missing.display())));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_check() {
+    let (code, out, err) = tiny(&["check", "-"], "(defn f [] (launch))\n(f");
+    assert_eq!((code, out.as_str()), (1, ""));
+    assert!(err.starts_with(
+        "_stdin_.tiny:1:13: error: unable to resolve symbol: launch\n\
+         _stdin_.tiny:2:1: error: unclosed `(`\n\
+         checked 1 file (1 form) in "
+    ));
+    assert!(err.ends_with("s: 2 errors, 0 warnings\n"));
+    assert_eq!(tiny(&["check", "-"], "(defn f [] (println 1))").0, 0);
+}
+
+#[test]
+fn test_check_json() {
+    let (code, out, err) = tiny(
+        &["check", "--message-format=json", "-", "missing.tiny"],
+        "(g)",
+    );
+    assert_eq!((code, err.as_str()), (1, ""));
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with(r#"{"code":"unresolved-symbol","file":"_stdin_.tiny","#));
+    assert!(lines[0].contains(r#""byte_start":1,"#));
+    assert!(lines[1].starts_with(r#"{"code":"io","file":"missing.tiny","#));
+    assert!(lines[1].contains(r#""span":null"#));
+    assert!(lines[2].starts_with(r#"{"errors":2,"files":2,"forms":1,"reason":"check-finished","#));
+}
+
+#[test]
+fn test_check_json_with_printing_module() {
+    let dir = temp_dir("check-json");
+    std::fs::write(
+        dir.join("noisy.tiny"),
+        "(import [std.io :as io])\n(println \"loaded\")\n(io/spit \"written.txt\" \"x\")",
+    )
+    .unwrap();
+    let main = dir.join("main.tiny");
+    std::fs::write(&main, "(import [noisy :as n])\n(defn main [] n/x)").unwrap();
+    let (code, out, _) = tiny(
+        &["check", "--message-format=json", main.to_str().unwrap()],
+        "",
+    );
+    // Only diagnostics are printed, and the module can't write files.
+    assert_eq!(code, 1);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].contains(r#""message":"spit needs the write capability, which is disabled""#));
+    assert!(lines[2].starts_with(r#"{"errors":2,"files":1,"#));
+    assert!(!dir.join("written.txt").exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_check_directory() {
+    let dir = temp_dir("check");
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("main.tiny"), "(defn main [] (helper))").unwrap();
+    std::fs::write(dir.join("lib/util.tiny"), "(def x 1) (def y [x)").unwrap();
+    std::fs::write(dir.join("notes.txt"), "(").unwrap();
+    let root = dir.to_str().unwrap();
+    let (code, _, err) = tiny(&["check", root], "");
+    assert_eq!(code, 1);
+    let lines: Vec<&str> = err.lines().collect();
+    assert_eq!(
+        lines[..2],
+        [
+            format!("{root}/lib/util.tiny:1:20: error: unexpected char ')'"),
+            format!("{root}/main.tiny:1:16: error: unable to resolve symbol: helper"),
+        ]
+    );
+    assert!(lines[2].starts_with("checked 2 files (2 forms) in "));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_check_watch() {
+    let dir = temp_dir("watch");
+    std::fs::write(dir.join("main.tiny"), "(import lib) (lib/f)").unwrap();
+    std::fs::write(dir.join("other.tiny"), "(println 1)").unwrap();
+    std::fs::write(dir.join("lib.tiny"), "(defn f [] 1)").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tiny"))
+        .args(["check", "--watch", "main.tiny", "other.tiny"])
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let stderr = BufReader::new(child.stderr.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stderr.lines() {
+            let _ = sender.send(line.unwrap());
+        }
+    });
+    // The lines up to the next prompt to change something.
+    let until_watching = || -> Vec<String> {
+        let mut lines = vec![];
+        while let Ok(line) = receiver.recv_timeout(Duration::from_secs(10)) {
+            if line.starts_with("watching ") {
+                return lines;
+            }
+            lines.push(line);
+        }
+        panic!("no prompt after {:?}", lines);
+    };
+    assert!(until_watching()[0].starts_with("checked 2 files (3 forms) in "));
+    std::fs::write(dir.join("lib.tiny"), "(defn f [] 1").unwrap();
+    let lines = until_watching();
+    assert_eq!(lines[0], "./lib.tiny:1:1: error: unclosed `(`");
+    assert_eq!(lines[1], "main.tiny:1:15: error: no such namespace: lib");
+    assert!(lines[2].starts_with("checked 1 file (2 forms) in "));
+    child.kill().unwrap();
+    child.wait().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_fmt() {
+    let (code, out, _) = tiny(&["fmt", "-"], "(defn f [x]\n(+ x  1))");
+    assert_eq!((code, out.as_str()), (0, "(defn f [x]\n  (+ x 1))\n"));
+    let dir = temp_dir("fmt");
+    let file = dir.join("f.tiny");
+    let path = file.to_str().unwrap();
+    std::fs::write(&file, "( f )").unwrap();
+    let (code, _, err) = tiny(&["fmt", "--check", path], "");
+    assert_eq!((code, err), (1, format!("{path}: would reformat\n")));
+    assert_eq!(tiny(&["fmt", path], "").0, 0);
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "(f)\n");
+    assert_eq!(tiny(&["fmt", "--check", path], "").0, 0);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_test() {
+    let (code, out, err) = tiny(
+        &["test", "-"],
+        "(deftest passes (is (= 2 (+ 1 1))))\n(deftest fails (is (= 3 (+ 1 1))))",
+    );
+    assert_eq!(code, 1);
+    assert_eq!(out, "_stdin_.tiny: 1 passed, 1 failed\n");
+    assert_eq!(
+        err,
+        "FAIL fails\n_stdin_.tiny:2:1: error: assertion failed: (= 3 (+ 1 1))\n"
+    );
+}
+
+#[test]
+fn test_compile() {
+    let dir = temp_dir("compile");
+    let file = dir.join("hello.tiny");
+    std::fs::write(&file, "(println \"hello\")").unwrap();
+    let (code, _, _) = tiny(&["compile", file.to_str().unwrap()], "");
+    assert_eq!(code, 0);
+    let compiled = dir.join("hello.tinyc");
+    let (code, out, _) = tiny(&["run", compiled.to_str().unwrap()], "");
+    assert_eq!((code, out.as_str()), (0, "hello\n"));
+
+    // stdin is compiled to stdout, not to a file named `-.tinyc`
+    let (code, out, _) = tiny(&["compile", "-"], "(println \"hello\")");
+    assert_eq!(code, 0);
+    assert_eq!(out.as_bytes(), &std::fs::read(&compiled).unwrap()[..]);
+    assert!(!PathBuf::from("-.tinyc").exists());
+}
+
+#[test]
+fn test_repl() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tiny"))
+        .arg("repl")
+        .env("XDG_DATA_HOME", temp_dir("repl"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let input = "(defenum Light Red Green)\n(match Red Red 1)\n\n(inc 1)\n(+ 1\n  :a)\n";
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "user/Light\n1\n2\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "_repl_.tiny:1:1: warning: match on Light is not exhaustive, missing Green\n\
+         _repl_.tiny:1:1: error: :a is not a number\n"
+    );
+}
+
+#[test]
+fn test_dump_and_usage() {
+    let (code, out, _) = tiny(&["dump", "-"], "(a 1)");
+    assert_eq!((code, out.as_str()), (0, "(\n  'a' symbol\n  '1' int\n)\n"));
+    assert_eq!(tiny(&["run"], "").0, 2);
+    assert_eq!(tiny(&["bogus"], "").0, 2);
+    let (code, out, _) = tiny(&["--version"], "");
+    assert_eq!((code, out.as_str()), (0, "tiny 0.1.0\n"));
+}
+
+#[test]
+fn test_lsp() {
+    let dir = temp_dir("lsp");
+    std::fs::write(dir.join("noisy.tiny"), "(println \"loaded\")").unwrap();
+    let opened = json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+        "textDocument": {
+            "uri": format!("file://{}", dir.join("main.tiny").display()),
+            "languageId": "tiny",
+            "version": 1,
+            "text": "(import noisy)",
+        },
+    }});
+    let message = |body: &str| format!("Content-Length: {}\r\n\r\n{body}", body.len());
+    let input = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+        r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"untitled:a","languageId":"tiny","version":1,"text":"(inc x)"}}}"#,
+        &opened.to_string(),
+        r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}"#,
+        r#"{"jsonrpc":"2.0","method":"exit"}"#,
+    ]
+    .map(message)
+    .concat();
+    let (code, out, _) = tiny(&["lsp"], &input);
+    assert_eq!(code, 0);
+    assert!(out.contains(r#""serverInfo":{"name":"tiny""#));
+    assert!(out.contains(r#""message":"unable to resolve symbol: x""#));
+    // What imported modules print doesn't get mixed into the protocol.
+    assert!(!out.contains("loaded"));
+    assert!(out.ends_with(r#"{"id":2,"jsonrpc":"2.0","result":null}"#));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Starts `tiny nrepl` with `args`, returning it and where it listens.
+fn nrepl(args: &[&str]) -> (std::process::Child, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tiny"))
+        .arg("nrepl")
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.as_mut().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let address = line.trim().rsplit(' ').next().unwrap().to_string();
+    (child, address)
+}
+
+/// Sends an eval of `code` and returns the responses up to `done`.
+fn nrepl_eval<S: Read + Write>(stream: S, code: &str) -> Vec<Json> {
+    let mut reader = BufReader::new(stream);
+    let request = json!({"op": "eval", "id": "1", "code": code});
+    writeln!(reader.get_mut(), "{request}").unwrap();
+    let mut responses = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let response: Json = serde_json::from_str(&line).unwrap();
+        let done = response["status"] == json!(["done"]);
+        responses.push(response);
+        if done {
+            return responses;
+        }
+    }
+}
+
+#[test]
+fn test_nrepl() {
+    let (mut child, address) = nrepl(&["--port", "0"]);
+    let stream = std::net::TcpStream::connect(&address).unwrap();
+    let responses = nrepl_eval(stream, "(println \"hi\") (+ 1 2)");
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(responses[0]["out"], "hi\n");
+    assert_eq!(responses[2]["value"], "3");
+    assert_eq!(responses.len(), 4);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_nrepl_unix_socket() {
+    let socket = temp_dir("nrepl").join("tiny.sock");
+    let _ = std::fs::remove_file(&socket);
+    let (mut child, address) = nrepl(&["--socket", socket.to_str().unwrap()]);
+    assert_eq!(PathBuf::from(&address), socket);
+    let stream = std::os::unix::net::UnixStream::connect(&socket).unwrap();
+    let responses = nrepl_eval(stream, "(str \"a\" 1)");
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(responses[0]["value"], "\"a1\"");
+}
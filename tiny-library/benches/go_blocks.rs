@@ -0,0 +1,33 @@
+//! Spawns 200,000 go blocks that each bump an atom, as
+//! examples/letgo/goroutines.tiny does, and waits for all of them.
+//!
+//! Run with `cargo bench -p tiny-library --bench go_blocks`.
+
+extern crate tiny_library;
+
+use std::time::Instant;
+use tiny_library::eval::Env;
+use tiny_library::value::Value;
+
+const PROGRAM: &str = "
+(let [state (atom 0)]
+  (dotimes [_ n]
+    (go (swap! state inc)))
+  (while (< @state n))
+  @state)";
+
+fn main() {
+    let n = 200_000;
+    let env = Env::new();
+    env.eval_str("go_blocks.tiny", &format!("(def n {n})"))
+        .unwrap();
+    for run in 1..=5 {
+        let begin = Instant::now();
+        let state = env.eval_str("go_blocks.tiny", PROGRAM).unwrap();
+        assert_eq!(state, Value::Int(n));
+        println!(
+            "run {run}: {n} go blocks in {:.1} ms",
+            begin.elapsed().as_secs_f64() * 1000.0
+        );
+    }
+}
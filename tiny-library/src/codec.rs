@@ -0,0 +1,445 @@
+//! Compact binary encoding of `Value` trees.
+//!
+//! Layout (all integers are LEB128 varints unless noted):
+//!
+//! ```text
+//! "TINY" version
+//! string-count (len bytes)*       ; interned symbol/keyword parts
+//! form-count value*
+//! ```
+//!
+//! Signed integers are zigzag encoded, big integers are length prefixed
+//! two's complement little-endian bytes, floats are 8 little-endian bytes,
+//! strings are length prefixed and collections are count prefixed.
+
+extern crate num_bigint;
+use self::num_bigint::BigInt;
+use crate::math;
+use crate::value::{Ratio, Value};
+use std::collections::HashMap;
+
+pub const MAGIC: &[u8; 4] = b"TINY";
+pub const VERSION: u8 = 2;
+/// How deeply collections may nest, so corrupt input can't exhaust the stack.
+pub const MAX_DEPTH: usize = 512;
+
+const TAG_INT: u8 = 0;
+const TAG_FLOAT: u8 = 1;
+const TAG_RATIONAL: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_SYMBOL: u8 = 4;
+const TAG_KEYWORD: u8 = 5;
+const TAG_LIST_PAREN: u8 = 6;
+const TAG_LIST_BRACKET: u8 = 7;
+const TAG_LIST_BRACE: u8 = 8;
+const TAG_NIL: u8 = 9;
+const TAG_FALSE: u8 = 10;
+const TAG_TRUE: u8 = 11;
+const TAG_BIG_INT: u8 = 12;
+
+/// A decoded value borrowing its strings from the input buffer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueRef<'a> {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    BigInt(&'a [u8]),
+    Float(f64),
+    Rational(i64, i64),
+    String(&'a str),
+    Symbol(&'a str, &'a str),
+    Keyword(&'a str, &'a str),
+    ListParen(Vec<ValueRef<'a>>),
+    ListBracket(Vec<ValueRef<'a>>),
+    ListBrace(Vec<ValueRef<'a>>),
+}
+
+impl<'a> ValueRef<'a> {
+    pub fn to_value(&self) -> Value {
+        match self {
+            ValueRef::Nil => Value::Nil,
+            ValueRef::Bool(v) => Value::Bool(*v),
+            ValueRef::Int(v) => Value::Int(*v),
+            ValueRef::BigInt(bytes) => Value::BigInt(BigInt::from_signed_bytes_le(bytes)),
+            ValueRef::Float(v) => Value::Float(*v),
+            ValueRef::Rational(n, d) => Value::Rational(Ratio::new(*n, *d)),
+            ValueRef::String(v) => Value::String((*v).into()),
+            ValueRef::Symbol(ns, name) => Value::Symbol((*ns).into(), (*name).into()),
+            ValueRef::Keyword(ns, name) => Value::Keyword((*ns).into(), (*name).into()),
+            ValueRef::ListParen(list) => Value::ListParen(to_values(list)),
+            ValueRef::ListBracket(list) => Value::ListBracket(to_values(list)),
+            ValueRef::ListBrace(list) => Value::ListBrace(to_values(list)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeError {
+    pub offset: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodeError {
+    pub message: String,
+}
+
+pub fn encode(forms: &[Value]) -> Result<Vec<u8>, EncodeError> {
+    let mut strings = Strings::default();
+    let mut body = vec![];
+    write_uint(&mut body, forms.len() as u64);
+    for form in forms {
+        encode_value(&mut body, &mut strings, form, 0)?;
+    }
+
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    write_uint(&mut out, strings.table.len() as u64);
+    for string in &strings.table {
+        write_uint(&mut out, string.len() as u64);
+        out.extend_from_slice(string.as_bytes());
+    }
+    out.extend(body);
+    Ok(out)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<Value>, DecodeError> {
+    Ok(to_values(&decode_ref(bytes)?))
+}
+
+pub fn decode_ref(bytes: &[u8]) -> Result<Vec<ValueRef<'_>>, DecodeError> {
+    let mut decoder = Decoder {
+        bytes,
+        pos: 0,
+        depth: 0,
+    };
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(decoder.error("invalid magic header"));
+    }
+    decoder.pos = MAGIC.len();
+    let version = decoder.read_byte()?;
+    if version != VERSION {
+        return Err(DecodeError {
+            offset: decoder.pos - 1,
+            message: format!("unsupported version {version}, expected {VERSION}"),
+        });
+    }
+
+    let count = decoder.read_len()?;
+    let mut strings = Vec::with_capacity(count);
+    for _ in 0..count {
+        strings.push(decoder.read_str()?);
+    }
+
+    let count = decoder.read_len()?;
+    let mut forms = Vec::with_capacity(count);
+    for _ in 0..count {
+        forms.push(decoder.read_value(&strings)?);
+    }
+    if decoder.pos != bytes.len() {
+        return Err(decoder.error("trailing bytes after last form"));
+    }
+    Ok(forms)
+}
+
+#[derive(Default)]
+struct Strings {
+    table: Vec<String>,
+    index: HashMap<String, u64>,
+}
+
+impl Strings {
+    fn intern(&mut self, string: &str) -> u64 {
+        if let Some(index) = self.index.get(string) {
+            return *index;
+        }
+        let index = self.table.len() as u64;
+        self.table.push(string.into());
+        self.index.insert(string.into(), index);
+        index
+    }
+}
+
+fn encode_value(
+    out: &mut Vec<u8>,
+    strings: &mut Strings,
+    value: &Value,
+    depth: usize,
+) -> Result<(), EncodeError> {
+    match value {
+        Value::Nil => out.push(TAG_NIL),
+        Value::Bool(false) => out.push(TAG_FALSE),
+        Value::Bool(true) => out.push(TAG_TRUE),
+        Value::Int(v) => {
+            out.push(TAG_INT);
+            write_int(out, *v);
+        }
+        Value::BigInt(v) => {
+            let bytes = v.to_signed_bytes_le();
+            out.push(TAG_BIG_INT);
+            write_uint(out, bytes.len() as u64);
+            out.extend_from_slice(&bytes);
+        }
+        Value::Float(v) => {
+            out.push(TAG_FLOAT);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        Value::Rational(rat) => {
+            out.push(TAG_RATIONAL);
+            write_int(out, rat.numer());
+            write_int(out, rat.denom());
+        }
+        Value::String(v) => {
+            out.push(TAG_STRING);
+            write_uint(out, v.len() as u64);
+            out.extend_from_slice(v.as_bytes());
+        }
+        Value::Symbol(ns, name) => encode_name(out, strings, TAG_SYMBOL, ns, name),
+        Value::Keyword(ns, name) => encode_name(out, strings, TAG_KEYWORD, ns, name),
+        Value::ListParen(list) => encode_list(out, strings, TAG_LIST_PAREN, list, depth)?,
+        Value::ListBracket(list) => encode_list(out, strings, TAG_LIST_BRACKET, list, depth)?,
+        Value::ListBrace(list) => encode_list(out, strings, TAG_LIST_BRACE, list, depth)?,
+        Value::Fn(f) => {
+            return Err(EncodeError {
+                message: format!("cannot encode function {}", f.name()),
+            })
+        }
+        Value::Struct(s) => {
+            return Err(EncodeError {
+                message: format!("cannot encode struct {}", s.ty.name),
+            })
+        }
+        Value::Atom(_) => {
+            return Err(EncodeError {
+                message: "cannot encode atom".into(),
+            })
+        }
+        Value::Chan(_) => {
+            return Err(EncodeError {
+                message: "cannot encode channel".into(),
+            })
+        }
+        Value::LazySeq(_) => {
+            return Err(EncodeError {
+                message: "cannot encode lazy seq".into(),
+            })
+        }
+        Value::Regex(_) => {
+            return Err(EncodeError {
+                message: "cannot encode regex".into(),
+            })
+        }
+        Value::Handle(_) => {
+            return Err(EncodeError {
+                message: "cannot encode file handle".into(),
+            })
+        }
+        Value::Error(ex) => {
+            return Err(EncodeError {
+                message: format!("cannot encode error {:?}", ex.message),
+            })
+        }
+        Value::Variant(v) => {
+            return Err(EncodeError {
+                message: format!("cannot encode variant {v}"),
+            })
+        }
+    }
+    Ok(())
+}
+
+fn encode_name(out: &mut Vec<u8>, strings: &mut Strings, tag: u8, ns: &str, name: &str) {
+    out.push(tag);
+    write_uint(out, strings.intern(ns));
+    write_uint(out, strings.intern(name));
+}
+
+fn encode_list(
+    out: &mut Vec<u8>,
+    strings: &mut Strings,
+    tag: u8,
+    list: &[Value],
+    depth: usize,
+) -> Result<(), EncodeError> {
+    if depth == MAX_DEPTH {
+        return Err(EncodeError {
+            message: format!("collections nested deeper than {MAX_DEPTH}"),
+        });
+    }
+    out.push(tag);
+    write_uint(out, list.len() as u64);
+    for item in list {
+        encode_value(out, strings, item, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn write_uint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn write_int(out: &mut Vec<u8>, v: i64) {
+    write_uint(out, ((v << 1) ^ (v >> 63)) as u64);
+}
+
+fn to_values(list: &[ValueRef]) -> Vec<Value> {
+    list.iter().map(ValueRef::to_value).collect()
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// The number of collections being read.
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn read_value(&mut self, strings: &[&'a str]) -> Result<ValueRef<'a>, DecodeError> {
+        let start = self.pos;
+        match self.read_byte()? {
+            TAG_NIL => Ok(ValueRef::Nil),
+            TAG_FALSE => Ok(ValueRef::Bool(false)),
+            TAG_TRUE => Ok(ValueRef::Bool(true)),
+            TAG_INT => Ok(ValueRef::Int(self.read_int()?)),
+            TAG_BIG_INT => {
+                let len = self.read_len()?;
+                Ok(ValueRef::BigInt(self.read_bytes(len)?))
+            }
+            TAG_FLOAT => {
+                let bytes = self.read_bytes(8)?;
+                let mut buf = [0; 8];
+                buf.copy_from_slice(bytes);
+                Ok(ValueRef::Float(f64::from_le_bytes(buf)))
+            }
+            TAG_RATIONAL => {
+                let (n, d) = (self.read_int()?, self.read_int()?);
+                // Only the normalized ratios the encoder writes, with a
+                // denominator above 1 and no common factor.
+                if math::ratio(n, d) != Some(Value::Rational(Ratio::new(n, d))) {
+                    return Err(DecodeError {
+                        offset: start,
+                        message: format!("invalid rational {n}/{d}"),
+                    });
+                }
+                Ok(ValueRef::Rational(n, d))
+            }
+            TAG_STRING => Ok(ValueRef::String(self.read_str()?)),
+            TAG_SYMBOL => {
+                let (ns, name) = self.read_name(strings)?;
+                Ok(ValueRef::Symbol(ns, name))
+            }
+            TAG_KEYWORD => {
+                let (ns, name) = self.read_name(strings)?;
+                Ok(ValueRef::Keyword(ns, name))
+            }
+            TAG_LIST_PAREN => Ok(ValueRef::ListParen(self.read_list(strings)?)),
+            TAG_LIST_BRACKET => Ok(ValueRef::ListBracket(self.read_list(strings)?)),
+            TAG_LIST_BRACE => Ok(ValueRef::ListBrace(self.read_list(strings)?)),
+            tag => Err(DecodeError {
+                offset: start,
+                message: format!("unknown tag {tag}"),
+            }),
+        }
+    }
+
+    fn read_list(&mut self, strings: &[&'a str]) -> Result<Vec<ValueRef<'a>>, DecodeError> {
+        if self.depth == MAX_DEPTH {
+            return Err(DecodeError {
+                offset: self.pos - 1,
+                message: format!("collections nested deeper than {MAX_DEPTH}"),
+            });
+        }
+        let count = self.read_len()?;
+        let mut items = Vec::with_capacity(count);
+        self.depth += 1;
+        for _ in 0..count {
+            items.push(self.read_value(strings)?);
+        }
+        self.depth -= 1;
+        Ok(items)
+    }
+
+    fn read_name(&mut self, strings: &[&'a str]) -> Result<(&'a str, &'a str), DecodeError> {
+        Ok((self.read_interned(strings)?, self.read_interned(strings)?))
+    }
+
+    fn read_interned(&mut self, strings: &[&'a str]) -> Result<&'a str, DecodeError> {
+        let start = self.pos;
+        let index = self.read_uint()?;
+        strings
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| DecodeError {
+                offset: start,
+                message: format!("string index {index} out of range"),
+            })
+    }
+
+    fn read_str(&mut self) -> Result<&'a str, DecodeError> {
+        let len = self.read_len()?;
+        let start = self.pos;
+        let bytes = self.read_bytes(len)?;
+        std::str::from_utf8(bytes).map_err(|_| DecodeError {
+            offset: start,
+            message: "invalid utf-8 in string".into(),
+        })
+    }
+
+    fn read_len(&mut self) -> Result<usize, DecodeError> {
+        let start = self.pos;
+        let len = self.read_uint()?;
+        // every entry takes at least one byte, so longer lengths are corrupt
+        if len > (self.bytes.len() - self.pos) as u64 {
+            return Err(DecodeError {
+                offset: start,
+                message: format!("length {len} exceeds input"),
+            });
+        }
+        Ok(len as usize)
+    }
+
+    fn read_int(&mut self) -> Result<i64, DecodeError> {
+        let v = self.read_uint()?;
+        Ok(((v >> 1) as i64) ^ -((v & 1) as i64))
+    }
+
+    fn read_uint(&mut self) -> Result<u64, DecodeError> {
+        let start = self.pos;
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_byte()?;
+            v |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(DecodeError {
+            offset: start,
+            message: "varint too long".into(),
+        })
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.bytes.len() - self.pos < len {
+            return Err(self.error("unexpected end of input"));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn error(&self, message: &str) -> DecodeError {
+        DecodeError {
+            offset: self.pos,
+            message: message.into(),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+#[cfg(test)]
+mod tests {
+    use codec::{
+        decode, decode_ref, encode, DecodeError, EncodeError, ValueRef, MAGIC, MAX_DEPTH, VERSION,
+    };
+    use reader::Reader;
+    use value::{Ratio, Value};
+
+    fn read_all(content: &str) -> Vec<Value> {
+        let mut reader = Reader::new("_test_.tiny", content);
+        let mut forms = vec![];
+        while let Some(form) = reader.read() {
+            forms.push(form.unwrap());
+        }
+        forms
+    }
+
+    #[test]
+    fn test_roundtrip_empty() {
+        let bytes = encode(&[]).unwrap();
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(bytes[4], VERSION);
+        assert_eq!(decode(&bytes), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_roundtrip_scalars() {
+        let forms = vec![
+            Value::Nil,
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Int(0),
+            Value::Int(-1),
+            Value::Int(i64::MAX),
+            Value::Int(i64::MIN),
+            Value::BigInt("-79228162514264337593543950336".parse().unwrap()),
+            Value::BigInt("9223372036854775808".parse().unwrap()),
+            Value::Float(-1.25),
+            Value::Rational(Ratio::new(-3, 4)),
+            Value::String("héllo\n".into()),
+            Value::Symbol("".into(), "str/join".into()),
+            Value::Keyword("".into(), "foo".into()),
+        ];
+        assert_eq!(decode(&encode(&forms).unwrap()), Ok(forms));
+    }
+
+    #[test]
+    fn test_roundtrip_source() {
+        let forms = read_all(
+            r#"
+(defn parse [ls]
+  (let loop [[l & r] ls sum 0 out []]
+    (cond (nil? l) out
+          (loop r (+ sum (parse-int l)) out))))
+{:a "a" :b 1/2 :c 3.5}
+"#,
+        );
+        assert_eq!(decode(&encode(&forms).unwrap()), Ok(forms));
+    }
+
+    #[test]
+    fn test_interned_names() {
+        let one = encode(&read_all(":key")).unwrap();
+        let many = encode(&read_all(":key :key :key :key")).unwrap();
+        // each repeated keyword costs only its tag and two table indexes
+        assert_eq!(many.len() - one.len(), 3 * 3);
+    }
+
+    #[test]
+    fn test_decode_borrows_strings() {
+        let bytes = encode(&[Value::String("zero copy".into())]).unwrap();
+        let forms = decode_ref(&bytes).unwrap();
+        match &forms[..] {
+            [ValueRef::String(s)] => {
+                let range = bytes.as_ptr_range();
+                assert!(range.contains(&s.as_ptr()));
+            }
+            _ => panic!("unexpected forms {:?}", forms),
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(
+            decode(b"NOPE\x02"),
+            Err(DecodeError {
+                offset: 0,
+                message: "invalid magic header".into(),
+            })
+        );
+        assert_eq!(
+            decode(b"TINY\x09"),
+            Err(DecodeError {
+                offset: 4,
+                message: "unsupported version 9, expected 2".into(),
+            })
+        );
+
+        let mut bytes = encode(&[Value::ListBracket(vec![Value::Int(1), Value::Int(2)])]).unwrap();
+        bytes.pop();
+        assert_eq!(
+            decode(&bytes),
+            Err(DecodeError {
+                offset: 12,
+                message: "unexpected end of input".into(),
+            })
+        );
+
+        assert_eq!(
+            decode(b"TINY\x02\x00\x01\x04\x00\x00"),
+            Err(DecodeError {
+                offset: 8,
+                message: "string index 0 out of range".into(),
+            })
+        );
+        assert_eq!(
+            decode(b"TINY\x02\x00\x01\x2a"),
+            Err(DecodeError {
+                offset: 7,
+                message: "unknown tag 42".into(),
+            })
+        );
+        for (bytes, rational) in [
+            (b"TINY\x02\x00\x01\x02\x02\x00", "1/0"),
+            (b"TINY\x02\x00\x01\x02\x04\x08", "2/4"),
+            (b"TINY\x02\x00\x01\x02\x02\x03", "1/-2"),
+        ] {
+            assert_eq!(
+                decode(bytes),
+                Err(DecodeError {
+                    offset: 7,
+                    message: format!("invalid rational {rational}"),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_depth() {
+        let mut nested = Value::Nil;
+        for _ in 0..MAX_DEPTH {
+            nested = Value::ListParen(vec![nested]);
+        }
+        let forms = vec![nested];
+        assert_eq!(decode(&encode(&forms).unwrap()), Ok(forms.clone()));
+        let deeper = Value::ListParen(forms);
+        assert_eq!(
+            encode(&[deeper]),
+            Err(EncodeError {
+                message: "collections nested deeper than 512".into(),
+            })
+        );
+
+        // a crafted file of nested list tags fails instead of overflowing
+        let mut bytes = b"TINY\x02\x00\x01".to_vec();
+        for _ in 0..1_000_000 {
+            bytes.extend_from_slice(&[6, 1]);
+        }
+        bytes.push(9);
+        assert_eq!(
+            decode(&bytes),
+            Err(DecodeError {
+                offset: 7 + 2 * MAX_DEPTH,
+                message: "collections nested deeper than 512".into(),
+            })
+        );
+    }
+}
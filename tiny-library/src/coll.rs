@@ -0,0 +1,1239 @@
+//! The collection, sequence and higher-order functions of `tiny.core`.
+//!
+//! Maps are flat lists of keys and values, so lookups are linear and `assoc`
+//! keeps the order keys were first added in. Functions that Clojure makes
+//! lazy return lazy sequences here too.
+
+use crate::core::{arity, Doc};
+use crate::eval::{self, Env, EvalError};
+use crate::math::{self, Num};
+use crate::seq::{self, Call};
+use crate::types::Struct;
+use crate::value::{NativeFn, Value};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+
+pub const NATIVES: &[(&str, NativeFn, Doc)] = &[
+    ("seq", seq, "Returns coll as a sequence, or nil if it is empty."),
+    ("first", first, "Returns the first item of coll, or nil if it is empty."),
+    ("second", second, "Returns the second item of coll."),
+    ("last", last, "Returns the last item of coll, realizing all of it."),
+    ("rest", rest, "Returns the items after the first, as an empty list if there are none."),
+    ("next", next, "Returns the items after the first, or nil if there are none."),
+    ("butlast", butlast, "Returns all items but the last, or nil if there are none."),
+    ("nth", nth, "Returns the item at index i, or not-found if given when i is out of bounds."),
+    ("cons", cons, "Returns a sequence of x followed by the items of coll."),
+    ("lazy-seq*", lazy_seq, "Returns a lazy sequence of what calling f returns."),
+    ("realized?", is_realized, "Returns true if the lazy sequence has been computed."),
+    ("doall", doall, "Realizes every item of a lazy sequence and returns it."),
+    ("range", range, "Returns the integers from start (0) to end (forever) by step (1)."),
+    ("repeat", repeat, "Returns x forever, or n times."),
+    ("iterate", iterate, "Returns x, (f x), (f (f x)) and so on."),
+    ("cycle", cycle, "Returns the items of coll over and over."),
+    ("take", take, "Returns the first n items of coll."),
+    ("drop", drop, "Returns the items of coll after the first n."),
+    ("take-while", take_while, "Returns the items of coll up to the first that pred is false for."),
+    ("drop-while", drop_while, "Returns the items of coll from the first that pred is false for."),
+    ("split-at", split_at, "Returns [(take n coll) (drop n coll)]."),
+    ("map", map, "Returns a lazy sequence of f applied to the items of each coll in turn."),
+    ("map-indexed", map_indexed, "Returns a lazy sequence of (f index item) for each item."),
+    ("mapv", mapv, "Returns a vector of f applied to the items of each coll in turn."),
+    ("mapcat", mapcat, "Returns the concatenation of the sequences (map f colls) returns."),
+    ("filter", filter, "Returns a lazy sequence of the items of coll pred is true for."),
+    ("filterv", filterv, "Returns a vector of the items of coll pred is true for."),
+    ("remove", remove, "Returns a lazy sequence of the items of coll pred is false for."),
+    ("keep", keep, "Returns a lazy sequence of the non-nil results of (f item)."),
+    ("concat", concat, "Returns a lazy sequence of the items of each coll in turn."),
+    ("interpose", interpose, "Returns the items of coll with sep between each two."),
+    ("distinct", distinct, "Returns the items of coll without repeats."),
+    ("flatten", flatten, "Returns the items of any nested lists and vectors in coll."),
+    ("partition", partition, "Returns lists of n items of coll, each step (n) items apart, padded from pad."),
+    ("partition-all", partition_all, "Like partition, but keeps lists shorter than n at the end."),
+    ("reduce", reduce, "Combines the items of coll with f, starting from init or the first item."),
+    ("reductions", reductions, "Returns a lazy sequence of the values reduce goes through."),
+    ("apply", apply, "Calls f with args and then the items of the last argument."),
+    ("some", some, "Returns the first truthy result of (pred item), or nil."),
+    ("every?", is_every, "Returns true if pred is true for every item of coll."),
+    ("not-any?", is_not_any, "Returns true if pred is false for every item of coll."),
+    ("sort", sort, "Returns the items of coll in order, by compare or by comparator."),
+    ("sort-by", sort_by, "Returns the items of coll in the order of (keyfn item)."),
+    ("compare", compare, "Returns a negative number, zero or a positive number as x is less than, equal to or greater than y."),
+    ("reverse", reverse, "Returns the items of coll in reverse order."),
+    ("frequencies", frequencies, "Returns a map from each distinct item of coll to how often it occurs."),
+    ("group-by", group_by, "Returns a map from each (f item) to a vector of the items with that result."),
+    ("count", count, "Returns the number of items in coll."),
+    ("empty?", is_empty, "Returns true if coll has no items."),
+    ("not-empty", not_empty, "Returns coll, or nil if it has no items."),
+    ("empty", empty, "Returns an empty collection of the same kind as coll."),
+    ("get", get, "Returns the value of key in a map, struct, vector or string, or not-found."),
+    ("get-in", get_in, "Returns the value at the path of keys ks in nested collections."),
+    ("contains?", contains, "Returns true if coll has key, or index for vectors and strings."),
+    ("assoc", assoc, "Returns coll with each key set to the value after it."),
+    ("assoc-in", assoc_in, "Returns m with the value at the path of keys ks set to v."),
+    ("dissoc", dissoc, "Returns map without keys."),
+    ("update", update, "Returns m with the value of k set to (f value args...)."),
+    ("update-in", update_in, "Returns m with the value at the path ks set to (f value args...)."),
+    ("conj", conj, "Returns coll with xs added: at the end of vectors, at the front of lists."),
+    ("into", into, "Returns to with every item of from added by conj."),
+    ("keys", keys, "Returns the keys of a map, or nil if it is empty."),
+    ("vals", vals, "Returns the values of a map, or nil if it is empty."),
+    ("key", key, "Returns the key of a map entry."),
+    ("val", val, "Returns the value of a map entry."),
+    ("merge", merge, "Returns a map of the entries of every map, later ones winning."),
+    ("select-keys", select_keys, "Returns a map of the entries of m whose keys are in ks."),
+    ("zipmap", zipmap, "Returns a map from each item of ks to the item of vs at the same index."),
+    ("hash-map", hash_map, "Returns a map of the keys and values given."),
+    ("vec", vec, "Returns a vector of the items of coll."),
+    ("identity", identity, "Returns x."),
+    ("seq?", is_seq, "Returns true if x is a list or lazy sequence."),
+    ("vector?", is_vector, "Returns true if x is a vector."),
+    ("map?", is_map, "Returns true if x is a map."),
+    ("coll?", is_coll, "Returns true if x is a list, vector, map or lazy sequence."),
+    ("sequential?", is_sequential, "Returns true if x is a list, vector or lazy sequence."),
+    ("string?", is_string, "Returns true if x is a string."),
+    ("keyword?", is_keyword, "Returns true if x is a keyword."),
+    ("number?", is_number, "Returns true if x is a number."),
+    ("int?", is_int, "Returns true if x is an integer."),
+    ("float?", is_float, "Returns true if x is a float."),
+    ("boolean?", is_boolean, "Returns true if x is true or false."),
+    ("fn?", is_fn, "Returns true if x is a function."),
+    ("some?", is_some, "Returns true if x is not nil."),
+    ("true?", is_true, "Returns true if x is true."),
+    ("false?", is_false, "Returns true if x is false."),
+    ("even?", is_even, "Returns true if n is even."),
+    ("odd?", is_odd, "Returns true if n is odd."),
+    ("pos?", is_pos, "Returns true if n is greater than zero."),
+    ("neg?", is_neg, "Returns true if n is less than zero."),
+    ("name", name, "Returns the name of a keyword or symbol, without its namespace, or a string."),
+    ("keyword", keyword, "Returns the keyword with the given name and optional namespace."),
+    ("symbol", symbol, "Returns the symbol with the given name and optional namespace."),
+];
+
+fn caller(env: &Env, f: &Value) -> Call {
+    let (env, f) = (env.clone(), f.clone());
+    Arc::new(move |args| env.apply(&f, args))
+}
+
+fn count_arg(name: &str, n: &Value) -> Result<usize, EvalError> {
+    match n {
+        Value::Int(n) => Ok((*n).max(0) as usize),
+        n => Err(EvalError::new(format!("{name} expects a count, got {n}"))),
+    }
+}
+
+fn seq(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => seq::seq(coll),
+        _ => Err(arity("seq", args.len())),
+    }
+}
+
+fn first(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => seq::first(coll),
+        _ => Err(arity("first", args.len())),
+    }
+}
+
+fn second(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => Ok(seq::split(coll, 2)?
+            .0
+            .into_iter()
+            .nth(1)
+            .unwrap_or(Value::Nil)),
+        _ => Err(arity("second", args.len())),
+    }
+}
+
+fn last(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => Ok(seq::to_vec(coll)?.pop().unwrap_or(Value::Nil)),
+        _ => Err(arity("last", args.len())),
+    }
+}
+
+fn rest(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => seq::rest(coll),
+        _ => Err(arity("rest", args.len())),
+    }
+}
+
+fn next(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => seq::next(coll),
+        _ => Err(arity("next", args.len())),
+    }
+}
+
+fn butlast(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => {
+            let mut items = seq::to_vec(coll)?;
+            items.pop();
+            Ok(if items.is_empty() {
+                Value::Nil
+            } else {
+                Value::ListParen(items)
+            })
+        }
+        _ => Err(arity("butlast", args.len())),
+    }
+}
+
+fn nth(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (coll, index, not_found) = match &args[..] {
+        [coll, index] => (coll, index, None),
+        [coll, index, not_found] => (coll, index, Some(not_found)),
+        _ => return Err(arity("nth", args.len())),
+    };
+    let i = match index {
+        Value::Int(i) => *i,
+        index => return Err(EvalError::new(format!("nth expects an index, got {index}"))),
+    };
+    let item = match usize::try_from(i) {
+        Ok(i) => match coll {
+            Value::ListParen(items) | Value::ListBracket(items) => items.get(i).cloned(),
+            Value::String(s) => s.chars().nth(i).map(|ch| Value::String(ch.into())),
+            coll => seq::split(coll, i + 1)?.0.into_iter().nth(i),
+        },
+        Err(_) => None,
+    };
+    match (item, not_found) {
+        (Some(item), _) => Ok(item),
+        (None, Some(not_found)) => Ok(not_found.clone()),
+        (None, None) => Err(EvalError::new(format!("index out of bounds: {i}"))),
+    }
+}
+
+fn cons(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [value, coll] => seq::cons(value.clone(), coll),
+        _ => Err(arity("cons", args.len())),
+    }
+}
+
+/// `lazy-seq` wraps its body in a function and passes it here.
+fn lazy_seq(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [f] => {
+            let (env, f) = (env.clone(), f.clone());
+            Ok(seq::lazy(move || env.apply(&f, vec![])))
+        }
+        _ => Err(arity("lazy-seq*", args.len())),
+    }
+}
+
+fn is_realized(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::LazySeq(seq)] => Ok(Value::Bool(seq.is_realized())),
+        [value] => Err(EvalError::new(format!(
+            "realized? expects a lazy seq, got {value}"
+        ))),
+        _ => Err(arity("realized?", args.len())),
+    }
+}
+
+fn doall(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => {
+            seq::to_vec(coll)?;
+            Ok(coll.clone())
+        }
+        _ => Err(arity("doall", args.len())),
+    }
+}
+
+fn range(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let ints = (args.iter())
+        .map(|arg| match arg {
+            Value::Int(n) => Ok(*n),
+            arg => Err(EvalError::new(format!("range expects integers, got {arg}"))),
+        })
+        .collect::<Result<Vec<i64>, EvalError>>()?;
+    match ints[..] {
+        [] => Ok(seq::range(0, None, 1)),
+        [end] => Ok(seq::range(0, Some(end), 1)),
+        [start, end] => Ok(seq::range(start, Some(end), 1)),
+        [start, end, step] => Ok(seq::range(start, Some(end), step)),
+        _ => Err(arity("range", args.len())),
+    }
+}
+
+fn repeat(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(seq::repeat(x.clone())),
+        [n, x] => Ok(seq::take(count_arg("repeat", n)?, seq::repeat(x.clone()))),
+        _ => Err(arity("repeat", args.len())),
+    }
+}
+
+fn iterate(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [f, x] => Ok(seq::iterate(caller(env, f), x.clone())),
+        _ => Err(arity("iterate", args.len())),
+    }
+}
+
+fn cycle(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => Ok(seq::cycle(coll.clone())),
+        _ => Err(arity("cycle", args.len())),
+    }
+}
+
+fn take(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [n, coll] => Ok(seq::take(count_arg("take", n)?, coll.clone())),
+        _ => Err(arity("take", args.len())),
+    }
+}
+
+fn drop(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [n, coll] => Ok(seq::drop(count_arg("drop", n)?, coll.clone())),
+        _ => Err(arity("drop", args.len())),
+    }
+}
+
+fn take_while(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [pred, coll] => Ok(seq::take_while(caller(env, pred), coll.clone())),
+        _ => Err(arity("take-while", args.len())),
+    }
+}
+
+fn drop_while(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [pred, coll] => Ok(seq::drop_while(caller(env, pred), coll.clone())),
+        _ => Err(arity("drop-while", args.len())),
+    }
+}
+
+fn split_at(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [n, coll] => {
+            let n = count_arg("split-at", n)?;
+            Ok(Value::ListBracket(vec![
+                seq::take(n, coll.clone()),
+                seq::drop(n, coll.clone()),
+            ]))
+        }
+        _ => Err(arity("split-at", args.len())),
+    }
+}
+
+/// Maps each item of `coll` through `f` a chunk at a time, keeping the
+/// items `keep` returns something for.
+fn map_items<F>(coll: &Value, keep: F) -> Value
+where
+    F: Fn(&Value) -> Result<Option<Value>, EvalError> + Send + Sync + 'static,
+{
+    seq::map_chunks(
+        coll.clone(),
+        Arc::new(move |items| {
+            let mut kept = vec![];
+            for item in items {
+                kept.extend(keep(item)?);
+            }
+            Ok(kept)
+        }),
+    )
+}
+
+fn map(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [f, coll] => {
+            let f = caller(env, f);
+            Ok(map_items(coll, move |item| f(vec![item.clone()]).map(Some)))
+        }
+        [f, colls @ ..] if !colls.is_empty() => Ok(seq::map_many(caller(env, f), colls.to_vec())),
+        _ => Err(arity("map", args.len())),
+    }
+}
+
+fn map_indexed(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [f, coll] => {
+            let f = caller(env, f);
+            let index = AtomicUsize::new(0);
+            Ok(map_items(coll, move |item| {
+                let i = index.fetch_add(1, AtomicOrdering::SeqCst) as i64;
+                f(vec![Value::Int(i), item.clone()]).map(Some)
+            }))
+        }
+        _ => Err(arity("map-indexed", args.len())),
+    }
+}
+
+fn mapv(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(arity("mapv", args.len()));
+    }
+    Ok(Value::ListBracket(seq::to_vec(&map(env, args)?)?))
+}
+
+fn mapcat(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(arity("mapcat", args.len()));
+    }
+    Ok(seq::join(Value::Nil, map(env, args)?))
+}
+
+fn filter(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [pred, coll] => {
+            let pred = caller(env, pred);
+            Ok(map_items(coll, move |item| {
+                let keep = pred(vec![item.clone()])?.is_truthy();
+                Ok(keep.then_some(item.clone()))
+            }))
+        }
+        _ => Err(arity("filter", args.len())),
+    }
+}
+
+fn filterv(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(arity("filterv", args.len()));
+    }
+    Ok(Value::ListBracket(seq::to_vec(&filter(env, args)?)?))
+}
+
+fn remove(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [pred, coll] => {
+            let pred = caller(env, pred);
+            Ok(map_items(coll, move |item| {
+                let drop = pred(vec![item.clone()])?.is_truthy();
+                Ok((!drop).then_some(item.clone()))
+            }))
+        }
+        _ => Err(arity("remove", args.len())),
+    }
+}
+
+fn keep(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [f, coll] => {
+            let f = caller(env, f);
+            Ok(map_items(coll, move |item| {
+                Ok(match f(vec![item.clone()])? {
+                    Value::Nil => None,
+                    value => Some(value),
+                })
+            }))
+        }
+        _ => Err(arity("keep", args.len())),
+    }
+}
+
+fn concat(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    Ok(seq::concat(args))
+}
+
+fn interpose(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [sep, coll] => {
+            let (sep, started) = (sep.clone(), Mutex::new(false));
+            Ok(seq::map_chunks(
+                coll.clone(),
+                Arc::new(move |items| {
+                    let mut started = started.lock().unwrap();
+                    let mut spaced = vec![];
+                    for item in items {
+                        if *started {
+                            spaced.push(sep.clone());
+                        }
+                        spaced.push(item.clone());
+                        *started = true;
+                    }
+                    Ok(spaced)
+                }),
+            ))
+        }
+        _ => Err(arity("interpose", args.len())),
+    }
+}
+
+fn distinct(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => {
+            let seen = Mutex::new(vec![]);
+            Ok(map_items(coll, move |item| {
+                let mut seen = seen.lock().unwrap();
+                if seen.contains(item) {
+                    return Ok(None);
+                }
+                seen.push(item.clone());
+                Ok(Some(item.clone()))
+            }))
+        }
+        _ => Err(arity("distinct", args.len())),
+    }
+}
+
+fn is_sequential_value(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::ListParen(_) | Value::ListBracket(_) | Value::LazySeq(_)
+    )
+}
+
+fn flatten_seq(coll: &Value) -> Value {
+    let items = map_items(coll, |item| {
+        Ok(Some(if is_sequential_value(item) {
+            flatten_seq(item)
+        } else {
+            Value::ListParen(vec![item.clone()])
+        }))
+    });
+    seq::join(Value::Nil, items)
+}
+
+fn flatten(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] if is_sequential_value(coll) => Ok(flatten_seq(coll)),
+        [_] => Ok(Value::ListParen(vec![])),
+        _ => Err(arity("flatten", args.len())),
+    }
+}
+
+fn partition(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (n, step, pad, coll) = match &args[..] {
+        [n, coll] => (n, n, None, coll),
+        [n, step, coll] => (n, step, None, coll),
+        [n, step, pad, coll] => (n, step, Some(pad.clone()), coll),
+        _ => return Err(arity("partition", args.len())),
+    };
+    let (n, step) = (count_arg("partition", n)?, count_arg("partition", step)?);
+    Ok(seq::partition(n, step.max(1), pad, false, coll.clone()))
+}
+
+fn partition_all(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (n, step, coll) = match &args[..] {
+        [n, coll] => (n, n, coll),
+        [n, step, coll] => (n, step, coll),
+        _ => return Err(arity("partition-all", args.len())),
+    };
+    let (n, step) = (
+        count_arg("partition-all", n)?,
+        count_arg("partition-all", step)?,
+    );
+    Ok(seq::partition(n, step.max(1), None, true, coll.clone()))
+}
+
+fn reduce(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (f, init, coll) = match &args[..] {
+        [f, coll] => (f, None, coll),
+        [f, init, coll] => (f, Some(init.clone()), coll),
+        _ => return Err(arity("reduce", args.len())),
+    };
+    let mut items = seq::iter(coll);
+    let mut acc = match init {
+        Some(init) => init,
+        None => match items.next() {
+            Some(first) => first?,
+            None => return env.apply(f, vec![]),
+        },
+    };
+    for item in items {
+        acc = env.apply(f, vec![acc, item?])?;
+    }
+    Ok(acc)
+}
+
+fn reductions(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [f, coll] => match seq::chunk(coll)? {
+            Some(chunk) => Ok(seq::reductions(
+                caller(env, f),
+                chunk.first().clone(),
+                chunk.rest(),
+            )),
+            None => Ok(Value::ListParen(vec![env.apply(f, vec![])?])),
+        },
+        [f, init, coll] => Ok(seq::reductions(caller(env, f), init.clone(), coll.clone())),
+        _ => Err(arity("reductions", args.len())),
+    }
+}
+
+fn apply(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [f, middle @ .., last] => {
+            let mut call = middle.to_vec();
+            call.extend(seq::to_vec(last)?);
+            env.apply(f, call)
+        }
+        _ => Err(arity("apply", args.len())),
+    }
+}
+
+fn some(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [pred, coll] => {
+            for item in seq::iter(coll) {
+                let result = env.apply(pred, vec![item?])?;
+                if result.is_truthy() {
+                    return Ok(result);
+                }
+            }
+            Ok(Value::Nil)
+        }
+        _ => Err(arity("some", args.len())),
+    }
+}
+
+fn is_every(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [pred, coll] => {
+            for item in seq::iter(coll) {
+                if !env.apply(pred, vec![item?])?.is_truthy() {
+                    return Ok(Value::Bool(false));
+                }
+            }
+            Ok(Value::Bool(true))
+        }
+        _ => Err(arity("every?", args.len())),
+    }
+}
+
+fn is_not_any(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(arity("not-any?", args.len()));
+    }
+    Ok(Value::Bool(!some(env, args)?.is_truthy()))
+}
+
+/// Orders values the way `compare` does: numbers by value, strings,
+/// keywords and symbols alphabetically, vectors by length and then item by
+/// item, and nil before everything.
+pub fn compare_values(a: &Value, b: &Value) -> Result<Ordering, EvalError> {
+    let ordering = match (a, b) {
+        (Value::Nil, Value::Nil) => Some(Ordering::Equal),
+        (Value::Nil, _) => Some(Ordering::Less),
+        (_, Value::Nil) => Some(Ordering::Greater),
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Keyword(a, b), Value::Keyword(c, d))
+        | (Value::Symbol(a, b), Value::Symbol(c, d)) => Some((a, b).cmp(&(c, d))),
+        (Value::ListBracket(a), Value::ListBracket(b)) => {
+            let mut ordering = a.len().cmp(&b.len());
+            for (a, b) in a.iter().zip(b) {
+                if ordering != Ordering::Equal {
+                    break;
+                }
+                ordering = compare_values(a, b)?;
+            }
+            Some(ordering)
+        }
+        (a, b) => match (math::num(a), math::num(b)) {
+            (Ok(a), Ok(b)) => math::compare(&a, &b),
+            _ => None,
+        },
+    };
+    ordering.ok_or_else(|| EvalError::new(format!("cannot compare {a} and {b}")))
+}
+
+/// Orders values with a comparator function, which returns a number like
+/// `compare` or true when its first argument comes first.
+fn comparator(env: &Env, f: &Value, a: &Value, b: &Value) -> Result<Ordering, EvalError> {
+    Ok(match env.apply(f, vec![a.clone(), b.clone()])? {
+        Value::Int(n) => n.cmp(&0),
+        Value::Float(n) => n.partial_cmp(&0.0).unwrap_or(Ordering::Equal),
+        Value::Bool(true) => Ordering::Less,
+        _ if env.apply(f, vec![b.clone(), a.clone()])?.is_truthy() => Ordering::Greater,
+        _ => Ordering::Equal,
+    })
+}
+
+/// A stable merge sort that stops at the first error `cmp` returns.
+fn merge_sort<T: Clone>(
+    items: &[T],
+    cmp: &mut dyn FnMut(&T, &T) -> Result<Ordering, EvalError>,
+) -> Result<Vec<T>, EvalError> {
+    if items.len() < 2 {
+        return Ok(items.to_vec());
+    }
+    let (left, right) = items.split_at(items.len() / 2);
+    let (left, right) = (merge_sort(left, cmp)?, merge_sort(right, cmp)?);
+    let mut sorted = Vec::with_capacity(items.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if cmp(&right[j], &left[i])? == Ordering::Less {
+            sorted.push(right[j].clone());
+            j += 1;
+        } else {
+            sorted.push(left[i].clone());
+            i += 1;
+        }
+    }
+    sorted.extend_from_slice(&left[i..]);
+    sorted.extend_from_slice(&right[j..]);
+    Ok(sorted)
+}
+
+fn sort(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let sorted = match &args[..] {
+        [coll] => merge_sort(&seq::to_vec(coll)?, &mut |a, b| compare_values(a, b))?,
+        [f, coll] => merge_sort(&seq::to_vec(coll)?, &mut |a, b| comparator(env, f, a, b))?,
+        _ => return Err(arity("sort", args.len())),
+    };
+    Ok(Value::ListParen(sorted))
+}
+
+fn sort_by(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (keyfn, f, coll) = match &args[..] {
+        [keyfn, coll] => (keyfn, None, coll),
+        [keyfn, f, coll] => (keyfn, Some(f), coll),
+        _ => return Err(arity("sort-by", args.len())),
+    };
+    let keyed = (seq::iter(coll))
+        .map(|item| {
+            let item = item?;
+            Ok((env.apply(keyfn, vec![item.clone()])?, item))
+        })
+        .collect::<Result<Vec<(Value, Value)>, EvalError>>()?;
+    let sorted = merge_sort(&keyed, &mut |(a, _), (b, _)| match f {
+        Some(f) => comparator(env, f, a, b),
+        None => compare_values(a, b),
+    })?;
+    Ok(Value::ListParen(
+        sorted.into_iter().map(|(_, item)| item).collect(),
+    ))
+}
+
+fn compare(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [a, b] => Ok(Value::Int(compare_values(a, b)? as i64)),
+        _ => Err(arity("compare", args.len())),
+    }
+}
+
+fn reverse(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => {
+            let mut items = seq::to_vec(coll)?;
+            items.reverse();
+            Ok(Value::ListParen(items))
+        }
+        _ => Err(arity("reverse", args.len())),
+    }
+}
+
+fn frequencies(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => {
+            let mut counts: Vec<Value> = vec![];
+            for item in seq::iter(coll) {
+                let item = item?;
+                match counts.chunks_mut(2).find(|entry| entry[0] == item) {
+                    Some(entry) => {
+                        if let Value::Int(n) = &mut entry[1] {
+                            *n += 1;
+                        }
+                    }
+                    None => counts.extend([item, Value::Int(1)]),
+                }
+            }
+            Ok(Value::ListBrace(counts))
+        }
+        _ => Err(arity("frequencies", args.len())),
+    }
+}
+
+fn group_by(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [f, coll] => {
+            let mut groups: Vec<Value> = vec![];
+            for item in seq::iter(coll) {
+                let item = item?;
+                let key = env.apply(f, vec![item.clone()])?;
+                match groups.chunks_mut(2).find(|entry| entry[0] == key) {
+                    Some(entry) => {
+                        if let Value::ListBracket(items) = &mut entry[1] {
+                            items.push(item);
+                        }
+                    }
+                    None => groups.extend([key, Value::ListBracket(vec![item])]),
+                }
+            }
+            Ok(Value::ListBrace(groups))
+        }
+        _ => Err(arity("group-by", args.len())),
+    }
+}
+
+fn count(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let n = match &args[..] {
+        [Value::Nil] => 0,
+        [Value::ListParen(items) | Value::ListBracket(items)] => items.len(),
+        [Value::ListBrace(items)] => items.len() / 2,
+        [Value::String(s)] => s.chars().count(),
+        [coll @ Value::LazySeq(_)] => seq::iter(coll).try_fold(0, |n, item| item.map(|_| n + 1))?,
+        [coll] => return Err(EvalError::new(format!("count not supported on {coll}"))),
+        _ => return Err(arity("count", args.len())),
+    };
+    Ok(Value::Int(n as i64))
+}
+
+fn is_empty(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => Ok(Value::Bool(seq::is_empty(coll)?)),
+        _ => Err(arity("empty?", args.len())),
+    }
+}
+
+fn not_empty(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] if seq::is_empty(coll)? => Ok(Value::Nil),
+        [coll] => Ok(coll.clone()),
+        _ => Err(arity("not-empty", args.len())),
+    }
+}
+
+fn empty(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::ListParen(_) | Value::LazySeq(_)] => Ok(Value::ListParen(vec![])),
+        [Value::ListBracket(_)] => Ok(Value::ListBracket(vec![])),
+        [Value::ListBrace(_)] => Ok(Value::ListBrace(vec![])),
+        [Value::String(_)] => Ok(Value::String(String::new())),
+        [_] => Ok(Value::Nil),
+        _ => Err(arity("empty", args.len())),
+    }
+}
+
+/// The value of `key` in `coll`; strings are indexed by character.
+fn lookup(coll: &Value, key: &Value) -> Option<Value> {
+    match (coll, key) {
+        (Value::String(s), Value::Int(i)) => usize::try_from(*i)
+            .ok()
+            .and_then(|i| s.chars().nth(i))
+            .map(|ch| Value::String(ch.into())),
+        (coll, key) => eval::get(coll, key),
+    }
+}
+
+fn get(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll, key] => Ok(lookup(coll, key).unwrap_or(Value::Nil)),
+        [coll, key, not_found] => Ok(lookup(coll, key).unwrap_or_else(|| not_found.clone())),
+        _ => Err(arity("get", args.len())),
+    }
+}
+
+fn get_in(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (coll, keys, not_found) = match &args[..] {
+        [coll, keys] => (coll, keys, Value::Nil),
+        [coll, keys, not_found] => (coll, keys, not_found.clone()),
+        _ => return Err(arity("get-in", args.len())),
+    };
+    let mut value = coll.clone();
+    for key in seq::iter(keys) {
+        match lookup(&value, &key?) {
+            Some(next) => value = next,
+            None => return Ok(not_found),
+        }
+    }
+    Ok(value)
+}
+
+fn contains(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let found = match &args[..] {
+        [Value::ListBrace(entries), key] => entries.chunks(2).any(|entry| entry[0] == *key),
+        [Value::Struct(instance), Value::Keyword(_, field)] => instance.ty.index(field).is_some(),
+        [coll @ (Value::ListBracket(_) | Value::String(_)), key @ Value::Int(_)] => {
+            lookup(coll, key).is_some()
+        }
+        [Value::Nil, _] => false,
+        [coll, _] => return Err(EvalError::new(format!("contains? not supported on {coll}"))),
+        _ => return Err(arity("contains?", args.len())),
+    };
+    Ok(Value::Bool(found))
+}
+
+/// `coll` with `key` set to `value`.
+fn assoc_one(coll: Value, key: Value, value: Value) -> Result<Value, EvalError> {
+    match coll {
+        Value::Nil => Ok(Value::ListBrace(vec![key, value])),
+        Value::ListBrace(mut entries) => {
+            match entries.chunks_mut(2).find(|entry| entry[0] == key) {
+                Some(entry) => entry[1] = value,
+                None => entries.extend([key, value]),
+            }
+            Ok(Value::ListBrace(entries))
+        }
+        Value::ListBracket(mut items) => match key {
+            Value::Int(i) if i >= 0 && i as usize <= items.len() => {
+                if i as usize == items.len() {
+                    items.push(value);
+                } else {
+                    items[i as usize] = value;
+                }
+                Ok(Value::ListBracket(items))
+            }
+            key => Err(EvalError::new(format!("index out of bounds: {key}"))),
+        },
+        Value::Struct(instance) => {
+            let i = match &key {
+                Value::Keyword(_, field) => instance.ty.index(field),
+                _ => None,
+            };
+            let i = i.ok_or_else(|| {
+                EvalError::new(format!("{} has no field {key}", instance.ty.name))
+            })?;
+            instance.ty.check(&instance.ty.fields[i], &value)?;
+            let mut values = instance.values.clone();
+            values[i] = value;
+            Ok(Value::Struct(Arc::new(Struct {
+                ty: instance.ty.clone(),
+                values,
+            })))
+        }
+        coll => Err(EvalError::new(format!("assoc not supported on {coll}"))),
+    }
+}
+
+fn assoc(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll, kvs @ ..] if !kvs.is_empty() && kvs.len().is_multiple_of(2) => {
+            let mut coll = coll.clone();
+            for kv in kvs.chunks(2) {
+                coll = assoc_one(coll, kv[0].clone(), kv[1].clone())?;
+            }
+            Ok(coll)
+        }
+        _ => Err(arity("assoc", args.len())),
+    }
+}
+
+/// `coll` with the value at the path `keys` replaced by what `f` returns
+/// for it.
+fn update_path(
+    coll: Value,
+    keys: &[Value],
+    f: &mut dyn FnMut(Value) -> Result<Value, EvalError>,
+) -> Result<Value, EvalError> {
+    match keys {
+        [] => f(coll),
+        [key, rest @ ..] => {
+            let inner = lookup(&coll, key).unwrap_or(Value::Nil);
+            let value = update_path(inner, rest, f)?;
+            assoc_one(coll, key.clone(), value)
+        }
+    }
+}
+
+fn assoc_in(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll, keys, value] => {
+            update_path(
+                coll.clone(),
+                &seq::to_vec(keys)?,
+                &mut |_| Ok(value.clone()),
+            )
+        }
+        _ => Err(arity("assoc-in", args.len())),
+    }
+}
+
+fn dissoc(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Nil, ..] => Ok(Value::Nil),
+        [Value::ListBrace(entries), keys @ ..] => Ok(Value::ListBrace(
+            (entries.chunks(2))
+                .filter(|entry| !keys.contains(&entry[0]))
+                .flatten()
+                .cloned()
+                .collect(),
+        )),
+        [coll, ..] => Err(EvalError::new(format!("dissoc not supported on {coll}"))),
+        [] => Err(arity("dissoc", 0)),
+    }
+}
+
+fn update(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll, key, f, extra @ ..] => {
+            let call = |value| {
+                let mut call = vec![value];
+                call.extend(extra.iter().cloned());
+                env.apply(f, call)
+            };
+            update_path(coll.clone(), std::slice::from_ref(key), &mut { call })
+        }
+        _ => Err(arity("update", args.len())),
+    }
+}
+
+fn update_in(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll, keys, f, extra @ ..] => {
+            let call = |value| {
+                let mut call = vec![value];
+                call.extend(extra.iter().cloned());
+                env.apply(f, call)
+            };
+            update_path(coll.clone(), &seq::to_vec(keys)?, &mut { call })
+        }
+        _ => Err(arity("update-in", args.len())),
+    }
+}
+
+/// `coll` with `x` added where it is cheapest: at the end of vectors and
+/// the front of lists; maps take `[key value]` entries or other maps.
+fn conj_one(coll: Value, x: Value) -> Result<Value, EvalError> {
+    match (coll, x) {
+        (Value::ListBracket(mut items), x) => {
+            items.push(x);
+            Ok(Value::ListBracket(items))
+        }
+        (Value::ListBrace(entries), Value::ListBracket(entry)) if entry.len() == 2 => assoc_one(
+            Value::ListBrace(entries),
+            entry[0].clone(),
+            entry[1].clone(),
+        ),
+        (Value::ListBrace(entries), Value::ListBrace(more)) => {
+            let mut map = Value::ListBrace(entries);
+            for entry in more.chunks(2) {
+                map = assoc_one(map, entry[0].clone(), entry[1].clone())?;
+            }
+            Ok(map)
+        }
+        (Value::ListBrace(_), x) => Err(EvalError::new(format!(
+            "can't conj {x} onto a map, expected a [key value] entry"
+        ))),
+        (coll @ (Value::Nil | Value::ListParen(_) | Value::LazySeq(_)), x) => seq::cons(x, &coll),
+        (coll, _) => Err(EvalError::new(format!("conj not supported on {coll}"))),
+    }
+}
+
+fn conj(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [] => Ok(Value::ListBracket(vec![])),
+        [coll, xs @ ..] => {
+            let mut coll = coll.clone();
+            for x in xs {
+                coll = conj_one(coll, x.clone())?;
+            }
+            Ok(coll)
+        }
+    }
+}
+
+fn into(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [to, from] => {
+            let mut coll = to.clone();
+            for x in seq::iter(from) {
+                coll = conj_one(coll, x?)?;
+            }
+            Ok(coll)
+        }
+        _ => Err(arity("into", args.len())),
+    }
+}
+
+fn entries(name: &str, map: &Value, i: usize) -> Result<Value, EvalError> {
+    match map {
+        Value::Nil => Ok(Value::Nil),
+        Value::ListBrace(entries) if entries.is_empty() => Ok(Value::Nil),
+        Value::ListBrace(entries) => Ok(Value::ListParen(
+            entries.chunks(2).map(|entry| entry[i].clone()).collect(),
+        )),
+        map => Err(EvalError::new(format!("{name} expects a map, got {map}"))),
+    }
+}
+
+fn keys(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [map] => entries("keys", map, 0),
+        _ => Err(arity("keys", args.len())),
+    }
+}
+
+fn vals(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [map] => entries("vals", map, 1),
+        _ => Err(arity("vals", args.len())),
+    }
+}
+
+fn key(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::ListBracket(entry)] if entry.len() == 2 => Ok(entry[0].clone()),
+        [entry] => Err(EvalError::new(format!(
+            "key expects a map entry, got {entry}"
+        ))),
+        _ => Err(arity("key", args.len())),
+    }
+}
+
+fn val(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::ListBracket(entry)] if entry.len() == 2 => Ok(entry[1].clone()),
+        [entry] => Err(EvalError::new(format!(
+            "val expects a map entry, got {entry}"
+        ))),
+        _ => Err(arity("val", args.len())),
+    }
+}
+
+fn merge(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let mut merged = Value::Nil;
+    for map in args {
+        merged = match (merged, map) {
+            (merged, Value::Nil) => merged,
+            (Value::Nil, map) => map,
+            (merged, map) => conj_one(merged, map)?,
+        };
+    }
+    Ok(merged)
+}
+
+fn select_keys(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [map, keys] => {
+            let mut selected = vec![];
+            for key in seq::iter(keys) {
+                let key = key?;
+                if let Some(value) = lookup(map, &key) {
+                    selected.extend([key, value]);
+                }
+            }
+            Ok(Value::ListBrace(selected))
+        }
+        _ => Err(arity("select-keys", args.len())),
+    }
+}
+
+fn zipmap(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [keys, vals] => {
+            let mut map = Value::ListBrace(vec![]);
+            for (key, value) in seq::iter(keys).zip(seq::iter(vals)) {
+                map = assoc_one(map, key?, value?)?;
+            }
+            Ok(map)
+        }
+        _ => Err(arity("zipmap", args.len())),
+    }
+}
+
+fn hash_map(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(EvalError::new("hash-map expects an even number of args"));
+    }
+    let mut map = Value::ListBrace(vec![]);
+    for kv in args.chunks(2) {
+        map = assoc_one(map, kv[0].clone(), kv[1].clone())?;
+    }
+    Ok(map)
+}
+
+fn vec(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [coll] => Ok(Value::ListBracket(seq::to_vec(coll)?)),
+        _ => Err(arity("vec", args.len())),
+    }
+}
+
+fn identity(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(x.clone()),
+        _ => Err(arity("identity", args.len())),
+    }
+}
+
+/// Defines a native that tests its one argument, `x`, with `test`.
+macro_rules! predicate {
+    ($fn_name:ident, $name:expr, |$x:ident| $test:expr) => {
+        fn $fn_name(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+            match &args[..] {
+                [$x] => Ok(Value::Bool($test)),
+                _ => Err(arity($name, args.len())),
+            }
+        }
+    };
+}
+
+fn sign(name: &str, x: &Value) -> Result<Ordering, EvalError> {
+    match x {
+        Value::Float(n) => Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+        Value::Int(_) | Value::BigInt(_) | Value::Rational(_) => {
+            Ok(math::compare(&math::num(x)?, &Num::Int(0)).unwrap())
+        }
+        x => Err(EvalError::new(format!("{name} expects a number, got {x}"))),
+    }
+}
+
+predicate!(is_seq, "seq?", |x| matches!(
+    x,
+    Value::ListParen(_) | Value::LazySeq(_)
+));
+predicate!(is_vector, "vector?", |x| matches!(x, Value::ListBracket(_)));
+predicate!(is_map, "map?", |x| matches!(x, Value::ListBrace(_)));
+predicate!(is_coll, "coll?", |x| matches!(
+    x,
+    Value::ListParen(_) | Value::ListBracket(_) | Value::ListBrace(_) | Value::LazySeq(_)
+));
+predicate!(is_sequential, "sequential?", |x| is_sequential_value(x));
+predicate!(is_string, "string?", |x| matches!(x, Value::String(_)));
+predicate!(is_keyword, "keyword?", |x| matches!(
+    x,
+    Value::Keyword(_, _)
+));
+predicate!(is_number, "number?", |x| matches!(
+    x,
+    Value::Int(_) | Value::BigInt(_) | Value::Float(_) | Value::Rational(_)
+));
+predicate!(is_int, "int?", |x| matches!(x, Value::Int(_)));
+predicate!(is_float, "float?", |x| matches!(x, Value::Float(_)));
+predicate!(is_boolean, "boolean?", |x| matches!(x, Value::Bool(_)));
+predicate!(is_fn, "fn?", |x| matches!(x, Value::Fn(_)));
+predicate!(is_some, "some?", |x| !matches!(x, Value::Nil));
+predicate!(is_true, "true?", |x| matches!(x, Value::Bool(true)));
+predicate!(is_false, "false?", |x| matches!(x, Value::Bool(false)));
+predicate!(is_even, "even?", |x| math::is_even("even?", x)?);
+predicate!(is_odd, "odd?", |x| !math::is_even("odd?", x)?);
+predicate!(is_pos, "pos?", |x| sign("pos?", x)? == Ordering::Greater);
+predicate!(is_neg, "neg?", |x| sign("neg?", x)? == Ordering::Less);
+
+fn name(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Keyword(_, name) | Value::Symbol(_, name)] => Ok(Value::String(
+            name.rsplit('/').next().unwrap_or(name).into(),
+        )),
+        [Value::String(s)] => Ok(Value::String(s.clone())),
+        [x] => Err(EvalError::new(format!("name not supported on {x}"))),
+        _ => Err(arity("name", args.len())),
+    }
+}
+
+fn keyword(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Keyword(_, _)] => Ok(args[0].clone()),
+        [Value::String(name) | Value::Symbol(_, name)] => {
+            Ok(Value::Keyword(String::new(), name.clone()))
+        }
+        [Value::String(ns), Value::String(name)] => Ok(Value::Keyword(ns.clone(), name.clone())),
+        [x] => Err(EvalError::new(format!("keyword not supported on {x}"))),
+        _ => Err(arity("keyword", args.len())),
+    }
+}
+
+fn symbol(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Symbol(_, _)] => Ok(args[0].clone()),
+        [Value::String(name) | Value::Keyword(_, name)] => {
+            Ok(Value::Symbol(String::new(), name.clone()))
+        }
+        [Value::String(ns), Value::String(name)] => Ok(Value::Symbol(ns.clone(), name.clone())),
+        [x] => Err(EvalError::new(format!("symbol not supported on {x}"))),
+        _ => Err(arity("symbol", args.len())),
+    }
+}
@@ -0,0 +1,387 @@
+#[cfg(test)]
+mod tests {
+    use coll;
+    use core;
+    use eval::Env;
+    use math;
+    use string;
+
+    /// Evaluates `content` and prints the result, or the error message.
+    fn show(content: &str) -> String {
+        match Env::new().eval_str("_test_.tiny", content) {
+            Ok(value) => value.to_string(),
+            Err(err) => format!("error: {}", err.message),
+        }
+    }
+
+    #[test]
+    fn test_docstrings() {
+        let natives = (core::NATIVES.iter())
+            .chain(math::CORE_NATIVES)
+            .chain(coll::NATIVES)
+            .chain(string::CORE_NATIVES)
+            .chain(core::MODULES.iter().flat_map(|(_, natives)| natives.iter()));
+        for (name, _, doc) in natives {
+            assert!(!doc.is_empty(), "{} has no docstring", name);
+        }
+        let env = Env::new();
+        assert!(env.doc("map").unwrap().contains("lazy sequence"));
+        assert!(env.doc("comp").unwrap().contains("Composes"));
+        env.eval_str(
+            "_test_.tiny",
+            "(defn foo \"Does foo.\" [x] x) (def bar \"A bar.\" 1)",
+        )
+        .unwrap();
+        assert_eq!(env.doc("foo"), Some("Does foo.".into()));
+        assert_eq!(env.doc("bar"), Some("A bar.".into()));
+        assert_eq!(env.doc("baz"), None);
+    }
+
+    #[test]
+    fn test_seq() {
+        assert_eq!(show("(seq [1 2])"), "(1 2)");
+        assert_eq!(show("(seq [])"), "nil");
+        assert_eq!(show("(seq {:a 1})"), "([:a 1])");
+        assert_eq!(
+            show("(seq 1)"),
+            "error: don't know how to create a seq from 1"
+        );
+    }
+
+    #[test]
+    fn test_first_second_last() {
+        assert_eq!(
+            show("[(first [1 2]) (first nil) (first \"ab\")]"),
+            "[1 nil \"a\"]"
+        );
+        assert_eq!(show("[(second [1 2]) (second [1])]"), "[2 nil]");
+        assert_eq!(show("[(last [1 2 3]) (last [])]"), "[3 nil]");
+    }
+
+    #[test]
+    fn test_rest_next_butlast() {
+        assert_eq!(show("[(rest [1 2]) (rest [1]) (rest nil)]"), "[(2) () ()]");
+        assert_eq!(show("[(next [1 2]) (next [1])]"), "[(2) nil]");
+        assert_eq!(show("[(butlast [1 2 3]) (butlast [1])]"), "[(1 2) nil]");
+    }
+
+    #[test]
+    fn test_nth() {
+        assert_eq!(show("(nth [1 2] 1)"), "2");
+        assert_eq!(show("(nth (range) 100)"), "100");
+        assert_eq!(show("(nth [1] 5 :none)"), ":none");
+        assert_eq!(show("(nth [1] 5)"), "error: index out of bounds: 5");
+    }
+
+    #[test]
+    fn test_cons() {
+        assert_eq!(show("(cons 1 [2 3])"), "(1 2 3)");
+        assert_eq!(show("(cons 1 nil)"), "(1)");
+        assert_eq!(show("(take 3 (cons 0 (range)))"), "(0 0 1)");
+    }
+
+    #[test]
+    fn test_lazy_seq_realized_doall() {
+        assert_eq!(show("(realized? (lazy-seq [1]))"), "false");
+        assert_eq!(
+            show("(let [s (lazy-seq [1])] (first s) (realized? s))"),
+            "true"
+        );
+        assert_eq!(show("(doall (map inc [1 2]))"), "(2 3)");
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(show("(range 3)"), "(0 1 2)");
+        assert_eq!(show("(range 1 7 2)"), "(1 3 5)");
+        assert_eq!(show("(range 3 0 -1)"), "(3 2 1)");
+        assert_eq!(show("(take 2 (range))"), "(0 1)");
+    }
+
+    #[test]
+    fn test_repeat_iterate_cycle() {
+        assert_eq!(show("(repeat 2 :x)"), "(:x :x)");
+        assert_eq!(show("(take 3 (repeat 1))"), "(1 1 1)");
+        assert_eq!(show("(take 4 (iterate (fn [x] (* x 2)) 1))"), "(1 2 4 8)");
+        assert_eq!(show("(take 5 (cycle [1 2]))"), "(1 2 1 2 1)");
+        assert_eq!(show("(cycle [])"), "()");
+    }
+
+    #[test]
+    fn test_take_drop() {
+        assert_eq!(show("(take 2 [1 2 3])"), "(1 2)");
+        assert_eq!(show("(take 5 [1])"), "(1)");
+        assert_eq!(show("(drop 2 [1 2 3])"), "(3)");
+        assert_eq!(show("(take-while odd? [1 3 4 5])"), "(1 3)");
+        assert_eq!(show("(drop-while odd? [1 3 4 5])"), "(4 5)");
+        assert_eq!(show("(split-at 1 [1 2 3])"), "[(1) (2 3)]");
+    }
+
+    #[test]
+    fn test_map() {
+        assert_eq!(show("(map inc [1 2])"), "(2 3)");
+        assert_eq!(show("(map + [1 2 3] [10 20])"), "(11 22)");
+        assert_eq!(show("(take 2 (map inc (range)))"), "(1 2)");
+        assert_eq!(show("(map-indexed vector [:a :b])"), "([0 :a] [1 :b])");
+        assert_eq!(show("(mapv inc [1 2])"), "[2 3]");
+        assert_eq!(show("(mapcat reverse [[1 2] [3]])"), "(2 1 3)");
+        assert_eq!(show("(first (map inc [:a]))"), "error: :a is not a number");
+    }
+
+    #[test]
+    fn test_filter_remove_keep() {
+        assert_eq!(show("(filter even? (range 5))"), "(0 2 4)");
+        assert_eq!(show("(filterv even? [1 2])"), "[2]");
+        assert_eq!(show("(remove even? (range 5))"), "(1 3)");
+        assert_eq!(
+            show("(keep (fn [x] (when (odd? x) (* x x))) (range 5))"),
+            "(1 9)"
+        );
+    }
+
+    #[test]
+    fn test_concat_interpose() {
+        assert_eq!(show("(concat [1] '(2) nil \"a\")"), "(1 2 \"a\")");
+        assert_eq!(show("(interpose 0 [1 2 3])"), "(1 0 2 0 3)");
+    }
+
+    #[test]
+    fn test_distinct_flatten() {
+        assert_eq!(show("(distinct [1 2 1 3 2])"), "(1 2 3)");
+        assert_eq!(show("(flatten [1 [2 (list 3 [4])] 5])"), "(1 2 3 4 5)");
+    }
+
+    #[test]
+    fn test_partition() {
+        assert_eq!(show("(partition 2 [1 2 3])"), "((1 2))");
+        assert_eq!(show("(partition 2 1 [1 2 3])"), "((1 2) (2 3))");
+        assert_eq!(show("(partition 2 2 [0] [1 2 3])"), "((1 2) (3 0))");
+        assert_eq!(show("(partition-all 2 [1 2 3])"), "((1 2) (3))");
+    }
+
+    #[test]
+    fn test_reduce_reductions() {
+        assert_eq!(show("(reduce + [1 2 3])"), "6");
+        assert_eq!(show("(reduce + 10 [1 2 3])"), "16");
+        assert_eq!(show("(reduce + [])"), "0");
+        assert_eq!(show("(reduce conj [] (range 3))"), "[0 1 2]");
+        assert_eq!(show("(reductions + [1 2 3])"), "(1 3 6)");
+        assert_eq!(show("(reductions + 0 [1 2])"), "(0 1 3)");
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(show("(apply + [1 2 3])"), "6");
+        assert_eq!(show("(apply + 1 2 '(3 4))"), "10");
+        assert_eq!(show("(apply str (range 3))"), "\"012\"");
+    }
+
+    #[test]
+    fn test_some_every() {
+        assert_eq!(show("(some even? [1 2])"), "true");
+        assert_eq!(show("(some even? [1 3])"), "nil");
+        assert_eq!(show("(every? odd? [1 3])"), "true");
+        assert_eq!(show("(every? odd? [])"), "true");
+        assert_eq!(show("(not-any? odd? [2 4])"), "true");
+    }
+
+    #[test]
+    fn test_sort() {
+        assert_eq!(show("(sort [3 1 2])"), "(1 2 3)");
+        assert_eq!(show("(sort > [3 1 2])"), "(3 2 1)");
+        assert_eq!(show("(sort [\"b\" \"a\" nil])"), "(nil \"a\" \"b\")");
+        assert_eq!(show("(sort-by count [[1 2] [1] []])"), "([] [1] [1 2])");
+        assert_eq!(
+            show("(sort-by first [[1 :b] [0 :c] [1 :a]])"),
+            "([0 :c] [1 :b] [1 :a])"
+        );
+        assert_eq!(show("(sort [1 :a])"), "error: cannot compare :a and 1");
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(
+            show("[(compare 1 2) (compare 2 2) (compare \"b\" \"a\")]"),
+            "[-1 0 1]"
+        );
+        assert_eq!(show("(compare [1 2] [1 3])"), "-1");
+    }
+
+    #[test]
+    fn test_reverse_frequencies_group_by() {
+        assert_eq!(show("(reverse [1 2 3])"), "(3 2 1)");
+        assert_eq!(show("(frequencies [:a :b :a])"), "{:a 2 :b 1}");
+        assert_eq!(
+            show("(group-by count [\"a\" \"bc\" \"d\"])"),
+            "{1 [\"a\" \"d\"] 2 [\"bc\"]}"
+        );
+    }
+
+    #[test]
+    fn test_count_empty() {
+        assert_eq!(
+            show("[(count [1 2]) (count {:a 1}) (count nil) (count \"héllo\")]"),
+            "[2 1 0 5]"
+        );
+        assert_eq!(show("(count (range 100))"), "100");
+        assert_eq!(
+            show("[(empty? []) (empty? nil) (empty? [1]) (empty? (range 0))]"),
+            "[true true false true]"
+        );
+        assert_eq!(show("[(not-empty []) (not-empty [1])]"), "[nil [1]]");
+        assert_eq!(
+            show("[(empty [1]) (empty {:a 1}) (empty '(1))]"),
+            "[[] {} ()]"
+        );
+        assert_eq!(show("(count 1)"), "error: count not supported on 1");
+    }
+
+    #[test]
+    fn test_get() {
+        assert_eq!(show("(get {:a 1} :a)"), "1");
+        assert_eq!(show("(get {:a 1} :b :none)"), ":none");
+        assert_eq!(show("(get [1 2] 1)"), "2");
+        assert_eq!(show("(get \"abc\" 1)"), "\"b\"");
+        assert_eq!(show("(get-in {:a [{:b 2}]} [:a 0 :b])"), "2");
+        assert_eq!(show("(get-in {} [:a :b] 0)"), "0");
+        assert_eq!(
+            show("[(contains? {:a nil} :a) (contains? [1] 1)]"),
+            "[true false]"
+        );
+    }
+
+    #[test]
+    fn test_assoc_dissoc() {
+        assert_eq!(show("(assoc {:a 1} :b 2 :a 3)"), "{:a 3 :b 2}");
+        assert_eq!(show("(assoc [1 2] 2 3)"), "[1 2 3]");
+        assert_eq!(show("(assoc nil :a 1)"), "{:a 1}");
+        assert_eq!(show("(assoc-in {:a {}} [:a :b] 1)"), "{:a {:b 1}}");
+        assert_eq!(show("(dissoc {:a 1 :b 2} :a :c)"), "{:b 2}");
+        assert_eq!(show("(assoc [1] 5 0)"), "error: index out of bounds: 5");
+        assert_eq!(show("(assoc 1 :a 1)"), "error: assoc not supported on 1");
+    }
+
+    #[test]
+    fn test_update() {
+        assert_eq!(show("(update {:a 1} :a + 10)"), "{:a 11}");
+        assert_eq!(show("(update [1 2] 0 inc)"), "[2 2]");
+        assert_eq!(show("(update-in {:a {:b 1}} [:a :b] inc)"), "{:a {:b 2}}");
+        assert_eq!(show("(update-in {} [:a :b] (fn [x] x))"), "{:a {:b nil}}");
+    }
+
+    #[test]
+    fn test_conj_into() {
+        assert_eq!(show("(conj [1] 2 3)"), "[1 2 3]");
+        assert_eq!(show("(conj '(1) 2 3)"), "(3 2 1)");
+        assert_eq!(show("(conj {:a 1} [:b 2])"), "{:a 1 :b 2}");
+        assert_eq!(show("(conj nil 1)"), "(1)");
+        assert_eq!(show("(into [] (range 3))"), "[0 1 2]");
+        assert_eq!(show("(into {} [[:a 1] [:b 2]])"), "{:a 1 :b 2}");
+        assert_eq!(show("(into '() [1 2])"), "(2 1)");
+    }
+
+    #[test]
+    fn test_keys_vals() {
+        assert_eq!(show("(keys {:a 1 :b 2})"), "(:a :b)");
+        assert_eq!(show("(vals {:a 1 :b 2})"), "(1 2)");
+        assert_eq!(show("(keys {})"), "nil");
+        assert_eq!(show("(map key {:a 1})"), "(:a)");
+        assert_eq!(show("(map val {:a 1})"), "(1)");
+    }
+
+    #[test]
+    fn test_maps() {
+        assert_eq!(show("(merge {:a 1} nil {:a 2 :b 3})"), "{:a 2 :b 3}");
+        assert_eq!(show("(select-keys {:a 1 :b 2} [:b :c])"), "{:b 2}");
+        assert_eq!(show("(zipmap [:a :b :c] [1 2])"), "{:a 1 :b 2}");
+        assert_eq!(show("(hash-map :a 1)"), "{:a 1}");
+        assert_eq!(show("(= {:a 1 :b 2} {:b 2 :a 1})"), "true");
+        assert_eq!(show("(= {:a 1} {:a 2})"), "false");
+        assert_eq!(
+            show("[(key (first {:a 1})) (val (first {:a 1}))]"),
+            "[:a 1]"
+        );
+        assert_eq!(show("(map key {:a 1 :b 2})"), "(:a :b)");
+        assert_eq!(show("(key :a)"), "error: key expects a map entry, got :a");
+        assert_eq!(
+            show("(val [1 2 3])"),
+            "error: val expects a map entry, got [1 2 3]"
+        );
+        assert_eq!(show("(take 2 (lazy-seq* (fn [] [1 2 3])))"), "(1 2)");
+        assert_eq!(show("(lazy-seq (cons 1 nil))"), "(1)");
+    }
+
+    #[test]
+    fn test_vec_identity() {
+        assert_eq!(show("(vec (range 3))"), "[0 1 2]");
+        assert_eq!(show("(vec nil)"), "[]");
+        assert_eq!(show("(identity :a)"), ":a");
+    }
+
+    #[test]
+    fn test_type_predicates() {
+        assert_eq!(
+            show("[(seq? '(1)) (seq? (range 1)) (seq? [1]) (vector? [1]) (map? {}) (coll? \"a\") (sequential? [])]"),
+            "[true true false true true false true]"
+        );
+        assert_eq!(
+            show("[(string? \"a\") (keyword? :a) (number? 1.5) (int? 1.5) (float? 1.5) (boolean? nil) (fn? inc)]"),
+            "[true true true false true false true]"
+        );
+        assert_eq!(
+            show("[(some? false) (some? nil) (true? 1) (false? false)]"),
+            "[true false false true]"
+        );
+    }
+
+    #[test]
+    fn test_number_predicates() {
+        assert_eq!(
+            show("[(even? 2) (odd? 2) (pos? 0) (neg? -1.5)]"),
+            "[true false false true]"
+        );
+        assert_eq!(
+            show("(even? 1.5)"),
+            "error: even? expects an integer, got 1.5"
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(
+            show("[(name :a) (name 'b/c) (name \"d\")]"),
+            "[\"a\" \"c\" \"d\"]"
+        );
+        assert_eq!(show("[(keyword \"a\") (keyword \"b\" \"c\")]"), "[:a :b/c]");
+        assert_eq!(show("[(symbol \"a\") (symbol \"b\" \"c\")]"), "[a b/c]");
+    }
+
+    #[test]
+    fn test_higher_order() {
+        assert_eq!(show("((comp inc (fn [x] (* x 2))) 3)"), "7");
+        assert_eq!(show("((comp) 1)"), "1");
+        assert_eq!(show("((partial + 1 2) 3)"), "6");
+        assert_eq!(show("((juxt first count) [5 6])"), "[5 2]");
+        assert_eq!(show("(filter (complement odd?) [1 2])"), "(2)");
+        assert_eq!(show("((constantly 7) 1 2)"), "7");
+    }
+
+    #[test]
+    fn test_binding_macros() {
+        assert_eq!(show("(if-let [x (first [1])] (inc x) :none)"), "2");
+        assert_eq!(show("(if-let [x (first [])] (inc x) :none)"), ":none");
+        assert_eq!(show("(when-let [x nil] :some)"), "nil");
+        assert_eq!(
+            show("(let [a (atom [])] (doseq [x [1 2] y [:a]] (swap! a conj [x y])) @a)"),
+            "[[1 :a] [2 :a]]"
+        );
+        assert_eq!(
+            show("(for [x (range 4) :let [y (* x x)] :when (odd? x)] y)"),
+            "(1 9)"
+        );
+        assert_eq!(
+            show("(for [x [1 2] y [:a :b]] [x y])"),
+            "([1 :a] [1 :b] [2 :a] [2 :b])"
+        );
+    }
+}
@@ -0,0 +1,702 @@
+use crate::coll;
+use crate::csp::{self, Channel, Op};
+use crate::eval::{self, Env, EvalError};
+use crate::io::{self, OutputStream};
+use crate::math;
+use crate::os;
+use crate::reader;
+use crate::seq;
+use crate::string;
+use crate::value::{Atom, ExInfo, Function, NativeFn, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const NATIVES: &[(&str, NativeFn, Doc)] = &[
+    ("=", eq, "Returns true if all args are equal; lazy sequences equal lists and vectors with the same items."),
+    ("not=", not_eq, "Returns true if any two args differ."),
+    ("not", not, "Returns true if x is nil or false."),
+    ("nil?", is_nil, "Returns true if x is nil."),
+    ("list?", is_list, "Returns true if x is a list."),
+    ("symbol?", is_symbol, "Returns true if x is a symbol."),
+    ("list", list, "Returns a list of items."),
+    ("vector", vector, "Returns a vector of items."),
+    ("str", str, "Returns the text of args joined together, with nil as nothing."),
+    ("print", print, "Prints args separated by spaces."),
+    ("println", println, "Prints args separated by spaces and a newline."),
+    ("gensym", gensym, "Returns a fresh symbol, named after prefix if given."),
+    ("atom", atom, "Returns an atom holding x, checked by :validator if given."),
+    ("deref", deref, "Returns the value of an atom."),
+    ("reset!", reset, "Sets the value of an atom to x."),
+    ("swap!", swap, "Sets the value of an atom to (f value args...), retrying if it changes meanwhile."),
+    ("compare-and-set!", compare_and_set, "Sets the value of an atom to new if it is old, returning whether it did."),
+    ("set-validator!", set_validator, "Sets the function that checks every new value of an atom."),
+    ("add-watch", add_watch, "Calls (f key atom old new) whenever the atom changes."),
+    ("remove-watch", remove_watch, "Removes the watch added with key."),
+    ("go*", go, "Calls f on the worker pool, returning a channel that gets its result."),
+    ("chan", chan, "Returns a channel, buffering n values if given."),
+    (">!", put_chan, "Puts x on a channel, waiting for room or a taker; false if it is closed."),
+    ("<!", take_chan, "Takes a value from a channel, waiting for one; nil once it is closed."),
+    ("alts!", alts, "Runs the first ready of a vector of takes and [channel value] puts, returning [value channel]."),
+    ("timeout", timeout, "Returns a channel that closes after ms milliseconds."),
+    ("close!", close, "Closes a channel."),
+    ("now", now, "Returns the milliseconds since the Unix epoch."),
+    ("ex-info", ex_info, "Returns an error with a message, a map of data and maybe a cause."),
+    ("ex-message", ex_message, "Returns the message of an error."),
+    ("ex-data", ex_data, "Returns the data of an error."),
+    ("ex-cause", ex_cause, "Returns the cause of an error."),
+    ("macroexpand-1", macroexpand_1, "Expands form once if it is a macro call."),
+    ("macroexpand", macroexpand, "Expands form until it is no longer a macro call."),
+    ("doc*", doc, "Prints the docstring of the function or macro a symbol names."),
+];
+
+/// The standard library namespaces built into every environment, so
+/// importing them needs no file.
+pub const MODULES: &[(&str, Natives)] = &[
+    ("std.string", string::NATIVES),
+    ("std.io", io::NATIVES),
+    ("std.math", math::NATIVES),
+    ("std.os", os::NATIVES),
+];
+
+/// Macros defined in tiny itself, evaluated into every new environment.
+pub const PRELUDE: &str = include_str!("core.tiny");
+
+/// The docstring of a native function.
+pub type Doc = &'static str;
+
+pub type Natives = &'static [(&'static str, NativeFn, Doc)];
+
+pub fn install(env: &Env) {
+    let natives = NATIVES
+        .iter()
+        .chain(math::CORE_NATIVES)
+        .chain(coll::NATIVES)
+        .chain(string::CORE_NATIVES);
+    define_natives(env, natives);
+    for (module, natives) in MODULES {
+        define_natives(&env.in_ns(module), natives.iter());
+    }
+    let math = env.in_ns("std.math");
+    for (name, value, doc) in math::CONSTANTS {
+        math.define(name, Value::Float(*value));
+        math.set_doc(name, doc);
+    }
+}
+
+fn define_natives<'a, I: Iterator<Item = &'a (&'static str, NativeFn, Doc)>>(
+    env: &Env,
+    natives: I,
+) {
+    for (name, native, doc) in natives {
+        env.define(name, Value::Fn(Function::Native(name, *native)));
+        env.set_doc(name, doc);
+    }
+}
+
+/// The text of a value as `str` and `println` show it: strings without
+/// quotes and nil as nothing.
+pub fn to_str(value: &Value) -> String {
+    match value {
+        Value::Nil => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn eq(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    for pair in args.windows(2) {
+        if !equal(&pair[0], &pair[1])? {
+            return Ok(Value::Bool(false));
+        }
+    }
+    Ok(Value::Bool(true))
+}
+
+/// Like `==`, but lazy sequences also equal lists and vectors with the
+/// same items, and maps with the same entries are equal in any order.
+fn equal(a: &Value, b: &Value) -> Result<bool, EvalError> {
+    let items = |a: &[Value], b: &[Value]| -> Result<bool, EvalError> {
+        if a.len() != b.len() {
+            return Ok(false);
+        }
+        for (a, b) in a.iter().zip(b) {
+            if !equal(a, b)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    };
+    match (a, b) {
+        (Value::LazySeq(_), Value::ListParen(_) | Value::ListBracket(_) | Value::LazySeq(_))
+        | (Value::ListParen(_) | Value::ListBracket(_), Value::LazySeq(_)) => {
+            items(&seq::to_vec(a)?, &seq::to_vec(b)?)
+        }
+        (Value::ListParen(a), Value::ListParen(b))
+        | (Value::ListBracket(a), Value::ListBracket(b)) => items(a, b),
+        (Value::ListBrace(a), Value::ListBrace(b)) => {
+            if a.len() != b.len() {
+                return Ok(false);
+            }
+            for entry in a.chunks(2) {
+                match eval::get(&Value::ListBrace(b.clone()), &entry[0]) {
+                    Some(value) if equal(&entry[1], &value)? => {}
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        (a, b) => Ok(a == b),
+    }
+}
+
+fn not_eq(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    Ok(Value::Bool(!eq(env, args)?.is_truthy()))
+}
+
+fn not(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [value] => Ok(Value::Bool(!value.is_truthy())),
+        _ => Err(arity("not", args.len())),
+    }
+}
+
+fn is_nil(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [value] => Ok(Value::Bool(*value == Value::Nil)),
+        _ => Err(arity("nil?", args.len())),
+    }
+}
+
+fn is_list(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [value] => Ok(Value::Bool(matches!(value, Value::ListParen(_)))),
+        _ => Err(arity("list?", args.len())),
+    }
+}
+
+fn is_symbol(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [value] => Ok(Value::Bool(matches!(value, Value::Symbol(_, _)))),
+        _ => Err(arity("symbol?", args.len())),
+    }
+}
+
+fn list(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    Ok(Value::ListParen(args))
+}
+
+fn vector(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    Ok(Value::ListBracket(args))
+}
+
+fn str(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    Ok(Value::String(args.iter().map(to_str).collect()))
+}
+
+fn print(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let line: Vec<String> = args.iter().map(to_str).collect();
+    env.print(OutputStream::Out, &line.join(" "));
+    Ok(Value::Nil)
+}
+
+fn println(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let line: Vec<String> = args.iter().map(to_str).collect();
+    env.print(OutputStream::Out, &format!("{}\n", line.join(" ")));
+    Ok(Value::Nil)
+}
+
+fn gensym(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let prefix = match &args[..] {
+        [] => "G".to_string(),
+        [prefix] => to_str(prefix),
+        _ => return Err(arity("gensym", args.len())),
+    };
+    Ok(Value::Symbol("".into(), eval::gensym(&prefix)))
+}
+
+fn macroexpand_1(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [form] => env.macroexpand_1(form),
+        _ => Err(arity("macroexpand-1", args.len())),
+    }
+}
+
+fn macroexpand(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [form] => env.macroexpand(form),
+        _ => Err(arity("macroexpand", args.len())),
+    }
+}
+
+fn atom(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (value, validator) = match &args[..] {
+        [value] => (value, None),
+        [value, Value::Keyword(_, option), validator] if option == "validator" => {
+            (value, Some(validator.clone()))
+        }
+        [_, option, _] => return Err(EvalError::new(format!("invalid atom option: {option}"))),
+        _ => return Err(arity("atom", args.len())),
+    };
+    let atom = Atom::new(value.clone());
+    *atom.validator.write().unwrap() = validator;
+    validate(env, &atom, value)?;
+    Ok(Value::Atom(Arc::new(atom)))
+}
+
+fn to_atom<'a>(name: &str, value: &'a Value) -> Result<&'a Arc<Atom>, EvalError> {
+    match value {
+        Value::Atom(atom) => Ok(atom),
+        value => Err(EvalError::new(format!(
+            "{name} expects an atom, got {value}"
+        ))),
+    }
+}
+
+fn validate(env: &Env, atom: &Atom, value: &Value) -> Result<(), EvalError> {
+    let validator = atom.validator.read().unwrap().clone();
+    match validator {
+        Some(validator) if !env.apply(&validator, vec![value.clone()])?.is_truthy() => {
+            Err(EvalError::new(format!("invalid reference state: {value}")))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Calls every watch of `atom` with its key, the atom and both values.
+fn notify(env: &Env, atom: &Value, old: Value, new: &Value) -> Result<(), EvalError> {
+    let watches = to_atom("notify", atom)?.watches.read().unwrap().clone();
+    for (key, watch) in watches {
+        env.apply(&watch, vec![key, atom.clone(), old.clone(), new.clone()])?;
+    }
+    Ok(())
+}
+
+fn deref(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [value] => Ok(to_atom("deref", value)?.deref()),
+        _ => Err(arity("deref", args.len())),
+    }
+}
+
+fn reset(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [atom, value] => {
+            validate(env, to_atom("reset!", atom)?, value)?;
+            let old = to_atom("reset!", atom)?.set(value.clone());
+            notify(env, atom, old, value)?;
+            Ok(value.clone())
+        }
+        _ => Err(arity("reset!", args.len())),
+    }
+}
+
+/// Applies `f` to the value and sets the result, retrying when another
+/// thread changed the value while `f` ran.
+fn swap(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (atom_value, f, rest) = match &args[..] {
+        [atom, f, rest @ ..] => (atom, f, rest),
+        _ => return Err(arity("swap!", args.len())),
+    };
+    let atom = to_atom("swap!", atom_value)?;
+    loop {
+        let (version, old) = atom.load();
+        let mut call = vec![old.clone()];
+        call.extend(rest.iter().cloned());
+        let new = env.apply(f, call)?;
+        validate(env, atom, &new)?;
+        if atom.compare_and_set(version, new.clone()) {
+            notify(env, atom_value, old, &new)?;
+            return Ok(new);
+        }
+    }
+}
+
+fn compare_and_set(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (atom_value, expected, new) = match &args[..] {
+        [atom, expected, new] => (atom, expected, new),
+        _ => return Err(arity("compare-and-set!", args.len())),
+    };
+    let atom = to_atom("compare-and-set!", atom_value)?;
+    validate(env, atom, new)?;
+    loop {
+        let (version, old) = atom.load();
+        if old != *expected {
+            return Ok(Value::Bool(false));
+        }
+        if atom.compare_and_set(version, new.clone()) {
+            notify(env, atom_value, old, new)?;
+            return Ok(Value::Bool(true));
+        }
+    }
+}
+
+fn set_validator(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [atom, validator] => {
+            let atom = to_atom("set-validator!", atom)?;
+            let validator = match validator {
+                Value::Nil => None,
+                validator => Some(validator.clone()),
+            };
+            let old = std::mem::replace(&mut *atom.validator.write().unwrap(), validator);
+            if let Err(err) = validate(env, atom, &atom.deref()) {
+                *atom.validator.write().unwrap() = old;
+                return Err(err);
+            }
+            Ok(Value::Nil)
+        }
+        _ => Err(arity("set-validator!", args.len())),
+    }
+}
+
+fn add_watch(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [atom, key, watch] => {
+            let mut watches = to_atom("add-watch", atom)?.watches.write().unwrap();
+            watches.retain(|(other, _)| other != key);
+            watches.push((key.clone(), watch.clone()));
+            Ok(atom.clone())
+        }
+        _ => Err(arity("add-watch", args.len())),
+    }
+}
+
+fn remove_watch(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [atom, key] => {
+            let mut watches = to_atom("remove-watch", atom)?.watches.write().unwrap();
+            watches.retain(|(other, _)| other != key);
+            Ok(atom.clone())
+        }
+        _ => Err(arity("remove-watch", args.len())),
+    }
+}
+
+/// Calls `f` on the worker pool, returning a channel that gets its result.
+/// `go` wraps its body in a function and passes it here.
+fn go(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let f = match &args[..] {
+        [f] => f.clone(),
+        _ => return Err(arity("go*", args.len())),
+    };
+    let result = Arc::new(Channel::new(1));
+    let (printer, channel) = (env.clone(), result.clone());
+    env.go(f, move |value| {
+        match value {
+            Ok(Value::Nil) => {}
+            Ok(value) => {
+                channel.put(value);
+            }
+            Err(err) => printer.print(OutputStream::Err, &describe(&err)),
+        }
+        channel.close();
+    });
+    Ok(Value::Chan(result))
+}
+
+/// The error message and trace, with each location as `name:line:col`
+/// when its file can be read, like the command line reports them.
+fn describe(err: &EvalError) -> String {
+    let mut contents = HashMap::new();
+    err.describe(|name, start| {
+        let content = (contents.entry(name.to_string()))
+            .or_insert_with(|| std::fs::read_to_string(name).ok());
+        match content {
+            Some(content) => {
+                let (line, col) = reader::line_col(content, start);
+                format!("{name}:{line}:{col}")
+            }
+            None => format!("{name}:{start}"),
+        }
+    })
+}
+
+fn chan(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let capacity = match &args[..] {
+        [] => 0,
+        [Value::Int(n)] if *n >= 0 => *n as usize,
+        [n] => {
+            return Err(EvalError::new(format!(
+                "chan expects a buffer size, got {n}"
+            )))
+        }
+        _ => return Err(arity("chan", args.len())),
+    };
+    Ok(Value::Chan(Arc::new(Channel::new(capacity))))
+}
+
+fn to_chan<'a>(name: &str, value: &'a Value) -> Result<&'a Arc<Channel>, EvalError> {
+    match value {
+        Value::Chan(channel) => Ok(channel),
+        value => Err(EvalError::new(format!(
+            "{name} expects a channel, got {value}"
+        ))),
+    }
+}
+
+fn put_chan(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    ChannelCall::new(">!", args)?.run(env)
+}
+
+fn take_chan(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    ChannelCall::new("<!", args)?.run(env)
+}
+
+/// Runs the first ready of a vector of operations, each a channel to take
+/// from or a `[channel value]` to put, and returns `[result channel]`.
+/// Takes the options `:priority true` and `:default value`.
+fn alts(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    ChannelCall::new("alts!", args)?.run(env)
+}
+
+/// The natives a go block parks on rather than blocking its worker.
+pub const PARKING: &[&str] = &["<!", ">!", "alts!"];
+
+/// A call to `<!`, `>!` or `alts!`: the operations it runs the first ready
+/// of.
+pub struct ChannelCall {
+    ops: Vec<Op>,
+    priority: bool,
+    default: Option<Value>,
+    /// Whether it returns `[result channel]`, as `alts!` does, rather than
+    /// the result alone.
+    alts: bool,
+}
+
+impl ChannelCall {
+    /// The call to the native `name` with `args`.
+    pub fn new(name: &str, args: Vec<Value>) -> Result<ChannelCall, EvalError> {
+        let call = |ops| ChannelCall {
+            ops,
+            priority: true,
+            default: None,
+            alts: false,
+        };
+        match (name, &args[..]) {
+            (">!", [_, Value::Nil]) => Err(EvalError::new("can't put nil on a channel")),
+            (">!", [channel, value]) => Ok(call(vec![Op::Put(
+                to_chan(">!", channel)?.clone(),
+                value.clone(),
+            )])),
+            ("<!", [channel]) => Ok(call(vec![Op::Take(to_chan("<!", channel)?.clone())])),
+            ("alts!", _) => ChannelCall::alts(args),
+            _ => Err(arity(name, args.len())),
+        }
+    }
+
+    fn alts(args: Vec<Value>) -> Result<ChannelCall, EvalError> {
+        let (specs, options) = match &args[..] {
+            [Value::ListBracket(specs), options @ ..] if options.len().is_multiple_of(2) => {
+                (specs, options)
+            }
+            [Value::ListBracket(_), ..] | [] => return Err(arity("alts!", args.len())),
+            [spec, ..] => {
+                return Err(EvalError::new(format!(
+                    "alts! expects a vector of operations, got {spec}"
+                )))
+            }
+        };
+        let (mut priority, mut default) = (false, None);
+        for option in options.chunks(2) {
+            match &option[0] {
+                Value::Keyword(_, name) if name == "priority" => priority = option[1].is_truthy(),
+                Value::Keyword(_, name) if name == "default" => default = Some(option[1].clone()),
+                option => return Err(EvalError::new(format!("invalid alts! option: {option}"))),
+            }
+        }
+        let ops = (specs.iter())
+            .map(|spec| match spec {
+                Value::ListBracket(put) => match &put[..] {
+                    [_, Value::Nil] => Err(EvalError::new("can't put nil on a channel")),
+                    [channel, value] => {
+                        Ok(Op::Put(to_chan("alts!", channel)?.clone(), value.clone()))
+                    }
+                    _ => Err(EvalError::new(format!("invalid alts! operation: {spec}"))),
+                },
+                channel => Ok(Op::Take(to_chan("alts!", channel)?.clone())),
+            })
+            .collect::<Result<Vec<Op>, EvalError>>()?;
+        if ops.is_empty() && default.is_none() {
+            return Err(EvalError::new("alts! expects at least one operation"));
+        }
+        Ok(ChannelCall {
+            ops,
+            priority,
+            default,
+            alts: true,
+        })
+    }
+
+    /// Runs the call, blocking until one of its operations is done or
+    /// `env` is interrupted.
+    pub fn run(self, env: &Env) -> Result<Value, EvalError> {
+        match self.park() {
+            Ok(value) => Ok(value),
+            Err(parked) => parked.wait(env),
+        }
+    }
+
+    /// Runs the call if it doesn't need to wait, or queues its operations
+    /// on their channels.
+    pub fn park(self) -> Result<Value, Parked> {
+        let ChannelCall {
+            ops,
+            priority,
+            default,
+            alts,
+        } = self;
+        let channels = alts.then(|| ops.iter().map(|op| op.channel().clone()).collect());
+        let outcome = Outcome { channels };
+        if let Some(default) = default {
+            return Ok(match csp::try_alts(&ops, priority) {
+                Some((i, value)) => outcome.of(i, value),
+                None => {
+                    Value::ListBracket(vec![default, Value::Keyword("".into(), "default".into())])
+                }
+            });
+        }
+        match csp::select(ops, priority) {
+            Ok((i, value)) => Ok(outcome.of(i, value)),
+            Err(pending) => Err(Parked { pending, outcome }),
+        }
+    }
+}
+
+/// A channel call whose operations are queued on their channels.
+pub struct Parked {
+    pending: csp::Pending,
+    outcome: Outcome,
+}
+
+impl Parked {
+    fn wait(self, env: &Env) -> Result<Value, EvalError> {
+        let (i, value) = env.wait(self.pending)?;
+        Ok(self.outcome.of(i, value))
+    }
+
+    /// Runs `resume` on the worker pool with the value of the call, once
+    /// one of its operations is done.
+    pub fn then<F: FnOnce(Value) + Send + 'static>(self, resume: F) {
+        let outcome = self.outcome;
+        self.pending
+            .then(move |i, value| resume(outcome.of(i, value)));
+    }
+}
+
+/// What a channel call returns for the operation that was done: its
+/// result, along with its channel for `alts!`.
+struct Outcome {
+    channels: Option<Vec<Arc<Channel>>>,
+}
+
+impl Outcome {
+    fn of(&self, i: usize, value: Value) -> Value {
+        match &self.channels {
+            Some(channels) => Value::ListBracket(vec![value, Value::Chan(channels[i].clone())]),
+            None => value,
+        }
+    }
+}
+
+fn timeout(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Int(ms)] if *ms >= 0 => {
+            Ok(Value::Chan(csp::timeout(Duration::from_millis(*ms as u64))))
+        }
+        [ms] => Err(EvalError::new(format!(
+            "timeout expects milliseconds, got {ms}"
+        ))),
+        _ => Err(arity("timeout", args.len())),
+    }
+}
+
+fn close(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [channel] => {
+            to_chan("close!", channel)?.close();
+            Ok(Value::Nil)
+        }
+        _ => Err(arity("close!", args.len())),
+    }
+}
+
+/// Milliseconds since the Unix epoch.
+fn now(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    if !args.is_empty() {
+        return Err(arity("now", args.len()));
+    }
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Value::Int(elapsed.as_millis() as i64))
+}
+
+fn ex_info(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (message, data, cause) = match &args[..] {
+        [message, data] => (message, data, None),
+        [message, data, cause] => (message, data, Some(cause.clone())),
+        _ => return Err(arity("ex-info", args.len())),
+    };
+    let message = match message {
+        Value::String(message) => message.clone(),
+        message => {
+            return Err(EvalError::new(format!(
+                "ex-info expects a message, got {message}"
+            )))
+        }
+    };
+    let data = match data {
+        Value::Nil => Value::ListBrace(vec![]),
+        Value::ListBrace(_) => data.clone(),
+        data => return Err(EvalError::new(format!("ex-info expects a map, got {data}"))),
+    };
+    Ok(Value::Error(Arc::new(ExInfo {
+        message,
+        data,
+        cause,
+    })))
+}
+
+fn ex_message(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Error(ex)] => Ok(Value::String(ex.message.clone())),
+        [_] => Ok(Value::Nil),
+        _ => Err(arity("ex-message", args.len())),
+    }
+}
+
+fn ex_data(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Error(ex)] => Ok(ex.data.clone()),
+        [_] => Ok(Value::Nil),
+        _ => Err(arity("ex-data", args.len())),
+    }
+}
+
+fn ex_cause(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Error(ex)] => Ok(ex.cause.clone().unwrap_or(Value::Nil)),
+        [_] => Ok(Value::Nil),
+        _ => Err(arity("ex-cause", args.len())),
+    }
+}
+
+/// Prints the docstring of what `name` refers to; `doc` quotes the name
+/// and passes it here.
+fn doc(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Symbol(_, name)] => {
+            let key = env.resolve(name)?;
+            let doc = env.doc(name).unwrap_or_else(|| "No docstring.".into());
+            env.print(OutputStream::Out, &format!("{key}\n  {doc}\n"));
+            Ok(Value::Nil)
+        }
+        [name] => Err(EvalError::new(format!("doc expects a symbol, got {name}"))),
+        _ => Err(arity("doc", args.len())),
+    }
+}
+
+pub fn arity(name: &str, count: usize) -> EvalError {
+    EvalError::new(format!("wrong number of args ({count}) passed to {name}"))
+}
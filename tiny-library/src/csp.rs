@@ -0,0 +1,613 @@
+//! Channels and the scheduler `go` blocks run on.
+//!
+//! Go blocks are tasks run by a fixed pool of worker threads, one per core.
+//! A channel operation that can't complete right away queues a waiter on
+//! its channel, which the operation that later matches it completes: a
+//! thread blocks on the waiter, while a go block parks, leaving what runs
+//! it next with the waiter so its worker is free for other tasks. A waiter
+//! completes once, so `alts!` queues the same one on each of its channels.
+
+use crate::eval;
+use crate::value::Value;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Task = Box<dyn FnOnce() + Send>;
+
+/// What runs a parked go block again, given the index and result of the
+/// operation it waited for.
+type Resume = Box<dyn FnOnce(usize, Value) + Send>;
+
+/// Runs `task` on the worker pool.
+pub fn spawn<F: FnOnce() + Send + 'static>(task: F) {
+    pool().spawn(Box::new(task));
+}
+
+/// A channel that values are put on and taken from in order. Unbuffered
+/// channels hand each value over: a put waits until it is taken.
+pub struct Channel {
+    capacity: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    buffer: VecDeque<Value>,
+    closed: bool,
+    /// The takes waiting for a value, with the index of their operation.
+    takes: VecDeque<(Arc<Waiter>, usize)>,
+    /// The puts waiting for room or a taker, with their values.
+    puts: VecDeque<(Arc<Waiter>, usize, Value)>,
+}
+
+impl Channel {
+    pub fn new(capacity: usize) -> Channel {
+        Channel {
+            capacity,
+            state: Mutex::new(State {
+                buffer: VecDeque::new(),
+                closed: false,
+                takes: VecDeque::new(),
+                puts: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Puts a value, waiting for room or for a taker. Returns false if the
+    /// channel is closed.
+    pub fn put(&self, value: Value) -> bool {
+        let waiter = Arc::new(Waiter::default());
+        self.start_put(&waiter, 0, value, true);
+        waiter.wait().1.is_truthy()
+    }
+
+    /// Takes a value, waiting for one. Returns nil once the channel is
+    /// closed and empty.
+    pub fn take(&self) -> Value {
+        let waiter = Arc::new(Waiter::default());
+        self.start_take(&waiter, 0, true);
+        waiter.wait().1
+    }
+
+    /// Puts a value if that doesn't need to wait: `None` if it would,
+    /// otherwise whether it was put.
+    pub fn try_put(&self, value: Value) -> Option<bool> {
+        let waiter = Arc::new(Waiter::default());
+        self.start_put(&waiter, 0, value, false);
+        waiter.result().map(|(_, put)| put.is_truthy())
+    }
+
+    /// Takes a value if one is ready, or nil if the channel is closed.
+    pub fn try_take(&self) -> Option<Value> {
+        let waiter = Arc::new(Waiter::default());
+        self.start_take(&waiter, 0, false);
+        waiter.result().map(|(_, value)| value)
+    }
+
+    /// Closes the channel. Values already put can still be taken, while
+    /// the puts still waiting fail.
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        for (waiter, index) in state.takes.drain(..) {
+            waiter.complete(index, Value::Nil);
+        }
+        for (waiter, index, _) in state.puts.drain(..) {
+            waiter.complete(index, Value::Bool(false));
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    /// Completes operation `index` of `waiter` with a value taken from the
+    /// channel if one is ready, or nil if it is closed, and otherwise
+    /// queues it if `wait` is set.
+    fn start_take(&self, waiter: &Arc<Waiter>, index: usize, wait: bool) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(value) = state.buffer.front() {
+            if waiter.complete(index, value.clone()) {
+                state.buffer.pop_front();
+                // The first waiting put fills the room made.
+                while let Some((putter, at, value)) = state.puts.pop_front() {
+                    if putter.complete(at, Value::Bool(true)) {
+                        state.buffer.push_back(value);
+                        break;
+                    }
+                }
+            }
+            return;
+        }
+        let mut i = 0;
+        while i < state.puts.len() {
+            let (putter, at, value) = &state.puts[i];
+            if Arc::ptr_eq(putter, waiter) {
+                i += 1;
+                continue;
+            }
+            let taken = (index, value.clone());
+            match Waiter::complete_both(waiter, taken, putter, (*at, Value::Bool(true))) {
+                Completed::Both => {
+                    state.puts.remove(i);
+                    return;
+                }
+                Completed::Other => {
+                    state.puts.remove(i);
+                }
+                Completed::Own => return,
+            }
+        }
+        if state.closed {
+            waiter.complete(index, Value::Nil);
+        } else if wait {
+            state.takes.push_back((waiter.clone(), index));
+        }
+    }
+
+    /// Completes operation `index` of `waiter` by handing `value` to a
+    /// waiting take or buffering it, with false if the channel is closed,
+    /// and otherwise queues it if `wait` is set.
+    fn start_put(&self, waiter: &Arc<Waiter>, index: usize, value: Value, wait: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            waiter.complete(index, Value::Bool(false));
+            return;
+        }
+        let mut i = 0;
+        while i < state.takes.len() {
+            let (taker, at) = &state.takes[i];
+            if Arc::ptr_eq(taker, waiter) {
+                i += 1;
+                continue;
+            }
+            let put = (index, Value::Bool(true));
+            match Waiter::complete_both(waiter, put, taker, (*at, value.clone())) {
+                Completed::Both => {
+                    state.takes.remove(i);
+                    return;
+                }
+                Completed::Other => {
+                    state.takes.remove(i);
+                }
+                Completed::Own => return,
+            }
+        }
+        if state.buffer.len() < self.capacity {
+            if waiter.complete(index, Value::Bool(true)) {
+                state.buffer.push_back(value);
+            }
+        } else if wait {
+            state.puts.push_back((waiter.clone(), index, value));
+        }
+    }
+
+    /// Drops the operations of `waiter` still queued, once another of its
+    /// operations completed.
+    fn cancel(&self, waiter: &Arc<Waiter>) {
+        let mut state = self.state.lock().unwrap();
+        state.takes.retain(|(other, _)| !Arc::ptr_eq(other, waiter));
+        state
+            .puts
+            .retain(|(other, _, _)| !Arc::ptr_eq(other, waiter));
+    }
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#<chan>")
+    }
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Channel) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Channel {
+    fn partial_cmp(&self, other: &Channel) -> Option<Ordering> {
+        if self == other {
+            Some(Ordering::Equal)
+        } else {
+            None
+        }
+    }
+}
+
+/// Channel operations waiting to be completed, once, by whichever of
+/// their channels is first.
+#[derive(Default)]
+struct Waiter {
+    slot: Mutex<Slot>,
+    completed: Condvar,
+}
+
+#[derive(Default)]
+struct Slot {
+    done: bool,
+    /// The index and result of the operation that completed, until taken.
+    result: Option<(usize, Value)>,
+    /// What runs the go block parked on the operations.
+    resume: Option<Resume>,
+}
+
+/// Which waiters `Waiter::complete_both` completed: both, or neither
+/// because the one named already was.
+enum Completed {
+    Both,
+    Own,
+    Other,
+}
+
+impl Waiter {
+    fn is_done(&self) -> bool {
+        self.slot.lock().unwrap().done
+    }
+
+    /// Completes operation `index` with `value`, unless one already was.
+    fn complete(&self, index: usize, value: Value) -> bool {
+        let slot = self.slot.lock().unwrap();
+        if slot.done {
+            return false;
+        }
+        self.fill(slot, index, value);
+        true
+    }
+
+    /// Completes `own` and `other` together if neither already was, as a
+    /// take and a put handing a value over. They are locked in the order
+    /// of their addresses, so two doing this at once can't deadlock.
+    fn complete_both(
+        own: &Waiter,
+        (own_index, own_value): (usize, Value),
+        other: &Waiter,
+        (other_index, other_value): (usize, Value),
+    ) -> Completed {
+        let own_first = (own as *const Waiter) < (other as *const Waiter);
+        let (first, second) = if own_first {
+            (own, other)
+        } else {
+            (other, own)
+        };
+        let first = first.slot.lock().unwrap();
+        let second = second.slot.lock().unwrap();
+        let (own_slot, other_slot) = if own_first {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        if own_slot.done {
+            return Completed::Own;
+        }
+        if other_slot.done {
+            return Completed::Other;
+        }
+        own.fill(own_slot, own_index, own_value);
+        other.fill(other_slot, other_index, other_value);
+        Completed::Both
+    }
+
+    fn fill(&self, mut slot: MutexGuard<Slot>, index: usize, value: Value) {
+        slot.done = true;
+        match slot.resume.take() {
+            Some(resume) => {
+                drop(slot);
+                resume(index, value);
+            }
+            None => {
+                slot.result = Some((index, value));
+                self.completed.notify_all();
+            }
+        }
+    }
+
+    /// Blocks until an operation completes, returning its index and result.
+    fn wait(&self) -> (usize, Value) {
+        let mut slot = self.slot.lock().unwrap();
+        loop {
+            if let Some(result) = slot.result.take() {
+                return result;
+            }
+            slot = self.completed.wait(slot).unwrap();
+        }
+    }
+
+    /// The index and result of the operation that completed, if one did.
+    fn result(&self) -> Option<(usize, Value)> {
+        self.slot.lock().unwrap().result.take()
+    }
+
+    /// Runs `resume` once an operation completes, right away if one did.
+    fn then(&self, resume: Resume) {
+        let mut slot = self.slot.lock().unwrap();
+        match slot.result.take() {
+            Some((index, value)) => {
+                drop(slot);
+                resume(index, value);
+            }
+            None => slot.resume = Some(resume),
+        }
+    }
+}
+
+/// One of the operations `alts!` chooses between.
+pub enum Op {
+    Take(Arc<Channel>),
+    Put(Arc<Channel>, Value),
+}
+
+impl Op {
+    pub fn channel(&self) -> &Arc<Channel> {
+        match self {
+            Op::Take(channel) | Op::Put(channel, _) => channel,
+        }
+    }
+
+    fn start(&self, waiter: &Arc<Waiter>, index: usize, wait: bool) {
+        match self {
+            Op::Take(channel) => channel.start_take(waiter, index, wait),
+            Op::Put(channel, value) => channel.start_put(waiter, index, value.clone(), wait),
+        }
+    }
+}
+
+/// Runs the first of `ops` that is ready, waiting until one is, and returns
+/// its index with what it returned. Unless `priority` is set, the ops are
+/// tried starting from a different one each time so none is starved.
+pub fn alts(ops: &[Op], priority: bool) -> (usize, Value) {
+    let waiter = start(ops, priority, true);
+    let ready = waiter.wait();
+    cancel(ops, &waiter);
+    ready
+}
+
+/// Runs the first of `ops` that is ready without waiting.
+pub fn try_alts(ops: &[Op], priority: bool) -> Option<(usize, Value)> {
+    start(ops, priority, false).result()
+}
+
+/// Like `alts`, but returns the ops queued on their channels instead of
+/// waiting, if none is ready.
+pub fn select(ops: Vec<Op>, priority: bool) -> Result<(usize, Value), Pending> {
+    let waiter = start(&ops, priority, true);
+    match waiter.result() {
+        Some(ready) => {
+            cancel(&ops, &waiter);
+            Ok(ready)
+        }
+        None => Err(Pending { ops, waiter }),
+    }
+}
+
+/// The operations of a parked go block, queued on their channels.
+pub struct Pending {
+    ops: Vec<Op>,
+    waiter: Arc<Waiter>,
+}
+
+impl Pending {
+    /// Blocks until an operation completes, returning its index and result,
+    /// or `None` if its waker woke it first.
+    pub fn wait(self) -> Option<(usize, Value)> {
+        let ready = self.waiter.wait();
+        cancel(&self.ops, &self.waiter);
+        Some(ready).filter(|&(index, _)| index != WOKEN)
+    }
+
+    /// What wakes `wait` without running any of the operations.
+    pub fn waker(&self) -> Waker {
+        Waker(self.waiter.clone())
+    }
+
+    /// Runs `resume` on the pool with the index and result of the first
+    /// operation to complete.
+    pub fn then<F: FnOnce(usize, Value) + Send + 'static>(self, resume: F) {
+        let Pending { ops, waiter } = self;
+        let waiting = Arc::downgrade(&waiter);
+        // Completing an operation holds its channel's lock, so the others
+        // are cancelled on the pool.
+        waiter.then(Box::new(move |index, value| {
+            spawn(move || {
+                if let Some(waiter) = waiting.upgrade() {
+                    cancel(&ops, &waiter);
+                }
+                resume(index, value)
+            })
+        }));
+    }
+}
+
+/// The index a woken waiter completes with, which no operation has.
+const WOKEN: usize = usize::MAX;
+
+/// Wakes a thread blocked on channel operations, as when its evaluation
+/// is interrupted. Operations still queued are dropped once their
+/// channels get to them.
+#[derive(Clone)]
+pub struct Waker(Arc<Waiter>);
+
+impl Waker {
+    pub fn wake(&self) {
+        self.0.complete(WOKEN, Value::Nil);
+    }
+}
+
+impl PartialEq for Waker {
+    fn eq(&self, other: &Waker) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Starts `ops`, stopping at the first that completes right away, and
+/// queues the others on their channels if `wait` is set.
+fn start(ops: &[Op], priority: bool, wait: bool) -> Arc<Waiter> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let first = if priority || ops.is_empty() {
+        0
+    } else {
+        NEXT.fetch_add(1, atomic::Ordering::Relaxed) % ops.len()
+    };
+    let waiter = Arc::new(Waiter::default());
+    for i in (0..ops.len()).map(|i| (first + i) % ops.len()) {
+        ops[i].start(&waiter, i, wait);
+        if waiter.is_done() {
+            break;
+        }
+    }
+    waiter
+}
+
+fn cancel(ops: &[Op], waiter: &Arc<Waiter>) {
+    if ops.len() > 1 {
+        for op in ops {
+            op.channel().cancel(waiter);
+        }
+    }
+}
+
+/// A channel that closes after `duration`.
+pub fn timeout(duration: Duration) -> Arc<Channel> {
+    let channel = Arc::new(Channel::new(0));
+    timer().add(Instant::now() + duration, channel.clone());
+    channel
+}
+
+struct Pool {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    /// How many workers run tasks.
+    size: usize,
+}
+
+struct Queue {
+    tasks: VecDeque<Task>,
+    workers: usize,
+    idle: usize,
+}
+
+fn pool() -> &'static Arc<Pool> {
+    static POOL: OnceLock<Arc<Pool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        Arc::new(Pool {
+            queue: Mutex::new(Queue {
+                tasks: VecDeque::new(),
+                workers: 0,
+                idle: 0,
+            }),
+            ready: Condvar::new(),
+            size: thread::available_parallelism().map_or(4, |n| n.get()),
+        })
+    })
+}
+
+impl Pool {
+    /// Queues `task`, waking an idle worker for it, or starting one if
+    /// fewer than `size` are running.
+    fn spawn(self: &Arc<Pool>, task: Task) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.tasks.push_back(task);
+        if queue.idle > 0 {
+            self.ready.notify_one();
+        } else if queue.workers < self.size {
+            queue.workers += 1;
+            let pool = self.clone();
+            thread::Builder::new()
+                .name("tiny-worker".into())
+                .stack_size(eval::STACK_SIZE)
+                .spawn(move || pool.work())
+                .expect("failed to start a worker thread");
+        }
+    }
+
+    fn work(&self) {
+        loop {
+            let task = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    if let Some(task) = queue.tasks.pop_front() {
+                        break task;
+                    }
+                    queue.idle += 1;
+                    queue = self.ready.wait(queue).unwrap();
+                    queue.idle -= 1;
+                }
+            };
+            task();
+        }
+    }
+}
+
+struct Timer {
+    deadlines: Mutex<BinaryHeap<Reverse<Deadline>>>,
+    changed: Condvar,
+}
+
+struct Deadline(Instant, Arc<Channel>);
+
+impl PartialEq for Deadline {
+    fn eq(&self, other: &Deadline) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Deadline {}
+
+impl PartialOrd for Deadline {
+    fn partial_cmp(&self, other: &Deadline) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Deadline {
+    fn cmp(&self, other: &Deadline) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+fn timer() -> &'static Arc<Timer> {
+    static TIMER: OnceLock<Arc<Timer>> = OnceLock::new();
+    TIMER.get_or_init(|| {
+        let timer = Arc::new(Timer {
+            deadlines: Mutex::new(BinaryHeap::new()),
+            changed: Condvar::new(),
+        });
+        let running = timer.clone();
+        thread::Builder::new()
+            .name("tiny-timer".into())
+            .spawn(move || running.run())
+            .expect("failed to start the timer thread");
+        timer
+    })
+}
+
+impl Timer {
+    fn add(&self, deadline: Instant, channel: Arc<Channel>) {
+        let mut deadlines = self.deadlines.lock().unwrap();
+        deadlines.push(Reverse(Deadline(deadline, channel)));
+        self.changed.notify_one();
+    }
+
+    /// Closes each channel once its deadline passes.
+    fn run(&self) {
+        let mut deadlines = self.deadlines.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            match deadlines.peek() {
+                Some(Reverse(Deadline(deadline, _))) if *deadline <= now => {
+                    let Reverse(Deadline(_, channel)) = deadlines.pop().unwrap();
+                    channel.close();
+                }
+                Some(Reverse(Deadline(deadline, _))) => {
+                    let wait = *deadline - now;
+                    deadlines = self.changed.wait_timeout(deadlines, wait).unwrap().0;
+                }
+                None => deadlines = self.changed.wait(deadlines).unwrap(),
+            }
+        }
+    }
+}
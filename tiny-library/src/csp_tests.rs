@@ -0,0 +1,133 @@
+#[cfg(test)]
+mod tests {
+    use csp::{self, Channel, Op};
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use value::Value;
+
+    #[test]
+    fn test_buffered_channel() {
+        let channel = Channel::new(2);
+        assert_eq!(channel.try_put(Value::Int(1)), Some(true));
+        assert_eq!(channel.try_put(Value::Int(2)), Some(true));
+        assert_eq!(channel.try_put(Value::Int(3)), None);
+        assert_eq!(channel.take(), Value::Int(1));
+        assert_eq!(channel.try_take(), Some(Value::Int(2)));
+        assert_eq!(channel.try_take(), None);
+    }
+
+    #[test]
+    fn test_unbuffered_channel() {
+        let channel = Arc::new(Channel::new(0));
+        assert_eq!(channel.try_put(Value::Int(1)), None);
+        let (done, finished) = mpsc::channel();
+        let putter = channel.clone();
+        thread::spawn(move || {
+            putter.put(Value::Int(1));
+            done.send(()).unwrap();
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert!(finished.try_recv().is_err());
+        assert_eq!(channel.take(), Value::Int(1));
+        finished.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn test_closed_channel() {
+        let channel = Channel::new(1);
+        assert!(channel.put(Value::Int(1)));
+        channel.close();
+        assert!(channel.is_closed());
+        assert!(!channel.put(Value::Int(2)));
+        assert_eq!(channel.take(), Value::Int(1));
+        assert_eq!(channel.take(), Value::Nil);
+        assert_eq!(channel.try_take(), Some(Value::Nil));
+    }
+
+    #[test]
+    fn test_alts() {
+        let empty = Arc::new(Channel::new(1));
+        let full = Arc::new(Channel::new(1));
+        full.put(Value::Int(1));
+        let ops = [Op::Take(empty.clone()), Op::Take(full.clone())];
+        assert_eq!(csp::alts(&ops, false), (1, Value::Int(1)));
+
+        let ops = [
+            Op::Take(empty.clone()),
+            Op::Put(full.clone(), Value::Int(2)),
+        ];
+        assert_eq!(csp::alts(&ops, true), (1, Value::Bool(true)));
+
+        let putter = empty.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            putter.put(Value::Int(3));
+        });
+        let ops = [Op::Take(empty), Op::Put(full, Value::Int(4))];
+        assert_eq!(csp::alts(&ops, true), (0, Value::Int(3)));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let start = Instant::now();
+        let late = csp::timeout(Duration::from_millis(200));
+        let early = csp::timeout(Duration::from_millis(20));
+        assert_eq!(early.take(), Value::Nil);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(!late.is_closed());
+        assert_eq!(late.take(), Value::Nil);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_alts_on_both_sides() {
+        // Both ends of an unbuffered channel wait in `alts`, so each
+        // completes only if the other's pending operation is found queued.
+        let channel = Arc::new(Channel::new(0));
+        let other = Arc::new(Channel::new(0));
+        let putter = channel.clone();
+        let other_put = other.clone();
+        let handle = thread::spawn(move || {
+            let ops = [
+                Op::Put(putter, Value::Int(1)),
+                Op::Put(other_put, Value::Int(2)),
+            ];
+            csp::alts(&ops, true)
+        });
+        thread::sleep(Duration::from_millis(20));
+        let ops = [Op::Take(channel), Op::Take(Arc::new(Channel::new(0)))];
+        assert_eq!(csp::alts(&ops, false), (0, Value::Int(1)));
+        assert_eq!(handle.join().unwrap(), (0, Value::Bool(true)));
+        // The cancelled put no longer waits on the other channel.
+        assert_eq!(other.try_take(), None);
+    }
+
+    #[test]
+    fn test_parked_tasks_dont_starve_the_pool() {
+        let channels: Vec<Arc<Channel>> = (0..10_000).map(|_| Arc::new(Channel::new(0))).collect();
+        let results = Arc::new(Channel::new(10_000));
+        for channel in &channels {
+            let (channel, results) = (channel.clone(), results.clone());
+            let done = move |value| {
+                results.put(value);
+            };
+            csp::spawn(move || match csp::select(vec![Op::Take(channel)], false) {
+                Ok((_, value)) => done(value),
+                Err(pending) => pending.then(move |_, value| done(value)),
+            });
+        }
+        for (i, channel) in channels.iter().enumerate() {
+            channel.put(Value::Int(i as i64));
+        }
+        let mut sum = 0;
+        for _ in 0..10_000 {
+            match results.take() {
+                Value::Int(i) => sum += i,
+                value => panic!("{:?}", value),
+            }
+        }
+        assert_eq!(sum, (0..10_000).sum::<i64>());
+    }
+}
@@ -0,0 +1,3190 @@
+use crate::core::{self, ChannelCall};
+use crate::csp;
+use crate::io::{Capabilities, Output, OutputStream};
+use crate::math::Rng;
+use crate::reader::{ReadError, ReadErrorKind, Reader, Span};
+use crate::seq;
+use crate::types::{EnumType, Field, StructType, Type, VariantType};
+use crate::value::{ExInfo, Function, Value};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::thread::LocalKey;
+
+/// The namespace natives and the prelude live in, referred by every other.
+pub const CORE: &str = "tiny.core";
+
+/// The namespace of code that isn't loaded as a module.
+pub const USER: &str = "user";
+
+/// How deeply calls to tiny functions can nest on a thread before they
+/// fail with an error rather than overflowing its stack.
+pub const MAX_DEPTH: usize = 2_000;
+
+/// How deeply macro expansions can nest on a thread, for macros that
+/// expand into themselves.
+pub const MAX_EXPANSION_DEPTH: usize = 1_000;
+
+/// The stack size threads evaluating tiny need for calls `MAX_DEPTH` deep,
+/// even in debug builds.
+pub const STACK_SIZE: usize = 256 << 20;
+
+thread_local! {
+    /// How deeply calls to tiny functions are nested on this thread.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// How deeply macro expansions are nested on this thread.
+    static EXPANSION_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A call to a tiny function or a macro expansion, counted in `DEPTH` or
+/// `EXPANSION_DEPTH` until it returns.
+struct Depth(&'static LocalKey<Cell<usize>>);
+
+impl Depth {
+    fn enter() -> Result<Depth, EvalError> {
+        Depth::count(&DEPTH, MAX_DEPTH).ok_or_else(|| EvalError::new("stack depth exceeded"))
+    }
+
+    fn expand() -> Option<Depth> {
+        Depth::count(&EXPANSION_DEPTH, MAX_EXPANSION_DEPTH)
+    }
+
+    fn count(key: &'static LocalKey<Cell<usize>>, max: usize) -> Option<Depth> {
+        key.with(|depth| {
+            if depth.get() >= max {
+                return None;
+            }
+            depth.set(depth.get() + 1);
+            Some(Depth(key))
+        })
+    }
+}
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        self.0.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalError {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    pub kind: ErrorKind,
+    pub message: String,
+    /// What `throw` threw, or `None` for errors raised by the evaluator.
+    pub value: Option<Box<Value>>,
+    /// The function calls the error unwound through, innermost first.
+    pub trace: Vec<TraceFrame>,
+}
+
+/// What an error or warning is about, for tools to tell them apart
+/// without parsing their messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ErrorKind {
+    Read(ReadErrorKind),
+    UnresolvedSymbol,
+    UnresolvedModule,
+    UnresolvedNamespace,
+    ImportCycle,
+    NonExhaustiveMatch,
+    #[default]
+    Other,
+}
+
+/// A call to a tiny function: the function and where it was called.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceFrame {
+    pub function: String,
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl EvalError {
+    /// An error without a location yet; the evaluator attaches the span of
+    /// the call that raised it.
+    pub fn new<S: Into<String>>(message: S) -> EvalError {
+        EvalError {
+            name: String::new(),
+            start: 0,
+            end: 0,
+            kind: ErrorKind::Other,
+            message: message.into(),
+            value: None,
+            trace: vec![],
+        }
+    }
+
+    fn located(name: &str, start: usize, end: usize, message: String) -> EvalError {
+        EvalError {
+            name: name.into(),
+            start,
+            end,
+            kind: ErrorKind::Other,
+            message,
+            value: None,
+            trace: vec![],
+        }
+    }
+
+    pub fn with_kind(mut self, kind: ErrorKind) -> EvalError {
+        self.kind = kind;
+        self
+    }
+
+    /// The message and trace as the command line prints them, with each
+    /// location written by `position`. A run of calls from the same place,
+    /// as in deep recursion, prints as its first frame and a count.
+    pub fn describe<F: FnMut(&str, usize) -> String>(&self, mut position: F) -> String {
+        let mut lines = format!(
+            "{}: error: {}\n",
+            position(&self.name, self.start),
+            self.message
+        );
+        let mut frames = self.trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            lines.push_str(&format!(
+                "  at {} ({})\n",
+                frame.function,
+                position(&frame.name, frame.start)
+            ));
+            let mut repeats = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeats += 1;
+            }
+            if repeats > 0 {
+                let calls = if repeats == 1 { "call" } else { "calls" };
+                lines.push_str(&format!(
+                    "  ... {repeats} more {calls} to {}\n",
+                    frame.function
+                ));
+            }
+        }
+        lines
+    }
+
+    /// The value a `catch` clause binds: the thrown value, or an `ex-info`
+    /// with the message of a runtime error.
+    pub fn to_value(&self) -> Value {
+        self.value.as_deref().cloned().unwrap_or_else(|| {
+            Value::Error(Arc::new(ExInfo {
+                message: self.message.clone(),
+                data: Value::ListBrace(vec![]),
+                cause: None,
+            }))
+        })
+    }
+
+    fn at(mut self, name: &str, start: usize, end: usize) -> EvalError {
+        if self.name.is_empty() && self.start == 0 && self.end == 0 {
+            self.name = name.into();
+            self.start = start;
+            self.end = end;
+        }
+        self
+    }
+}
+
+impl From<ReadError> for EvalError {
+    fn from(err: ReadError) -> EvalError {
+        EvalError::located(&err.name, err.start, err.end, err.message)
+            .with_kind(ErrorKind::Read(err.kind))
+    }
+}
+
+/// A problem found while analyzing that doesn't stop evaluation, such as a
+/// `match` that misses some variants.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+/// What `Env::check_str` found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checked {
+    /// How many forms were read.
+    pub forms: usize,
+    pub errors: Vec<EvalError>,
+}
+
+/// The global environment: every top-level definition of every loaded
+/// namespace, keyed by qualified name. An `Env` evaluates in one namespace
+/// and clones of it share everything else.
+#[derive(Clone)]
+pub struct Env {
+    ns: String,
+    globals: Arc<RwLock<HashMap<String, Value>>>,
+    macros: Arc<RwLock<HashMap<String, Value>>>,
+    namespaces: Arc<RwLock<HashMap<String, Namespace>>>,
+    modules: Arc<Modules>,
+    warnings: Arc<Mutex<Vec<Warning>>>,
+    docs: Arc<RwLock<HashMap<String, String>>>,
+    capabilities: Arc<RwLock<Capabilities>>,
+    rng: Arc<Mutex<Rng>>,
+    output: Arc<RwLock<Option<Output>>>,
+    interrupted: Arc<AtomicBool>,
+    /// What wakes the natives blocked on channels, so interrupting stops
+    /// them too.
+    blocked: Arc<Mutex<Vec<csp::Waker>>>,
+}
+
+#[derive(Default)]
+struct Namespace {
+    aliases: HashMap<String, String>,
+    refers: HashMap<String, String>,
+    private: HashSet<String>,
+}
+
+/// Where modules are looked up, the chain of modules being loaded and the
+/// files they were loaded from.
+#[derive(Default)]
+struct Modules {
+    roots: RwLock<Vec<PathBuf>>,
+    loading: Mutex<Vec<String>>,
+    files: Mutex<Vec<PathBuf>>,
+}
+
+impl Default for Env {
+    fn default() -> Env {
+        Env::new()
+    }
+}
+
+impl Env {
+    pub fn new() -> Env {
+        let env = Env {
+            ns: CORE.into(),
+            globals: Arc::new(RwLock::new(HashMap::new())),
+            macros: Arc::new(RwLock::new(HashMap::new())),
+            namespaces: Arc::new(RwLock::new(HashMap::new())),
+            modules: Arc::new(Modules::default()),
+            warnings: Arc::new(Mutex::new(vec![])),
+            docs: Arc::new(RwLock::new(HashMap::new())),
+            capabilities: Arc::new(RwLock::new(Capabilities::ALL)),
+            rng: Arc::new(Mutex::new(Rng::default())),
+            output: Arc::new(RwLock::new(None)),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            blocked: Arc::new(Mutex::new(vec![])),
+        }
+        .in_ns(CORE);
+        core::install(&env);
+        env.eval_str("core.tiny", core::PRELUDE)
+            .expect("the core prelude evaluates");
+        env.in_ns(USER)
+    }
+
+    /// The namespace this environment evaluates in.
+    pub fn ns(&self) -> &str {
+        &self.ns
+    }
+
+    /// An environment sharing every definition with this one but evaluating
+    /// in namespace `ns`, which is created if needed.
+    pub fn in_ns(&self, ns: &str) -> Env {
+        self.namespaces
+            .write()
+            .unwrap()
+            .entry(ns.into())
+            .or_default();
+        Env {
+            ns: ns.into(),
+            ..self.clone()
+        }
+    }
+
+    pub fn define(&self, name: &str, value: Value) {
+        let key = format!("{}/{name}", self.ns);
+        self.globals.write().unwrap().insert(key, value);
+    }
+
+    /// The value `name` refers to in this namespace.
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        let key = self.resolve(name).ok()?;
+        self.globals.read().unwrap().get(&key).cloned()
+    }
+
+    pub fn define_macro(&self, name: &str, expander: Value) {
+        let key = format!("{}/{name}", self.ns);
+        self.macros.write().unwrap().insert(key, expander);
+    }
+
+    pub fn lookup_macro(&self, name: &str) -> Option<Value> {
+        let key = self.resolve(name).ok()?;
+        self.macros.read().unwrap().get(&key).cloned()
+    }
+
+    pub fn set_doc(&self, name: &str, doc: &str) {
+        let key = format!("{}/{name}", self.ns);
+        self.docs.write().unwrap().insert(key, doc.into());
+    }
+
+    /// The docstring of what `name` refers to in this namespace.
+    pub fn doc(&self, name: &str) -> Option<String> {
+        let key = self.resolve(name).ok()?;
+        self.docs.read().unwrap().get(&key).cloned()
+    }
+
+    /// What the `std.io` and `std.os` functions may do, in every clone of
+    /// this environment.
+    pub fn capabilities(&self) -> Capabilities {
+        *self.capabilities.read().unwrap()
+    }
+
+    pub fn set_capabilities(&self, capabilities: Capabilities) {
+        *self.capabilities.write().unwrap() = capabilities;
+    }
+
+    /// Sends what every clone of this environment prints to `output`, or
+    /// to stdout and stderr when it is `None`.
+    pub fn set_output(&self, output: Option<Output>) {
+        *self.output.write().unwrap() = output;
+    }
+
+    /// Prints `text` to `stream`, or to the output set instead.
+    pub fn print(&self, stream: OutputStream, text: &str) {
+        let output = self.output.read().unwrap().clone();
+        match (output, stream) {
+            (Some(output), stream) => output(stream, text),
+            (None, OutputStream::Out) => {
+                print!("{text}");
+                let _ = std::io::stdout().flush();
+            }
+            (None, OutputStream::Err) => eprint!("{text}"),
+        }
+    }
+
+    /// Makes evaluation in every clone of this environment fail with
+    /// `interrupted` until `resume` is called, waking the natives blocked
+    /// on channels. Other natives that block only notice once they return.
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+        for waker in self.blocked.lock().unwrap().iter() {
+            waker.wake();
+        }
+    }
+
+    /// Blocks until one of the channel operations of `pending` is done,
+    /// failing with `interrupted` if this environment is interrupted first.
+    pub fn wait(&self, pending: csp::Pending) -> Result<(usize, Value), EvalError> {
+        let waker = pending.waker();
+        self.blocked.lock().unwrap().push(waker.clone());
+        if self.is_interrupted() {
+            waker.wake();
+        }
+        let ready = pending.wait();
+        self.blocked.lock().unwrap().retain(|other| *other != waker);
+        ready.ok_or_else(|| EvalError::new("interrupted"))
+    }
+
+    pub fn resume(&self) {
+        self.interrupted.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed)
+    }
+
+    /// The random numbers of every clone of this environment.
+    pub fn rng(&self) -> MutexGuard<'_, Rng> {
+        self.rng.lock().unwrap()
+    }
+
+    /// The warnings found since the last call, oldest first.
+    pub fn take_warnings(&self) -> Vec<Warning> {
+        std::mem::take(&mut *self.warnings.lock().unwrap())
+    }
+
+    /// The files modules were loaded from, including ones that failed to
+    /// load, in the order they were first imported.
+    pub fn module_files(&self) -> Vec<PathBuf> {
+        self.modules.files.lock().unwrap().clone()
+    }
+
+    /// Adds a directory that `import` looks for modules in.
+    pub fn add_source_root<P: Into<PathBuf>>(&self, root: P) {
+        self.modules.roots.write().unwrap().push(root.into());
+    }
+
+    /// The file module `ns` is loaded from: `app.todo-input` is
+    /// `app/todo_input.tiny` under the first source root that has it.
+    pub fn module_path(&self, ns: &str) -> Option<PathBuf> {
+        let relative: PathBuf = ns.split('.').map(|part| part.replace('-', "_")).collect();
+        let relative = relative.with_extension("tiny");
+        self.modules
+            .roots
+            .read()
+            .unwrap()
+            .iter()
+            .map(|root| root.join(&relative))
+            .find(|path| path.is_file())
+    }
+
+    /// Loads module `ns` unless it is already loaded.
+    pub fn load_module(&self, ns: &str) -> Result<(), EvalError> {
+        {
+            let mut loading = self.modules.loading.lock().unwrap();
+            if let Some(i) = loading.iter().position(|module| module == ns) {
+                let chain = loading[i..].join(" -> ");
+                let message = format!("import cycle: {chain} -> {ns}");
+                return Err(EvalError::new(message).with_kind(ErrorKind::ImportCycle));
+            }
+            if self.namespaces.read().unwrap().contains_key(ns) {
+                return Ok(());
+            }
+            loading.push(ns.into());
+        }
+        let result = self.load_module_file(ns);
+        self.modules.loading.lock().unwrap().pop();
+        if result.is_err() {
+            self.namespaces.write().unwrap().remove(ns);
+        }
+        result
+    }
+
+    fn load_module_file(&self, ns: &str) -> Result<(), EvalError> {
+        let path = self.module_path(ns).ok_or_else(|| {
+            let roots: Vec<String> = (self.modules.roots.read().unwrap().iter())
+                .map(|root| root.display().to_string())
+                .collect();
+            EvalError::new(format!(
+                "could not find module {ns} in source roots [{}]",
+                roots.join(", ")
+            ))
+            .with_kind(ErrorKind::UnresolvedModule)
+        })?;
+        let name = path.display().to_string();
+        self.modules.files.lock().unwrap().push(path.clone());
+        let content = std::fs::read_to_string(&path)
+            .map_err(|err| EvalError::new(format!("could not read {name}: {err}")))?;
+        self.in_ns(ns).eval_str(&name, &content).map(|_| ())
+    }
+
+    /// The qualified name `symbol` refers to in this namespace: `alias/name`
+    /// goes through an import alias, and a bare name is looked up in this
+    /// namespace, its referred names and then `tiny.core`. Unknown bare
+    /// names belong to this namespace so they can be defined later.
+    pub fn resolve(&self, symbol: &str) -> Result<String, EvalError> {
+        let namespaces = self.namespaces.read().unwrap();
+        let current = &namespaces[&self.ns];
+        if let Some((prefix, name)) = split_symbol(symbol) {
+            let target = current.aliases.get(prefix).map_or(prefix, String::as_str);
+            let ns = namespaces.get(target).ok_or_else(|| {
+                EvalError::new(format!("no such namespace: {prefix}"))
+                    .with_kind(ErrorKind::UnresolvedNamespace)
+            })?;
+            if target != self.ns && ns.private.contains(name) {
+                return Err(EvalError::new(format!("{target}/{name} is not public")));
+            }
+            return Ok(format!("{target}/{name}"));
+        }
+        let own = format!("{}/{symbol}", self.ns);
+        if self.is_defined(&own) {
+            return Ok(own);
+        }
+        if let Some(key) = current.refers.get(symbol) {
+            return Ok(key.clone());
+        }
+        let core = format!("{CORE}/{symbol}");
+        if self.is_defined(&core) {
+            return Ok(core);
+        }
+        Ok(own)
+    }
+
+    /// The names that resolve in this namespace without being special
+    /// forms, sorted: its own definitions, the names it refers to, those of
+    /// `tiny.core`, and `alias/name` for the public names of each namespace
+    /// it aliases.
+    pub fn visible_names(&self) -> Vec<String> {
+        let namespaces = self.namespaces.read().unwrap();
+        let current = &namespaces[&self.ns];
+        let mut names: Vec<String> = current.refers.keys().cloned().collect();
+        let globals = self.globals.read().unwrap();
+        let macros = self.macros.read().unwrap();
+        for key in globals.keys().chain(macros.keys()) {
+            let (ns, name) = match split_symbol(key) {
+                Some(parts) => parts,
+                None => continue,
+            };
+            if ns == self.ns || ns == CORE {
+                names.push(name.into());
+            }
+            for (alias, target) in &current.aliases {
+                let private = namespaces
+                    .get(target)
+                    .is_some_and(|ns| ns.private.contains(name));
+                if target == ns && (!private || target == &self.ns) {
+                    names.push(format!("{alias}/{name}"));
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn is_defined(&self, key: &str) -> bool {
+        self.globals.read().unwrap().contains_key(key)
+            || self.macros.read().unwrap().contains_key(key)
+    }
+
+    fn global(&self, key: &str) -> Option<Value> {
+        self.globals.read().unwrap().get(key).cloned()
+    }
+
+    /// Makes `alias/name` refer to `module/name` in this namespace.
+    fn alias(&self, alias: &str, module: &str) {
+        let mut namespaces = self.namespaces.write().unwrap();
+        let current = namespaces.get_mut(&self.ns).unwrap();
+        current.aliases.insert(alias.into(), module.into());
+    }
+
+    /// Makes the bare `name` refer to `module/name` in this namespace.
+    fn refer(&self, module: &str, name: &str) -> Result<(), String> {
+        let key = format!("{module}/{name}");
+        if !self.is_defined(&key) {
+            return Err(format!("{name} does not exist in {module}"));
+        }
+        let mut namespaces = self.namespaces.write().unwrap();
+        if namespaces[module].private.contains(name) {
+            return Err(format!("{key} is not public"));
+        }
+        let current = namespaces.get_mut(&self.ns).unwrap();
+        current.refers.insert(name.into(), key);
+        Ok(())
+    }
+
+    /// The names `module` makes available to other namespaces.
+    fn public_names(&self, module: &str) -> Vec<String> {
+        let prefix = format!("{module}/");
+        let namespaces = self.namespaces.read().unwrap();
+        let private = &namespaces[module].private;
+        let globals = self.globals.read().unwrap();
+        let macros = self.macros.read().unwrap();
+        let mut names: Vec<String> = (globals.keys().chain(macros.keys()))
+            .filter_map(|key| key.strip_prefix(&prefix))
+            .filter(|name| !name.contains('/') && !private.contains(*name))
+            .map(String::from)
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn set_private(&self, name: &str) {
+        let mut namespaces = self.namespaces.write().unwrap();
+        let current = namespaces.get_mut(&self.ns).unwrap();
+        current.private.insert(name.into());
+    }
+
+    /// Expands `form` once if it is a call to a macro, otherwise returns it
+    /// unchanged.
+    pub fn macroexpand_1(&self, form: &Value) -> Result<Value, EvalError> {
+        if let Value::ListParen(items) = form {
+            if let Some(Value::Symbol(_, head)) = items.first() {
+                if let Some(expander) = self.lookup_macro(head) {
+                    return self.apply(&expander, items[1..].to_vec());
+                }
+            }
+        }
+        Ok(form.clone())
+    }
+
+    /// Expands `form` until its head is no longer a macro.
+    pub fn macroexpand(&self, form: &Value) -> Result<Value, EvalError> {
+        let mut form = form.clone();
+        loop {
+            let expanded = self.macroexpand_1(&form)?;
+            if expanded == form {
+                return Ok(form);
+            }
+            form = expanded;
+        }
+    }
+
+    pub fn eval(&self, form: &Value) -> Result<Value, EvalError> {
+        self.eval_form("", form, &Span::default())
+    }
+
+    pub fn eval_form(&self, name: &str, form: &Value, span: &Span) -> Result<Value, EvalError> {
+        let expr = Analyzer::new(self, name).analyze(form, span)?;
+        Eval { env: self, name }.eval(&expr, &Scope::default())
+    }
+
+    /// Reads and evaluates every form in `content`, returning the last value.
+    pub fn eval_str(&self, name: &str, content: &str) -> Result<Value, EvalError> {
+        let mut reader = Reader::new(name, content);
+        let mut result = Value::Nil;
+        while let Some(form) = reader.read_spanned() {
+            let (form, span) = form?;
+            result = self.eval_form(name, &form, &span)?;
+        }
+        Ok(result)
+    }
+
+    /// Checks `content` without running it, finding errors in order. Each
+    /// form is analyzed, and its definitions are declared so later forms
+    /// can use them: macros, structs, enums, imports and functions take
+    /// effect, while other values are left nil. Once every form is
+    /// checked, references to globals never defined are errors.
+    pub fn check_str(&self, name: &str, content: &str) -> Checked {
+        let mut reader = Reader::new(name, content);
+        let mut forms = 0;
+        let mut errors = vec![];
+        let mut references = vec![];
+        while let Some(form) = reader.read_spanned() {
+            let (form, span) = match form {
+                Ok(form) => form,
+                Err(err) => {
+                    // The reader can't find where the next form starts.
+                    errors.push(err.into());
+                    break;
+                }
+            };
+            forms += 1;
+            let declared = Analyzer::new(self, name)
+                .analyze(&form, &span)
+                .and_then(|expr| {
+                    expr.globals(&mut references);
+                    self.declare(name, &expr)
+                });
+            if let Err(err) = declared {
+                errors.push(err);
+            }
+        }
+        for (key, symbol, start, end) in references {
+            if !self.is_defined(&key) {
+                let message = format!("unable to resolve symbol: {symbol}");
+                let err = EvalError::located(name, start, end, message);
+                errors.push(err.with_kind(ErrorKind::UnresolvedSymbol));
+            }
+        }
+        errors.sort_by_key(|err| err.start);
+        // Expanding a macro can repeat an argument at the span of the call.
+        errors.dedup_by(|a, b| (a.start, a.end, &a.message) == (b.start, b.end, &b.message));
+        Checked { forms, errors }
+    }
+
+    /// Makes the definitions of `expr` take effect for `check_str`, without
+    /// evaluating anything that could have side effects.
+    fn declare(&self, name: &str, expr: &Expr) -> Result<(), EvalError> {
+        let eval = Eval { env: self, name };
+        match &expr.kind {
+            Kind::Def(key, value) => {
+                let value = match value.kind {
+                    Kind::Fn(_) | Kind::Const(_) => eval.eval(value, &Scope::default())?,
+                    _ => Value::Nil,
+                };
+                self.globals.write().unwrap().insert(key.clone(), value);
+            }
+            Kind::DefMacro(_, _) | Kind::DefStruct(_) | Kind::DefEnum(_) => {
+                eval.eval(expr, &Scope::default())?;
+            }
+            Kind::Do(items) => {
+                for item in items {
+                    self.declare(name, item)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Calls `f`, a function of no arguments, as a go block on the worker
+    /// pool, and passes its result to `done`. `<!`, `>!` and `alts!`
+    /// called in its body park it rather than blocking its worker, while in
+    /// the functions it calls they block as they do anywhere else.
+    pub fn go<F: FnOnce(Result<Value, EvalError>) + Send + 'static>(&self, f: Value, done: F) {
+        if let Value::Fn(Function::Lambda(lambda)) = &f {
+            let parks = (lambda.func.arities.iter()).position(|arity| {
+                arity.params.is_empty() && arity.rest.is_none() && arity.body.parks
+            });
+            if let Some(arity) = parks {
+                let block = GoBlock {
+                    env: self.clone(),
+                    lambda: lambda.clone(),
+                    arity,
+                    frames: vec![],
+                    done: Box::new(done),
+                };
+                return csp::spawn(move || block.run(None));
+            }
+        }
+        let env = self.clone();
+        csp::spawn(move || done(env.apply(&f, vec![])));
+    }
+
+    pub fn apply(&self, f: &Value, args: Vec<Value>) -> Result<Value, EvalError> {
+        match f {
+            Value::Fn(Function::Native(_, native)) => native(self, args),
+            Value::Fn(Function::Lambda(lambda)) => {
+                let _depth = Depth::enter()?;
+                let arity = lambda.arity(args.len()).ok_or_else(|| {
+                    EvalError::new(format!(
+                        "wrong number of args ({}) passed to {}",
+                        args.len(),
+                        lambda.name()
+                    ))
+                })?;
+                let eval = Eval {
+                    env: self,
+                    name: &lambda.func.file,
+                };
+                let mut base = lambda.scope.clone();
+                if let Some(name) = &lambda.func.name {
+                    base = base.bind(name, f.clone());
+                }
+                let mut args = args;
+                if arity.rest.is_some() {
+                    let rest = args.split_off(arity.params.len());
+                    args.push(if rest.is_empty() {
+                        Value::Nil
+                    } else {
+                        Value::ListParen(rest)
+                    });
+                }
+                // `recur` passes the rest parameter as a single seq
+                loop {
+                    let mut scope = base.clone();
+                    for (param, arg) in arity.params.iter().chain(&arity.rest).zip(args) {
+                        scope = eval.bind(param, arg, scope)?;
+                    }
+                    match eval.eval_tail(&arity.body, &scope)? {
+                        Tail::Value(value) => return Ok(value),
+                        Tail::Recur(next) => args = next,
+                    }
+                }
+            }
+            Value::Fn(Function::Struct(ty)) => StructType::construct(ty, args),
+            Value::Fn(Function::Variant(ty, tag)) => EnumType::construct(ty, *tag, args),
+            Value::Fn(Function::Field(ty, i)) => match &args[..] {
+                [Value::Struct(instance)] if instance.ty.key == ty.key => {
+                    Ok(instance.values[*i].clone())
+                }
+                [value] => Err(EvalError::new(format!(
+                    "{} expects a {}, got {value}",
+                    ty.fields[*i].accessor, ty.name
+                ))),
+                _ => Err(EvalError::new(format!(
+                    "wrong number of args ({}) passed to {}",
+                    args.len(),
+                    ty.fields[*i].accessor
+                ))),
+            },
+            Value::Keyword(_, _) => match &args[..] {
+                [map] => Ok(get(map, f).unwrap_or(Value::Nil)),
+                [map, default] => Ok(get(map, f).unwrap_or_else(|| default.clone())),
+                _ => Err(EvalError::new(format!(
+                    "wrong number of args ({}) passed to {f}",
+                    args.len()
+                ))),
+            },
+            Value::ListBrace(_) | Value::ListBracket(_) => match &args[..] {
+                [key] => Ok(get(f, key).unwrap_or(Value::Nil)),
+                _ => Err(EvalError::new(format!(
+                    "wrong number of args ({}) passed to {f}",
+                    args.len()
+                ))),
+            },
+            _ => Err(EvalError::new(format!("{f} is not a function"))),
+        }
+    }
+}
+
+/// Splits `alias/name` into its parts; `/` alone is a plain name.
+fn split_symbol(symbol: &str) -> Option<(&str, &str)> {
+    match symbol.find('/') {
+        Some(i) if i > 0 && i + 1 < symbol.len() => Some((&symbol[..i], &symbol[i + 1..])),
+        _ => None,
+    }
+}
+
+/// A fresh symbol name that can't clash with any name in user code.
+pub fn gensym(prefix: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(1);
+    format!(
+        "{prefix}__{}__auto__",
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Looks up `key` in a map or struct, or an index in a vector.
+pub fn get(coll: &Value, key: &Value) -> Option<Value> {
+    match (coll, key) {
+        (Value::Struct(instance), Value::Keyword(_, field)) => instance.get(field).cloned(),
+        (Value::ListBrace(entries), key) => entries
+            .chunks(2)
+            .find(|entry| &entry[0] == key)
+            .map(|entry| entry[1].clone()),
+        (Value::ListBracket(items), Value::Int(index)) => usize::try_from(*index)
+            .ok()
+            .and_then(|i| items.get(i).cloned()),
+        _ => None,
+    }
+}
+
+/// A function created by `fn` or `defn`, closed over the locals in scope
+/// where it was created.
+pub struct Lambda {
+    func: Arc<FnExpr>,
+    scope: Scope,
+}
+
+impl Lambda {
+    pub fn name(&self) -> &str {
+        self.func.name.as_deref().unwrap_or("fn")
+    }
+
+    fn arity(&self, count: usize) -> Option<&Arity> {
+        let arities = &self.func.arities;
+        arities
+            .iter()
+            .find(|arity| arity.rest.is_none() && arity.params.len() == count)
+            .or_else(|| {
+                arities
+                    .iter()
+                    .find(|arity| arity.rest.is_some() && arity.params.len() <= count)
+            })
+    }
+}
+
+struct FnExpr {
+    name: Option<String>,
+    arities: Vec<Arity>,
+    file: String,
+}
+
+struct Arity {
+    params: Vec<Pattern>,
+    rest: Option<Pattern>,
+    body: Expr,
+}
+
+/// A binding form: a plain symbol or a sequential/associative destructuring.
+struct Pattern {
+    kind: PatternKind,
+    start: usize,
+    end: usize,
+}
+
+enum PatternKind {
+    Bind(String),
+    Seq {
+        items: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+        all: Option<String>,
+    },
+    Map {
+        entries: Vec<(Pattern, Value)>,
+        defaults: Vec<(String, Expr)>,
+        all: Option<String>,
+    },
+}
+
+impl Pattern {
+    /// Adds the globals the default values of the pattern refer to.
+    fn globals(&self, out: &mut Vec<Reference>) {
+        match &self.kind {
+            PatternKind::Bind(_) => {}
+            PatternKind::Seq { items, rest, .. } => {
+                items.iter().for_each(|item| item.globals(out));
+                rest.iter().for_each(|rest| rest.globals(out));
+            }
+            PatternKind::Map {
+                entries, defaults, ..
+            } => {
+                entries.iter().for_each(|(pattern, _)| pattern.globals(out));
+                defaults
+                    .iter()
+                    .for_each(|(_, default)| default.globals(out));
+            }
+        }
+    }
+
+    fn names(&self, names: &mut Vec<String>) {
+        match &self.kind {
+            PatternKind::Bind(name) => names.push(name.clone()),
+            PatternKind::Seq { items, rest, all } => {
+                items.iter().for_each(|item| item.names(names));
+                rest.iter().for_each(|rest| rest.names(names));
+                names.extend(all.iter().cloned());
+            }
+            PatternKind::Map { entries, all, .. } => {
+                entries.iter().for_each(|(pattern, _)| pattern.names(names));
+                names.extend(all.iter().cloned());
+            }
+        }
+    }
+}
+
+struct Expr {
+    kind: Kind,
+    start: usize,
+    end: usize,
+    /// Whether evaluating it can park a go block: it calls `<!`, `>!` or
+    /// `alts!` other than in a function it defines.
+    parks: bool,
+}
+
+/// A reference to a global: its key, the symbol naming it and its span.
+type Reference = (String, String, usize, usize);
+
+impl Expr {
+    fn new(kind: Kind, span: &Span) -> Expr {
+        let parks = kind.parks();
+        Expr {
+            kind,
+            start: span.start,
+            end: span.end,
+            parks,
+        }
+    }
+
+    /// Child `i` of an expression that parks, as go blocks number them.
+    fn child(&self, i: usize) -> &Expr {
+        match &self.kind {
+            Kind::Vector(items) | Kind::Map(items) | Kind::Do(items) | Kind::Recur(items) => {
+                &items[i]
+            }
+            Kind::Def(_, value) | Kind::Throw(value) => value,
+            Kind::If(test, then, otherwise) => [test, then, otherwise][i],
+            Kind::Let(bindings, body) | Kind::Loop(bindings, body) => {
+                bindings.get(i).map_or(body, |(_, value)| value)
+            }
+            Kind::Case(value, clauses, default) => match i {
+                0 => value,
+                i if i <= clauses.len() => &clauses[i - 1].1,
+                _ => default.as_ref().unwrap(),
+            },
+            // Each clause has its guard and then its body.
+            Kind::Match(value, clauses) => match i {
+                0 => value,
+                i if i % 2 == 1 => clauses[i / 2].guard.as_ref().unwrap(),
+                i => &clauses[i / 2 - 1].body,
+            },
+            Kind::Try(body, catches, finally) => match i {
+                0 => body,
+                i if i <= catches.len() => &catches[i - 1].body,
+                _ => finally.as_ref().unwrap(),
+            },
+            Kind::Call(f, args) => match i {
+                0 => f,
+                i => &args[i - 1],
+            },
+            _ => unreachable!("only expressions that park have their children stepped"),
+        }
+    }
+
+    /// The expression at `path`, the numbers of the children to go through.
+    fn at(&self, path: &[usize]) -> &Expr {
+        path.iter().fold(self, |expr, &i| expr.child(i))
+    }
+
+    /// Adds the globals this expression refers to.
+    fn globals(&self, out: &mut Vec<Reference>) {
+        let all = |exprs: &[Expr], out: &mut Vec<Reference>| {
+            exprs.iter().for_each(|expr| expr.globals(out));
+        };
+        match &self.kind {
+            Kind::Const(_) | Kind::Local(_) | Kind::DefEnum(_) => {}
+            Kind::Global(key, name) => out.push((key.clone(), name.clone(), self.start, self.end)),
+            Kind::Vector(items) | Kind::Map(items) | Kind::Do(items) | Kind::Recur(items) => {
+                all(items, out)
+            }
+            Kind::Def(_, value) | Kind::DefMacro(_, value) | Kind::Throw(value) => {
+                value.globals(out)
+            }
+            Kind::DefStruct(def) => {
+                for (_, _, default) in &def.fields {
+                    default.iter().for_each(|default| default.globals(out));
+                }
+            }
+            Kind::SyntaxQuote(template) => template.globals(out),
+            Kind::Fn(func) => {
+                for arity in &func.arities {
+                    arity
+                        .params
+                        .iter()
+                        .chain(&arity.rest)
+                        .for_each(|p| p.globals(out));
+                    arity.body.globals(out);
+                }
+            }
+            Kind::If(test, then, otherwise) => {
+                test.globals(out);
+                then.globals(out);
+                otherwise.globals(out);
+            }
+            Kind::Let(bindings, body) | Kind::Loop(bindings, body) => {
+                for (pattern, value) in bindings {
+                    pattern.globals(out);
+                    value.globals(out);
+                }
+                body.globals(out);
+            }
+            Kind::Case(value, clauses, default) => {
+                value.globals(out);
+                clauses.iter().for_each(|(_, body)| body.globals(out));
+                default.iter().for_each(|default| default.globals(out));
+            }
+            Kind::Match(value, clauses) => {
+                value.globals(out);
+                for clause in clauses {
+                    clause.guard.iter().for_each(|guard| guard.globals(out));
+                    clause.body.globals(out);
+                }
+            }
+            Kind::Try(body, catches, finally) => {
+                body.globals(out);
+                catches.iter().for_each(|catch| catch.body.globals(out));
+                finally.iter().for_each(|finally| finally.globals(out));
+            }
+            Kind::Call(f, args) => {
+                f.globals(out);
+                all(args, out);
+            }
+        }
+    }
+}
+
+impl Kind {
+    fn parks(&self) -> bool {
+        let any = |exprs: &[Expr]| exprs.iter().any(|expr| expr.parks);
+        let some = |expr: &Option<Box<Expr>>| expr.as_ref().is_some_and(|expr| expr.parks);
+        match self {
+            Kind::Vector(items) | Kind::Map(items) | Kind::Do(items) | Kind::Recur(items) => {
+                any(items)
+            }
+            Kind::Def(_, value) | Kind::Throw(value) => value.parks,
+            Kind::If(test, then, otherwise) => test.parks || then.parks || otherwise.parks,
+            Kind::Let(bindings, body) | Kind::Loop(bindings, body) => {
+                bindings.iter().any(|(_, value)| value.parks) || body.parks
+            }
+            Kind::Case(value, clauses, default) => {
+                value.parks || clauses.iter().any(|(_, body)| body.parks) || some(default)
+            }
+            Kind::Match(value, clauses) => {
+                value.parks
+                    || (clauses.iter()).any(|clause| {
+                        clause.body.parks || clause.guard.as_ref().is_some_and(|guard| guard.parks)
+                    })
+            }
+            Kind::Try(body, catches, finally) => {
+                body.parks || catches.iter().any(|catch| catch.body.parks) || some(finally)
+            }
+            Kind::Call(f, args) => {
+                let parking = match &f.kind {
+                    Kind::Global(key, _) => split_symbol(key)
+                        .is_some_and(|(ns, name)| ns == CORE && core::PARKING.contains(&name)),
+                    _ => false,
+                };
+                parking || f.parks || any(args)
+            }
+            // Quoted templates, definitions of types and macros, and
+            // functions are evaluated as a whole.
+            _ => false,
+        }
+    }
+}
+
+enum Kind {
+    Const(Value),
+    Local(String),
+    Global(String, String),
+    Vector(Vec<Expr>),
+    Map(Vec<Expr>),
+    Def(String, Box<Expr>),
+    DefMacro(String, Box<Expr>),
+    DefStruct(StructExpr),
+    DefEnum(EnumExpr),
+    SyntaxQuote(Template),
+    Fn(Arc<FnExpr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    Do(Vec<Expr>),
+    Let(Vec<(Pattern, Expr)>, Box<Expr>),
+    Loop(Vec<(Pattern, Expr)>, Box<Expr>),
+    Recur(Vec<Expr>),
+    Case(Box<Expr>, Vec<(Vec<Value>, Expr)>, Option<Box<Expr>>),
+    Match(Box<Expr>, Vec<Clause>),
+    Throw(Box<Expr>),
+    Try(Box<Expr>, Vec<Catch>, Option<Box<Expr>>),
+    Call(Box<Expr>, Vec<Expr>),
+}
+
+/// A `defstruct`: the type's name, and each field's name, type and
+/// default value.
+struct StructExpr {
+    key: String,
+    name: String,
+    fields: Vec<(String, Option<Type>, Option<Expr>)>,
+}
+
+/// A `defenum`: the type's name and each variant's name and field types.
+struct EnumExpr {
+    key: String,
+    name: String,
+    variants: Vec<(String, Vec<Type>)>,
+}
+
+/// A `match` clause: the body runs when the pattern matches and the guard,
+/// if any, holds.
+struct Clause {
+    pattern: MatchPattern,
+    guard: Option<Expr>,
+    body: Expr,
+}
+
+/// A `catch` clause of a `try`.
+struct Catch {
+    class: CatchClass,
+    name: String,
+    body: Expr,
+}
+
+impl Catch {
+    fn catches(&self, value: &Value) -> bool {
+        match &self.class {
+            CatchClass::All => true,
+            CatchClass::ExInfo => matches!(value, Value::Error(_)),
+            CatchClass::Type(ty) => ty.check(value),
+        }
+    }
+}
+
+/// What a `catch` clause catches.
+enum CatchClass {
+    /// `Throwable`, `Exception` or `:default`: anything.
+    All,
+    /// `ExceptionInfo`: `ex-info` values and runtime errors.
+    ExInfo,
+    /// Thrown values of a type, such as a struct or `String`.
+    Type(Type),
+}
+
+enum MatchPattern {
+    Wildcard,
+    Bind(String),
+    Literal(Value),
+    Variant(Arc<EnumType>, usize, Vec<MatchPattern>),
+    Seq(Vec<MatchPattern>, Option<Box<MatchPattern>>),
+}
+
+impl MatchPattern {
+    /// Whether the pattern matches every value.
+    fn is_irrefutable(&self) -> bool {
+        matches!(self, MatchPattern::Wildcard | MatchPattern::Bind(_))
+    }
+
+    fn names(&self, names: &mut Vec<String>) {
+        match self {
+            MatchPattern::Bind(name) => names.push(name.clone()),
+            MatchPattern::Variant(_, _, items) => items.iter().for_each(|item| item.names(names)),
+            MatchPattern::Seq(items, rest) => {
+                items.iter().for_each(|item| item.names(names));
+                rest.iter().for_each(|rest| rest.names(names));
+            }
+            MatchPattern::Wildcard | MatchPattern::Literal(_) => {}
+        }
+    }
+}
+
+/// A syntax-quoted form, with the unquoted parts to fill in.
+enum Template {
+    Const(Value),
+    Unquote(Box<Expr>),
+    Splice(Box<Expr>),
+    ListParen(Vec<Template>),
+    ListBracket(Vec<Template>),
+    ListBrace(Vec<Template>),
+}
+
+impl Template {
+    fn globals(&self, out: &mut Vec<Reference>) {
+        match self {
+            Template::Const(_) => {}
+            Template::Unquote(expr) | Template::Splice(expr) => expr.globals(out),
+            Template::ListParen(items)
+            | Template::ListBracket(items)
+            | Template::ListBrace(items) => items.iter().for_each(|item| item.globals(out)),
+        }
+    }
+}
+
+/// Turns forms into expressions, resolving locals and checking the shape
+/// of special forms.
+struct Analyzer<'a> {
+    env: &'a Env,
+    name: &'a str,
+    locals: Vec<String>,
+    target: Option<Target>,
+    tail: bool,
+}
+
+/// What `recur` jumps back to: the innermost `loop`, named `let` or
+/// function arity.
+#[derive(Clone)]
+struct Target {
+    arity: usize,
+    name: Option<String>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(env: &'a Env, name: &'a str) -> Analyzer<'a> {
+        Analyzer {
+            env,
+            name,
+            locals: vec![],
+            target: None,
+            tail: false,
+        }
+    }
+
+    fn analyze(&mut self, form: &Value, span: &Span) -> Result<Expr, EvalError> {
+        self.analyze_at(form, span, false)
+    }
+
+    /// Analyzes `form`, which is in tail position when `tail` is set.
+    fn analyze_at(&mut self, form: &Value, span: &Span, tail: bool) -> Result<Expr, EvalError> {
+        let outer = std::mem::replace(&mut self.tail, tail);
+        let expr = self.analyze_form(form, span);
+        self.tail = outer;
+        expr
+    }
+
+    /// Analyzes `body` as the body of a new recur target.
+    fn analyze_target(
+        &mut self,
+        target: Target,
+        items: &[Value],
+        span: &Span,
+        i: usize,
+    ) -> Result<Expr, EvalError> {
+        let outer_target = self.target.replace(target);
+        let outer_tail = std::mem::replace(&mut self.tail, true);
+        let body = self.analyze_body(items, span, i);
+        self.target = outer_target;
+        self.tail = outer_tail;
+        body
+    }
+
+    fn analyze_form(&mut self, form: &Value, span: &Span) -> Result<Expr, EvalError> {
+        let kind = match form {
+            Value::Symbol(_, name) => match name.as_str() {
+                "nil" => Kind::Const(Value::Nil),
+                "true" => Kind::Const(Value::Bool(true)),
+                "false" => Kind::Const(Value::Bool(false)),
+                name if self.locals.iter().any(|local| local == name) => Kind::Local(name.into()),
+                name => match self.env.resolve(name) {
+                    Ok(key) => Kind::Global(key, name.into()),
+                    Err(err) => return Err(err.at(self.name, span.start, span.end)),
+                },
+            },
+            Value::ListBracket(items) => Kind::Vector(self.analyze_all(items, span, 0)?),
+            Value::ListBrace(items) => {
+                if items.len() % 2 != 0 {
+                    return Err(
+                        self.error(span, "map literal must contain an even number of forms")
+                    );
+                }
+                Kind::Map(self.analyze_all(items, span, 0)?)
+            }
+            Value::ListParen(items) if items.is_empty() => Kind::Const(form.clone()),
+            Value::ListParen(items) => match &items[0] {
+                Value::Symbol(_, head) if self.locals.contains(head) => {
+                    self.analyze_call(items, span)?
+                }
+                Value::Symbol(_, head)
+                    if self.target.as_ref().and_then(|t| t.name.as_ref()) == Some(head) =>
+                {
+                    self.analyze_recur(head, items, span)?
+                }
+                Value::Symbol(_, head) => match self.analyze_special(head, items, span)? {
+                    Some(kind) => kind,
+                    None => match self.env.lookup_macro(head) {
+                        Some(expander) => return self.analyze_macro(head, &expander, items, span),
+                        None => self.analyze_call(items, span)?,
+                    },
+                },
+                _ => self.analyze_call(items, span)?,
+            },
+            _ => Kind::Const(form.clone()),
+        };
+        Ok(Expr::new(kind, span))
+    }
+
+    fn analyze_special(
+        &mut self,
+        head: &str,
+        items: &[Value],
+        span: &Span,
+    ) -> Result<Option<Kind>, EvalError> {
+        let args = &items[1..];
+        let kind = match head {
+            "quote" => match args {
+                [form] => Kind::Const(form.clone()),
+                _ => return Err(self.error(span, "quote expects exactly one argument")),
+            },
+            "do" => self.analyze_body(items, span, 1)?.kind,
+            "def" | "def-" => {
+                let name = self.symbol(args.first(), child(span, 1), head)?;
+                let value = match args {
+                    [_] => self.constant(Value::Nil, span),
+                    [_, value] => self.analyze(value, child(span, 2))?,
+                    [_, Value::String(doc), value] => {
+                        self.env.set_doc(&name, doc);
+                        self.analyze(value, child(span, 3))?
+                    }
+                    _ => return Err(self.error(span, &format!("too many arguments to {head}"))),
+                };
+                if head == "def-" {
+                    self.env.set_private(&name);
+                }
+                Kind::Def(self.qualify(&name), Box::new(value))
+            }
+            "defn" | "defn-" => {
+                let name = self.symbol(args.first(), child(span, 1), head)?;
+                self.docstring(&name, items);
+                let lambda = self.analyze_lambda(Some(name.clone()), items, 2, span)?;
+                if head == "defn-" {
+                    self.env.set_private(&name);
+                }
+                Kind::Def(self.qualify(&name), Box::new(Expr::new(lambda, span)))
+            }
+            "defmacro" => {
+                let name = self.symbol(args.first(), child(span, 1), "defmacro")?;
+                self.docstring(&name, items);
+                let lambda = self.analyze_lambda(Some(name.clone()), items, 2, span)?;
+                Kind::DefMacro(self.qualify(&name), Box::new(Expr::new(lambda, span)))
+            }
+            "syntax-quote" => match args {
+                [form] => {
+                    let mut gensyms = HashMap::new();
+                    Kind::SyntaxQuote(self.analyze_template(form, child(span, 1), &mut gensyms)?)
+                }
+                _ => return Err(self.error(span, "syntax-quote expects exactly one argument")),
+            },
+            "unquote" | "unquote-splicing" => {
+                return Err(self.error(span, &format!("{head} used outside of syntax-quote")))
+            }
+            "defstruct" => Kind::DefStruct(self.analyze_struct(items, span)?),
+            "defenum" => Kind::DefEnum(self.analyze_enum(items, span)?),
+            "throw" => match args {
+                [value] => Kind::Throw(Box::new(self.analyze(value, child(span, 1))?)),
+                _ => return Err(self.error(span, "throw expects exactly one argument")),
+            },
+            "try" => self.analyze_try(items, span)?,
+            "catch" | "finally" => {
+                return Err(self.error(span, &format!("{head} used outside of try")))
+            }
+            "match" => self.analyze_match(items, span)?,
+            "import" => {
+                for (i, spec) in args.iter().enumerate() {
+                    self.analyze_import(spec, child(span, i + 1))?;
+                }
+                Kind::Const(Value::Nil)
+            }
+            "fn" => match args.first() {
+                Some(Value::Symbol(_, name)) => {
+                    self.analyze_lambda(Some(name.clone()), items, 2, span)?
+                }
+                _ => self.analyze_lambda(None, items, 1, span)?,
+            },
+            "if" => match args {
+                [_, _] | [_, _, _] => {
+                    let test = self.analyze(&args[0], child(span, 1))?;
+                    let then = self.analyze_at(&args[1], child(span, 2), self.tail)?;
+                    let otherwise = match args.get(2) {
+                        Some(otherwise) => self.analyze_at(otherwise, child(span, 3), self.tail)?,
+                        None => self.constant(Value::Nil, span),
+                    };
+                    Kind::If(Box::new(test), Box::new(then), Box::new(otherwise))
+                }
+                _ => return Err(self.error(span, "if expects a test, a then and an optional else")),
+            },
+            "when" => {
+                if args.is_empty() {
+                    return Err(self.error(span, "when expects a test"));
+                }
+                let test = self.analyze(&args[0], child(span, 1))?;
+                let body = self.analyze_body(items, span, 2)?;
+                Kind::If(
+                    Box::new(test),
+                    Box::new(body),
+                    Box::new(self.constant(Value::Nil, span)),
+                )
+            }
+            "cond" => return self.analyze_cond(items, span, 1).map(Some),
+            "case" => {
+                if args.is_empty() {
+                    return Err(self.error(span, "case expects an expression"));
+                }
+                let value = self.analyze(&args[0], child(span, 1))?;
+                let mut clauses = vec![];
+                let mut default = None;
+                let mut i = 2;
+                while i < items.len() {
+                    if i + 1 == items.len() {
+                        let tail = self.tail;
+                        default = Some(Box::new(self.analyze_at(
+                            &items[i],
+                            child(span, i),
+                            tail,
+                        )?));
+                        break;
+                    }
+                    let keys = match &items[i] {
+                        Value::ListParen(keys) => keys.iter().map(literal).collect(),
+                        key => vec![literal(key)],
+                    };
+                    let tail = self.tail;
+                    clauses.push((
+                        keys,
+                        self.analyze_at(&items[i + 1], child(span, i + 1), tail)?,
+                    ));
+                    i += 2;
+                }
+                Kind::Case(Box::new(value), clauses, default)
+            }
+            "let" | "loop" => {
+                // `(let name [bindings] body)` is a loop that recurs by calling `name`
+                let (target, i) = match args.first() {
+                    Some(Value::Symbol(_, name)) if head == "let" => (Some(name.clone()), 2),
+                    _ => (None, 1),
+                };
+                let bindings = match items.get(i) {
+                    Some(Value::ListBracket(bindings)) if bindings.len() % 2 == 0 => bindings,
+                    _ => {
+                        return Err(self.error(
+                            span,
+                            &format!("{head} expects a vector with an even number of forms"),
+                        ))
+                    }
+                };
+                let locals = self.locals.len();
+                let bindings_span = child(span, i);
+                let mut analyzed = vec![];
+                for (i, pair) in bindings.chunks(2).enumerate() {
+                    let value = self.analyze(&pair[1], child(bindings_span, i * 2 + 1))?;
+                    let pattern = self.analyze_pattern(&pair[0], child(bindings_span, i * 2))?;
+                    pattern.names(&mut self.locals);
+                    analyzed.push((pattern, value));
+                }
+                let body = if head == "loop" || target.is_some() {
+                    let target = Target {
+                        arity: analyzed.len(),
+                        name: target,
+                    };
+                    self.analyze_target(target, items, span, i + 1)
+                        .map(|body| Kind::Loop(analyzed, Box::new(body)))
+                } else {
+                    self.analyze_body(items, span, i + 1)
+                        .map(|body| Kind::Let(analyzed, Box::new(body)))
+                };
+                self.locals.truncate(locals);
+                body?
+            }
+            "recur" => self.analyze_recur(head, items, span)?,
+            _ => return Ok(None),
+        };
+        Ok(Some(kind))
+    }
+
+    /// Analyzes `(defstruct Name fields...)` where each field is `name`,
+    /// `(name Type)` or `(name :type Type :value default)`, or the fields
+    /// are one vector of names.
+    fn analyze_struct(&mut self, items: &[Value], span: &Span) -> Result<StructExpr, EvalError> {
+        let name = self.symbol(items.get(1), child(span, 1), "defstruct")?;
+        // skip a docstring
+        let i = match items.get(2) {
+            Some(Value::String(_)) => 3,
+            _ => 2,
+        };
+        // `[a b c]` declares untyped fields
+        let (specs, specs_span, i) = match &items[i..] {
+            [Value::ListBracket(names)]
+                if names.iter().all(|name| matches!(name, Value::Symbol(_, _))) =>
+            {
+                (&names[..], child(span, i), 0)
+            }
+            _ => (items, span, i),
+        };
+        let mut fields = vec![];
+        for (j, spec) in specs.iter().enumerate().skip(i) {
+            let spec_span = child(specs_span, j);
+            let (field, options) = match spec {
+                Value::ListParen(spec) | Value::ListBracket(spec) if !spec.is_empty() => (
+                    self.symbol(spec.first(), child(spec_span, 0), "defstruct")?,
+                    &spec[1..],
+                ),
+                Value::Symbol(_, field) => (field.clone(), &[][..]),
+                spec => return Err(self.error(spec_span, &format!("invalid field spec: {spec}"))),
+            };
+            let mut ty = None;
+            let mut default = None;
+            let mut k = 0;
+            while k < options.len() {
+                let option_span = child(spec_span, k + 1);
+                // `:type T` and `type: T` are the same option
+                let option = match &options[k] {
+                    Value::Keyword(_, option) => Some(option.as_str()),
+                    Value::Symbol(_, option) if options.len() > k + 1 => option.strip_suffix(':'),
+                    _ => None,
+                };
+                match (option, options.get(k + 1)) {
+                    (Some("type"), Some(form)) => {
+                        ty = Some(self.parse_type(form, child(spec_span, k + 2))?);
+                        k += 2;
+                    }
+                    (Some("value"), Some(form)) => {
+                        default = Some(self.analyze(form, child(spec_span, k + 2))?);
+                        k += 2;
+                    }
+                    (None, _) if k == 0 => {
+                        ty = Some(self.parse_type(&options[k], option_span)?);
+                        k += 1;
+                    }
+                    _ => {
+                        return Err(self.error(
+                            option_span,
+                            &format!("invalid field option: {}", options[k]),
+                        ))
+                    }
+                }
+            }
+            if fields.iter().any(|(other, _, _)| *other == field) {
+                return Err(self.error(spec_span, &format!("duplicate field {field}")));
+            }
+            fields.push((field, ty, default));
+        }
+        Ok(StructExpr {
+            key: self.qualify(&name),
+            name,
+            fields,
+        })
+    }
+
+    /// Analyzes `(try body... (catch [e Type] handler...)... (finally
+    /// cleanup...))`. Catch clauses can also be written `(catch Type e ...)`.
+    fn analyze_try(&mut self, items: &[Value], span: &Span) -> Result<Kind, EvalError> {
+        // nothing in a try is in tail position: recur can't skip the handlers
+        let outer = std::mem::replace(&mut self.tail, false);
+        let result = self.analyze_try_clauses(items, span);
+        self.tail = outer;
+        result
+    }
+
+    fn analyze_try_clauses(&mut self, items: &[Value], span: &Span) -> Result<Kind, EvalError> {
+        let clause = |item: &Value, head: &str| {
+            matches!(item, Value::ListParen(items)
+                if matches!(items.first(), Some(Value::Symbol(_, name)) if name == head))
+        };
+        let body_end = (items.iter().enumerate().skip(1))
+            .find(|(_, item)| clause(item, "catch") || clause(item, "finally"))
+            .map_or(items.len(), |(i, _)| i);
+        let body = self.analyze_body(&items[..body_end], span, 1)?;
+        let mut catches = vec![];
+        let mut finally = None;
+        for (i, item) in items.iter().enumerate().skip(body_end) {
+            let item_span = child(span, i);
+            let clause_items = match item {
+                Value::ListParen(clause_items)
+                    if clause(item, "catch") || clause(item, "finally") =>
+                {
+                    clause_items
+                }
+                _ => {
+                    return Err(self.error(
+                        item_span,
+                        "only catch or finally clauses can follow catch in try",
+                    ))
+                }
+            };
+            if finally.is_some() {
+                return Err(self.error(item_span, "finally clause must be last in try"));
+            }
+            if clause(item, "finally") {
+                finally = Some(Box::new(self.analyze_body(clause_items, item_span, 1)?));
+                continue;
+            }
+            let (name, class, class_span, i) = match &clause_items[1..] {
+                [Value::ListBracket(binding), ..] if binding.len() == 2 => {
+                    let binding_span = child(item_span, 1);
+                    let name = self.symbol(binding.first(), child(binding_span, 0), "catch")?;
+                    (name, &binding[1], child(binding_span, 1), 2)
+                }
+                [class, name, ..] => {
+                    let name = self.symbol(Some(name), child(item_span, 2), "catch")?;
+                    (name, class, child(item_span, 1), 3)
+                }
+                _ => return Err(self.error(item_span, "catch expects [name Type] and a body")),
+            };
+            let class = match class {
+                Value::Symbol(_, class)
+                    if class == "Throwable" || class == "Exception" || class == "Error" =>
+                {
+                    CatchClass::All
+                }
+                Value::Keyword(_, default) if default == "default" => CatchClass::All,
+                Value::Symbol(_, class) if class == "ExceptionInfo" => CatchClass::ExInfo,
+                class => CatchClass::Type(self.parse_type(class, class_span)?),
+            };
+            let locals = self.locals.len();
+            self.locals.push(name.clone());
+            let handler = self.analyze_body(clause_items, item_span, i);
+            self.locals.truncate(locals);
+            catches.push(Catch {
+                class,
+                name,
+                body: handler?,
+            });
+        }
+        Ok(Kind::Try(Box::new(body), catches, finally))
+    }
+
+    /// Analyzes `(defenum Name variants...)` where each variant is `Name`
+    /// or `(Name Type...)`.
+    fn analyze_enum(&mut self, items: &[Value], span: &Span) -> Result<EnumExpr, EvalError> {
+        let name = self.symbol(items.get(1), child(span, 1), "defenum")?;
+        let i = match items.get(2) {
+            Some(Value::String(_)) => 3,
+            _ => 2,
+        };
+        let mut variants: Vec<(String, Vec<Type>)> = vec![];
+        for (j, spec) in items.iter().enumerate().skip(i) {
+            let spec_span = child(span, j);
+            let (variant, fields) = match spec {
+                Value::Symbol(_, variant) => (variant.clone(), vec![]),
+                Value::ListParen(spec) if !spec.is_empty() => {
+                    let variant = self.symbol(spec.first(), child(spec_span, 0), "defenum")?;
+                    let fields = (spec.iter().enumerate().skip(1))
+                        .map(|(k, field)| self.parse_type(field, child(spec_span, k)))
+                        .collect::<Result<_, _>>()?;
+                    (variant, fields)
+                }
+                spec => return Err(self.error(spec_span, &format!("invalid variant spec: {spec}"))),
+            };
+            if variants.iter().any(|(other, _)| *other == variant) {
+                return Err(self.error(spec_span, &format!("duplicate variant {variant}")));
+            }
+            variants.push((variant, fields));
+        }
+        Ok(EnumExpr {
+            key: self.qualify(&name),
+            name,
+            variants,
+        })
+    }
+
+    /// Analyzes `(match value pattern [:when guard] body ...)` and warns
+    /// when the clauses miss variants of the matched enum.
+    fn analyze_match(&mut self, items: &[Value], span: &Span) -> Result<Kind, EvalError> {
+        if items.len() < 2 {
+            return Err(self.error(span, "match expects an expression"));
+        }
+        let value = self.analyze(&items[1], child(span, 1))?;
+        let mut clauses = vec![];
+        let mut i = 2;
+        while i < items.len() {
+            let pattern = self.analyze_match_pattern(&items[i], child(span, i))?;
+            let guarded =
+                matches!(items.get(i + 1), Some(Value::Keyword(_, when)) if when == "when");
+            let body = if guarded { i + 3 } else { i + 1 };
+            if body >= items.len() {
+                return Err(self.error(child(span, i), "match clause expects a body"));
+            }
+            let locals = self.locals.len();
+            pattern.names(&mut self.locals);
+            let guard = if guarded {
+                Some(self.analyze(&items[i + 2], child(span, i + 2)))
+            } else {
+                None
+            };
+            let tail = self.tail;
+            let body_expr = self.analyze_at(&items[body], child(span, body), tail);
+            self.locals.truncate(locals);
+            clauses.push(Clause {
+                pattern,
+                guard: guard.transpose()?,
+                body: body_expr?,
+            });
+            i = body + 1;
+        }
+        self.check_exhaustive(&clauses, span);
+        Ok(Kind::Match(Box::new(value), clauses))
+    }
+
+    fn check_exhaustive(&self, clauses: &[Clause], span: &Span) {
+        let mut ty = None;
+        let mut covered = HashSet::new();
+        for clause in clauses {
+            match &clause.pattern {
+                pattern if pattern.is_irrefutable() && clause.guard.is_none() => return,
+                MatchPattern::Variant(enum_ty, tag, items) => {
+                    ty = Some(enum_ty);
+                    if clause.guard.is_none() && items.iter().all(MatchPattern::is_irrefutable) {
+                        covered.insert(*tag);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let ty = match ty {
+            Some(ty) => ty,
+            None => return,
+        };
+        let missing: Vec<&str> = (ty.variants.iter().enumerate())
+            .filter(|(tag, _)| !covered.contains(tag))
+            .map(|(_, variant)| variant.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            self.env.warnings.lock().unwrap().push(Warning {
+                name: self.name.into(),
+                start: span.start,
+                end: span.end,
+                kind: ErrorKind::NonExhaustiveMatch,
+                message: format!(
+                    "match on {} is not exhaustive, missing {}",
+                    ty.name,
+                    missing.join(", ")
+                ),
+            });
+        }
+    }
+
+    /// Analyzes a `match` pattern: `_`, a name to bind, a literal, a
+    /// variant `Name` or `(Name patterns...)`, or `[patterns... & rest]`.
+    fn analyze_match_pattern(
+        &mut self,
+        form: &Value,
+        span: &Span,
+    ) -> Result<MatchPattern, EvalError> {
+        Ok(match form {
+            Value::Symbol(_, name) if name == "_" => MatchPattern::Wildcard,
+            Value::Symbol(_, name) if name == "nil" || name == "true" || name == "false" => {
+                MatchPattern::Literal(literal(form))
+            }
+            Value::Symbol(_, name) if !self.locals.contains(name) => match self.env.lookup(name) {
+                Some(Value::Variant(variant)) => {
+                    MatchPattern::Variant(variant.ty.clone(), variant.tag, vec![])
+                }
+                Some(Value::Fn(Function::Variant(ty, tag))) => {
+                    let fields = ty.variants[tag].fields.len();
+                    return Err(self.error(
+                        span,
+                        &format!("{name} has {fields} field(s), use ({name} ...) to match it"),
+                    ));
+                }
+                _ => MatchPattern::Bind(name.clone()),
+            },
+            Value::Symbol(_, name) => MatchPattern::Bind(name.clone()),
+            Value::ListParen(items) if !items.is_empty() => {
+                let (ty, tag) = match &items[0] {
+                    Value::Symbol(_, name) => match self.env.lookup(name) {
+                        Some(Value::Variant(variant)) => (variant.ty.clone(), variant.tag),
+                        Some(Value::Fn(Function::Variant(ty, tag))) => (ty, tag),
+                        _ => return Err(self.error(span, &format!("{name} is not a variant"))),
+                    },
+                    head => return Err(self.error(span, &format!("{head} is not a variant"))),
+                };
+                let fields = ty.variants[tag].fields.len();
+                if items.len() - 1 != fields {
+                    return Err(self.error(
+                        span,
+                        &format!(
+                            "{} has {fields} field(s), pattern has {}",
+                            ty.variants[tag].name,
+                            items.len() - 1
+                        ),
+                    ));
+                }
+                let items = (items.iter().enumerate().skip(1))
+                    .map(|(i, item)| self.analyze_match_pattern(item, child(span, i)))
+                    .collect::<Result<_, _>>()?;
+                MatchPattern::Variant(ty, tag, items)
+            }
+            Value::ListBracket(forms) => {
+                let mut items = vec![];
+                let mut rest = None;
+                let mut i = 0;
+                while i < forms.len() {
+                    match &forms[i] {
+                        Value::Symbol(_, amp) if amp == "&" && i + 2 == forms.len() => {
+                            let pattern =
+                                self.analyze_match_pattern(&forms[i + 1], child(span, i + 1))?;
+                            rest = Some(Box::new(pattern));
+                            i += 2;
+                        }
+                        form => {
+                            items.push(self.analyze_match_pattern(form, child(span, i))?);
+                            i += 1;
+                        }
+                    }
+                }
+                MatchPattern::Seq(items, rest)
+            }
+            Value::ListParen(_) | Value::ListBrace(_) => {
+                return Err(self.error(span, &format!("unsupported pattern: {form}")))
+            }
+            form => MatchPattern::Literal(form.clone()),
+        })
+    }
+
+    fn parse_type(&self, form: &Value, span: &Span) -> Result<Type, EvalError> {
+        Type::parse(form, &|name| {
+            self.env.resolve(name).map_err(|err| err.message)
+        })
+        .map_err(|message| self.error(span, &message))
+    }
+
+    /// Loads the module named by an import spec, `module` or
+    /// `[module :as alias :refer [names]]`, and makes its names available
+    /// here. Modules are loaded while analyzing so later forms can use them.
+    fn analyze_import(&mut self, spec: &Value, span: &Span) -> Result<(), EvalError> {
+        let (module, options) = match spec {
+            Value::Symbol(_, module) => (module, &[][..]),
+            Value::ListBracket(items) => match items.first() {
+                Some(Value::Symbol(_, module)) => (module, &items[1..]),
+                _ => return Err(self.error(span, "import expects a module name")),
+            },
+            spec => return Err(self.error(span, &format!("invalid import spec: {spec}"))),
+        };
+        self.env
+            .load_module(module)
+            .map_err(|err| err.at(self.name, span.start, span.end))?;
+        let mut refers = vec![];
+        let mut i = 0;
+        while i < options.len() {
+            let option_span = child(span, i + 1);
+            match (&options[i], options.get(i + 1)) {
+                (Value::Keyword(_, option), _) if option == "refer-all" => {
+                    refers.extend(self.env.public_names(module));
+                    i += 1;
+                }
+                (Value::Keyword(_, option), Some(value)) if option == "as" => {
+                    let alias = self.symbol(Some(value), child(span, i + 2), ":as")?;
+                    self.env.alias(&alias, module);
+                    i += 2;
+                }
+                (Value::Keyword(_, option), Some(value)) if option == "refer" => {
+                    match value {
+                        Value::Keyword(_, all) if all == "all" => {
+                            refers.extend(self.env.public_names(module))
+                        }
+                        Value::ListBracket(names) => {
+                            for (j, name) in names.iter().enumerate() {
+                                let names_span = child(span, i + 2);
+                                refers.push(self.symbol(
+                                    Some(name),
+                                    child(names_span, j),
+                                    ":refer",
+                                )?);
+                            }
+                        }
+                        name => {
+                            refers.push(self.symbol(Some(name), child(span, i + 2), ":refer")?)
+                        }
+                    }
+                    i += 2;
+                }
+                (option, _) => {
+                    return Err(self.error(option_span, &format!("invalid import option: {option}")))
+                }
+            }
+        }
+        for name in refers {
+            self.env
+                .refer(module, &name)
+                .map_err(|message| self.error(span, &message))?;
+        }
+        Ok(())
+    }
+
+    /// Expands a macro call and analyzes the expansion in its place. Errors
+    /// raised by the expander point at the call.
+    fn analyze_macro(
+        &mut self,
+        head: &str,
+        expander: &Value,
+        items: &[Value],
+        span: &Span,
+    ) -> Result<Expr, EvalError> {
+        let _depth = Depth::expand()
+            .ok_or_else(|| self.error(span, &format!("macro expansion too deep in {head}")))?;
+        let expanded = self
+            .env
+            .apply(expander, items[1..].to_vec())
+            .map_err(|err| self.error(span, &format!("error expanding {head}: {}", err.message)))?;
+        let span = Span {
+            start: span.start,
+            end: span.end,
+            items: vec![],
+        };
+        self.analyze_form(&expanded, &span)
+    }
+
+    /// Analyzes the body of a syntax-quote. Symbols ending in `#` become
+    /// the same fresh symbol everywhere in one template.
+    fn analyze_template(
+        &mut self,
+        form: &Value,
+        span: &Span,
+        gensyms: &mut HashMap<String, String>,
+    ) -> Result<Template, EvalError> {
+        let items = match form {
+            Value::Symbol(ns, name) if ns.is_empty() && name.len() > 1 && name.ends_with('#') => {
+                let name = gensyms
+                    .entry(name.clone())
+                    .or_insert_with(|| gensym(&name[..name.len() - 1]));
+                return Ok(Template::Const(Value::Symbol("".into(), name.clone())));
+            }
+            // symbols mean what they mean where the template is written
+            Value::Symbol(ns, name)
+                if ns.is_empty()
+                    && split_symbol(name).is_none()
+                    && !SPECIAL_FORMS.contains(&name.as_str())
+                    && !["nil", "true", "false", "&"].contains(&name.as_str()) =>
+            {
+                let name = self.env.resolve(name).unwrap_or_else(|_| name.clone());
+                return Ok(Template::Const(Value::Symbol("".into(), name)));
+            }
+            Value::ListParen(items) => match items.first() {
+                Some(Value::Symbol(_, head)) if head == "unquote" || head == "unquote-splicing" => {
+                    let form = match &items[..] {
+                        [_, form] => form,
+                        _ => {
+                            return Err(
+                                self.error(span, &format!("{head} expects exactly one argument"))
+                            )
+                        }
+                    };
+                    let expr = self.analyze(form, child(span, 1))?;
+                    return Ok(if head == "unquote" {
+                        Template::Unquote(Box::new(expr))
+                    } else {
+                        Template::Splice(Box::new(expr))
+                    });
+                }
+                _ => items,
+            },
+            Value::ListBracket(items) | Value::ListBrace(items) => items,
+            form => return Ok(Template::Const(form.clone())),
+        };
+        let items = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| self.analyze_template(item, child(span, i), gensyms))
+            .collect::<Result<_, _>>()?;
+        Ok(match form {
+            Value::ListParen(_) => Template::ListParen(items),
+            Value::ListBracket(_) => Template::ListBracket(items),
+            _ => Template::ListBrace(items),
+        })
+    }
+
+    fn analyze_recur(
+        &mut self,
+        head: &str,
+        items: &[Value],
+        span: &Span,
+    ) -> Result<Kind, EvalError> {
+        let what = if head == "recur" {
+            "recur".to_string()
+        } else {
+            format!("call {head}")
+        };
+        let arity = match &self.target {
+            Some(target) => target.arity,
+            None => return Err(self.error(span, "recur used outside of loop")),
+        };
+        if !self.tail {
+            return Err(self.error(span, &format!("can only {what} from tail position")));
+        }
+        if arity != items.len() - 1 {
+            return Err(self.error(
+                span,
+                &format!(
+                    "mismatched argument count to {head}, expected: {arity} args, got: {}",
+                    items.len() - 1
+                ),
+            ));
+        }
+        Ok(Kind::Recur(self.analyze_all(items, span, 1)?))
+    }
+
+    fn analyze_cond(&mut self, items: &[Value], span: &Span, i: usize) -> Result<Kind, EvalError> {
+        let tail = self.tail;
+        match items.len() - i {
+            0 => Ok(Kind::Const(Value::Nil)),
+            1 => Ok(self.analyze_at(&items[i], child(span, i), tail)?.kind),
+            _ => {
+                let test = self.analyze(&items[i], child(span, i))?;
+                let then = self.analyze_at(&items[i + 1], child(span, i + 1), tail)?;
+                let otherwise = Expr::new(self.analyze_cond(items, span, i + 2)?, span);
+                Ok(Kind::If(
+                    Box::new(test),
+                    Box::new(then),
+                    Box::new(otherwise),
+                ))
+            }
+        }
+    }
+
+    fn analyze_lambda(
+        &mut self,
+        name: Option<String>,
+        items: &[Value],
+        i: usize,
+        span: &Span,
+    ) -> Result<Kind, EvalError> {
+        // skip a docstring in `(defn name "doc" [params] ...)`
+        let i = match items.get(i) {
+            Some(Value::String(_)) if items.len() > i + 1 => i + 1,
+            _ => i,
+        };
+
+        let locals = self.locals.len();
+        self.locals.extend(name.iter().cloned());
+        let arities = match items.get(i) {
+            Some(Value::ListBracket(_)) => {
+                self.analyze_arity(items, i, span).map(|arity| vec![arity])
+            }
+            Some(Value::ListParen(_)) => items
+                .iter()
+                .enumerate()
+                .skip(i)
+                .map(|(i, clause)| match clause {
+                    Value::ListParen(clause) => self.analyze_arity(clause, 0, child(span, i)),
+                    _ => {
+                        Err(self.error(child(span, i), "expected an arity clause ([params] body)"))
+                    }
+                })
+                .collect(),
+            _ => Err(self.error(span, "expected a parameter vector")),
+        };
+        self.locals.truncate(locals);
+        let arities = arities?;
+
+        let variadic = arities.iter().filter(|arity| arity.rest.is_some()).count();
+        let fixed = arities.iter().filter(|arity| arity.rest.is_none());
+        if variadic > 1 {
+            return Err(self.error(span, "can't have more than 1 variadic overload"));
+        }
+        for (j, arity) in fixed.clone().enumerate() {
+            if fixed
+                .clone()
+                .skip(j + 1)
+                .any(|other| other.params.len() == arity.params.len())
+            {
+                return Err(self.error(span, "can't have 2 overloads with same arity"));
+            }
+            if arities
+                .iter()
+                .any(|other| other.rest.is_some() && other.params.len() < arity.params.len())
+            {
+                return Err(self.error(
+                    span,
+                    "can't have fixed arity function with more params than variadic function",
+                ));
+            }
+        }
+
+        Ok(Kind::Fn(Arc::new(FnExpr {
+            name,
+            arities,
+            file: self.name.into(),
+        })))
+    }
+
+    /// Analyzes `[params] body...` starting at `items[i]`.
+    fn analyze_arity(
+        &mut self,
+        items: &[Value],
+        i: usize,
+        span: &Span,
+    ) -> Result<Arity, EvalError> {
+        let params = match items.get(i) {
+            Some(Value::ListBracket(params)) => params,
+            _ => return Err(self.error(span, "expected a parameter vector")),
+        };
+        let params_span = child(span, i);
+        let mut names = vec![];
+        let mut rest = None;
+        let mut j = 0;
+        while j < params.len() {
+            match &params[j] {
+                Value::Symbol(_, param) if param == "&" => {
+                    if j + 2 != params.len() {
+                        return Err(self.error(
+                            child(params_span, j),
+                            "expected exactly one parameter after &",
+                        ));
+                    }
+                    rest = Some(self.analyze_pattern(&params[j + 1], child(params_span, j + 1))?);
+                    j += 2;
+                }
+                param => {
+                    names.push(self.analyze_pattern(param, child(params_span, j))?);
+                    j += 1;
+                }
+            }
+        }
+
+        let locals = self.locals.len();
+        for pattern in names.iter().chain(&rest) {
+            pattern.names(&mut self.locals);
+        }
+        let target = Target {
+            arity: names.len() + rest.iter().count(),
+            name: None,
+        };
+        let body = self.analyze_target(target, items, span, i + 1);
+        self.locals.truncate(locals);
+        Ok(Arity {
+            params: names,
+            rest,
+            body: body?,
+        })
+    }
+
+    fn analyze_pattern(&mut self, form: &Value, span: &Span) -> Result<Pattern, EvalError> {
+        let kind = match form {
+            Value::Symbol(_, name) if name == "&" => {
+                return Err(self.error(span, "unexpected & in binding form"))
+            }
+            Value::Symbol(_, name) => PatternKind::Bind(name.clone()),
+            Value::ListBracket(forms) => {
+                let mut items = vec![];
+                let mut rest = None;
+                let mut all = None;
+                let mut i = 0;
+                while i < forms.len() {
+                    match &forms[i] {
+                        Value::Symbol(_, amp) if amp == "&" && rest.is_none() && all.is_none() => {
+                            match forms.get(i + 1) {
+                                Some(form) if !is_as(form) => {
+                                    let pattern = self.analyze_pattern(form, child(span, i + 1))?;
+                                    rest = Some(Box::new(pattern));
+                                }
+                                _ => {
+                                    return Err(self
+                                        .error(child(span, i), "expected a binding form after &"))
+                                }
+                            }
+                            i += 2;
+                        }
+                        form if is_as(form) => {
+                            all = Some(self.symbol(forms.get(i + 1), child(span, i + 1), ":as")?);
+                            i += 2;
+                        }
+                        _ if rest.is_some() || all.is_some() => {
+                            return Err(self.error(
+                                child(span, i),
+                                &format!("unexpected binding form after & or :as: {}", forms[i]),
+                            ))
+                        }
+                        form => {
+                            items.push(self.analyze_pattern(form, child(span, i))?);
+                            i += 1;
+                        }
+                    }
+                }
+                PatternKind::Seq { items, rest, all }
+            }
+            Value::ListBrace(forms) => {
+                if forms.len() % 2 != 0 {
+                    return Err(self.error(
+                        span,
+                        "map binding form must contain an even number of forms",
+                    ));
+                }
+                let mut entries = vec![];
+                let mut defaults = vec![];
+                let mut all = None;
+                for (i, pair) in forms.chunks(2).enumerate() {
+                    let (key_span, value_span) = (child(span, i * 2), child(span, i * 2 + 1));
+                    match &pair[0] {
+                        Value::Keyword(_, option) if option == "as" => {
+                            all = Some(self.symbol(Some(&pair[1]), value_span, ":as")?);
+                        }
+                        Value::Keyword(_, option)
+                            if option == "keys" || option == "strs" || option == "syms" =>
+                        {
+                            let names = match &pair[1] {
+                                Value::ListBracket(names) => names,
+                                _ => {
+                                    return Err(self.error(
+                                        value_span,
+                                        &format!(":{option} expects a vector of symbols"),
+                                    ))
+                                }
+                            };
+                            for (j, name) in names.iter().enumerate() {
+                                let name = self.symbol(
+                                    Some(name),
+                                    child(value_span, j),
+                                    &format!(":{option}"),
+                                )?;
+                                let key = match option.as_str() {
+                                    "keys" => Value::Keyword("".into(), name.clone()),
+                                    "strs" => Value::String(name.clone()),
+                                    _ => Value::Symbol("".into(), name.clone()),
+                                };
+                                let span = child(value_span, j);
+                                entries.push((
+                                    Pattern {
+                                        kind: PatternKind::Bind(name),
+                                        start: span.start,
+                                        end: span.end,
+                                    },
+                                    key,
+                                ));
+                            }
+                        }
+                        Value::Keyword(_, option) if option == "or" => {
+                            let pairs = match &pair[1] {
+                                Value::ListBrace(pairs) if pairs.len() % 2 == 0 => pairs,
+                                _ => {
+                                    return Err(
+                                        self.error(value_span, ":or expects a map of defaults")
+                                    )
+                                }
+                            };
+                            for (j, default) in pairs.chunks(2).enumerate() {
+                                let name = self.symbol(
+                                    Some(&default[0]),
+                                    child(value_span, j * 2),
+                                    ":or",
+                                )?;
+                                let value =
+                                    self.analyze(&default[1], child(value_span, j * 2 + 1))?;
+                                defaults.push((name, value));
+                            }
+                        }
+                        form => {
+                            let pattern = self.analyze_pattern(form, key_span)?;
+                            entries.push((pattern, literal(&pair[1])));
+                        }
+                    }
+                }
+                let mut names = vec![];
+                entries
+                    .iter()
+                    .for_each(|(pattern, _)| pattern.names(&mut names));
+                if let Some((name, value)) = defaults.iter().find(|(name, _)| !names.contains(name))
+                {
+                    return Err(EvalError::located(
+                        self.name,
+                        value.start,
+                        value.end,
+                        format!(":or default for {name}, which is not bound by the pattern"),
+                    ));
+                }
+                PatternKind::Map {
+                    entries,
+                    defaults,
+                    all,
+                }
+            }
+            form => return Err(self.error(span, &format!("unsupported binding form: {form}"))),
+        };
+        Ok(Pattern {
+            kind,
+            start: span.start,
+            end: span.end,
+        })
+    }
+
+    fn analyze_call(&mut self, items: &[Value], span: &Span) -> Result<Kind, EvalError> {
+        let f = self.analyze(&items[0], child(span, 0))?;
+        let args = self.analyze_all(items, span, 1)?;
+        Ok(Kind::Call(Box::new(f), args))
+    }
+
+    /// Analyzes `items[i..]` as a body whose last form inherits the tail
+    /// position of the body itself.
+    fn analyze_body(&mut self, items: &[Value], span: &Span, i: usize) -> Result<Expr, EvalError> {
+        let mut body = vec![];
+        for (j, item) in items.iter().enumerate().skip(i) {
+            let tail = self.tail && j + 1 == items.len();
+            body.push(self.analyze_at(item, child(span, j), tail)?);
+        }
+        Ok(if body.len() == 1 {
+            body.pop().unwrap()
+        } else {
+            Expr::new(Kind::Do(body), span)
+        })
+    }
+
+    fn analyze_all(
+        &mut self,
+        items: &[Value],
+        span: &Span,
+        i: usize,
+    ) -> Result<Vec<Expr>, EvalError> {
+        items
+            .iter()
+            .enumerate()
+            .skip(i)
+            .map(|(i, item)| self.analyze(item, child(span, i)))
+            .collect()
+    }
+
+    /// The qualified name of a definition of `name` in this namespace.
+    /// Records the docstring of `(defn name "doc" ...)`, if it has one.
+    fn docstring(&self, name: &str, items: &[Value]) {
+        if let (Some(Value::String(doc)), true) = (items.get(2), items.len() > 3) {
+            self.env.set_doc(name, doc);
+        }
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        format!("{}/{name}", self.env.ns)
+    }
+
+    fn symbol(&self, form: Option<&Value>, span: &Span, head: &str) -> Result<String, EvalError> {
+        match form {
+            Some(Value::Symbol(_, name)) => Ok(name.clone()),
+            Some(form) => Err(self.error(span, &format!("{head} expects a symbol, found {form}"))),
+            None => Err(self.error(span, &format!("{head} expects a symbol"))),
+        }
+    }
+
+    fn constant(&self, value: Value, span: &Span) -> Expr {
+        Expr::new(Kind::Const(value), span)
+    }
+
+    fn error(&self, span: &Span, message: &str) -> EvalError {
+        EvalError::located(self.name, span.start, span.end, message.into())
+    }
+}
+
+/// The names the evaluator handles itself rather than looking up.
+pub const SPECIAL_FORMS: &[&str] = &[
+    "quote",
+    "do",
+    "def",
+    "def-",
+    "defn",
+    "defn-",
+    "defmacro",
+    "defstruct",
+    "defenum",
+    "throw",
+    "try",
+    "catch",
+    "finally",
+    "match",
+    "syntax-quote",
+    "unquote",
+    "unquote-splicing",
+    "import",
+    "fn",
+    "if",
+    "when",
+    "cond",
+    "case",
+    "let",
+    "loop",
+    "recur",
+];
+
+/// The span of the `i`th item of a list, or of the list itself when the
+/// form was not read from source.
+fn child(span: &Span, i: usize) -> &Span {
+    span.items.get(i).unwrap_or(span)
+}
+
+fn is_as(form: &Value) -> bool {
+    matches!(form, Value::Keyword(_, name) if name == "as")
+}
+
+fn literal(form: &Value) -> Value {
+    match form {
+        Value::Symbol(_, name) if name == "nil" => Value::Nil,
+        Value::Symbol(_, name) if name == "true" => Value::Bool(true),
+        Value::Symbol(_, name) if name == "false" => Value::Bool(false),
+        form => form.clone(),
+    }
+}
+
+/// Local bindings, as an immutable linked list so closures and loop
+/// iterations can share their tails.
+#[derive(Clone, Default)]
+struct Scope(Option<Arc<Frame>>);
+
+struct Frame {
+    name: String,
+    value: Value,
+    parent: Scope,
+}
+
+impl Scope {
+    fn bind(&self, name: &str, value: Value) -> Scope {
+        Scope(Some(Arc::new(Frame {
+            name: name.into(),
+            value,
+            parent: self.clone(),
+        })))
+    }
+
+    fn get(&self, name: &str) -> Option<&Value> {
+        let mut scope = self;
+        while let Some(frame) = &scope.0 {
+            if frame.name == name {
+                return Some(&frame.value);
+            }
+            scope = &frame.parent;
+        }
+        None
+    }
+}
+
+enum Tail {
+    Value(Value),
+    Recur(Vec<Value>),
+}
+
+impl Tail {
+    /// The value of an expression not in tail position.
+    fn value(self) -> Value {
+        match self {
+            Tail::Value(value) => value,
+            Tail::Recur(_) => unreachable!("the analyzer only allows recur in tail position"),
+        }
+    }
+}
+
+/// A go block whose body parks, evaluated a step at a time on a stack of
+/// frames of its own, so a channel operation can stop it and any worker
+/// resume it. Children that don't park are evaluated as usual.
+struct GoBlock {
+    env: Env,
+    lambda: Arc<Lambda>,
+    arity: usize,
+    frames: Vec<GoFrame>,
+    done: Box<dyn FnOnce(Result<Value, EvalError>) + Send>,
+}
+
+/// An expression of a go block being evaluated.
+#[derive(Default)]
+struct GoFrame {
+    /// The children to go through from the body to the expression.
+    path: Vec<usize>,
+    /// The scope the expression is evaluated in.
+    base: Scope,
+    /// The scope its bindings made so far.
+    scope: Scope,
+    /// The child evaluated last.
+    child: usize,
+    /// The values of the children evaluated so far.
+    values: Vec<Value>,
+    /// What a `try` returns once its finally body is evaluated.
+    result: Option<Result<Tail, EvalError>>,
+}
+
+/// What evaluating an expression of a go block does next.
+enum Step {
+    /// Evaluates a child in a scope, and then steps again with its result.
+    Child(usize, Scope),
+    Done(Result<Tail, EvalError>),
+    /// Waits for a channel call, and then steps again with its value.
+    Park(ChannelCall),
+}
+
+impl GoBlock {
+    /// Evaluates the body until it parks or returns, given the result of
+    /// the channel call it parked on.
+    fn run(mut self, mut input: Option<Result<Tail, EvalError>>) {
+        let lambda = self.lambda.clone();
+        let body = &lambda.func.arities[self.arity].body;
+        let eval = Eval {
+            env: &self.env,
+            name: &lambda.func.file,
+        };
+        loop {
+            let frame = match self.frames.last_mut() {
+                Some(frame) => frame,
+                None => match input.take() {
+                    Some(Ok(Tail::Value(value))) => return (self.done)(Ok(value)),
+                    Some(Err(err)) => return (self.done)(Err(err)),
+                    // The body starts, or starts over after `recur`.
+                    None | Some(Ok(Tail::Recur(_))) => {
+                        let mut base = lambda.scope.clone();
+                        if let Some(name) = &lambda.func.name {
+                            base = base.bind(name, Value::Fn(Function::Lambda(lambda.clone())));
+                        }
+                        self.frames.push(GoFrame {
+                            scope: base.clone(),
+                            base,
+                            ..GoFrame::default()
+                        });
+                        continue;
+                    }
+                },
+            };
+            let expr = body.at(&frame.path);
+            let step = if self.env.is_interrupted() {
+                Step::Done(Err(eval.error(expr, "interrupted".into())))
+            } else {
+                eval.step(expr, frame, input.take())
+            };
+            match step {
+                Step::Child(i, scope) => {
+                    frame.child = i;
+                    let child = expr.child(i);
+                    if child.parks {
+                        let mut path = frame.path.clone();
+                        path.push(i);
+                        self.frames.push(GoFrame {
+                            path,
+                            scope: scope.clone(),
+                            base: scope,
+                            ..GoFrame::default()
+                        });
+                    } else {
+                        input = Some(eval.eval_tail(child, &scope));
+                    }
+                }
+                Step::Done(result) => {
+                    self.frames.pop();
+                    input = Some(result);
+                }
+                Step::Park(call) => {
+                    // The call is done once it has its value, like any other.
+                    self.frames.pop();
+                    match call.park() {
+                        Ok(value) => input = Some(Ok(Tail::Value(value))),
+                        Err(parked) => {
+                            return parked.then(move |value| self.run(Some(Ok(Tail::Value(value)))))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Eval<'a> {
+    env: &'a Env,
+    name: &'a str,
+}
+
+impl<'a> Eval<'a> {
+    fn eval(&self, expr: &Expr, scope: &Scope) -> Result<Value, EvalError> {
+        if self.env.is_interrupted() {
+            return Err(self.error(expr, "interrupted".into()));
+        }
+        match &expr.kind {
+            Kind::Const(value) => Ok(value.clone()),
+            Kind::Local(name) => Ok(scope.get(name).cloned().unwrap()),
+            Kind::Global(key, name) => self.env.global(key).ok_or_else(|| {
+                let message = format!("unable to resolve symbol: {name}");
+                self.error(expr, message)
+                    .with_kind(ErrorKind::UnresolvedSymbol)
+            }),
+            Kind::Vector(items) => Ok(Value::ListBracket(self.eval_all(items, scope)?)),
+            Kind::Map(items) => Ok(Value::ListBrace(self.eval_all(items, scope)?)),
+            Kind::Def(name, value) => {
+                let value = self.eval(value, scope)?;
+                self.env
+                    .globals
+                    .write()
+                    .unwrap()
+                    .insert(name.clone(), value);
+                Ok(Value::Symbol("".into(), name.clone()))
+            }
+            Kind::DefMacro(name, expander) => {
+                let expander = self.eval(expander, scope)?;
+                self.env
+                    .macros
+                    .write()
+                    .unwrap()
+                    .insert(name.clone(), expander);
+                Ok(Value::Symbol("".into(), name.clone()))
+            }
+            Kind::DefStruct(def) => {
+                let mut fields = vec![];
+                for (name, ty, default) in &def.fields {
+                    let default = match default {
+                        Some(default) => Some(self.eval(default, scope)?),
+                        None => None,
+                    };
+                    fields.push(Field {
+                        name: name.clone(),
+                        ty: ty.clone(),
+                        default,
+                        accessor: format!("{}-{name}", def.name),
+                    });
+                }
+                let ty = Arc::new(StructType {
+                    name: def.name.clone(),
+                    key: def.key.clone(),
+                    fields,
+                });
+                for field in &ty.fields {
+                    if let Some(default) = &field.default {
+                        ty.check(field, default)
+                            .map_err(|err| err.at(self.name, expr.start, expr.end))?;
+                    }
+                }
+                let mut globals = self.env.globals.write().unwrap();
+                let ns = def.key.strip_suffix(def.name.as_str()).unwrap_or_default();
+                for (i, field) in ty.fields.iter().enumerate() {
+                    let accessor = Value::Fn(Function::Field(ty.clone(), i));
+                    globals.insert(format!("{ns}{}", field.accessor), accessor);
+                }
+                globals.insert(def.key.clone(), Value::Fn(Function::Struct(ty)));
+                Ok(Value::Symbol("".into(), def.key.clone()))
+            }
+            Kind::DefEnum(def) => {
+                let ty = Arc::new(EnumType {
+                    name: def.name.clone(),
+                    key: def.key.clone(),
+                    variants: (def.variants.iter())
+                        .map(|(name, fields)| VariantType {
+                            name: name.clone(),
+                            fields: fields.clone(),
+                        })
+                        .collect(),
+                });
+                let ns = def.key.strip_suffix(def.name.as_str()).unwrap_or_default();
+                for (tag, variant) in ty.variants.iter().enumerate() {
+                    let value = if variant.fields.is_empty() {
+                        EnumType::construct(&ty, tag, vec![])?
+                    } else {
+                        Value::Fn(Function::Variant(ty.clone(), tag))
+                    };
+                    let key = format!("{ns}{}", variant.name);
+                    self.env.globals.write().unwrap().insert(key, value);
+                }
+                Ok(Value::Symbol("".into(), def.key.clone()))
+            }
+            Kind::SyntaxQuote(template) => match self.eval_template(template, scope)? {
+                Some(value) => Ok(value),
+                None => Err(self.error(expr, "unquote-splicing used outside of a list".into())),
+            },
+            Kind::Fn(func) => Ok(Value::Fn(Function::Lambda(Arc::new(Lambda {
+                func: func.clone(),
+                scope: scope.clone(),
+            })))),
+            Kind::Loop(bindings, body) => {
+                let mut inner = scope.clone();
+                for (pattern, value) in bindings {
+                    let value = self.eval(value, &inner)?;
+                    inner = self.bind(pattern, value, inner)?;
+                }
+                loop {
+                    match self.eval_tail(body, &inner)? {
+                        Tail::Value(value) => return Ok(value),
+                        Tail::Recur(args) => {
+                            inner = scope.clone();
+                            for ((pattern, _), value) in bindings.iter().zip(args) {
+                                inner = self.bind(pattern, value, inner)?;
+                            }
+                        }
+                    }
+                }
+            }
+            Kind::Recur(_) => unreachable!("the analyzer only allows recur in tail position"),
+            Kind::Call(f, args) => {
+                let f = self.eval(f, scope)?;
+                let args = self.eval_all(args, scope)?;
+                self.call(expr, &f, args)
+            }
+            Kind::Throw(value) => Err(self.throw(expr, self.eval(value, scope)?)),
+            _ => match self.eval_tail(expr, scope)? {
+                Tail::Value(value) => Ok(value),
+                Tail::Recur(_) => unreachable!("the analyzer only allows recur in tail position"),
+            },
+        }
+    }
+
+    /// Evaluates `expr` in tail position, where `recur` is allowed.
+    fn eval_tail(&self, expr: &Expr, scope: &Scope) -> Result<Tail, EvalError> {
+        match &expr.kind {
+            Kind::If(test, then, otherwise) => {
+                if self.eval(test, scope)?.is_truthy() {
+                    self.eval_tail(then, scope)
+                } else {
+                    self.eval_tail(otherwise, scope)
+                }
+            }
+            Kind::Do(body) => match body.split_last() {
+                Some((last, init)) => {
+                    for expr in init {
+                        self.eval(expr, scope)?;
+                    }
+                    self.eval_tail(last, scope)
+                }
+                None => Ok(Tail::Value(Value::Nil)),
+            },
+            Kind::Let(bindings, body) => {
+                let mut inner = scope.clone();
+                for (pattern, value) in bindings {
+                    let value = self.eval(value, &inner)?;
+                    inner = self.bind(pattern, value, inner)?;
+                }
+                self.eval_tail(body, &inner)
+            }
+            Kind::Case(value, clauses, default) => {
+                let value = self.eval(value, scope)?;
+                for (keys, body) in clauses {
+                    if keys.contains(&value) {
+                        return self.eval_tail(body, scope);
+                    }
+                }
+                match default {
+                    Some(default) => self.eval_tail(default, scope),
+                    None => Err(self.error(expr, format!("no matching clause: {value}"))),
+                }
+            }
+            Kind::Match(value, clauses) => {
+                let value = self.eval(value, scope)?;
+                for clause in clauses {
+                    let inner = match self.match_pattern(&clause.pattern, &value, scope.clone()) {
+                        Some(inner) => inner,
+                        None => continue,
+                    };
+                    if let Some(guard) = &clause.guard {
+                        if !self.eval(guard, &inner)?.is_truthy() {
+                            continue;
+                        }
+                    }
+                    return self.eval_tail(&clause.body, &inner);
+                }
+                Err(self.error(expr, format!("no matching clause: {value}")))
+            }
+            Kind::Try(body, catches, finally) => {
+                let result = match self.eval(body, scope) {
+                    Ok(value) => Ok(Tail::Value(value)),
+                    Err(err) => {
+                        let value = err.to_value();
+                        match catches.iter().find(|catch| catch.catches(&value)) {
+                            Some(catch) => {
+                                let inner = scope.bind(&catch.name, value);
+                                self.eval(&catch.body, &inner).map(Tail::Value)
+                            }
+                            None => Err(err),
+                        }
+                    }
+                };
+                if let Some(finally) = finally {
+                    self.eval(finally, scope)?;
+                }
+                result
+            }
+            Kind::Recur(args) => Ok(Tail::Recur(self.eval_all(args, scope)?)),
+            _ => Ok(Tail::Value(self.eval(expr, scope)?)),
+        }
+    }
+
+    /// The scope with the names in `pattern` bound, if `value` matches it.
+    fn match_pattern(&self, pattern: &MatchPattern, value: &Value, scope: Scope) -> Option<Scope> {
+        match (pattern, value) {
+            (MatchPattern::Wildcard, _) => Some(scope),
+            (MatchPattern::Bind(name), value) => Some(scope.bind(name, value.clone())),
+            (MatchPattern::Literal(literal), value) if literal == value => Some(scope),
+            (MatchPattern::Variant(ty, tag, items), Value::Variant(variant))
+                if variant.ty.key == ty.key && variant.tag == *tag =>
+            {
+                (items.iter().zip(&variant.values)).try_fold(scope, |scope, (item, value)| {
+                    self.match_pattern(item, value, scope)
+                })
+            }
+            (
+                MatchPattern::Seq(items, rest),
+                Value::ListParen(values) | Value::ListBracket(values),
+            ) => {
+                let fits = match rest {
+                    Some(_) => values.len() >= items.len(),
+                    None => values.len() == items.len(),
+                };
+                if !fits {
+                    return None;
+                }
+                let mut scope = (items.iter().zip(values))
+                    .try_fold(scope, |scope, (item, value)| {
+                        self.match_pattern(item, value, scope)
+                    })?;
+                if let Some(rest) = rest {
+                    let values = &values[items.len()..];
+                    let values = if values.is_empty() {
+                        Value::Nil
+                    } else {
+                        Value::ListParen(values.to_vec())
+                    };
+                    scope = self.match_pattern(rest, &values, scope)?;
+                }
+                Some(scope)
+            }
+            (MatchPattern::Seq(items, rest), Value::LazySeq(_)) => {
+                let (values, more) = seq::split(value, items.len()).ok()?;
+                if values.len() < items.len() || (rest.is_none() && !seq::is_empty(&more).ok()?) {
+                    return None;
+                }
+                let mut scope = (items.iter().zip(&values))
+                    .try_fold(scope, |scope, (item, value)| {
+                        self.match_pattern(item, value, scope)
+                    })?;
+                if let Some(rest) = rest {
+                    scope = self.match_pattern(rest, &seq::seq(&more).ok()?, scope)?;
+                }
+                Some(scope)
+            }
+            _ => None,
+        }
+    }
+
+    /// Binds the names in `pattern` to the matching parts of `value`.
+    fn bind(&self, pattern: &Pattern, value: Value, scope: Scope) -> Result<Scope, EvalError> {
+        match &pattern.kind {
+            PatternKind::Bind(name) => Ok(scope.bind(name, value)),
+            PatternKind::Seq { items, rest, all } if matches!(value, Value::LazySeq(_)) => {
+                let located = |err: EvalError| err.at(self.name, pattern.start, pattern.end);
+                let (values, more) = seq::split(&value, items.len()).map_err(located)?;
+                let mut scope = scope;
+                let mut values = values.into_iter();
+                for item in items {
+                    scope = self.bind(item, values.next().unwrap_or(Value::Nil), scope)?;
+                }
+                if let Some(rest) = rest {
+                    scope = self.bind(rest, seq::seq(&more).map_err(located)?, scope)?;
+                }
+                if let Some(all) = all {
+                    scope = scope.bind(all, value);
+                }
+                Ok(scope)
+            }
+            PatternKind::Seq { items, rest, all } => {
+                let values = match &value {
+                    Value::Nil => vec![],
+                    Value::ListParen(values) | Value::ListBracket(values) => values.clone(),
+                    Value::String(s) => s.chars().map(|ch| Value::String(ch.into())).collect(),
+                    value => {
+                        return Err(EvalError::located(
+                            self.name,
+                            pattern.start,
+                            pattern.end,
+                            format!("cannot destructure {value} as a sequence"),
+                        ))
+                    }
+                };
+                let mut scope = scope;
+                let mut values = values.into_iter();
+                for item in items {
+                    scope = self.bind(item, values.next().unwrap_or(Value::Nil), scope)?;
+                }
+                if let Some(rest) = rest {
+                    let values: Vec<Value> = values.collect();
+                    let values = if values.is_empty() {
+                        Value::Nil
+                    } else {
+                        Value::ListParen(values)
+                    };
+                    scope = self.bind(rest, values, scope)?;
+                }
+                if let Some(all) = all {
+                    scope = scope.bind(all, value);
+                }
+                Ok(scope)
+            }
+            PatternKind::Map {
+                entries,
+                defaults,
+                all,
+            } => {
+                let map = match &value {
+                    Value::Nil | Value::ListBrace(_) => value.clone(),
+                    // keyword arguments collected by `& {:keys [...]}`
+                    Value::ListParen(items) if items.len() % 2 == 0 => {
+                        Value::ListBrace(items.clone())
+                    }
+                    value => {
+                        return Err(EvalError::located(
+                            self.name,
+                            pattern.start,
+                            pattern.end,
+                            format!("cannot destructure {value} as a map"),
+                        ))
+                    }
+                };
+                let mut scope = scope;
+                for (item, key) in entries {
+                    let value = match (get(&map, key), &item.kind) {
+                        (Some(value), _) => value,
+                        (None, PatternKind::Bind(name)) => {
+                            match defaults.iter().find(|(default, _)| default == name) {
+                                Some((_, default)) => self.eval(default, &scope)?,
+                                None => Value::Nil,
+                            }
+                        }
+                        (None, _) => Value::Nil,
+                    };
+                    scope = self.bind(item, value, scope)?;
+                }
+                if let Some(all) = all {
+                    scope = scope.bind(all, value);
+                }
+                Ok(scope)
+            }
+        }
+    }
+
+    /// Fills in a syntax-quote template; `None` for a splice, which only
+    /// makes sense inside a list.
+    fn eval_template(
+        &self,
+        template: &Template,
+        scope: &Scope,
+    ) -> Result<Option<Value>, EvalError> {
+        Ok(Some(match template {
+            Template::Const(value) => value.clone(),
+            Template::Unquote(expr) => self.eval(expr, scope)?,
+            Template::Splice(_) => return Ok(None),
+            Template::ListParen(items) => Value::ListParen(self.splice_items(items, scope)?),
+            Template::ListBracket(items) => Value::ListBracket(self.splice_items(items, scope)?),
+            Template::ListBrace(items) => Value::ListBrace(self.splice_items(items, scope)?),
+        }))
+    }
+
+    fn splice_items(&self, items: &[Template], scope: &Scope) -> Result<Vec<Value>, EvalError> {
+        let mut values = vec![];
+        for item in items {
+            match item {
+                Template::Splice(expr) => match self.eval(expr, scope)? {
+                    Value::Nil => {}
+                    Value::ListParen(items) | Value::ListBracket(items) => values.extend(items),
+                    value @ Value::LazySeq(_) => values.extend(
+                        seq::to_vec(&value)
+                            .map_err(|err| err.at(self.name, expr.start, expr.end))?,
+                    ),
+                    value => {
+                        return Err(
+                            self.error(expr, format!("cannot splice {value}, expected a list"))
+                        )
+                    }
+                },
+                item => values.extend(self.eval_template(item, scope)?),
+            }
+        }
+        Ok(values)
+    }
+
+    fn eval_all(&self, exprs: &[Expr], scope: &Scope) -> Result<Vec<Value>, EvalError> {
+        exprs.iter().map(|expr| self.eval(expr, scope)).collect()
+    }
+
+    /// Applies `f` to `args` for the call `expr`, locating its errors there.
+    fn call(&self, expr: &Expr, f: &Value, args: Vec<Value>) -> Result<Value, EvalError> {
+        self.env.apply(f, args).map_err(|err| {
+            let mut err = err.at(self.name, expr.start, expr.end);
+            if let Value::Fn(Function::Lambda(lambda)) = f {
+                err.trace.push(TraceFrame {
+                    function: lambda.name().into(),
+                    name: self.name.into(),
+                    start: expr.start,
+                    end: expr.end,
+                });
+            }
+            err
+        })
+    }
+
+    /// The error `(throw value)` raises at `expr`.
+    fn throw(&self, expr: &Expr, value: Value) -> EvalError {
+        let message = match &value {
+            Value::Error(ex) => ex.message.clone(),
+            value => value.to_string(),
+        };
+        let mut err = self.error(expr, message);
+        err.value = Some(Box::new(value));
+        err
+    }
+
+    /// The next step of evaluating `expr` in a go block, given the result
+    /// of its child evaluated last, if any.
+    fn step(
+        &self,
+        expr: &Expr,
+        frame: &mut GoFrame,
+        input: Option<Result<Tail, EvalError>>,
+    ) -> Step {
+        if let Kind::Try(_, catches, finally) = &expr.kind {
+            return self.step_try(frame, input, catches, finally.is_some());
+        }
+        let input = match input {
+            Some(Err(err)) => return Step::Done(Err(err)),
+            Some(Ok(result)) => Some(result),
+            None => None,
+        };
+        let base = frame.base.clone();
+        match &expr.kind {
+            Kind::Vector(items) | Kind::Map(items) | Kind::Recur(items) => {
+                frame.values.extend(input.map(Tail::value));
+                if frame.values.len() < items.len() {
+                    return Step::Child(frame.values.len(), base);
+                }
+                let values = std::mem::take(&mut frame.values);
+                Step::Done(Ok(match &expr.kind {
+                    Kind::Vector(_) => Tail::Value(Value::ListBracket(values)),
+                    Kind::Map(_) => Tail::Value(Value::ListBrace(values)),
+                    _ => Tail::Recur(values),
+                }))
+            }
+            Kind::Call(_, args) => {
+                frame.values.extend(input.map(Tail::value));
+                if frame.values.len() <= args.len() {
+                    return Step::Child(frame.values.len(), base);
+                }
+                let mut args = std::mem::take(&mut frame.values);
+                let f = args.remove(0);
+                match &f {
+                    Value::Fn(Function::Native(name, _)) if core::PARKING.contains(name) => {
+                        match ChannelCall::new(name, args) {
+                            Ok(call) => Step::Park(call),
+                            Err(err) => Step::Done(Err(err.at(self.name, expr.start, expr.end))),
+                        }
+                    }
+                    f => Step::Done(self.call(expr, f, args).map(Tail::Value)),
+                }
+            }
+            Kind::Def(key, _) => match input {
+                None => Step::Child(0, base),
+                Some(result) => {
+                    let mut globals = self.env.globals.write().unwrap();
+                    globals.insert(key.clone(), result.value());
+                    Step::Done(Ok(Tail::Value(Value::Symbol("".into(), key.clone()))))
+                }
+            },
+            Kind::Throw(_) => match input {
+                None => Step::Child(0, base),
+                Some(result) => Step::Done(Err(self.throw(expr, result.value()))),
+            },
+            Kind::If(_, _, _) => match input {
+                None => Step::Child(0, base),
+                Some(test) if frame.child == 0 => {
+                    Step::Child(if test.value().is_truthy() { 1 } else { 2 }, base)
+                }
+                Some(result) => Step::Done(Ok(result)),
+            },
+            Kind::Do(items) => match input {
+                None => Step::Child(0, base),
+                Some(result) if frame.child + 1 == items.len() => Step::Done(Ok(result)),
+                Some(_) => Step::Child(frame.child + 1, base),
+            },
+            Kind::Let(bindings, _) | Kind::Loop(bindings, _) => {
+                let result = match input {
+                    None => return Step::Child(0, base),
+                    Some(result) => result,
+                };
+                let scope = if frame.child < bindings.len() {
+                    let (pattern, _) = &bindings[frame.child];
+                    self.bind(pattern, result.value(), frame.scope.clone())
+                } else {
+                    match (result, &expr.kind) {
+                        (Tail::Recur(args), Kind::Loop(_, _)) => (bindings.iter().zip(args))
+                            .try_fold(base, |scope, ((pattern, _), value)| {
+                                self.bind(pattern, value, scope)
+                            }),
+                        (result, _) => return Step::Done(Ok(result)),
+                    }
+                };
+                match scope {
+                    Ok(scope) => {
+                        frame.scope = scope;
+                        let next = (frame.child + 1).min(bindings.len());
+                        Step::Child(next, frame.scope.clone())
+                    }
+                    Err(err) => Step::Done(Err(err)),
+                }
+            }
+            Kind::Case(_, clauses, default) => match input {
+                None => Step::Child(0, base),
+                Some(result) if frame.child > 0 => Step::Done(Ok(result)),
+                Some(value) => {
+                    let value = value.value();
+                    match clauses.iter().position(|(keys, _)| keys.contains(&value)) {
+                        Some(i) => Step::Child(1 + i, base),
+                        None if default.is_some() => Step::Child(1 + clauses.len(), base),
+                        None => Step::Done(Err(
+                            self.error(expr, format!("no matching clause: {value}"))
+                        )),
+                    }
+                }
+            },
+            Kind::Match(_, clauses) => {
+                let from = match input {
+                    None => return Step::Child(0, base),
+                    Some(value) if frame.child == 0 => {
+                        frame.values.push(value.value());
+                        0
+                    }
+                    Some(guard) if frame.child % 2 == 1 => {
+                        if guard.value().is_truthy() {
+                            return Step::Child(frame.child + 1, frame.scope.clone());
+                        }
+                        frame.child / 2 + 1
+                    }
+                    Some(result) => return Step::Done(Ok(result)),
+                };
+                let value = &frame.values[0];
+                for (i, clause) in clauses.iter().enumerate().skip(from) {
+                    if let Some(inner) = self.match_pattern(&clause.pattern, value, base.clone()) {
+                        frame.scope = inner;
+                        let guard = if clause.guard.is_some() { 1 } else { 2 };
+                        return Step::Child(2 * i + guard, frame.scope.clone());
+                    }
+                }
+                Step::Done(Err(self.error(expr, format!("no matching clause: {value}"))))
+            }
+            _ => unreachable!("only expressions that park are stepped"),
+        }
+    }
+
+    /// The next step of a `try` in a go block: after its body, the catch
+    /// clause for an error, and then its finally body, if any.
+    fn step_try(
+        &self,
+        frame: &mut GoFrame,
+        input: Option<Result<Tail, EvalError>>,
+        catches: &[Catch],
+        finally: bool,
+    ) -> Step {
+        let result = match input {
+            None => return Step::Child(0, frame.base.clone()),
+            Some(Ok(_)) if frame.child > catches.len() => {
+                return Step::Done(frame.result.take().unwrap())
+            }
+            Some(Err(err)) if frame.child == 0 => {
+                let value = err.to_value();
+                match catches.iter().position(|catch| catch.catches(&value)) {
+                    Some(i) => {
+                        let inner = frame.base.bind(&catches[i].name, value);
+                        return Step::Child(1 + i, inner);
+                    }
+                    None => Err(err),
+                }
+            }
+            Some(result) => result.map(|result| Tail::Value(result.value())),
+        };
+        if finally && frame.child <= catches.len() {
+            frame.result = Some(result);
+            Step::Child(1 + catches.len(), frame.base.clone())
+        } else {
+            Step::Done(result)
+        }
+    }
+
+    fn error(&self, expr: &Expr, message: String) -> EvalError {
+        EvalError::located(self.name, expr.start, expr.end, message)
+    }
+}
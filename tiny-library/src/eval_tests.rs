@@ -0,0 +1,1550 @@
+#[cfg(test)]
+mod tests {
+    use eval::{Env, ErrorKind, EvalError, TraceFrame, Warning, MAX_DEPTH, STACK_SIZE};
+    use io::OutputStream;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use value::Value;
+
+    /// Evaluates `content`, leaving out the trace of any error.
+    fn eval(content: &str) -> Result<Value, EvalError> {
+        Env::new()
+            .eval_str("_test_.tiny", content)
+            .map_err(|err| EvalError {
+                trace: vec![],
+                ..err
+            })
+    }
+
+    fn error(start: usize, end: usize, message: &str) -> Result<Value, EvalError> {
+        error_of(ErrorKind::Other, start, end, message)
+    }
+
+    fn error_of(
+        kind: ErrorKind,
+        start: usize,
+        end: usize,
+        message: &str,
+    ) -> Result<Value, EvalError> {
+        Err(EvalError {
+            name: "_test_.tiny".into(),
+            start,
+            end,
+            kind,
+            message: message.into(),
+            value: None,
+            trace: vec![],
+        })
+    }
+
+    #[test]
+    fn test_eval_literals() {
+        assert_eq!(eval(""), Ok(Value::Nil));
+        assert_eq!(eval("nil"), Ok(Value::Nil));
+        assert_eq!(eval("true false"), Ok(Value::Bool(false)));
+        assert_eq!(eval("42"), Ok(Value::Int(42)));
+        assert_eq!(eval("\"foo\""), Ok(Value::String("foo".into())));
+        assert_eq!(eval(":foo"), Ok(Value::Keyword("".into(), "foo".into())));
+        assert_eq!(
+            eval("[1 (+ 1 1) nil]"),
+            Ok(Value::ListBracket(vec![
+                Value::Int(1),
+                Value::Int(2),
+                Value::Nil
+            ]))
+        );
+        assert_eq!(
+            eval("{:a (* 2 3)}"),
+            Ok(Value::ListBrace(vec![
+                Value::Keyword("".into(), "a".into()),
+                Value::Int(6)
+            ]))
+        );
+        assert_eq!(
+            eval("{:a}"),
+            error(0, 4, "map literal must contain an even number of forms")
+        );
+    }
+
+    #[test]
+    fn test_eval_quote() {
+        assert_eq!(
+            eval("(quote (a 1))"),
+            Ok(Value::ListParen(vec![
+                Value::Symbol("".into(), "a".into()),
+                Value::Int(1)
+            ]))
+        );
+        assert_eq!(
+            eval("(quote)"),
+            error(0, 7, "quote expects exactly one argument")
+        );
+    }
+
+    #[test]
+    fn test_eval_def() {
+        assert_eq!(eval("(def x 10) (* x 2)"), Ok(Value::Int(20)));
+        assert_eq!(eval("(def x \"doc\" 10) x"), Ok(Value::Int(10)));
+        assert_eq!(
+            eval("(def 1 2)"),
+            error(5, 6, "def expects a symbol, found 1")
+        );
+        assert_eq!(
+            eval("(+ 1 y)"),
+            error_of(
+                ErrorKind::UnresolvedSymbol,
+                5,
+                6,
+                "unable to resolve symbol: y"
+            )
+        );
+    }
+
+    #[test]
+    fn test_eval_defn() {
+        assert_eq!(
+            eval(
+                "
+(defn fac [n] (fact-h n 1))
+(defn fact-h [n acc]
+  (if (zero? n)
+    acc
+    (fact-h (dec n) (* acc n))))
+(fac 20)"
+            ),
+            Ok(Value::Int(2432902008176640000))
+        );
+        assert_eq!(
+            eval("(defn double \"Doubles v\" [v] (* 2 v)) (double 4)"),
+            Ok(Value::Int(8))
+        );
+        assert_eq!(
+            eval("(defn f [a] a) (f 1 2)"),
+            error(15, 22, "wrong number of args (2) passed to f")
+        );
+    }
+
+    #[test]
+    fn test_eval_fn() {
+        assert_eq!(eval("((fn [a b] (- a b)) 5 3)"), Ok(Value::Int(2)));
+        assert_eq!(
+            eval("((fn fib [n] (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))) 10)"),
+            Ok(Value::Int(55))
+        );
+        assert_eq!(eval("(1 2)"), error(0, 5, "1 is not a function"));
+        assert_eq!(eval("(fn a)"), error(0, 6, "expected a parameter vector"));
+    }
+
+    #[test]
+    fn test_eval_closures() {
+        assert_eq!(
+            eval("(defn adder [n] (fn [x] (+ x n))) (def add2 (adder 2)) (add2 40)"),
+            Ok(Value::Int(42))
+        );
+        assert_eq!(
+            eval("(let [a 1 f (fn [] a)] (let [a 2] (f)))"),
+            Ok(Value::Int(1))
+        );
+        assert_eq!(
+            eval("(defn compose [f g] (fn [x] (f (g x)))) ((compose inc inc) 1)"),
+            Ok(Value::Int(3))
+        );
+    }
+
+    #[test]
+    fn test_eval_multi_arity() {
+        let emit = "
+(defn emit
+  \"Emits an ast\"
+  ([ast] (emit :default ast))
+  ([state ast] [state ast]))";
+        assert_eq!(
+            eval(&format!("{emit} (emit 1)")),
+            Ok(Value::ListBracket(vec![
+                Value::Keyword("".into(), "default".into()),
+                Value::Int(1)
+            ]))
+        );
+        assert_eq!(
+            eval(&format!("{emit} (emit 1 2)")),
+            Ok(Value::ListBracket(vec![Value::Int(1), Value::Int(2)]))
+        );
+        assert_eq!(
+            eval(&format!("{emit}\n(emit)")),
+            error(88, 94, "wrong number of args (0) passed to emit")
+        );
+        assert_eq!(
+            eval("((fn ([] 0) ([a] 1) ([a & more] more)) 1 2 3)"),
+            Ok(Value::ListParen(vec![Value::Int(2), Value::Int(3)]))
+        );
+        assert_eq!(eval("((fn [& xs] xs))"), Ok(Value::Nil));
+        assert_eq!(
+            eval("((fn [a & xs] [a xs]) 1 2)"),
+            Ok(Value::ListBracket(vec![
+                Value::Int(1),
+                Value::ListParen(vec![Value::Int(2)])
+            ]))
+        );
+        assert_eq!(
+            eval("((fn [a b & xs] a) 1)"),
+            error(0, 21, "wrong number of args (1) passed to fn")
+        );
+    }
+
+    #[test]
+    fn test_eval_arity_errors() {
+        assert_eq!(
+            eval("(fn ([a] 1) ([b] 2))"),
+            error(0, 20, "can't have 2 overloads with same arity")
+        );
+        assert_eq!(
+            eval("(fn ([& a] 1) ([& b] 2))"),
+            error(0, 24, "can't have more than 1 variadic overload")
+        );
+        assert_eq!(
+            eval("(fn ([a b] 1) ([& b] 2))"),
+            error(
+                0,
+                24,
+                "can't have fixed arity function with more params than variadic function"
+            )
+        );
+        assert_eq!(
+            eval("(fn [a &] a)"),
+            error(7, 8, "expected exactly one parameter after &")
+        );
+        assert_eq!(
+            eval("(fn ([a] 1) 2)"),
+            error(12, 13, "expected an arity clause ([params] body)")
+        );
+    }
+
+    #[test]
+    fn test_eval_let() {
+        assert_eq!(eval("(let [a 1 b (+ a 1)] (* a b 10))"), Ok(Value::Int(20)));
+        assert_eq!(eval("(let [a 1] (let [a 2] a))"), Ok(Value::Int(2)));
+        assert_eq!(
+            eval("(let [a] a)"),
+            error(0, 11, "let expects a vector with an even number of forms")
+        );
+    }
+
+    #[test]
+    fn test_eval_sequential_destructuring() {
+        assert_eq!(eval("(let [[a b] [1 2]] (+ a b))"), Ok(Value::Int(3)));
+        assert_eq!(
+            eval("(let [[a [b c] & more :as all] (list 1 [2 3] 4 5)] [a b c more all])"),
+            eval("[1 2 3 (quote (4 5)) (quote (1 [2 3] 4 5))]")
+        );
+        assert_eq!(
+            eval("(let [[a b & more] [1]] [a b more])"),
+            eval("[1 nil nil]")
+        );
+        assert_eq!(
+            eval("(let [[a b] nil [c] \"xy\"] [a b c])"),
+            eval("[nil nil \"x\"]")
+        );
+        assert_eq!(
+            eval("(loop [[l & r] [1 2 3] sum 0] (if (nil? l) sum (recur r (+ sum l))))"),
+            Ok(Value::Int(6))
+        );
+        assert_eq!(
+            eval("(defn f [[a b] & [c]] [a b c]) (f [1 2] 3 4)"),
+            eval("[1 2 3]")
+        );
+    }
+
+    #[test]
+    fn test_eval_associative_destructuring() {
+        assert_eq!(
+            eval("(let [{a :a [b] :b} {:a 1 :b [2]}] [a b])"),
+            eval("[1 2]")
+        );
+        assert_eq!(
+            eval(
+                "(let [{:keys [type repl value] :or {repl \"main\"} :as event}
+                       {:type \"out\" :value 1}]
+                   [type repl value event])"
+            ),
+            eval("[\"out\" \"main\" 1 {:type \"out\" :value 1}]")
+        );
+        assert_eq!(
+            eval("(let [{:strs [a] :syms [b]} {\"a\" 1 (quote b) 2}] [a b])"),
+            eval("[1 2]")
+        );
+        assert_eq!(
+            eval("(let [{:keys [a] :or {a 5}} nil] a)"),
+            Ok(Value::Int(5))
+        );
+        assert_eq!(
+            eval("(let [{:keys [a] :or {a 5}} {:a false}] a)"),
+            Ok(Value::Bool(false))
+        );
+        assert_eq!(
+            eval("(defn f [x & {:keys [scale]}] (* x scale)) (f 2 :scale 3)"),
+            Ok(Value::Int(6))
+        );
+        assert_eq!(eval("(let [{{c :c} :b} {:b {:c 3}}] c)"), Ok(Value::Int(3)));
+    }
+
+    #[test]
+    fn test_eval_destructuring_errors() {
+        assert_eq!(
+            eval("(let [1 2] 1)"),
+            error(6, 7, "unsupported binding form: 1")
+        );
+        assert_eq!(
+            eval("(let [[a &] 2] 1)"),
+            error(9, 10, "expected a binding form after &")
+        );
+        assert_eq!(
+            eval("(let [[& a b] 2] 1)"),
+            error(11, 12, "unexpected binding form after & or :as: b")
+        );
+        assert_eq!(
+            eval("(let [{:keys a} 2] 1)"),
+            error(13, 14, ":keys expects a vector of symbols")
+        );
+        assert_eq!(
+            eval("(let [{:keys [1]} 2] 1)"),
+            error(14, 15, ":keys expects a symbol, found 1")
+        );
+        assert_eq!(
+            eval("(let [{:keys [a] :or [a 1]} 2] 1)"),
+            error(21, 26, ":or expects a map of defaults")
+        );
+        assert_eq!(
+            eval("(let [{:keys [a] :or {b 1}} 2] 1)"),
+            error(
+                24,
+                25,
+                ":or default for b, which is not bound by the pattern"
+            )
+        );
+        assert_eq!(
+            eval("(let [{a :a} 2] 1)"),
+            error(6, 12, "cannot destructure 2 as a map")
+        );
+        assert_eq!(
+            eval("(let [[a] :k] 1)"),
+            error(6, 9, "cannot destructure :k as a sequence")
+        );
+    }
+
+    #[test]
+    fn test_eval_conditionals() {
+        assert_eq!(eval("(if nil 1 2)"), Ok(Value::Int(2)));
+        assert_eq!(eval("(if false 1)"), Ok(Value::Nil));
+        assert_eq!(eval("(if 0 1 2)"), Ok(Value::Int(1)));
+        assert_eq!(eval("(when true 1 2 3)"), Ok(Value::Int(3)));
+        assert_eq!(eval("(when false 1 2 3)"), Ok(Value::Nil));
+        assert_eq!(eval("(cond false 1 nil 2 :else 3)"), Ok(Value::Int(3)));
+        assert_eq!(eval("(cond false 1 (= 1 1) 2)"), Ok(Value::Int(2)));
+        assert_eq!(eval("(cond false 1 4)"), Ok(Value::Int(4)));
+        assert_eq!(eval("(cond false 1)"), Ok(Value::Nil));
+        assert_eq!(
+            eval("(if)"),
+            error(0, 4, "if expects a test, a then and an optional else")
+        );
+    }
+
+    #[test]
+    fn test_eval_case() {
+        let case = "(defn f [x] (case x -1 :neg 0 :zero (1 2 3) :small \"s\" :str :big))";
+        assert_eq!(
+            eval(&format!("{case} [(f -1) (f 0) (f 2) (f \"s\") (f 9)]")),
+            Ok(Value::ListBracket(vec![
+                Value::Keyword("".into(), "neg".into()),
+                Value::Keyword("".into(), "zero".into()),
+                Value::Keyword("".into(), "small".into()),
+                Value::Keyword("".into(), "str".into()),
+                Value::Keyword("".into(), "big".into()),
+            ]))
+        );
+        assert_eq!(
+            eval("(case 3 1 :one 2 :two)"),
+            error(0, 22, "no matching clause: 3")
+        );
+    }
+
+    #[test]
+    fn test_eval_do() {
+        assert_eq!(eval("(do)"), Ok(Value::Nil));
+        assert_eq!(eval("(do (def a 1) (+ a 1))"), Ok(Value::Int(2)));
+    }
+
+    #[test]
+    fn test_eval_loop_recur() {
+        assert_eq!(
+            eval("(loop [i 0 acc 0] (if (< i 100000) (recur (inc i) (+ acc i)) acc))"),
+            Ok(Value::Int(4999950000))
+        );
+        assert_eq!(
+            eval("(loop [i 3] (when (> i 0) (recur (dec i))))"),
+            Ok(Value::Nil)
+        );
+        assert_eq!(eval("(recur 1)"), error(0, 9, "recur used outside of loop"));
+        assert_eq!(
+            eval("(loop [a 1] (recur))"),
+            error(
+                12,
+                19,
+                "mismatched argument count to recur, expected: 1 args, got: 0"
+            )
+        );
+        assert_eq!(
+            eval("(loop [a 1] (+ 1 (recur 2)))"),
+            error(17, 26, "can only recur from tail position")
+        );
+    }
+
+    #[test]
+    fn test_eval_fn_recur() {
+        assert_eq!(
+            eval("(defn sum [n acc] (if (zero? n) acc (recur (dec n) (+ acc n)))) (sum 100 0)"),
+            Ok(Value::Int(5050))
+        );
+        assert_eq!(
+            eval("((fn [n & xs] (if (zero? n) xs (recur (dec n) (list n xs)))) 2 :a)"),
+            eval("(quote (1 (2 (:a))))")
+        );
+        assert_eq!(
+            eval("(defn f ([] (f 3)) ([n] (when (> n 0) (recur (dec n))))) (f)"),
+            Ok(Value::Nil)
+        );
+    }
+
+    #[test]
+    fn test_eval_named_let() {
+        assert_eq!(
+            eval(
+                "
+(defn parse [ls]
+   (let loop [[l & r] ls sum 0 out []]
+     (cond (nil? l) out
+           (= l \"\") (loop r 0 (vector out sum))
+           (loop r (+ sum l) out))))
+(parse [1 2 \"\" 3])"
+            ),
+            eval("[[] 3]")
+        );
+        assert_eq!(
+            eval("(let next [i 0] (case i 3 i (next (inc i))))"),
+            Ok(Value::Int(3))
+        );
+        assert_eq!(
+            eval("(let lp [i 0] (+ 1 (lp i)))"),
+            error(19, 25, "can only call lp from tail position")
+        );
+        assert_eq!(
+            eval("(let lp [i 0] (lp))"),
+            error(
+                14,
+                18,
+                "mismatched argument count to lp, expected: 1 args, got: 0"
+            )
+        );
+    }
+
+    #[test]
+    fn test_eval_recur_tail_position() {
+        assert_eq!(
+            eval("(fn [x] (if (recur x) 1 2))"),
+            error(12, 21, "can only recur from tail position")
+        );
+        assert_eq!(
+            eval("(loop [a 1] (do (recur 1) 2))"),
+            error(16, 25, "can only recur from tail position")
+        );
+        assert_eq!(
+            eval("(loop [a 1] [(recur 1)])"),
+            error(13, 22, "can only recur from tail position")
+        );
+        assert_eq!(
+            eval("(loop [a (recur 1)] a)"),
+            error(9, 18, "recur used outside of loop")
+        );
+        assert_eq!(
+            eval("(loop [a 1] (let [b (recur 2)] b))"),
+            error(20, 29, "can only recur from tail position")
+        );
+    }
+
+    #[test]
+    fn test_eval_recur_constant_stack() {
+        // would overflow the small stack if each iteration nested a call
+        let result = std::thread::Builder::new()
+            .stack_size(512 * 1024)
+            .spawn(|| {
+                eval(
+                    "
+(defn count-down [n] (if (zero? n) :done (recur (dec n))))
+[(count-down 50000)
+ (let lp [i 0] (if (< i 50000) (lp (inc i)) i))]",
+                )
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(result, eval("[:done 50000]"));
+    }
+
+    #[test]
+    fn test_eval_keyword_and_collection_calls() {
+        assert_eq!(eval("(:a {:a 1 :b 2})"), Ok(Value::Int(1)));
+        assert_eq!(eval("(:c {:a 1} 3)"), Ok(Value::Int(3)));
+        assert_eq!(eval("({:a 1} :a)"), Ok(Value::Int(1)));
+        assert_eq!(eval("([10 20] 1)"), Ok(Value::Int(20)));
+    }
+
+    #[test]
+    fn test_eval_native_errors() {
+        assert_eq!(eval("(+ 1 \"a\")"), error(0, 9, "\"a\" is not a number"));
+        assert_eq!(eval("(/ 1 0)"), error(0, 7, "divide by zero"));
+    }
+
+    #[test]
+    fn test_print_values() {
+        assert_eq!(
+            eval("[nil true 1 1.0 \"a\\nb\" :k (quote (a b)) {:a 1}]")
+                .map(|value| value.to_string()),
+            Ok("[nil true 1 1.0 \"a\\nb\" :k (a b) {:a 1}]".into())
+        );
+    }
+
+    fn show(content: &str) -> Result<String, EvalError> {
+        eval(content).map(|value| value.to_string())
+    }
+
+    #[test]
+    fn test_eval_defmacro() {
+        assert_eq!(
+            eval(
+                "
+(defmacro unless [test & body] `(if ~test nil (do ~@body)))
+[(unless false 1 2) (unless true 1)]"
+            ),
+            eval("[2 nil]")
+        );
+        assert_eq!(
+            show("(let [x 1 xs [2 3]] `(a ~x ~@xs [~x] {:k ~x}))"),
+            Ok("(user/a 1 2 3 [1] {:k 1})".into())
+        );
+        assert_eq!(show("`(a ~@nil b)"), Ok("(user/a user/b)".into()));
+    }
+
+    #[test]
+    fn test_eval_macro_hygiene() {
+        // `x#` is the same fresh symbol within one template and differs between expansions
+        let result = eval("(let [form `(x# x#)] [form `x#])").unwrap();
+        let (form, other) = match result {
+            Value::ListBracket(items) => (items[0].clone(), items[1].clone()),
+            result => panic!("{:?}", result),
+        };
+        let name = match form {
+            Value::ListParen(items) => {
+                assert_eq!(items[0], items[1]);
+                items[0].clone()
+            }
+            form => panic!("{:?}", form),
+        };
+        assert_ne!(name, other);
+        assert_ne!(name, Value::Symbol("".into(), "x".into()));
+        // the macro's local doesn't capture the caller's `x`
+        assert_eq!(
+            eval(
+                "
+(defmacro twice [e] `(let [x# ~e] (+ x# x#)))
+(let [x 5] (twice x))"
+            ),
+            Ok(Value::Int(10))
+        );
+        assert_eq!(eval("(or false nil 3)"), Ok(Value::Int(3)));
+        assert_eq!(eval("(let [and# 1] (and true and#))"), Ok(Value::Int(1)));
+    }
+
+    #[test]
+    fn test_macroexpand() {
+        let env = Env::new();
+        env.eval_str("_test_.tiny", "(defmacro inc! [x] `(inc ~x))")
+            .unwrap();
+        let form = env
+            .eval_str("_test_.tiny", "(quote (inc! (inc! 1)))")
+            .unwrap();
+        assert_eq!(
+            env.macroexpand_1(&form).map(|form| form.to_string()),
+            Ok("(tiny.core/inc (inc! 1))".into())
+        );
+        assert_eq!(
+            env.eval_str("_test_.tiny", "(macroexpand '(-> 1 (inc! ) inc!))")
+                .map(|form| form.to_string()),
+            Ok("(tiny.core/inc (inc! 1))".into())
+        );
+        assert_eq!(
+            env.eval_str("_test_.tiny", "(macroexpand-1 '(+ 1 2))")
+                .map(|form| form.to_string()),
+            Ok("(+ 1 2)".into())
+        );
+    }
+
+    #[test]
+    fn test_eval_macro_errors() {
+        assert_eq!(
+            eval("(defmacro m [a] a)\n(m)"),
+            error(
+                19,
+                22,
+                "error expanding m: wrong number of args (0) passed to m"
+            )
+        );
+        assert_eq!(
+            eval("(defmacro m [] `(~@1))\n(m)"),
+            error(
+                23,
+                26,
+                "error expanding m: cannot splice 1, expected a list"
+            )
+        );
+        assert_eq!(
+            eval("(defmacro m [] '(undefined))\n(inc (m))"),
+            error_of(
+                ErrorKind::UnresolvedSymbol,
+                34,
+                37,
+                "unable to resolve symbol: undefined"
+            )
+        );
+        assert_eq!(
+            eval("(+ 1 ~x)"),
+            error(5, 7, "unquote used outside of syntax-quote")
+        );
+    }
+
+    #[test]
+    fn test_eval_core_macros() {
+        assert_eq!(eval("(when-not false 1 2)"), Ok(Value::Int(2)));
+        assert_eq!(eval("(if-not nil 1 2)"), Ok(Value::Int(1)));
+        assert_eq!(
+            eval("[(and) (and 1 2) (and 1 nil 2)]"),
+            eval("[true 2 nil]")
+        );
+        assert_eq!(
+            eval("[(or) (or nil false) (or nil 2 3)]"),
+            eval("[nil false 2]")
+        );
+        assert_eq!(eval("(-> 1 inc (- 10) (* 2))"), Ok(Value::Int(-16)));
+        assert_eq!(eval("(->> 1 inc (- 10) (* 2))"), Ok(Value::Int(16)));
+        assert_eq!(
+            eval("(loop [n 0] (if (< n 3) (recur (inc n)) (or false n)))"),
+            Ok(Value::Int(3))
+        );
+    }
+
+    /// A source root holding `files`, fresh for each test.
+    fn source_root(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("tiny-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    fn eval_in(root: &PathBuf, content: &str) -> Result<Value, EvalError> {
+        let env = Env::new();
+        env.add_source_root(root);
+        env.eval_str("_test_.tiny", content)
+    }
+
+    #[test]
+    fn test_import() {
+        let root = source_root(
+            "import",
+            &[
+                (
+                    "app/components/todo_input.tiny",
+                    "(import [app.helpers :as h]) (defn render [] (h/wrap \"input\"))",
+                ),
+                (
+                    "app/helpers.tiny",
+                    "
+(def- prefix \"<\")
+(defn wrap [s] (str prefix s \">\"))
+(defmacro unless [test & body] `(if ~test nil (do ~@body)))
+(def enter-key 13)",
+                ),
+            ],
+        );
+        assert_eq!(
+            eval_in(
+                &root,
+                "
+(import [app.components.todo-input :as todo-input]
+        [app.helpers :refer [enter-key unless]])
+[(todo-input/render) enter-key (unless false 1)]"
+            ),
+            eval("[\"<input>\" 13 1]")
+        );
+        assert_eq!(
+            eval_in(&root, "(import [app.helpers :refer :all]) (wrap enter-key)"),
+            Ok(Value::String("<13>".into()))
+        );
+        assert_eq!(
+            eval_in(&root, "(import app.helpers) (app.helpers/wrap 1)"),
+            Ok(Value::String("<1>".into()))
+        );
+        assert_eq!(
+            eval_in(&root, "(import [app.helpers :as h]) h/prefix"),
+            error(29, 37, "app.helpers/prefix is not public")
+        );
+        assert_eq!(
+            eval_in(&root, "(import app.helpers) app.helpers/prefix"),
+            error(21, 39, "app.helpers/prefix is not public")
+        );
+        assert_eq!(
+            eval_in(&root, "(import [app.helpers :refer [prefix]])"),
+            error(8, 37, "app.helpers/prefix is not public")
+        );
+        assert_eq!(
+            eval_in(&root, "(import [app.helpers :refer [nope]])"),
+            error(8, 35, "nope does not exist in app.helpers")
+        );
+        assert_eq!(
+            eval_in(&root, "(h/wrap 1)"),
+            error_of(ErrorKind::UnresolvedNamespace, 1, 7, "no such namespace: h")
+        );
+    }
+
+    #[test]
+    fn test_import_caches_modules() {
+        let root = source_root(
+            "import-cache",
+            &[
+                ("counter.tiny", "(println \"loading\") (def loaded 1)"),
+                ("a.tiny", "(import counter)"),
+            ],
+        );
+        let env = Env::new();
+        env.add_source_root(&root);
+        env.eval_str("_test_.tiny", "(import a counter)").unwrap();
+        std::fs::write(root.join("counter.tiny"), "(def loaded 2)").unwrap();
+        assert_eq!(
+            env.eval_str("_test_.tiny", "(import counter) counter/loaded"),
+            Ok(Value::Int(1))
+        );
+        assert_eq!(
+            env.module_files(),
+            [root.join("a.tiny"), root.join("counter.tiny")]
+        );
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let root = source_root(
+            "import-errors",
+            &[
+                ("a.tiny", "(import b)"),
+                ("b.tiny", "(import c)"),
+                ("c.tiny", "(import a)"),
+                ("broken.tiny", "(def x (+ 1 :a))"),
+            ],
+        );
+        let err = eval_in(&root, "(import a)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ImportCycle);
+        assert_eq!(err.message, "import cycle: a -> b -> c -> a");
+        assert_eq!(err.name, root.join("c.tiny").display().to_string());
+        assert_eq!((err.start, err.end), (8, 9));
+
+        let err = eval_in(&root, "(import broken)").unwrap_err();
+        assert_eq!(err.message, ":a is not a number");
+        assert_eq!(err.name, root.join("broken.tiny").display().to_string());
+
+        let err = eval_in(&root, "(import missing.mod)").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnresolvedModule);
+        assert_eq!(
+            err.message,
+            format!(
+                "could not find module missing.mod in source roots [{}]",
+                root.display()
+            )
+        );
+        assert_eq!((err.start, err.end), (8, 19));
+    }
+
+    #[test]
+    fn test_defstruct() {
+        let structs = "
+(defstruct Todo (text String) (completed Bool))
+(defstruct Model
+  (text    String)
+  (todos   (List Todo))
+  (editing (Maybe Todo)))
+(defstruct person
+  (name       :type lib/string :value \"Paul\")
+  (address    type: lib/string value: \"Z Street\")
+  (age        :type Int))
+(defstruct Point [x y])
+";
+        let eval = |content: &str| eval(&format!("{structs}{content}"));
+        assert_eq!(
+            eval("(Todo \"milk\" false)").map(|value| value.to_string()),
+            Ok("(Todo :text \"milk\" :completed false)".into())
+        );
+        assert_eq!(
+            eval("(person :age 30 :address \"Main Avenue\")").map(|value| value.to_string()),
+            Ok("(person :name \"Paul\" :address \"Main Avenue\" :age 30)".into())
+        );
+        assert_eq!(
+            eval(
+                "
+(let [todo (Todo \"milk\" true)
+      model (Model \"\" [todo] nil)]
+  [(Todo-text todo) (:completed todo) (:missing todo :none) (Model-todos model)])"
+            ),
+            eval("[\"milk\" true :none [(Todo \"milk\" true)]]")
+        );
+        assert_eq!(
+            eval("(= (Point 1 2) (Point :x 1 :y 2))"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(eval("(= (Point 1 2) (Point 2 1))"), Ok(Value::Bool(false)));
+        let printed = eval("(Model \"a\" [(Todo \"b\" false)] nil)").unwrap();
+        assert_eq!(eval(&printed.to_string()), Ok(printed));
+
+        let message = |content: &str| eval(content).unwrap_err().message;
+        assert_eq!(
+            message("(Todo 1 false)"),
+            "field text of Todo expects String, got 1"
+        );
+        assert_eq!(
+            message("(Model \"\" [1] nil)"),
+            "field todos of Model expects (List Todo), got [1]"
+        );
+        assert_eq!(
+            message("(Model \"\" [] (Point 1 2))"),
+            "field editing of Model expects (Maybe Todo), got (Point :x 1 :y 2)"
+        );
+        assert_eq!(
+            message("(person :name \"x\")"),
+            "field age of person expects Int, got nil"
+        );
+        assert_eq!(
+            message("(person :age 1 :height 2)"),
+            "person has no field :height"
+        );
+        assert_eq!(
+            message("(Point 1)"),
+            "wrong number of args (1) passed to Point"
+        );
+        assert_eq!(
+            message("(Todo-text (Point 1 2))"),
+            "Todo-text expects a Todo, got (Point :x 1 :y 2)"
+        );
+        assert_eq!(
+            self::eval("(defstruct P (x Int :value \"a\"))"),
+            error(0, 32, "field x of P expects Int, got \"a\"")
+        );
+        assert_eq!(
+            self::eval("(defstruct P (x (Set Int)))"),
+            error(16, 25, "unsupported type: (Set Int)")
+        );
+        assert_eq!(
+            self::eval("(defstruct P (x Int) (x Int))"),
+            error(21, 28, "duplicate field x")
+        );
+    }
+
+    #[test]
+    fn test_defenum() {
+        let enums = "
+(defenum Filter All Incomplete Completed)
+(defenum Msg
+  (AddTodo Int)
+  (UpdateText String)
+  (SetFilter Filter)
+  (Reset))
+";
+        let eval = |content: &str| eval(&format!("{enums}{content}"));
+        assert_eq!(
+            eval("[(AddTodo 1) (SetFilter Completed) Reset All]").map(|value| value.to_string()),
+            Ok("[(AddTodo 1) (SetFilter Completed) Reset All]".into())
+        );
+        assert_eq!(eval("(= (AddTodo 1) (AddTodo 1))"), Ok(Value::Bool(true)));
+        assert_eq!(eval("(= (AddTodo 1) (AddTodo 2))"), Ok(Value::Bool(false)));
+        let message = |content: &str| eval(content).unwrap_err().message;
+        assert_eq!(
+            message("(AddTodo \"1\")"),
+            "argument 1 of AddTodo expects Int, got \"1\""
+        );
+        assert_eq!(
+            message("(SetFilter (AddTodo 1))"),
+            "argument 1 of SetFilter expects Filter, got (AddTodo 1)"
+        );
+        assert_eq!(
+            message("(AddTodo)"),
+            "wrong number of args (0) passed to AddTodo"
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let update = "
+(defenum Filter All Incomplete Completed)
+(defenum Msg (AddTodo Int) (UpdateText String) (SetFilter Filter) (Reset))
+(defn update [msg]
+  (match msg
+    (AddTodo 0) :zero
+    (AddTodo n) :when (< n 0) [:negative n]
+    (AddTodo n) [:add n]
+    (UpdateText text) [:text text]
+    (SetFilter All) :all
+    (SetFilter _) :some
+    Reset :reset))
+";
+        let eval = |content: &str| eval(&format!("{update}{content}"));
+        let at = |start, end, message| error(update.len() + start, update.len() + end, message);
+        assert_eq!(
+            eval(
+                "[(update (AddTodo 0)) (update (AddTodo -1)) (update (AddTodo 2))
+                  (update (UpdateText \"a\")) (update (SetFilter All))
+                  (update (SetFilter Completed)) (update Reset)]"
+            ),
+            eval("[:zero [:negative -1] [:add 2] [:text \"a\"] :all :some :reset]")
+        );
+        assert_eq!(
+            eval(
+                "(defn f [v]
+                   (match v
+                     [:move x y] (+ x y)
+                     [:say & words] words
+                     \"hi\" :greeting
+                     nil :nothing
+                     other [:other other]))
+                 [(f [:move 1 2]) (f [:say 1 2]) (f \"hi\") (f nil) (f [:move 1])]"
+            ),
+            eval("[3 (list 1 2) :greeting :nothing [:other [:move 1]]]")
+        );
+        assert_eq!(
+            eval("(loop [n 3] (match n 0 :done _ (recur (dec n))))"),
+            Ok(Value::Keyword("".into(), "done".into()))
+        );
+        assert_eq!(
+            eval("(match (AddTodo 1) (UpdateText t) t)"),
+            at(0, 36, "no matching clause: (AddTodo 1)")
+        );
+        assert_eq!(
+            eval("(match Reset (Reset x) x)"),
+            at(13, 22, "Reset has 0 field(s), pattern has 1")
+        );
+        assert_eq!(
+            eval("(match 1 AddTodo 1)"),
+            at(
+                9,
+                16,
+                "AddTodo has 1 field(s), use (AddTodo ...) to match it"
+            )
+        );
+    }
+
+    #[test]
+    fn test_match_exhaustiveness() {
+        let env = Env::new();
+        let result = env.eval_str(
+            "_test_.tiny",
+            "
+(defenum Msg (AddTodo Int) (UpdateText String) (Reset))
+(defn f [msg] (match msg (AddTodo 1) 1 (UpdateText t) :when t 2))
+(defn g [msg] (match msg (AddTodo _) 1 (UpdateText t) 2 Reset 3))
+(defn h [msg] (match msg (AddTodo 1) 1 _ 2))",
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            env.take_warnings(),
+            vec![Warning {
+                name: "_test_.tiny".into(),
+                start: 71,
+                end: 121,
+                kind: ErrorKind::NonExhaustiveMatch,
+                message: "match on Msg is not exhaustive, missing AddTodo, UpdateText, Reset"
+                    .into(),
+            }]
+        );
+        assert_eq!(env.take_warnings(), vec![]);
+    }
+
+    #[test]
+    fn test_try_catch() {
+        assert_eq!(
+            eval("(try (+ 1 2) (catch [e Exception] :caught))"),
+            Ok(Value::Int(3))
+        );
+        assert_eq!(
+            eval("(try (/ 1 0) (catch [e ExceptionInfo] [(ex-message e) (ex-data e)]))"),
+            eval("[\"divide by zero\" {}]")
+        );
+        assert_eq!(
+            eval(
+                "
+(try
+  (throw (ex-info \"boom\" {:code 42}))
+  (catch [e String] :string)
+  (catch [e ExceptionInfo] (:code (ex-data e))))"
+            ),
+            Ok(Value::Int(42))
+        );
+        assert_eq!(
+            eval(
+                "
+(defstruct NotFound (path String))
+(try (throw (NotFound \"/x\")) (catch [e NotFound] (NotFound-path e)))"
+            ),
+            Ok(Value::String("/x".into()))
+        );
+        assert_eq!(
+            eval("(try (throw :oops) (catch Throwable e [:caught e]))"),
+            eval("[:caught :oops]")
+        );
+        assert_eq!(
+            eval(
+                "
+(let [err (ex-info \"outer\" {} (ex-info \"inner\" {:a 1}))]
+  [(ex-message (ex-cause err)) (ex-data (ex-cause err)) (ex-cause (ex-cause err))])"
+            ),
+            eval("[\"inner\" {:a 1} nil]")
+        );
+        assert_eq!(
+            eval("(ex-info \"a\" {:b 1} (ex-info \"c\" nil))").map(|value| value.to_string()),
+            Ok("(ex-info \"a\" {:b 1} (ex-info \"c\" {}))".into())
+        );
+    }
+
+    #[test]
+    fn test_try_finally() {
+        let env = Env::new();
+        let result = env.eval_str(
+            "_test_.tiny",
+            "
+(def log [])
+(defn note [x] (def log [log x]))
+[(try (note :body) 1 (finally (note :finally)))
+ (try (throw 1) (catch [e Int] (note :catch) e) (finally (note :finally)))]",
+        );
+        assert_eq!(result, eval("[1 1]"));
+        assert_eq!(
+            env.lookup("log").map(|log| log.to_string()),
+            Some("[[[[[] :body] :finally] :catch] :finally]".into())
+        );
+        let err = env
+            .eval_str(
+                "_test_.tiny",
+                "(try (throw :x) (catch [e String] 1) (finally (note :f)))",
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.value,
+            Some(Box::new(Value::Keyword("".into(), "x".into())))
+        );
+        assert_eq!(
+            env.lookup("log")
+                .map(|log| log.to_string().ends_with(":f]")),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_throw_propagation() {
+        let err = Env::new()
+            .eval_str(
+                "_test_.tiny",
+                "
+(defn check [x] (if (< x 0) (throw (ex-info \"negative\" {:x x})) x))
+(defn run [xs] (check (- 0 xs)))
+(run 5)",
+            )
+            .unwrap_err();
+        assert_eq!(err.message, "negative");
+        assert_eq!((err.start, err.end), (29, 64));
+        assert_eq!(
+            err.value.map(|value| value.to_string()),
+            Some("(ex-info \"negative\" {:x -5})".into())
+        );
+        assert_eq!(
+            err.trace,
+            vec![
+                TraceFrame {
+                    function: "check".into(),
+                    name: "_test_.tiny".into(),
+                    start: 84,
+                    end: 100,
+                },
+                TraceFrame {
+                    function: "run".into(),
+                    name: "_test_.tiny".into(),
+                    start: 102,
+                    end: 109,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_try_errors() {
+        assert_eq!(
+            eval("(throw)"),
+            error(0, 7, "throw expects exactly one argument")
+        );
+        assert_eq!(
+            eval("(try 1 (finally 2) (catch [e Exception] 3))"),
+            error(19, 42, "finally clause must be last in try")
+        );
+        assert_eq!(
+            eval("(try 1 (catch [e Exception] 3) 4)"),
+            error(
+                31,
+                32,
+                "only catch or finally clauses can follow catch in try"
+            )
+        );
+        assert_eq!(
+            eval("(catch [e Exception] 3)"),
+            error(0, 23, "catch used outside of try")
+        );
+        assert_eq!(
+            eval("(loop [i 0] (try (recur 1)))"),
+            error(17, 26, "can only recur from tail position")
+        );
+    }
+
+    #[test]
+    fn test_atoms() {
+        assert_eq!(
+            eval(
+                "
+(def state (atom 0))
+[(swap! state inc) (swap! state + 10 5) @state (reset! state :x) (deref state)]"
+            ),
+            eval("[1 16 16 :x :x]")
+        );
+        assert_eq!(
+            eval("(let [a (atom 1)] [(compare-and-set! a 2 3) (compare-and-set! a 1 3) @a])"),
+            eval("[false true 3]")
+        );
+        assert_eq!(
+            eval("(str (atom [1 \"a\"]))"),
+            Ok(Value::String("#<atom [1 \"a\"]>".into()))
+        );
+        assert_eq!(eval("(let [a (atom 1)] (= a a))"), Ok(Value::Bool(true)));
+        assert_eq!(eval("(= (atom 1) (atom 1))"), Ok(Value::Bool(false)));
+        assert_eq!(
+            eval("(deref 1)"),
+            error(0, 9, "deref expects an atom, got 1")
+        );
+    }
+
+    #[test]
+    fn test_atom_validators() {
+        assert_eq!(
+            eval("(def a (atom 1 :validator (fn [v] (< v 3)))) (swap! a inc) (swap! a inc)"),
+            error(59, 72, "invalid reference state: 3")
+        );
+        assert_eq!(
+            eval(
+                "(def a (atom 1 :validator (fn [v] (< v 3))))
+                 (try (reset! a 5) (catch [e Exception] @a))"
+            ),
+            Ok(Value::Int(1))
+        );
+        assert_eq!(
+            eval("(atom 5 :validator (fn [v] (< v 3)))"),
+            error(0, 36, "invalid reference state: 5")
+        );
+        assert_eq!(
+            eval("(def a (atom 5)) (set-validator! a zero?)"),
+            error(17, 41, "invalid reference state: 5")
+        );
+        assert_eq!(
+            eval(
+                "(def a (atom 5)) (set-validator! a nil) (reset! a 0) (set-validator! a zero?) @a"
+            ),
+            Ok(Value::Int(0))
+        );
+    }
+
+    #[test]
+    fn test_atom_watches() {
+        assert_eq!(
+            eval(
+                "
+(def log (atom []))
+(def state (atom 0))
+(add-watch state :log (fn [k r old new] (swap! log (fn [l] [l k old new]))))
+(swap! state inc)
+(reset! state 10)
+(remove-watch state :log)
+(swap! state inc)
+@log"
+            ),
+            eval("[[[] :log 0 1] :log 1 10]")
+        );
+    }
+
+    #[test]
+    fn test_concurrent_swap() {
+        let env = Env::new();
+        env.eval_str("_test_.tiny", "(def counter (atom 0)) (def seen (atom 0))")
+            .unwrap();
+        env.eval_str(
+            "_test_.tiny",
+            "(add-watch counter :seen (fn [k r old new] (swap! seen inc)))",
+        )
+        .unwrap();
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let env = env.clone();
+                std::thread::spawn(move || {
+                    env.eval_str("_test_.tiny", "(dotimes [i 500] (swap! counter inc))")
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+        assert_eq!(
+            env.eval_str("_test_.tiny", "[@counter @seen]"),
+            eval("[4000 4000]")
+        );
+    }
+    #[test]
+    fn test_go_blocks() {
+        assert_eq!(eval("(<! (go (+ 1 2)))"), Ok(Value::Int(3)));
+        assert_eq!(eval("(<! (go nil))"), Ok(Value::Nil));
+        assert_eq!(
+            eval(
+                "
+(let [state (atom 0)
+      done (chan 100)]
+  (dotimes [_ 100]
+    (go (swap! state inc) (>! done true)))
+  (dotimes [_ 100] (<! done))
+  @state)"
+            ),
+            Ok(Value::Int(100))
+        );
+        assert_eq!(
+            eval(
+                "
+(let [state (atom 0)]
+  (dotimes [_ 1000] (go (swap! state inc)))
+  (while (< @state 1000))
+  @state)"
+            ),
+            Ok(Value::Int(1000))
+        );
+    }
+
+    #[test]
+    fn test_stack_depth() {
+        let deep = |n| {
+            let program = format!("(defn f [n] (if (= n 0) 0 (+ 1 (f (- n 1))))) (f {n})");
+            eval(&program)
+        };
+        let results = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || [deep(MAX_DEPTH - 1), deep(MAX_DEPTH), deep(100)])
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(results[0], Ok(Value::Int(MAX_DEPTH as i64 - 1)));
+        assert_eq!(
+            results[1].as_ref().unwrap_err().message,
+            "stack depth exceeded"
+        );
+        assert_eq!(results[2], Ok(Value::Int(100)));
+    }
+
+    #[test]
+    fn test_macro_expansion_depth() {
+        let result = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(|| eval("(defmacro m [] (quote (m))) (m)"))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(result.unwrap_err().message, "macro expansion too deep in m");
+    }
+
+    #[test]
+    fn test_go_blocks_park() {
+        // Parked go blocks wait on their channels, not on worker threads.
+        assert_eq!(
+            eval(
+                "
+(let [cs (vec (map (fn [_] (chan)) (range 20000)))
+      out (chan 20000)]
+  (doseq [c cs] (go (>! out (<! c))))
+  (doseq [c cs] (>! c 1))
+  (loop [n 0 sum 0]
+    (if (= n 20000) sum (recur (inc n) (+ sum (<! out))))))"
+            ),
+            Ok(Value::Int(20000))
+        );
+        assert_eq!(
+            eval("(let [c (chan)] (go (alts! [[c 1]])) (= (alts! [c]) [1 c]))"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            eval(
+                "
+(let [c (chan) out (chan 1)]
+  (go (>! out (try
+                (loop [acc [] n (<! c)]
+                  (case n
+                    0 (throw (ex-info \"done\" {:acc acc}))
+                    (recur (match n
+                             x :when (odd? x) (conj acc (<! c))
+                             _ acc)
+                           (<! c))))
+                (catch [e ExceptionInfo] (:acc (ex-data e)))
+                (finally (<! c)))))
+  (doseq [n [1 :a 2 3 :b 0 :end]] (>! c n))
+  (<! out))"
+            ),
+            eval("[:a :b]")
+        );
+        assert_eq!(
+            eval("(let [c (chan 1)] (>! c 0) (<! (go (/ 1 (<! c)))))"),
+            Ok(Value::Nil)
+        );
+    }
+
+    #[test]
+    fn test_channels() {
+        assert_eq!(
+            eval(
+                "
+(let [c (chan)
+      out (chan 10)]
+  (go (loop [i 0]
+        (when (< i 3)
+          (>! c i)
+          (recur (inc i))))
+      (close! c))
+  (loop [acc []]
+    (let [v (<! c)]
+      (if (nil? v) acc (recur [acc v])))))"
+            ),
+            eval("[[[[] 0] 1] 2]")
+        );
+        assert_eq!(
+            eval("(let [c (chan 1)] [(>! c 1) (close! c) (>! c 2) (<! c) (<! c)])"),
+            eval("[true nil false 1 nil]")
+        );
+        assert_eq!(
+            eval("(let [a (chan 1) b (chan 1)] (>! b :x) (= (alts! [a b]) [:x b]))"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            eval("(let [a (chan 1)] (= (alts! [[a 1]]) [true a]))"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(eval("(alts! [(chan)] :default 0)"), eval("[0 :default]"));
+        assert_eq!(
+            eval("(let [t (timeout 10)] (= (alts! [(chan) t]) [nil t]))"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(eval("(<! (timeout 5))"), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn test_channel_errors() {
+        assert_eq!(
+            eval("(>! (chan 1) nil)"),
+            error(0, 17, "can't put nil on a channel")
+        );
+        assert_eq!(eval("(<! 1)"), error(0, 6, "<! expects a channel, got 1"));
+        assert_eq!(
+            eval("(chan -1)"),
+            error(0, 9, "chan expects a buffer size, got -1")
+        );
+        assert_eq!(
+            eval("(alts! (chan))"),
+            error(0, 14, "alts! expects a vector of operations, got #<chan>")
+        );
+        assert_eq!(
+            eval("(alts! [] :default)"),
+            error(0, 19, "wrong number of args (2) passed to alts!")
+        );
+    }
+    #[test]
+    fn test_lazy_seqs() {
+        assert_eq!(show("(take 3 (range))"), Ok("(0 1 2)".into()));
+        assert_eq!(
+            show("(defn nat [n] (lazy-seq (cons n (nat (inc n))))) (take 4 (drop 2 (nat 0)))"),
+            Ok("(2 3 4 5)".into())
+        );
+        assert_eq!(
+            eval(
+                "
+(def calls (atom 0))
+(def s (lazy-seq (swap! calls inc) [1 2 3]))
+[(realized? s) (first s) (first s) (realized? s) @calls]"
+            ),
+            eval("[false 1 1 true 1]")
+        );
+        assert_eq!(
+            show("[(first (range 5)) (next (range 1)) (seq (range 0)) (rest \"ab\")]"),
+            Ok("[0 nil nil (\"b\")]".into())
+        );
+        assert_eq!(
+            eval("[(= (range 3) [0 1 2]) (= (range 3) '(0 1 2)) (= [(range 2)] [[0 1]])]"),
+            eval("[true true true]")
+        );
+        assert_eq!(
+            eval("[(not= (map inc [1 2]) [2 3]) (not= {:a 1 :b 2} {:b 2 :a 1}) (not= (range 2) [0 2])]"),
+            eval("[false false true]")
+        );
+        assert_eq!(show("(cons 1 (range 2 4))"), Ok("(1 2 3)".into()));
+        assert_eq!(show("(rest (range 1))"), Ok("()".into()));
+        assert_eq!(
+            eval("(first (drop 100000 (range)))"),
+            Ok(Value::Int(100000))
+        );
+    }
+
+    #[test]
+    fn test_destructure_lazy_seqs() {
+        assert_eq!(
+            show("(let [[a b & more] (range)] [a b (take 2 more)])"),
+            Ok("[0 1 (2 3)]".into())
+        );
+        assert_eq!(
+            eval("(match (range 3) [a b] :two [a b c] (+ a b c))"),
+            Ok(Value::Int(3))
+        );
+        assert_eq!(
+            show("(match (range) [a & r] (take 2 r))"),
+            Ok("(1 2)".into())
+        );
+        assert_eq!(show("`(0 ~@(range 1 3))"), Ok("(0 1 2)".into()));
+    }
+
+    #[test]
+    fn test_lazy_seq_errors() {
+        assert_eq!(
+            eval("(first 1)"),
+            error(0, 9, "don't know how to create a seq from 1")
+        );
+        assert_eq!(
+            eval("(range 1.5)"),
+            error(0, 11, "range expects integers, got 1.5")
+        );
+        assert_eq!(
+            eval("(def s (lazy-seq (cons 1 (throw (ex-info \"boom\" {}))))) (first s)"),
+            Err(EvalError {
+                name: "_test_.tiny".into(),
+                start: 7,
+                end: 54,
+                kind: ErrorKind::Other,
+                message: "boom".into(),
+                value: eval("(ex-info \"boom\" {})").ok().map(Box::new),
+                trace: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_str() {
+        let check = |content: &str| -> Vec<(usize, usize, String)> {
+            (Env::new()
+                .check_str("_test_.tiny", content)
+                .errors
+                .into_iter())
+            .map(|err| (err.start, err.end, err.message))
+            .collect()
+        };
+        assert_eq!(
+            check("(defn f [] (g 1)) (defn g [x] (h x)) (def y (println \"hi\"))"),
+            [(31, 32, "unable to resolve symbol: h".into())]
+        );
+        assert_eq!(
+            check("(defmacro twice [x] `(do ~x ~x)) (twice (launch))"),
+            [(33, 49, "unable to resolve symbol: launch".into())]
+        );
+        assert_eq!(
+            check("(defn f [] (g)) (let [x] x) (f"),
+            [
+                (12, 13, "unable to resolve symbol: g".into()),
+                (
+                    16,
+                    27,
+                    "let expects a vector with an even number of forms".into()
+                ),
+                (28, 30, "unclosed `(`".into())
+            ]
+        );
+        assert_eq!(Env::new().check_str("_test_.tiny", "1 (g) 2").forms, 3);
+    }
+
+    #[test]
+    fn test_output() {
+        let env = Env::new();
+        let printed = Arc::new(Mutex::new(vec![]));
+        let sink = printed.clone();
+        env.set_output(Some(Arc::new(move |stream, text: &str| {
+            sink.lock().unwrap().push((stream, text.to_string()))
+        })));
+        env.eval_str("_test_.tiny", "(print 1 2) (println \"three\") (doc inc)")
+            .unwrap();
+        let doc = format!("tiny.core/inc\n  {}\n", env.doc("inc").unwrap());
+        assert_eq!(
+            *printed.lock().unwrap(),
+            [
+                (OutputStream::Out, "1 2".into()),
+                (OutputStream::Out, "three\n".into()),
+                (OutputStream::Out, doc),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_go_block_errors_are_printed() {
+        let env = Env::new();
+        let printed = Arc::new(Mutex::new(vec![]));
+        let sink = printed.clone();
+        env.set_output(Some(Arc::new(move |stream, text: &str| {
+            sink.lock().unwrap().push((stream, text.to_string()))
+        })));
+        env.eval_str("_test_.tiny", "(<! (go (/ 1 0)))").unwrap();
+        assert_eq!(
+            *printed.lock().unwrap(),
+            [(
+                OutputStream::Err,
+                "_test_.tiny:4: error: divide by zero\n".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let env = Env::new();
+        env.eval_str("_test_.tiny", "(defn spin [n] (recur (inc n)))")
+            .unwrap();
+        let spinning = env.clone();
+        let thread = std::thread::spawn(move || spinning.eval_str("_test_.tiny", "(spin 0)"));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        env.interrupt();
+        let err = thread.join().unwrap().unwrap_err();
+        assert_eq!(err.message, "interrupted");
+        assert!(env.eval_str("_test_.tiny", "1").is_err());
+        env.resume();
+        assert_eq!(env.eval_str("_test_.tiny", "1"), Ok(Value::Int(1)));
+        // Blocking channel operations are woken.
+        let waiting = env.clone();
+        let thread = std::thread::spawn(move || waiting.eval_str("_test_.tiny", "(<! (chan))"));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        env.interrupt();
+        assert_eq!(thread.join().unwrap().unwrap_err().message, "interrupted");
+    }
+}
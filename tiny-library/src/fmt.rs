@@ -0,0 +1,136 @@
+//! `tiny fmt`: lays out source in the canonical style, keeping line breaks
+//! and comments where they were written.
+
+use reader::{tokens, ReadError, Reader, TokenKind};
+
+/// How many arguments come before the body of forms headed by `head`, for
+/// forms with a body: their continued lines are indented by two unless
+/// more arguments than that are on the first line.
+fn body_args(head: &str) -> Option<usize> {
+    match head {
+        "fn" => Some(usize::MAX),
+        _ if head.starts_with("def") => Some(usize::MAX),
+        "do" | "try" | "finally" | "go" | "cond" | "import" => Some(0),
+        "let" | "loop" => Some(2),
+        "catch" | "doseq" | "dotimes" | "for" | "while" | "case" | "match" | "ns" | "binding" => {
+            Some(1)
+        }
+        _ if head.starts_with("if") || head.starts_with("when") || head.starts_with("with-") => {
+            Some(1)
+        }
+        _ => None,
+    }
+}
+
+/// A bracket opened but not yet closed.
+struct Frame {
+    bracket: char,
+    /// The column of the bracket.
+    col: usize,
+    /// The line the bracket is on.
+    line: usize,
+    head: Option<String>,
+    items: usize,
+    /// The column of the second item, if it is on the line of the bracket.
+    first_arg: Option<usize>,
+    /// How many items after the first are on the line of the bracket.
+    args_on_line: usize,
+}
+
+impl Frame {
+    /// The column of the lines continuing this form.
+    fn indent(&self) -> usize {
+        let body_args = self.head.as_deref().and_then(body_args);
+        match (self.bracket, body_args, self.first_arg) {
+            ('(', Some(args), _) if self.args_on_line <= args => self.col + 2,
+            ('(', _, Some(col)) => col,
+            _ => self.col + 1,
+        }
+    }
+}
+
+/// Formats `content`, which must read without errors: one space between
+/// forms on a line, closing brackets kept on the line of the last item,
+/// continued lines indented by their enclosing form, runs of blank lines
+/// shortened to one and a newline at the end.
+pub fn format(name: &str, content: &str) -> Result<String, ReadError> {
+    let mut reader = Reader::new(name, content);
+    while let Some(form) = reader.read() {
+        form?;
+    }
+    let mut out = String::with_capacity(content.len());
+    let mut stack: Vec<Frame> = vec![];
+    let (mut line, mut col) = (0, 0);
+    let mut prev: Option<(TokenKind, usize)> = None;
+    for token in tokens(content) {
+        let text = &content[token.start..token.end];
+        let newlines = match prev {
+            Some((_, end)) => content[end..token.start].matches('\n').count(),
+            None => 0,
+        };
+        let after_comment = matches!(prev, Some((TokenKind::Comment, _)));
+        if prev.is_some() && (after_comment || (newlines > 0 && token.kind != TokenKind::Close)) {
+            out.push_str(if newlines > 1 { "\n\n" } else { "\n" });
+            line += 1;
+            let indent = stack.last().map_or(0, Frame::indent);
+            out.push_str(&" ".repeat(indent));
+            col = indent;
+        } else if let (TokenKind::Comment, Some((_, end))) = (token.kind, prev) {
+            // Comments after code keep their alignment.
+            let gap = &content[end..token.start];
+            out.push_str(gap);
+            col += gap.chars().count();
+        } else if !matches!(
+            prev,
+            None | Some((TokenKind::Open, _)) | Some((TokenKind::Prefix, _))
+        ) && token.kind != TokenKind::Close
+            // Touching tokens such as `#` and `(` stay together.
+            && prev.is_some_and(|(_, end)| end < token.start)
+        {
+            out.push(' ');
+            col += 1;
+        }
+        let starts_item = !matches!(token.kind, TokenKind::Close | TokenKind::Comment)
+            && !matches!(prev, Some((TokenKind::Prefix, _)));
+        if starts_item {
+            if let Some(frame) = stack.last_mut() {
+                frame.items += 1;
+                if frame.items == 1 && token.kind == TokenKind::Symbol {
+                    frame.head = Some(text.into());
+                }
+                if frame.items >= 2 && frame.line == line {
+                    frame.first_arg = frame.first_arg.or(Some(col));
+                    frame.args_on_line = frame.items - 1;
+                }
+            }
+        }
+        match token.kind {
+            TokenKind::Open => stack.push(Frame {
+                bracket: text.chars().next().unwrap_or('('),
+                col,
+                line,
+                head: None,
+                items: 0,
+                first_arg: None,
+                args_on_line: 0,
+            }),
+            TokenKind::Close => {
+                stack.pop();
+            }
+            _ => {}
+        }
+        out.push_str(text);
+        match text.rfind('\n') {
+            Some(i) => {
+                line += text.matches('\n').count();
+                col = text[i + 1..].chars().count();
+            }
+            None => col += text.chars().count(),
+        }
+        prev = Some((token.kind, token.end));
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Ok(out)
+}
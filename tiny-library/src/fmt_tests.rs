@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use fmt::format;
+
+    fn fmt(content: &str) -> String {
+        format("_test_.tiny", content).unwrap()
+    }
+
+    #[test]
+    fn test_spacing() {
+        assert_eq!(fmt("( + 1   2 )"), "(+ 1 2)\n");
+        assert_eq!(fmt("[ 1 2\t3 ] { :a  1 }"), "[1 2 3] {:a 1}\n");
+        assert_eq!(fmt("' ( a ~@ b @ c)"), "'(a ~@b @c)\n");
+        assert_eq!(fmt("(f \"a  b\"   #\"x  y\")"), "(f \"a  b\" #\"x  y\")\n");
+        assert_eq!(fmt(""), "");
+    }
+
+    #[test]
+    fn test_line_breaks() {
+        assert_eq!(
+            fmt("(def x 1)\n\n\n\n(def y 2)   \n"),
+            "(def x 1)\n\n(def y 2)\n"
+        );
+        assert_eq!(fmt("(f a\n)\n"), "(f a)\n");
+        assert_eq!(fmt("(f a ; note\n)"), "(f a ; note\n   )\n");
+        assert_eq!(fmt("; top\n(f)"), "; top\n(f)\n");
+    }
+
+    #[test]
+    fn test_indentation() {
+        assert_eq!(
+            fmt("(defn f [x]\n(let [y 1\nz 2]\n(+ x\ny z)))"),
+            "(defn f [x]\n  (let [y 1\n        z 2]\n    (+ x\n       y z)))\n"
+        );
+        assert_eq!(
+            fmt("(when-let [x 1]\nx)\n(f\na)\n{:a 1\n:b 2}"),
+            "(when-let [x 1]\n  x)\n(f\n a)\n{:a 1\n :b 2}\n"
+        );
+        assert_eq!(fmt("(f \"a\nb\" c\nd)"), "(f \"a\nb\" c\n   d)\n");
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let once = fmt(include_str!("core.tiny"));
+        assert_eq!(fmt(&once), once);
+    }
+
+    #[test]
+    fn test_refuses_unreadable_input() {
+        let err = format("_test_.tiny", "(f [a)").unwrap_err();
+        assert_eq!(err.start, 5);
+    }
+}
@@ -0,0 +1,492 @@
+//! The `std.io` namespace: files, line readers and directories.
+//!
+//! Every function here needs a capability of the environment, so embedders
+//! can run code that must not touch the file system.
+
+extern crate glob;
+use crate::core::{arity, to_str, Doc};
+use crate::eval::{Env, EvalError};
+use crate::seq::{self, Chunk, CHUNK_SIZE};
+use crate::value::{NativeFn, Value};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+
+pub const NATIVES: &[(&str, NativeFn, Doc)] = &[
+    ("slurp", slurp, "Returns the contents of the file at path."),
+    (
+        "spit",
+        spit,
+        "Writes the text of x to the file at path, after what it has if :append is true.",
+    ),
+    (
+        "reader",
+        reader,
+        "Opens the file at path for reading lines.",
+    ),
+    (
+        "writer",
+        writer,
+        "Opens the file at path for writing, after what it has if :append is true.",
+    ),
+    (
+        "read-line",
+        read_line,
+        "Returns the next line of a reader without its end, or nil at the end of the file.",
+    ),
+    (
+        "line-seq",
+        line_seq,
+        "Returns a lazy sequence of the remaining lines of a reader.",
+    ),
+    (
+        "read-lines",
+        read_lines,
+        "Returns a lazy sequence of the lines of the file at path, closing it after the last.",
+    ),
+    ("write", write, "Writes the text of args to a writer."),
+    ("flush", flush, "Writes what a writer buffers to its file."),
+    (
+        "close",
+        close,
+        "Closes a reader or writer, flushing a writer first.",
+    ),
+    (
+        "list-dir",
+        list_dir,
+        "Returns the sorted paths of the entries of the directory at path.",
+    ),
+    (
+        "glob",
+        glob,
+        "Returns the sorted paths matching a pattern of *, ?, [abc] and ** for any directories.",
+    ),
+    (
+        "exists?",
+        exists,
+        "Returns true if there is a file or directory at path.",
+    ),
+    ("dir?", is_dir, "Returns true if path is a directory."),
+    ("file?", is_file, "Returns true if path is a file."),
+    (
+        "make-dirs",
+        make_dirs,
+        "Creates the directory at path and any missing parents.",
+    ),
+    (
+        "delete",
+        delete,
+        "Deletes the file or empty directory at path.",
+    ),
+];
+
+/// What the functions of `std.io` and `std.os` may do. A function whose
+/// capability is disabled fails when called.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Capabilities {
+    /// Reading files and directories.
+    pub read: bool,
+    /// Creating, writing and deleting files and directories.
+    pub write: bool,
+    /// Reading and setting environment variables.
+    pub env: bool,
+    /// Running subprocesses and exiting.
+    pub process: bool,
+}
+
+impl Capabilities {
+    pub const ALL: Capabilities = Capabilities {
+        read: true,
+        write: true,
+        env: true,
+        process: true,
+    };
+
+    pub const NONE: Capabilities = Capabilities {
+        read: false,
+        write: false,
+        env: false,
+        process: false,
+    };
+}
+
+/// The stream printed text goes to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputStream {
+    Out,
+    Err,
+}
+
+/// Receives what an environment prints, instead of stdout and stderr.
+pub type Output = Arc<dyn Fn(OutputStream, &str) + Send + Sync>;
+
+/// Fails unless `allowed`, the capability named `capability` that `name`
+/// needs.
+pub fn require(name: &str, capability: &str, allowed: bool) -> Result<(), EvalError> {
+    if allowed {
+        Ok(())
+    } else {
+        Err(EvalError::new(format!(
+            "{name} needs the {capability} capability, which is disabled"
+        )))
+    }
+}
+
+/// An open file: a reader of lines or a writer, until it is closed.
+pub struct Handle {
+    path: String,
+    stream: Mutex<Stream>,
+}
+
+enum Stream {
+    Reader(BufReader<File>),
+    Writer(BufWriter<File>),
+    Closed,
+}
+
+impl Handle {
+    /// The next line without its `\n` or `\r\n`, or `None` at the end.
+    pub fn read_line(&self) -> Result<Option<String>, EvalError> {
+        let mut stream = self.stream.lock().unwrap();
+        let reader = match &mut *stream {
+            Stream::Reader(reader) => reader,
+            _ => return Err(self.not_open("reading")),
+        };
+        let mut line = String::new();
+        if reader
+            .read_line(&mut line)
+            .map_err(|err| self.error("read", err))?
+            == 0
+        {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    pub fn write(&self, text: &str) -> Result<(), EvalError> {
+        match &mut *self.stream.lock().unwrap() {
+            Stream::Writer(writer) => {
+                (writer.write_all(text.as_bytes())).map_err(|err| self.error("write", err))
+            }
+            _ => Err(self.not_open("writing")),
+        }
+    }
+
+    pub fn flush(&self) -> Result<(), EvalError> {
+        match &mut *self.stream.lock().unwrap() {
+            Stream::Writer(writer) => writer.flush().map_err(|err| self.error("write", err)),
+            _ => Err(self.not_open("writing")),
+        }
+    }
+
+    /// Closes the file, flushing a writer first. Closing twice does nothing.
+    pub fn close(&self) -> Result<(), EvalError> {
+        let mut stream = self.stream.lock().unwrap();
+        if let Stream::Writer(writer) = &mut *stream {
+            writer.flush().map_err(|err| self.error("write", err))?;
+        }
+        *stream = Stream::Closed;
+        Ok(())
+    }
+
+    fn error(&self, action: &str, err: std::io::Error) -> EvalError {
+        EvalError::new(format!("could not {action} {}: {err}", self.path))
+    }
+
+    fn not_open(&self, purpose: &str) -> EvalError {
+        EvalError::new(format!("{} is not open for {purpose}", self.path))
+    }
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match *self.stream.lock().unwrap() {
+            Stream::Reader(_) => "reader",
+            Stream::Writer(_) => "writer",
+            Stream::Closed => "closed",
+        };
+        write!(f, "#<{kind} {}>", self.path)
+    }
+}
+
+impl PartialEq for Handle {
+    fn eq(&self, other: &Handle) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Handle {
+    fn partial_cmp(&self, other: &Handle) -> Option<Ordering> {
+        if self == other {
+            Some(Ordering::Equal)
+        } else {
+            None
+        }
+    }
+}
+
+fn path_arg<'a>(name: &str, x: &'a Value) -> Result<&'a str, EvalError> {
+    match x {
+        Value::String(path) => Ok(path),
+        x => Err(EvalError::new(format!("{name} expects a path, got {x}"))),
+    }
+}
+
+fn handle_arg<'a>(name: &str, x: &'a Value) -> Result<&'a Arc<Handle>, EvalError> {
+    match x {
+        Value::Handle(handle) => Ok(handle),
+        x => Err(EvalError::new(format!(
+            "{name} expects a reader or writer, got {x}"
+        ))),
+    }
+}
+
+/// Whether `options`, keyword and value pairs, set `:append` to true.
+fn append_option(name: &str, options: &[Value]) -> Result<bool, EvalError> {
+    match options {
+        [] => Ok(false),
+        [Value::Keyword(_, option), value] if option == "append" => Ok(value.is_truthy()),
+        options => Err(EvalError::new(format!(
+            "{name} expects :append as its only option, got {}",
+            Value::ListParen(options.to_vec())
+        ))),
+    }
+}
+
+fn open_error(path: &str, err: std::io::Error) -> EvalError {
+    EvalError::new(format!("could not open {path}: {err}"))
+}
+
+fn open_reader(path: &str) -> Result<Arc<Handle>, EvalError> {
+    let file = File::open(path).map_err(|err| open_error(path, err))?;
+    Ok(Arc::new(Handle {
+        path: path.into(),
+        stream: Mutex::new(Stream::Reader(BufReader::new(file))),
+    }))
+}
+
+/// The lines left in `handle`, read a chunk at a time, closing it after the
+/// last if `close` is set.
+fn lines(handle: Arc<Handle>, close: bool) -> Value {
+    seq::lazy(move || {
+        let mut lines = vec![];
+        while lines.len() < CHUNK_SIZE {
+            match handle.read_line()? {
+                Some(line) => lines.push(Value::String(line)),
+                None => break,
+            }
+        }
+        let more = if lines.len() < CHUNK_SIZE {
+            if close {
+                handle.close()?;
+            }
+            Value::Nil
+        } else {
+            self::lines(handle, close)
+        };
+        Ok(seq::from_chunk(Chunk::new(lines, more)))
+    })
+}
+
+fn slurp(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("slurp", "read", env.capabilities().read)?;
+    match &args[..] {
+        [path] => {
+            let path = path_arg("slurp", path)?;
+            fs::read_to_string(path)
+                .map(Value::String)
+                .map_err(|err| EvalError::new(format!("could not read {path}: {err}")))
+        }
+        _ => Err(arity("slurp", args.len())),
+    }
+}
+
+fn spit(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("spit", "write", env.capabilities().write)?;
+    let (path, content, options) = match &args[..] {
+        [path, content, options @ ..] => (path_arg("spit", path)?, content, options),
+        _ => return Err(arity("spit", args.len())),
+    };
+    let append = append_option("spit", options)?;
+    (OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append))
+    .open(path)
+    .and_then(|mut file| file.write_all(to_str(content).as_bytes()))
+    .map_err(|err| EvalError::new(format!("could not write {path}: {err}")))?;
+    Ok(Value::Nil)
+}
+
+fn reader(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("reader", "read", env.capabilities().read)?;
+    match &args[..] {
+        [path] => Ok(Value::Handle(open_reader(path_arg("reader", path)?)?)),
+        _ => Err(arity("reader", args.len())),
+    }
+}
+
+fn writer(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("writer", "write", env.capabilities().write)?;
+    let (path, options) = match &args[..] {
+        [path, options @ ..] => (path_arg("writer", path)?, options),
+        _ => return Err(arity("writer", args.len())),
+    };
+    let append = append_option("writer", options)?;
+    let file = (OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append))
+    .open(path)
+    .map_err(|err| open_error(path, err))?;
+    Ok(Value::Handle(Arc::new(Handle {
+        path: path.into(),
+        stream: Mutex::new(Stream::Writer(BufWriter::new(file))),
+    })))
+}
+
+fn read_line(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [reader] => Ok(match handle_arg("read-line", reader)?.read_line()? {
+            Some(line) => Value::String(line),
+            None => Value::Nil,
+        }),
+        _ => Err(arity("read-line", args.len())),
+    }
+}
+
+fn line_seq(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [reader] => Ok(lines(handle_arg("line-seq", reader)?.clone(), false)),
+        _ => Err(arity("line-seq", args.len())),
+    }
+}
+
+fn read_lines(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("read-lines", "read", env.capabilities().read)?;
+    match &args[..] {
+        [path] => Ok(lines(open_reader(path_arg("read-lines", path)?)?, true)),
+        _ => Err(arity("read-lines", args.len())),
+    }
+}
+
+fn write(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [writer, args @ ..] => {
+            let writer = handle_arg("write", writer)?;
+            for arg in args {
+                writer.write(&to_str(arg))?;
+            }
+            Ok(Value::Nil)
+        }
+        [] => Err(arity("write", 0)),
+    }
+}
+
+fn flush(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [writer] => handle_arg("flush", writer)?.flush().map(|_| Value::Nil),
+        _ => Err(arity("flush", args.len())),
+    }
+}
+
+fn close(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [handle] => handle_arg("close", handle)?.close().map(|_| Value::Nil),
+        _ => Err(arity("close", args.len())),
+    }
+}
+
+fn sorted_paths(mut paths: Vec<String>) -> Value {
+    paths.sort();
+    Value::ListBracket(paths.into_iter().map(Value::String).collect())
+}
+
+fn list_dir(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("list-dir", "read", env.capabilities().read)?;
+    let path = match &args[..] {
+        [path] => path_arg("list-dir", path)?,
+        _ => return Err(arity("list-dir", args.len())),
+    };
+    let error = |err: std::io::Error| EvalError::new(format!("could not list {path}: {err}"));
+    let mut paths = vec![];
+    for entry in fs::read_dir(path).map_err(error)? {
+        paths.push(entry.map_err(error)?.path().display().to_string());
+    }
+    Ok(sorted_paths(paths))
+}
+
+fn glob(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("glob", "read", env.capabilities().read)?;
+    let pattern = match &args[..] {
+        [pattern] => path_arg("glob", pattern)?,
+        _ => return Err(arity("glob", args.len())),
+    };
+    let entries = glob::glob(pattern)
+        .map_err(|err| EvalError::new(format!("invalid glob pattern {pattern:?}: {err}")))?;
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry.map_err(|err| EvalError::new(format!("could not read {err}")))?;
+        paths.push(path.display().to_string());
+    }
+    Ok(sorted_paths(paths))
+}
+
+/// Defines a native testing the file system at a path.
+macro_rules! path_test {
+    ($fn_name:ident, $name:expr, |$path:ident| $test:expr) => {
+        fn $fn_name(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+            require($name, "read", env.capabilities().read)?;
+            match &args[..] {
+                [path] => {
+                    let $path = std::path::Path::new(path_arg($name, path)?);
+                    Ok(Value::Bool($test))
+                }
+                _ => Err(arity($name, args.len())),
+            }
+        }
+    };
+}
+
+path_test!(exists, "exists?", |path| path.exists());
+path_test!(is_dir, "dir?", |path| path.is_dir());
+path_test!(is_file, "file?", |path| path.is_file());
+
+fn make_dirs(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("make-dirs", "write", env.capabilities().write)?;
+    match &args[..] {
+        [path] => {
+            let path = path_arg("make-dirs", path)?;
+            fs::create_dir_all(path)
+                .map(|_| Value::Nil)
+                .map_err(|err| EvalError::new(format!("could not create {path}: {err}")))
+        }
+        _ => Err(arity("make-dirs", args.len())),
+    }
+}
+
+fn delete(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("delete", "write", env.capabilities().write)?;
+    let path = match &args[..] {
+        [path] => path_arg("delete", path)?,
+        _ => return Err(arity("delete", args.len())),
+    };
+    let result = if std::path::Path::new(path).is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    };
+    result
+        .map(|_| Value::Nil)
+        .map_err(|err| EvalError::new(format!("could not delete {path}: {err}")))
+}
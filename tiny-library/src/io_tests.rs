@@ -0,0 +1,226 @@
+#[cfg(test)]
+mod tests {
+    use eval::Env;
+    use io::Capabilities;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// A fresh directory for the test `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tiny-io-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Evaluates `content` in `env` with `std.io` and `std.os` imported,
+    /// `$dir` standing for `dir`, and prints the result or the error message.
+    fn show_in(env: &Env, dir: &Path, content: &str) -> String {
+        let content = format!(
+            "(import [std.io :as io] [std.os :as os]) {}",
+            content.replace("$dir", &dir.display().to_string())
+        );
+        match env.eval_str("_test_.tiny", &content) {
+            Ok(value) => value.to_string(),
+            Err(err) => format!("error: {}", err.message),
+        }
+    }
+
+    fn show(dir: &Path, content: &str) -> String {
+        show_in(&Env::new(), dir, content)
+    }
+
+    #[test]
+    fn test_slurp_spit() {
+        let dir = temp_dir("slurp");
+        assert_eq!(show(&dir, "(io/spit \"$dir/a.txt\" \"héllo\")"), "nil");
+        assert_eq!(
+            show(&dir, "(io/spit \"$dir/a.txt\" 42 :append true)"),
+            "nil"
+        );
+        assert_eq!(show(&dir, "(io/slurp \"$dir/a.txt\")"), "\"héllo42\"");
+        assert_eq!(
+            show(
+                &dir,
+                "(io/spit \"$dir/a.txt\" \"new\") (io/slurp \"$dir/a.txt\")"
+            ),
+            "\"new\""
+        );
+        assert!(show(&dir, "(io/slurp \"$dir/none.txt\")").starts_with("error: could not read"));
+        assert_eq!(
+            show(&dir, "(io/spit \"$dir/a.txt\" \"\" :mode :w)"),
+            "error: spit expects :append as its only option, got (:mode :w)"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_line_readers() {
+        let dir = temp_dir("lines");
+        let lines: Vec<String> = (0..100).map(|i| format!("line {i}\r\n")).collect();
+        fs::write(dir.join("a.txt"), lines.concat()).unwrap();
+        assert_eq!(show(&dir, "(count (io/read-lines \"$dir/a.txt\"))"), "100");
+        assert_eq!(
+            show(&dir, "(last (io/read-lines \"$dir/a.txt\"))"),
+            "\"line 99\""
+        );
+        // line-seq reads ahead a chunk at a time.
+        assert_eq!(
+            show(&dir, "(let [r (io/reader \"$dir/a.txt\")] [(io/read-line r) (first (io/line-seq r)) (io/read-line r)])"),
+            "[\"line 0\" \"line 1\" \"line 33\"]"
+        );
+        assert!(show(&dir, "(io/reader \"$dir/empty.txt\")").starts_with("error: could not open"));
+        fs::write(dir.join("empty.txt"), "").unwrap();
+        assert_eq!(show(&dir, "(io/read-lines \"$dir/empty.txt\")"), "()");
+        assert_eq!(
+            show(&dir, "(io/read-line (io/reader \"$dir/empty.txt\"))"),
+            "nil"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_with_open() {
+        let dir = temp_dir("with-open");
+        assert_eq!(
+            show(&dir, "(with-open [w (io/writer \"$dir/a.txt\") v (io/writer \"$dir/b.txt\")] (io/write w \"a\" 1) (io/write v :b) w)"),
+            format!("#<closed {}/a.txt>", dir.display())
+        );
+        assert_eq!(
+            show(
+                &dir,
+                "[(io/slurp \"$dir/a.txt\") (io/slurp \"$dir/b.txt\")]"
+            ),
+            "[\"a1\" \":b\"]"
+        );
+        assert_eq!(
+            show(&dir, "(def r (io/reader \"$dir/a.txt\")) (try (with-open [x r] (throw (ex-info \"oops\" {}))) (catch [e ExceptionInfo] r))"),
+            format!("#<closed {}/a.txt>", dir.display())
+        );
+        assert_eq!(
+            show(
+                &dir,
+                "(let [r (io/reader \"$dir/a.txt\")] (io/close r) (io/read-line r))"
+            ),
+            format!("error: {}/a.txt is not open for reading", dir.display())
+        );
+        assert_eq!(
+            show(&dir, "(io/write (io/reader \"$dir/a.txt\") 1)"),
+            format!("error: {}/a.txt is not open for writing", dir.display())
+        );
+        assert_eq!(
+            show(&dir, "(io/close 1)"),
+            "error: close expects a reader or writer, got 1"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_directories() {
+        let dir = temp_dir("dirs");
+        assert_eq!(show(&dir, "(io/make-dirs \"$dir/a/b\")"), "nil");
+        fs::write(dir.join("a/x.txt"), "").unwrap();
+        fs::write(dir.join("a/b/y.txt"), "").unwrap();
+        fs::write(dir.join("z.md"), "").unwrap();
+        let paths = |names: &[&str]| {
+            let paths: Vec<String> = (names.iter())
+                .map(|name| format!("\"{}/{name}\"", dir.display()))
+                .collect();
+            format!("[{}]", paths.join(" "))
+        };
+        assert_eq!(show(&dir, "(io/list-dir \"$dir\")"), paths(&["a", "z.md"]));
+        assert_eq!(
+            show(&dir, "(io/glob \"$dir/**/*.txt\")"),
+            paths(&["a/b/y.txt", "a/x.txt"])
+        );
+        assert_eq!(show(&dir, "(io/glob \"$dir/*.md\")"), paths(&["z.md"]));
+        assert_eq!(
+            show(&dir, "[(io/exists? \"$dir/z.md\") (io/file? \"$dir/a\") (io/dir? \"$dir/a\") (io/exists? \"$dir/no\")]"),
+            "[true false true false]"
+        );
+        assert_eq!(
+            show(&dir, "(io/delete \"$dir/z.md\") (io/exists? \"$dir/z.md\")"),
+            "false"
+        );
+        assert!(show(&dir, "(io/list-dir \"$dir/no\")").starts_with("error: could not list"));
+        assert!(show(&dir, "(io/glob \"$dir/***\")").starts_with("error: invalid glob pattern"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_env_vars() {
+        let dir = temp_dir("env");
+        assert_eq!(
+            show(
+                &dir,
+                "(os/setenv \"TINY_IO_TEST\" \"é\") (os/getenv \"TINY_IO_TEST\")"
+            ),
+            "\"é\""
+        );
+        assert_eq!(show(&dir, "(get (os/getenv) \"TINY_IO_TEST\")"), "\"é\"");
+        assert_eq!(
+            show(
+                &dir,
+                "(os/setenv \"TINY_IO_TEST\" nil) (os/getenv \"TINY_IO_TEST\" :unset)"
+            ),
+            ":unset"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_sh() {
+        let dir = temp_dir("sh");
+        assert_eq!(
+            show(
+                &dir,
+                "(os/sh \"sh\" \"-c\" \"cat; echo $X >&2; exit 3\" :in \"hi\" :env {\"X\" \"x\"})"
+            ),
+            "{:exit 3 :out \"hi\" :err \"x\\n\"}"
+        );
+        assert_eq!(
+            show(&dir, "(:out (os/sh \"pwd\" :dir \"$dir\"))"),
+            format!("\"{}\\n\"", dir.display())
+        );
+        assert_eq!(
+            show(&dir, "(os/sh \"tiny-no-such-program\")"),
+            "error: could not run tiny-no-such-program: No such file or directory (os error 2)"
+        );
+        assert_eq!(
+            show(&dir, "(os/sh \"true\" :out 1)"),
+            "error: invalid sh option: (:out 1)"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let dir = temp_dir("capabilities");
+        let env = Env::new();
+        env.set_capabilities(Capabilities {
+            write: false,
+            process: false,
+            ..Capabilities::ALL
+        });
+        assert_eq!(show_in(&env, &dir, "(io/exists? \"$dir\")"), "true");
+        assert_eq!(
+            show_in(&env, &dir, "(io/spit \"$dir/a.txt\" 1)"),
+            "error: spit needs the write capability, which is disabled"
+        );
+        assert_eq!(
+            show_in(&env, &dir, "(os/sh \"true\")"),
+            "error: sh needs the process capability, which is disabled"
+        );
+        env.set_capabilities(Capabilities::NONE);
+        assert_eq!(
+            show_in(&env, &dir, "(io/slurp \"$dir/a.txt\")"),
+            "error: slurp needs the read capability, which is disabled"
+        );
+        assert_eq!(
+            show_in(&env, &dir, "(os/getenv \"HOME\")"),
+            "error: getenv needs the env capability, which is disabled"
+        );
+        assert!(!dir.join("a.txt").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,5 +1,35 @@
+pub mod codec;
+pub mod coll;
+pub mod core;
+pub mod csp;
+pub mod eval;
+pub mod fmt;
+pub mod io;
+pub mod math;
+pub mod os;
 pub mod reader;
+pub mod seq;
+pub mod string;
+pub mod types;
 pub mod value;
 
+#[cfg(test)]
+mod codec_tests;
+#[cfg(test)]
+mod coll_tests;
+#[cfg(test)]
+mod csp_tests;
+#[cfg(test)]
+mod eval_tests;
+#[cfg(test)]
+mod fmt_tests;
+#[cfg(test)]
+mod io_tests;
+#[cfg(test)]
+mod math_tests;
 #[cfg(test)]
 mod reader_tests;
+#[cfg(test)]
+mod seq_tests;
+#[cfg(test)]
+mod string_tests;
@@ -0,0 +1,845 @@
+//! Numbers: the arithmetic of `tiny.core` and the `std.math` namespace.
+//!
+//! Integers are `Int` until a result no longer fits in 64 bits and then
+//! `BigInt`, and dividing integers that don't divide evenly gives a
+//! `Rational`. Results are normalized, so a `BigInt` that fits is an `Int`
+//! and a ratio over 1 is an integer. A `Float` anywhere makes the result a
+//! `Float`, and so does ratio arithmetic whose parts outgrow 64 bits.
+
+extern crate num_bigint;
+extern crate num_integer;
+extern crate num_traits;
+use self::num_bigint::BigInt;
+use self::num_integer::Integer;
+use self::num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+use crate::core::{arity, Doc};
+use crate::eval::{Env, EvalError};
+use crate::seq;
+use crate::value::{NativeFn, Ratio, Value};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const CORE_NATIVES: &[(&str, NativeFn, Doc)] = &[
+    ("+", add, "Returns the sum of nums, or 0."),
+    ("-", sub, "Subtracts the other nums from the first, or negates a single num."),
+    ("*", mul, "Returns the product of nums, or 1."),
+    ("/", div, "Divides the first num by the others, or returns the reciprocal of a single num; integers that don't divide evenly give a ratio."),
+    ("<", lt, "Returns true if nums are in increasing order."),
+    (">", gt, "Returns true if nums are in decreasing order."),
+    ("<=", le, "Returns true if nums are in non-decreasing order."),
+    (">=", ge, "Returns true if nums are in non-increasing order."),
+    ("==", num_eq, "Returns true if nums are equal in value, whatever their types."),
+    ("zero?", is_zero, "Returns true if n is zero."),
+    ("inc", inc, "Returns n plus one."),
+    ("dec", dec, "Returns n minus one."),
+    ("quot", quot, "Returns n divided by d, rounded toward zero."),
+    ("rem", rem, "Returns the remainder of dividing n by d, which has the sign of n."),
+    ("mod", modulo, "Returns n modulo d, which has the sign of d."),
+    ("abs", abs, "Returns the absolute value of n."),
+    ("min", min, "Returns the smallest of nums."),
+    ("max", max, "Returns the largest of nums."),
+    ("int", int, "Returns x as an integer: floats and ratios are rounded toward zero, and a one char string gives its code point."),
+    ("float", float, "Returns x as a float."),
+    ("integer?", is_integer, "Returns true if x is an integer of any size."),
+    ("ratio?", is_ratio, "Returns true if x is a ratio."),
+    ("rational?", is_rational, "Returns true if x is an integer or a ratio."),
+    ("numerator", numerator, "Returns the numerator of a ratio or integer."),
+    ("denominator", denominator, "Returns the denominator of a ratio or integer."),
+    ("bit-and", bit_and, "Returns the bitwise and of integers."),
+    ("bit-or", bit_or, "Returns the bitwise or of integers."),
+    ("bit-xor", bit_xor, "Returns the bitwise exclusive or of integers."),
+    ("bit-not", bit_not, "Returns the bitwise complement of an integer."),
+    ("bit-shift-left", bit_shift_left, "Shifts the 64 bits of x left by n, dropping the bits shifted out."),
+    ("bit-shift-right", bit_shift_right, "Shifts x right by n, keeping its sign."),
+    ("unsigned-bit-shift-right", unsigned_bit_shift_right, "Shifts the 64 bits of x right by n, filling with zeros."),
+    ("rand", rand, "Returns a random float from 0 up to n, or 1."),
+    ("rand-int", rand_int, "Returns a random integer from 0 up to n."),
+    ("rand-nth", rand_nth, "Returns a random item of coll."),
+    ("shuffle", shuffle, "Returns the items of coll in random order, as a vector."),
+    ("set-rand-seed!", set_rand_seed, "Seeds the random numbers of this environment so they repeat from run to run."),
+];
+
+pub const NATIVES: &[(&str, NativeFn, Doc)] = &[
+    ("sin", sin, "Returns the sine of an angle in radians."),
+    ("cos", cos, "Returns the cosine of an angle in radians."),
+    ("tan", tan, "Returns the tangent of an angle in radians."),
+    ("asin", asin, "Returns the arc sine of x, in radians."),
+    ("acos", acos, "Returns the arc cosine of x, in radians."),
+    ("atan", atan, "Returns the arc tangent of x, in radians."),
+    (
+        "atan2",
+        atan2,
+        "Returns the angle in radians of the point (x, y), given y and x.",
+    ),
+    ("sinh", sinh, "Returns the hyperbolic sine of x."),
+    ("cosh", cosh, "Returns the hyperbolic cosine of x."),
+    ("tanh", tanh, "Returns the hyperbolic tangent of x."),
+    ("exp", exp, "Returns e to the power of x."),
+    ("log", log, "Returns the natural logarithm of x."),
+    ("log10", log10, "Returns the base 10 logarithm of x."),
+    ("log2", log2, "Returns the base 2 logarithm of x."),
+    ("sqrt", sqrt, "Returns the square root of x."),
+    ("cbrt", cbrt, "Returns the cube root of x."),
+    ("pow", pow, "Returns x to the power of y, as a float."),
+    (
+        "hypot",
+        hypot,
+        "Returns the length of the hypotenuse of a right triangle with sides x and y.",
+    ),
+    (
+        "floor",
+        floor,
+        "Returns the largest whole number not above x; a float stays a float.",
+    ),
+    (
+        "ceil",
+        ceil,
+        "Returns the smallest whole number not below x; a float stays a float.",
+    ),
+    (
+        "round",
+        round,
+        "Returns the integer nearest to x, rounding halves away from zero.",
+    ),
+    (
+        "signum",
+        signum,
+        "Returns -1, 0 or 1 as x is negative, zero or positive, as a float for a float.",
+    ),
+];
+
+/// The values `std.math` defines besides its functions.
+pub const CONSTANTS: &[(&str, f64, Doc)] = &[
+    (
+        "pi",
+        std::f64::consts::PI,
+        "The ratio of a circle's circumference to its diameter.",
+    ),
+    (
+        "e",
+        std::f64::consts::E,
+        "The base of the natural logarithm.",
+    ),
+];
+
+/// A number of any type, for doing arithmetic on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Num {
+    Int(i64),
+    Big(BigInt),
+    /// A ratio in lowest terms with a denominator above 1.
+    Ratio(i64, i64),
+    Float(f64),
+}
+
+pub fn num(value: &Value) -> Result<Num, EvalError> {
+    match value {
+        Value::Int(n) => Ok(Num::Int(*n)),
+        Value::BigInt(n) => Ok(Num::Big(n.clone())),
+        Value::Rational(rat) => Ok(Num::Ratio(rat.numer(), rat.denom())),
+        Value::Float(n) => Ok(Num::Float(*n)),
+        value => Err(EvalError::new(format!("{value} is not a number"))),
+    }
+}
+
+impl Num {
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Num::Int(n) => *n as f64,
+            Num::Big(n) => n.to_f64().unwrap_or(f64::NAN),
+            Num::Ratio(n, d) => *n as f64 / *d as f64,
+            Num::Float(n) => *n,
+        }
+    }
+
+    pub fn into_value(self) -> Value {
+        match self {
+            Num::Int(n) => Value::Int(n),
+            Num::Big(n) => Value::BigInt(n),
+            Num::Ratio(n, d) => Value::Rational(Ratio::new(n, d)),
+            Num::Float(n) => Value::Float(n),
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, Num::Float(_))
+    }
+
+    /// The integer this is, if it is one.
+    fn to_big(&self) -> Option<BigInt> {
+        match self {
+            Num::Int(n) => Some(BigInt::from(*n)),
+            Num::Big(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+
+    /// The numerator and denominator of an exact number.
+    fn to_fraction(&self) -> Option<Fraction> {
+        match self {
+            Num::Int(n) => Some((BigInt::from(*n), BigInt::from(1))),
+            Num::Big(n) => Some((n.clone(), BigInt::from(1))),
+            Num::Ratio(n, d) => Some((BigInt::from(*n), BigInt::from(*d))),
+            Num::Float(_) => None,
+        }
+    }
+}
+
+/// A numerator and denominator.
+type Fraction = (BigInt, BigInt);
+
+/// An integer as an `Int` if it fits.
+fn big(n: BigInt) -> Num {
+    match n.to_i64() {
+        Some(n) => Num::Int(n),
+        None => Num::Big(n),
+    }
+}
+
+/// `n/d` normalized: an integer if `d` divides `n`, otherwise a ratio in
+/// lowest terms, or a float if its parts don't fit in 64 bits.
+fn fraction(n: BigInt, d: BigInt) -> Num {
+    let gcd = n.gcd(&d);
+    let (mut n, mut d) = (n / &gcd, d / &gcd);
+    if d.is_negative() {
+        n = -n;
+        d = -d;
+    }
+    if d == BigInt::from(1) {
+        return big(n);
+    }
+    match (n.to_i64(), d.to_i64()) {
+        (Some(n), Some(d)) => Num::Ratio(n, d),
+        _ => Num::Float(n.to_f64().unwrap_or(f64::NAN) / d.to_f64().unwrap_or(f64::NAN)),
+    }
+}
+
+/// The integer the reader reads from a string of digits with an optional
+/// sign, however large.
+pub fn integer(digits: &str) -> Value {
+    match digits.parse() {
+        Ok(n) => Value::Int(n),
+        Err(_) => Value::BigInt(digits.parse().expect("an integer literal")),
+    }
+}
+
+/// The ratio `n/d` as the reader reads it, or `None` if `d` is zero.
+pub fn ratio(n: i64, d: i64) -> Option<Value> {
+    if d == 0 {
+        return None;
+    }
+    Some(fraction(BigInt::from(n), BigInt::from(d)).into_value())
+}
+
+/// An operation at each level of the tower; `ratio` takes and returns
+/// numerators and denominators.
+struct Op {
+    int: fn(i64, i64) -> Option<i64>,
+    big: fn(BigInt, BigInt) -> BigInt,
+    ratio: fn(Fraction, Fraction) -> Fraction,
+    float: fn(f64, f64) -> f64,
+}
+
+/// An integer operation of `quot`, `rem` or `mod`.
+struct Division {
+    int: fn(i64, i64) -> Option<i64>,
+    big: fn(BigInt, BigInt) -> BigInt,
+    float: fn(f64, f64) -> f64,
+}
+
+const ADD: Op = Op {
+    int: i64::checked_add,
+    big: |a, b| a + b,
+    ratio: |(an, ad), (bn, bd)| (an * &bd + bn * &ad, ad * bd),
+    float: |a, b| a + b,
+};
+
+const SUB: Op = Op {
+    int: i64::checked_sub,
+    big: |a, b| a - b,
+    ratio: |(an, ad), (bn, bd)| (an * &bd - bn * &ad, ad * bd),
+    float: |a, b| a - b,
+};
+
+const MUL: Op = Op {
+    int: i64::checked_mul,
+    big: |a, b| a * b,
+    ratio: |(an, ad), (bn, bd)| (an * bn, ad * bd),
+    float: |a, b| a * b,
+};
+
+fn apply(op: &Op, a: Num, b: Num) -> Num {
+    if a.is_float() || b.is_float() {
+        return Num::Float((op.float)(a.to_f64(), b.to_f64()));
+    }
+    if let (Num::Int(x), Num::Int(y)) = (&a, &b) {
+        if let Some(n) = (op.int)(*x, *y) {
+            return Num::Int(n);
+        }
+    }
+    if let (Some(x), Some(y)) = (a.to_big(), b.to_big()) {
+        return big((op.big)(x, y));
+    }
+    let (n, d) = (op.ratio)(a.to_fraction().unwrap(), b.to_fraction().unwrap());
+    fraction(n, d)
+}
+
+fn divide(a: Num, b: Num) -> Result<Num, EvalError> {
+    if a.is_float() || b.is_float() {
+        return Ok(Num::Float(a.to_f64() / b.to_f64()));
+    }
+    let ((an, ad), (bn, bd)) = (a.to_fraction().unwrap(), b.to_fraction().unwrap());
+    if bn.is_zero() {
+        return Err(EvalError::new("divide by zero"));
+    }
+    Ok(fraction(an * bd, ad * bn))
+}
+
+/// Orders two numbers, exactly unless one is a float. NaN is unordered.
+pub fn compare(a: &Num, b: &Num) -> Option<Ordering> {
+    match (a, b) {
+        (Num::Int(x), Num::Int(y)) => Some(x.cmp(y)),
+        _ if a.is_float() || b.is_float() => a.to_f64().partial_cmp(&b.to_f64()),
+        _ => {
+            let ((an, ad), (bn, bd)) = (a.to_fraction().unwrap(), b.to_fraction().unwrap());
+            Some((an * bd).cmp(&(bn * ad)))
+        }
+    }
+}
+
+fn fold(args: Vec<Value>, init: i64, op: &Op) -> Result<Value, EvalError> {
+    let mut acc = Num::Int(init);
+    for (i, arg) in args.iter().enumerate() {
+        acc = if i == 0 {
+            num(arg)?
+        } else {
+            apply(op, acc, num(arg)?)
+        };
+    }
+    Ok(acc.into_value())
+}
+
+fn add(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    fold(args, 0, &ADD)
+}
+
+fn mul(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    fold(args, 1, &MUL)
+}
+
+fn sub(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [] => Err(arity("-", 0)),
+        [x] => Ok(apply(&SUB, Num::Int(0), num(x)?).into_value()),
+        _ => fold(args, 0, &SUB),
+    }
+}
+
+fn div(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (mut acc, rest) = match &args[..] {
+        [] => return Err(arity("/", 0)),
+        [only] => (Num::Int(1), std::slice::from_ref(only)),
+        [first, rest @ ..] => (num(first)?, rest),
+    };
+    for arg in rest {
+        acc = divide(acc, num(arg)?)?;
+    }
+    Ok(acc.into_value())
+}
+
+fn compare_all(args: &[Value], test: fn(Ordering) -> bool) -> Result<Value, EvalError> {
+    let nums = args.iter().map(num).collect::<Result<Vec<_>, _>>()?;
+    let ordered = (nums.windows(2)).all(|pair| compare(&pair[0], &pair[1]).is_some_and(test));
+    Ok(Value::Bool(ordered))
+}
+
+fn lt(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    compare_all(&args, Ordering::is_lt)
+}
+
+fn gt(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    compare_all(&args, Ordering::is_gt)
+}
+
+fn le(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    compare_all(&args, Ordering::is_le)
+}
+
+fn ge(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    compare_all(&args, Ordering::is_ge)
+}
+
+fn num_eq(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    compare_all(&args, Ordering::is_eq)
+}
+
+fn is_zero(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(Value::Bool(num(x)?.to_f64() == 0.0)),
+        _ => Err(arity("zero?", args.len())),
+    }
+}
+
+fn inc(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(apply(&ADD, num(x)?, Num::Int(1)).into_value()),
+        _ => Err(arity("inc", args.len())),
+    }
+}
+
+fn dec(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(apply(&SUB, num(x)?, Num::Int(1)).into_value()),
+        _ => Err(arity("dec", args.len())),
+    }
+}
+
+/// `quot`, `rem` or `mod` of two numbers: integers stay exact, anything
+/// else is done on floats.
+fn integer_division(name: &str, args: Vec<Value>, op: &Division) -> Result<Value, EvalError> {
+    let (n, d) = match &args[..] {
+        [n, d] => (num(n)?, num(d)?),
+        _ => return Err(arity(name, args.len())),
+    };
+    if d == Num::Int(0) {
+        return Err(EvalError::new("divide by zero"));
+    }
+    if let (Num::Int(x), Num::Int(y)) = (&n, &d) {
+        if let Some(n) = (op.int)(*x, *y) {
+            return Ok(Value::Int(n));
+        }
+    }
+    Ok(match (n.to_big(), d.to_big()) {
+        (Some(x), Some(y)) => big((op.big)(x, y)).into_value(),
+        _ => Value::Float((op.float)(n.to_f64(), d.to_f64())),
+    })
+}
+
+/// Moves a remainder with the sign of the dividend to the sign of `d`.
+fn floor_rem<T: PartialOrd + Default + std::ops::Add<Output = T> + Copy>(r: T, d: T) -> T {
+    let zero = T::default();
+    if r != zero && (r < zero) != (d < zero) {
+        r + d
+    } else {
+        r
+    }
+}
+
+const QUOT: Division = Division {
+    int: i64::checked_div,
+    big: |a, b| a / b,
+    float: |a, b| (a / b).trunc(),
+};
+
+const REM: Division = Division {
+    int: i64::checked_rem,
+    big: |a, b| a % b,
+    float: |a, b| a % b,
+};
+
+const MOD: Division = Division {
+    int: |a, b| Some(floor_rem(a.checked_rem(b)?, b)),
+    big: |a, b| a.mod_floor(&b),
+    float: |a, b| floor_rem(a % b, b),
+};
+
+fn quot(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    integer_division("quot", args, &QUOT)
+}
+
+fn rem(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    integer_division("rem", args, &REM)
+}
+
+fn modulo(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    integer_division("mod", args, &MOD)
+}
+
+fn abs(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let x = match &args[..] {
+        [x] => num(x)?,
+        _ => return Err(arity("abs", args.len())),
+    };
+    Ok(match x {
+        Num::Float(n) => Value::Float(n.abs()),
+        x if compare(&x, &Num::Int(0)) == Some(Ordering::Less) => {
+            apply(&SUB, Num::Int(0), x).into_value()
+        }
+        x => x.into_value(),
+    })
+}
+
+fn extreme(name: &str, args: Vec<Value>, keep: Ordering) -> Result<Value, EvalError> {
+    let mut best: Option<(Num, Value)> = None;
+    for arg in args {
+        let n = num(&arg)?;
+        best = match best {
+            Some((b, value)) if compare(&n, &b) != Some(keep) && !n.to_f64().is_nan() => {
+                Some((b, value))
+            }
+            _ => Some((n, arg)),
+        };
+    }
+    best.map(|(_, value)| value).ok_or_else(|| arity(name, 0))
+}
+
+fn min(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    extreme("min", args, Ordering::Less)
+}
+
+fn max(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    extreme("max", args, Ordering::Greater)
+}
+
+fn int(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let x = match &args[..] {
+        [x] => x,
+        _ => return Err(arity("int", args.len())),
+    };
+    if let Value::String(s) = x {
+        let mut chars = s.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(ch), None) => Ok(Value::Int(ch as i64)),
+            _ => Err(EvalError::new(format!(
+                "int expects a number or a char, got {x}"
+            ))),
+        };
+    }
+    Ok(match num(x)? {
+        Num::Ratio(n, d) => Value::Int(n / d),
+        Num::Float(n) => match BigInt::from_f64(n.trunc()) {
+            Some(n) => big(n).into_value(),
+            None => return Err(EvalError::new(format!("cannot convert {x} to an integer"))),
+        },
+        n => n.into_value(),
+    })
+}
+
+fn float(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(Value::Float(num(x)?.to_f64())),
+        _ => Err(arity("float", args.len())),
+    }
+}
+
+/// Defines a native testing the type of one value.
+macro_rules! type_test {
+    ($fn_name:ident, $name:expr, $($pattern:pat)|+) => {
+        fn $fn_name(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+            match &args[..] {
+                [x] => Ok(Value::Bool(matches!(x, $($pattern)|+))),
+                _ => Err(arity($name, args.len())),
+            }
+        }
+    };
+}
+
+type_test!(is_integer, "integer?", Value::Int(_) | Value::BigInt(_));
+type_test!(is_ratio, "ratio?", Value::Rational(_));
+type_test!(
+    is_rational,
+    "rational?",
+    Value::Int(_) | Value::BigInt(_) | Value::Rational(_)
+);
+
+fn numerator(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Rational(rat)] => Ok(Value::Int(rat.numer())),
+        [x @ (Value::Int(_) | Value::BigInt(_))] => Ok(x.clone()),
+        [x] => Err(EvalError::new(format!(
+            "numerator expects a rational, got {x}"
+        ))),
+        _ => Err(arity("numerator", args.len())),
+    }
+}
+
+fn denominator(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Rational(rat)] => Ok(Value::Int(rat.denom())),
+        [Value::Int(_) | Value::BigInt(_)] => Ok(Value::Int(1)),
+        [x] => Err(EvalError::new(format!(
+            "denominator expects a rational, got {x}"
+        ))),
+        _ => Err(arity("denominator", args.len())),
+    }
+}
+
+fn int_arg(name: &str, x: &Value) -> Result<i64, EvalError> {
+    match x {
+        Value::Int(n) => Ok(*n),
+        x => Err(EvalError::new(format!(
+            "{name} expects an integer, got {x}"
+        ))),
+    }
+}
+
+/// Whether `x`, an integer of any size, is even.
+pub fn is_even(name: &str, x: &Value) -> Result<bool, EvalError> {
+    match x {
+        Value::BigInt(n) => Ok(n.is_even()),
+        x => Ok(int_arg(name, x)? % 2 == 0),
+    }
+}
+
+fn bitwise(name: &str, args: Vec<Value>, op: fn(i64, i64) -> i64) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(arity(name, args.len()));
+    }
+    let mut acc = int_arg(name, &args[0])?;
+    for arg in &args[1..] {
+        acc = op(acc, int_arg(name, arg)?);
+    }
+    Ok(Value::Int(acc))
+}
+
+fn bit_and(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    bitwise("bit-and", args, |a, b| a & b)
+}
+
+fn bit_or(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    bitwise("bit-or", args, |a, b| a | b)
+}
+
+fn bit_xor(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    bitwise("bit-xor", args, |a, b| a ^ b)
+}
+
+fn bit_not(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(Value::Int(!int_arg("bit-not", x)?)),
+        _ => Err(arity("bit-not", args.len())),
+    }
+}
+
+fn shift(name: &str, args: Vec<Value>, op: fn(i64, u32) -> i64) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x, n] => Ok(Value::Int(op(
+            int_arg(name, x)?,
+            (int_arg(name, n)? & 63) as u32,
+        ))),
+        _ => Err(arity(name, args.len())),
+    }
+}
+
+fn bit_shift_left(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    shift("bit-shift-left", args, |x, n| x << n)
+}
+
+fn bit_shift_right(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    shift("bit-shift-right", args, |x, n| x >> n)
+}
+
+fn unsigned_bit_shift_right(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    shift("unsigned-bit-shift-right", args, |x, n| {
+        ((x as u64) >> n) as i64
+    })
+}
+
+/// The random numbers of an environment: splitmix64, which is fast and
+/// good enough for games and tests but not for secrets.
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    /// A generator seeded from the clock, differently for each one made.
+    fn default() -> Rng {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos =
+            (SystemTime::now().duration_since(UNIX_EPOCH)).map_or(0, |time| time.as_nanos() as u64);
+        Rng::new(
+            nanos
+                ^ COUNTER
+                    .fetch_add(1, AtomicOrdering::Relaxed)
+                    .rotate_left(32),
+        )
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A float from 0 up to 1.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// An integer from 0 up to `n`, without favoring any.
+    pub fn below(&mut self, n: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
+
+fn rand(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let scale = match &args[..] {
+        [] => 1.0,
+        [n] => num(n)?.to_f64(),
+        _ => return Err(arity("rand", args.len())),
+    };
+    Ok(Value::Float(env.rng().next_f64() * scale))
+}
+
+fn rand_int(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Int(n)] if *n > 0 => Ok(Value::Int(env.rng().below(*n as u64) as i64)),
+        [n] => Err(EvalError::new(format!(
+            "rand-int expects a positive integer, got {n}"
+        ))),
+        _ => Err(arity("rand-int", args.len())),
+    }
+}
+
+fn rand_nth(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let items = match &args[..] {
+        [coll] => seq::to_vec(coll)?,
+        _ => return Err(arity("rand-nth", args.len())),
+    };
+    if items.is_empty() {
+        return Err(EvalError::new("rand-nth expects a non-empty collection"));
+    }
+    let i = env.rng().below(items.len() as u64) as usize;
+    Ok(items[i].clone())
+}
+
+fn shuffle(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let mut items = match &args[..] {
+        [coll] => seq::to_vec(coll)?,
+        _ => return Err(arity("shuffle", args.len())),
+    };
+    let mut rng = env.rng();
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i as u64 + 1) as usize);
+    }
+    Ok(Value::ListBracket(items))
+}
+
+fn set_rand_seed(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [seed] => {
+            *env.rng() = Rng::new(int_arg("set-rand-seed!", seed)? as u64);
+            Ok(Value::Nil)
+        }
+        _ => Err(arity("set-rand-seed!", args.len())),
+    }
+}
+
+/// Defines a native applying a float function to one number.
+macro_rules! float_fn {
+    ($fn_name:ident, $name:expr, $f:expr) => {
+        fn $fn_name(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+            match &args[..] {
+                [x] => Ok(Value::Float($f(num(x)?.to_f64()))),
+                _ => Err(arity($name, args.len())),
+            }
+        }
+    };
+}
+
+float_fn!(sin, "sin", f64::sin);
+float_fn!(cos, "cos", f64::cos);
+float_fn!(tan, "tan", f64::tan);
+float_fn!(asin, "asin", f64::asin);
+float_fn!(acos, "acos", f64::acos);
+float_fn!(atan, "atan", f64::atan);
+float_fn!(sinh, "sinh", f64::sinh);
+float_fn!(cosh, "cosh", f64::cosh);
+float_fn!(tanh, "tanh", f64::tanh);
+float_fn!(exp, "exp", f64::exp);
+float_fn!(log, "log", f64::ln);
+float_fn!(log10, "log10", f64::log10);
+float_fn!(log2, "log2", f64::log2);
+float_fn!(sqrt, "sqrt", f64::sqrt);
+float_fn!(cbrt, "cbrt", f64::cbrt);
+
+/// Defines a native applying a float function to two numbers.
+macro_rules! float_fn2 {
+    ($fn_name:ident, $name:expr, $f:expr) => {
+        fn $fn_name(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+            match &args[..] {
+                [x, y] => Ok(Value::Float($f(num(x)?.to_f64(), num(y)?.to_f64()))),
+                _ => Err(arity($name, args.len())),
+            }
+        }
+    };
+}
+
+float_fn2!(atan2, "atan2", f64::atan2);
+float_fn2!(pow, "pow", f64::powf);
+float_fn2!(hypot, "hypot", f64::hypot);
+
+/// Rounds `x` to a whole number: floats with `float`, keeping them floats,
+/// and ratios exactly with `ratio` of the numerator and denominator.
+fn whole(
+    name: &str,
+    args: Vec<Value>,
+    float: fn(f64) -> f64,
+    ratio: fn(i64, i64) -> i64,
+) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(match num(x)? {
+            Num::Float(n) => Value::Float(float(n)),
+            Num::Ratio(n, d) => Value::Int(ratio(n, d)),
+            n => n.into_value(),
+        }),
+        _ => Err(arity(name, args.len())),
+    }
+}
+
+fn floor(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    whole("floor", args, f64::floor, |n, d| Integer::div_floor(&n, &d))
+}
+
+fn ceil(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    whole("ceil", args, f64::ceil, |n, d| Integer::div_ceil(&n, &d))
+}
+
+fn round(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let x = match &args[..] {
+        [x] => x,
+        _ => return Err(arity("round", args.len())),
+    };
+    match whole("round", args.clone(), f64::round, |n, d| {
+        // Halves round away from zero: add half the denominator to |n|.
+        let rounded = (2 * i128::from(n).abs() + i128::from(d)) / (2 * i128::from(d));
+        i64::try_from(rounded).unwrap() * n.signum()
+    })? {
+        Value::Float(n) => match BigInt::from_f64(n) {
+            Some(n) => Ok(big(n).into_value()),
+            None => Err(EvalError::new(format!("cannot round {x} to an integer"))),
+        },
+        n => Ok(n),
+    }
+}
+
+fn signum(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(match num(x)? {
+            Num::Float(n) if n == 0.0 || n.is_nan() => Value::Float(n),
+            Num::Float(n) => Value::Float(n.signum()),
+            n => Value::Int(match compare(&n, &Num::Int(0)) {
+                Some(Ordering::Less) => -1,
+                Some(Ordering::Greater) => 1,
+                _ => 0,
+            }),
+        }),
+        _ => Err(arity("signum", args.len())),
+    }
+}
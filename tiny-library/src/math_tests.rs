@@ -0,0 +1,212 @@
+#[cfg(test)]
+mod tests {
+    use eval::Env;
+    use math::Rng;
+
+    /// Evaluates `content` with `std.math` imported as `m` and prints the
+    /// result, or the error message.
+    fn show(content: &str) -> String {
+        show_in(&Env::new(), content)
+    }
+
+    fn show_in(env: &Env, content: &str) -> String {
+        let content = format!("(import [std.math :as m]) {content}");
+        match env.eval_str("_test_.tiny", &content) {
+            Ok(value) => value.to_string(),
+            Err(err) => format!("error: {}", err.message),
+        }
+    }
+
+    #[test]
+    fn test_int_promotes_to_big_int() {
+        assert_eq!(show("(+ 9223372036854775807 1)"), "9223372036854775808");
+        assert_eq!(show("(- -9223372036854775808 1)"), "-9223372036854775809");
+        assert_eq!(
+            show("(* 4294967296 4294967296 4294967296)"),
+            "79228162514264337593543950336"
+        );
+        assert_eq!(show("(- 9223372036854775808 1)"), "9223372036854775807");
+        assert_eq!(show("(integer? (- 9223372036854775808 1))"), "true");
+        assert_eq!(show("(inc 9223372036854775807)"), "9223372036854775808");
+        assert_eq!(show("(- -9223372036854775808)"), "9223372036854775808");
+        assert_eq!(
+            show("(reduce * (range 1 26))"),
+            "15511210043330985984000000"
+        );
+        assert_eq!(
+            show("(= (+ 9223372036854775807 1) 9223372036854775808)"),
+            "true"
+        );
+        assert_eq!(show("(< 9223372036854775807 9223372036854775808)"), "true");
+        assert_eq!(show("(even? 9223372036854775808)"), "true");
+        assert_eq!(show("(pos? 9223372036854775808)"), "true");
+    }
+
+    #[test]
+    fn test_ratios() {
+        assert_eq!(show("(/ 1 3)"), "1/3");
+        assert_eq!(show("(/ 6 3)"), "2");
+        assert_eq!(show("(/ 4 -6)"), "-2/3");
+        assert_eq!(show("(/ 3)"), "1/3");
+        assert_eq!(show("2/4"), "1/2");
+        assert_eq!(show("4/2"), "2");
+        assert_eq!(show("(+ 1/2 1/3)"), "5/6");
+        assert_eq!(show("(+ 1/2 1/2)"), "1");
+        assert_eq!(show("(* 2/3 3)"), "2");
+        assert_eq!(show("(- 1/2 1)"), "-1/2");
+        assert_eq!(show("(/ 1/2 1/4)"), "2");
+        assert_eq!(show("(< 1/3 0.34 1/2)"), "true");
+        assert_eq!(show("(== 1/2 0.5)"), "true");
+        assert_eq!(show("[(numerator 2/3) (denominator 2/3)]"), "[2 3]");
+        assert_eq!(
+            show("[(ratio? 1/2) (rational? 1) (rational? 1.0)]"),
+            "[true true false]"
+        );
+    }
+
+    #[test]
+    fn test_floats_are_contagious() {
+        assert_eq!(show("(+ 1 0.5)"), "1.5");
+        assert_eq!(show("(+ 1/2 0.25)"), "0.75");
+        assert_eq!(show("(* 9223372036854775808 1.0)"), "9.223372036854776e18");
+        assert_eq!(show("(/ 1.0 0)"), "inf");
+        assert_eq!(show("(/ 1 0)"), "error: divide by zero");
+        assert_eq!(show("(float 1/4)"), "0.25");
+        assert_eq!(
+            show("[(int 2.9) (int -2.9) (int 7/2) (int \"a\")]"),
+            "[2 -2 3 97]"
+        );
+        assert_eq!(show("(int (m/pow 10 19))"), "10000000000000000000");
+    }
+
+    #[test]
+    fn test_quot_rem_mod() {
+        let cases = [
+            ("7 2", "3 1 1"),
+            ("-7 2", "-3 -1 1"),
+            ("7 -2", "-3 1 -1"),
+            ("-7 -2", "3 -1 -1"),
+            ("6 3", "2 0 0"),
+            ("-6 3", "-2 0 0"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(
+                show(&format!("[(quot {args}) (rem {args}) (mod {args})]")),
+                format!("[{expected}]"),
+                "{args}"
+            );
+        }
+        assert_eq!(
+            show("[(quot 7.5 2) (rem -7.5 2) (mod -7.5 2)]"),
+            "[3.0 -1.5 0.5]"
+        );
+        assert_eq!(show("(mod 9223372036854775809 10)"), "9");
+        assert_eq!(show("(mod -9223372036854775809 10)"), "1");
+        assert_eq!(
+            show("(quot -9223372036854775808 -1)"),
+            "9223372036854775808"
+        );
+        assert_eq!(show("(rem 1 0)"), "error: divide by zero");
+    }
+
+    #[test]
+    fn test_abs_min_max() {
+        assert_eq!(
+            show("[(abs -3) (abs 3) (abs -1/2) (abs -2.5)]"),
+            "[3 3 1/2 2.5]"
+        );
+        assert_eq!(show("(abs -9223372036854775808)"), "9223372036854775808");
+        assert_eq!(show("[(min 3 1 2) (max 3 1 2) (max 1 2.5)]"), "[1 3 2.5]");
+        assert_eq!(
+            show("(min)"),
+            "error: wrong number of args (0) passed to min"
+        );
+    }
+
+    #[test]
+    fn test_bit_ops() {
+        assert_eq!(
+            show("[(bit-and 12 10) (bit-or 12 10) (bit-xor 12 10)]"),
+            "[8 14 6]"
+        );
+        assert_eq!(show("(bit-and 15 7 3)"), "3");
+        assert_eq!(show("(bit-not 0)"), "-1");
+        assert_eq!(
+            show("[(bit-shift-left 1 4) (bit-shift-left 1 64)]"),
+            "[16 1]"
+        );
+        assert_eq!(show("(bit-shift-left 1 63)"), "-9223372036854775808");
+        assert_eq!(
+            show("[(bit-shift-right -16 2) (unsigned-bit-shift-right -1 60)]"),
+            "[-4 15]"
+        );
+        assert_eq!(
+            show("(bit-and 1.5 1)"),
+            "error: bit-and expects an integer, got 1.5"
+        );
+    }
+
+    #[test]
+    fn test_std_math() {
+        assert_eq!(
+            show("[(m/sqrt 16) (m/pow 2 10) (m/cbrt 27)]"),
+            "[4.0 1024.0 3.0]"
+        );
+        assert_eq!(
+            show("[(m/sin 0) (m/cos 0) (m/exp 0) (m/log 1)]"),
+            "[0.0 1.0 1.0 0.0]"
+        );
+        assert_eq!(
+            show("[(m/log10 1000) (m/log2 8) (m/hypot 3 4)]"),
+            "[3.0 3.0 5.0]"
+        );
+        assert_eq!(show("(m/atan2 1 1)"), show("(/ m/pi 4)"));
+        assert_eq!(
+            show("[(m/floor 2.5) (m/ceil 2.5) (m/floor -7/2) (m/ceil 7/2)]"),
+            "[2.0 3.0 -4 4]"
+        );
+        assert_eq!(
+            show("[(m/round 2.5) (m/round -2.5) (m/round 5/2) (m/round -1/3)]"),
+            "[3 -3 3 0]"
+        );
+        assert_eq!(
+            show("[(m/signum -3) (m/signum 0.5) (m/signum 0)]"),
+            "[-1 1.0 0]"
+        );
+        assert_eq!(show("m/e"), "2.718281828459045");
+        assert_eq!(show("(m/sqrt :a)"), "error: :a is not a number");
+    }
+
+    #[test]
+    fn test_seeded_rand_repeats() {
+        let draws = "[(rand) (rand 10) (rand-int 100) (rand-nth [:a :b :c]) (shuffle (range 5))]";
+        let run = || {
+            let env = Env::new();
+            show_in(&env, &format!("(set-rand-seed! 42) {draws}"))
+        };
+        assert_eq!(run(), run());
+        let env = Env::new();
+        show_in(&env, "(set-rand-seed! 42)");
+        assert_eq!(show_in(&env, draws), run());
+        assert_eq!(show("(sort (shuffle (range 10)))"), "(0 1 2 3 4 5 6 7 8 9)");
+        assert_eq!(
+            show("(every? (fn [n] (< -1 n 3)) (map (fn [_] (rand-int 3)) (range 100)))"),
+            "true"
+        );
+        assert_eq!(
+            show("(rand-int 0)"),
+            "error: rand-int expects a positive integer, got 0"
+        );
+    }
+
+    #[test]
+    fn test_rng_below_is_in_range() {
+        let mut rng = Rng::new(7);
+        let mut seen = [false; 5];
+        for _ in 0..200 {
+            seen[rng.below(5) as usize] = true;
+            assert!((0.0..1.0).contains(&rng.next_f64()));
+        }
+        assert_eq!(seen, [true; 5]);
+    }
+}
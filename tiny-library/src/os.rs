@@ -0,0 +1,155 @@
+//! The `std.os` namespace: environment variables and subprocesses.
+
+use crate::core::{arity, to_str, Doc};
+use crate::eval::{Env, EvalError};
+use crate::io::require;
+use crate::value::{NativeFn, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+pub const NATIVES: &[(&str, NativeFn, Doc)] = &[
+    ("getenv", getenv, "Returns the environment variable name, or default or nil if it is unset; with no args, a map of all of them."),
+    ("setenv", setenv, "Sets the environment variable name to value, or unsets it if value is nil."),
+    ("sh", sh, "Runs a program with string args, returning {:exit code :out stdout :err stderr}; options :in, :dir and :env follow the args."),
+    ("exit", exit, "Exits the process with status code, or 0."),
+];
+
+fn name_arg<'a>(name: &str, x: &'a Value) -> Result<&'a str, EvalError> {
+    match x {
+        Value::String(s) => Ok(s),
+        x => Err(EvalError::new(format!("{name} expects a string, got {x}"))),
+    }
+}
+
+fn getenv(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("getenv", "env", env.capabilities().env)?;
+    match &args[..] {
+        [] => {
+            let mut vars: Vec<(String, String)> = std::env::vars().collect();
+            vars.sort();
+            Ok(Value::ListBrace(
+                vars.into_iter()
+                    .flat_map(|(name, value)| [Value::String(name), Value::String(value)])
+                    .collect(),
+            ))
+        }
+        [name] | [name, _] => match std::env::var(name_arg("getenv", name)?) {
+            Ok(value) => Ok(Value::String(value)),
+            Err(_) => Ok(args.get(1).cloned().unwrap_or(Value::Nil)),
+        },
+        _ => Err(arity("getenv", args.len())),
+    }
+}
+
+fn setenv(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("setenv", "env", env.capabilities().env)?;
+    match &args[..] {
+        [name, Value::Nil] => std::env::remove_var(name_arg("setenv", name)?),
+        [name, value] => std::env::set_var(name_arg("setenv", name)?, to_str(value)),
+        _ => return Err(arity("setenv", args.len())),
+    }
+    Ok(Value::Nil)
+}
+
+/// Where `sh` runs a program and what it gives it.
+#[derive(Default)]
+struct ShOptions {
+    input: Option<String>,
+    dir: Option<String>,
+    vars: Vec<(String, String)>,
+}
+
+fn sh_options(options: &[Value]) -> Result<ShOptions, EvalError> {
+    let mut sh = ShOptions::default();
+    for option in options.chunks(2) {
+        match option {
+            [Value::Keyword(_, name), value] if name == "in" => sh.input = Some(to_str(value)),
+            [Value::Keyword(_, name), value] if name == "dir" => {
+                sh.dir = Some(name_arg("sh :dir", value)?.into())
+            }
+            [Value::Keyword(_, name), Value::ListBrace(vars)] if name == "env" => {
+                for var in vars.chunks(2) {
+                    sh.vars.push((to_str(&var[0]), to_str(&var[1])));
+                }
+            }
+            option => {
+                return Err(EvalError::new(format!(
+                    "invalid sh option: {}",
+                    Value::ListParen(option.to_vec())
+                )))
+            }
+        }
+    }
+    Ok(sh)
+}
+
+fn sh(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("sh", "process", env.capabilities().process)?;
+    let program = match args.first() {
+        Some(program) => name_arg("sh", program)?,
+        None => return Err(arity("sh", 0)),
+    };
+    let count = (args[1..].iter())
+        .take_while(|arg| !matches!(arg, Value::Keyword(_, _)))
+        .count();
+    let (program_args, options) = args[1..].split_at(count);
+    let options = sh_options(options)?;
+    let mut command = Command::new(program);
+    for arg in program_args {
+        command.arg(name_arg("sh", arg)?);
+    }
+    if let Some(dir) = &options.dir {
+        command.current_dir(dir);
+    }
+    command.envs(options.vars);
+    command
+        .stdin(if options.input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let error = |err: std::io::Error| EvalError::new(format!("could not run {program}: {err}"));
+    let mut child = command.spawn().map_err(error)?;
+    // Writing input from another thread lets the program fill its output
+    // pipes without waiting for us to finish writing.
+    let writer = match (child.stdin.take(), options.input) {
+        (Some(mut stdin), Some(input)) => {
+            Some(thread::spawn(move || stdin.write_all(input.as_bytes())))
+        }
+        _ => None,
+    };
+    let output = child.wait_with_output().map_err(error)?;
+    if let Some(writer) = writer {
+        match writer.join() {
+            Ok(Err(err)) if err.kind() != std::io::ErrorKind::BrokenPipe => return Err(error(err)),
+            _ => {}
+        }
+    }
+    let exit = match output.status.code() {
+        Some(code) => Value::Int(code as i64),
+        None => Value::Nil,
+    };
+    Ok(Value::ListBrace(vec![
+        Value::Keyword(String::new(), "exit".into()),
+        exit,
+        Value::Keyword(String::new(), "out".into()),
+        Value::String(String::from_utf8_lossy(&output.stdout).into()),
+        Value::Keyword(String::new(), "err".into()),
+        Value::String(String::from_utf8_lossy(&output.stderr).into()),
+    ]))
+}
+
+fn exit(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    require("exit", "process", env.capabilities().process)?;
+    match &args[..] {
+        [] => std::process::exit(0),
+        [Value::Int(code)] => std::process::exit(*code as i32),
+        [code] => Err(EvalError::new(format!(
+            "exit expects a status code, got {code}"
+        ))),
+        _ => Err(arity("exit", args.len())),
+    }
+}
@@ -1,6 +1,7 @@
 extern crate anyhow;
 use self::anyhow::Result;
-use crate::value::{Ratio, Value};
+use crate::math;
+use crate::value::{Regex, Value};
 use std::str::CharIndices;
 
 pub struct Reader<'a> {
@@ -19,27 +20,58 @@ impl<'a> Reader<'a> {
     }
 
     pub fn read(&mut self) -> Option<Result<Value, ReadError>> {
+        self.read_spanned()
+            .map(|result| result.map(|(value, _)| value))
+    }
+
+    pub fn read_spanned(&mut self) -> Option<Result<(Value, Span), ReadError>> {
         self.skip_whitespace();
-        self.chars.clone().next().map(|(pos, ch)| match (pos, ch) {
-            (start, '0'..='9') => self.read_number(start),
-            (start, ch @ '+') | (start, ch @ '-') => self.read_number_or_symbol(start, ch),
-            (start, '"') => self.read_string(start),
-            (start, ':') => self.read_keyword(start),
-            (start, open @ '(') => self.read_list(start, open, ')', Value::ListParen),
-            (start, open @ '[') => self.read_list(start, open, ']', Value::ListBracket),
-            (start, open @ '{') => self.read_list(start, open, '}', Value::ListBrace),
-            (start, ch) if is_symbol_head(ch) => self.read_symbol(start),
-            (_, '/') => {
+        self.chars.clone().next().map(|(pos, ch)| {
+            let (value, items) = match (pos, ch) {
+                (start, '\'') => self.read_wrapped(start, 1, "quote")?,
+                (start, '`') => self.read_wrapped(start, 1, "syntax-quote")?,
+                (start, '~') if self.content[start..].starts_with("~@") => {
+                    self.read_wrapped(start, 2, "unquote-splicing")?
+                }
+                (start, '~') => self.read_wrapped(start, 1, "unquote")?,
+                (start, '@') => self.read_wrapped(start, 1, "deref")?,
+                (start, open @ '(') => self.read_list(start, open, ')', Value::ListParen)?,
+                (start, open @ '[') => self.read_list(start, open, ']', Value::ListBracket)?,
+                (start, open @ '{') => self.read_list(start, open, '}', Value::ListBrace)?,
+                (start, ch) => (self.read_atom(start, ch)?, vec![]),
+            };
+            let end = self.pos();
+            Ok((
+                value,
+                Span {
+                    start: pos,
+                    end,
+                    items,
+                },
+            ))
+        })
+    }
+
+    fn read_atom(&mut self, start: usize, ch: char) -> Result<Value, ReadError> {
+        match ch {
+            '0'..='9' => self.read_number(start),
+            '+' | '-' => self.read_number_or_symbol(start, ch),
+            '"' => self.read_string(start),
+            '#' if self.content[start..].starts_with("#\"") => self.read_regex(start),
+            ':' => self.read_keyword(start),
+            ch if is_symbol_head(ch) => self.read_symbol(start),
+            '/' => {
                 self.chars.next();
                 Ok(Value::Symbol("".into(), "/".into()))
             }
-            (start, char) => Err(ReadError {
+            char => Err(ReadError {
                 name: self.name.into(),
                 start,
                 end: start,
+                kind: ReadErrorKind::UnexpectedChar,
                 message: format!("unexpected char '{char}'"),
             }),
-        })
+        }
     }
 
     fn read_number(&mut self, start: usize) -> Result<Value, ReadError> {
@@ -85,6 +117,7 @@ impl<'a> Reader<'a> {
                 name: self.name.into(),
                 start,
                 end: start,
+                kind: ReadErrorKind::UnexpectedChar,
                 message: format!("unexpected char '{char}'"),
             }),
         }
@@ -99,17 +132,18 @@ impl<'a> Reader<'a> {
         } else if peek == Some('/') {
             self.chars.next();
             let end = self.advance_while(|ch| ch.is_ascii_digit());
-            match parse_ratio(self.content[start..end].into()) {
-                Ok(ratio) => Ok(Value::Rational(ratio)),
+            match parse_ratio(&self.content[start..end]) {
+                Ok(ratio) => Ok(ratio),
                 _ => Err(ReadError {
                     name: self.name.into(),
                     start,
                     end,
+                    kind: ReadErrorKind::InvalidNumber,
                     message: "invalid rational".into(),
                 }),
             }
         } else {
-            Ok(Value::Int(self.content[start..end].parse().unwrap()))
+            Ok(math::integer(&self.content[start..end]))
         }
     }
 
@@ -133,18 +167,58 @@ impl<'a> Reader<'a> {
                                 name: self.name.into(),
                                 start: pos - 1,
                                 end: pos + 1,
+                                kind: ReadErrorKind::InvalidEscape,
                                 message: format!("invalid string escape `\\{ch}`"),
                             });
                         }
-                        None => unimplemented!(),
+                        None => break,
                     });
                 }
                 Some((_, ch)) => string.push(ch),
+                None => break,
+            }
+        }
+        Err(ReadError {
+            name: self.name.into(),
+            start,
+            end: self.content.len(),
+            kind: ReadErrorKind::UnterminatedString,
+            message: "expected closing `\"`, found EOF".into(),
+        })
+    }
+
+    /// Reads `#"pattern"`, where backslashes are kept as they are so the
+    /// pattern reads like it does in the regex syntax.
+    fn read_regex(&mut self, start: usize) -> Result<Value, ReadError> {
+        self.chars.next();
+        self.chars.next();
+        let mut pattern = String::new();
+        loop {
+            match self.chars.next() {
+                Some((end, '"')) => {
+                    return Regex::new(&pattern)
+                        .map(Value::Regex)
+                        .map_err(|message| ReadError {
+                            name: self.name.into(),
+                            start,
+                            end: end + 1,
+                            kind: ReadErrorKind::InvalidRegex,
+                            message: format!("invalid regex: {message}"),
+                        });
+                }
+                Some((_, '\\')) => {
+                    pattern.push('\\');
+                    if let Some((_, ch)) = self.chars.next() {
+                        pattern.push(ch);
+                    }
+                }
+                Some((_, ch)) => pattern.push(ch),
                 None => {
                     return Err(ReadError {
                         name: self.name.into(),
                         start,
                         end: self.content.len(),
+                        kind: ReadErrorKind::UnterminatedString,
                         message: "expected closing `\"`, found EOF".into(),
                     });
                 }
@@ -152,29 +226,68 @@ impl<'a> Reader<'a> {
         }
     }
 
+    /// Reads the form after a `len` chars prefix such as `'` as `(wrapper form)`.
+    fn read_wrapped(
+        &mut self,
+        start: usize,
+        len: usize,
+        wrapper: &str,
+    ) -> Result<(Value, Vec<Span>), ReadError> {
+        for _ in 0..len {
+            self.chars.next();
+        }
+        let prefix = Span {
+            start,
+            end: start + len,
+            items: vec![],
+        };
+        match self.read_spanned() {
+            Some(Ok((form, span))) => Ok((
+                Value::ListParen(vec![Value::Symbol("".into(), wrapper.into()), form]),
+                vec![prefix, span],
+            )),
+            Some(Err(err)) => Err(err),
+            None => Err(ReadError {
+                name: self.name.into(),
+                start,
+                end: self.content.len(),
+                kind: ReadErrorKind::MissingForm { len },
+                message: format!(
+                    "expected a form after `{}`",
+                    &self.content[start..start + len]
+                ),
+            }),
+        }
+    }
+
     fn read_list<List: Fn(Vec<Value>) -> Value>(
         &mut self,
         start: usize,
         open: char,
         close: char,
         list: List,
-    ) -> Result<Value, ReadError> {
+    ) -> Result<(Value, Vec<Span>), ReadError> {
         self.chars.next();
         let mut items = vec![];
+        let mut spans = vec![];
         loop {
             self.skip_whitespace();
             if self.peek() == Some(close) {
                 self.chars.next();
-                return Ok(list(items));
+                return Ok((list(items), spans));
             }
-            match self.read() {
-                Some(Ok(value)) => items.push(value),
+            match self.read_spanned() {
+                Some(Ok((value, span))) => {
+                    items.push(value);
+                    spans.push(span);
+                }
                 Some(Err(err)) => return Err(err),
                 None => {
                     return Err(ReadError {
                         name: self.name.into(),
                         start,
                         end: self.content.len(),
+                        kind: ReadErrorKind::Unclosed { close },
                         message: format!("unclosed `{open}`"),
                     })
                 }
@@ -182,6 +295,13 @@ impl<'a> Reader<'a> {
         }
     }
 
+    fn pos(&self) -> usize {
+        self.chars
+            .clone()
+            .next()
+            .map_or(self.content.len(), |(pos, _)| pos)
+    }
+
     fn peek(&self) -> Option<char> {
         self.chars.clone().next().map(|(_, ch)| ch)
     }
@@ -189,7 +309,7 @@ impl<'a> Reader<'a> {
     fn skip_whitespace(&mut self) {
         loop {
             self.advance_while(|ch| ch.is_whitespace());
-            if self.chars.clone().next().map_or(false, |(_, ch)| ch == ';') {
+            if self.chars.clone().next().is_some_and(|(_, ch)| ch == ';') {
                 self.advance_while(|ch| ch != '\n');
                 self.chars.next();
             } else {
@@ -198,6 +318,74 @@ impl<'a> Reader<'a> {
         }
     }
 
+    /// The next token, without reading forms: unlike `read`, this never
+    /// fails, so it also splits up unbalanced or invalid input.
+    pub fn next_token(&mut self) -> Option<Token> {
+        self.advance_while(|ch| ch.is_whitespace());
+        let (start, ch) = self.chars.clone().next()?;
+        let kind = match ch {
+            ';' => {
+                self.advance_while(|ch| ch != '\n');
+                TokenKind::Comment
+            }
+            '(' | '[' | '{' => {
+                self.chars.next();
+                TokenKind::Open
+            }
+            ')' | ']' | '}' => {
+                self.chars.next();
+                TokenKind::Close
+            }
+            '\'' | '`' | '@' | '~' => {
+                self.chars.next();
+                if self.content[start..].starts_with("~@") {
+                    self.chars.next();
+                }
+                TokenKind::Prefix
+            }
+            '"' => {
+                self.skip_quoted();
+                TokenKind::String
+            }
+            '#' if self.content[start..].starts_with("#\"") => {
+                self.chars.next();
+                self.skip_quoted();
+                TokenKind::Regex
+            }
+            ch => match self.read_atom(start, ch) {
+                Ok(Value::Symbol(_, _)) => TokenKind::Symbol,
+                Ok(Value::Keyword(_, _)) => TokenKind::Keyword,
+                Ok(_) => TokenKind::Number,
+                Err(_) => {
+                    if self.pos() == start {
+                        self.chars.next();
+                    }
+                    TokenKind::Error
+                }
+            },
+        };
+        Some(Token {
+            kind,
+            start,
+            end: self.pos(),
+        })
+    }
+
+    /// Skips a string from its opening quote to its closing quote, or to
+    /// the end of the input.
+    fn skip_quoted(&mut self) {
+        self.chars.next();
+        while let Some((_, ch)) = self.chars.next() {
+            match ch {
+                '"' => return,
+                '\\' => {
+                    self.chars.next();
+                }
+                _ => {}
+            }
+        }
+    }
+
     fn advance_while<F: Fn(char) -> bool>(&mut self, f: F) -> usize {
         loop {
             match self.chars.clone().next() {
@@ -216,14 +404,99 @@ impl<'a> Reader<'a> {
     }
 }
 
+/// A piece of source as `Reader::next_token` splits it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
+    /// `(`, `[` or `{`.
+    Open,
+    /// `)`, `]` or `}`.
+    Close,
+    /// A reader macro such as `'` or `~@`.
+    Prefix,
+    Comment,
+    /// A string, possibly missing its closing quote.
+    String,
+    Regex,
+    Number,
+    Keyword,
+    Symbol,
+    /// Text that can't start a form.
+    Error,
+}
+
+/// The tokens of `content`, in order.
+pub fn tokens(content: &str) -> Vec<Token> {
+    let mut reader = Reader::new("", content);
+    std::iter::from_fn(|| reader.next_token()).collect()
+}
+
+/// The 1-based line and column, in characters, of byte `offset` of
+/// `content`.
+pub fn line_col(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Byte range of a form, with the spans of its items when it is a list.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub items: Vec<Span>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct ReadError {
     pub name: String,
     pub start: usize,
     pub end: usize,
+    pub kind: ReadErrorKind,
     pub message: String,
 }
 
+/// What went wrong reading a form.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReadErrorKind {
+    /// A list, vector or map whose closing bracket `close` never came.
+    Unclosed {
+        close: char,
+    },
+    /// A string or regex whose closing `"` never came.
+    UnterminatedString,
+    InvalidEscape,
+    InvalidRegex,
+    InvalidNumber,
+    /// A quote or other prefix, `len` bytes long, at the end of the input.
+    MissingForm {
+        len: usize,
+    },
+    UnexpectedChar,
+}
+
+impl ReadError {
+    /// Whether the input ended inside a form, so that more input could
+    /// complete it.
+    pub fn is_unterminated(&self) -> bool {
+        matches!(
+            self.kind,
+            ReadErrorKind::Unclosed { .. }
+                | ReadErrorKind::UnterminatedString
+                | ReadErrorKind::MissingForm { .. }
+        )
+    }
+}
+
 fn is_symbol_head(ch: char) -> bool {
     matches!(ch, 'a'..='z'
         | 'A'..='Z'
@@ -256,11 +529,11 @@ fn is_symbol_tail(ch: char) -> bool {
     is_symbol_head(ch) || (matches!(ch, '0'..='9' | '/'))
 }
 
-fn parse_ratio(s: &str) -> Result<Ratio, String> {
+fn parse_ratio(s: &str) -> Result<Value, String> {
     let mut split = s.splitn(2, '/');
     match (split.next(), split.next(), split.next()) {
         (Some(numer), Some(denom), None) => match (numer.parse::<i64>(), denom.parse::<i64>()) {
-            (Ok(n), Ok(d)) => Ok(Ratio::new(n, d)),
+            (Ok(n), Ok(d)) => math::ratio(n, d).ok_or_else(|| "invalid rational".into()),
             (_, _) => Err("invalid rational".into()),
         },
         _ => Err("invalid rational".into()),
@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests {
-    use reader::{ReadError, Reader};
-    use value::Value;
+    use reader::{line_col, tokens, ReadError, ReadErrorKind, Reader, Span, TokenKind};
+    use value::{Ratio, Regex, Value};
 
     #[test]
     fn test_read_empty() {
@@ -26,6 +26,72 @@ mod tests {
         assert_eq!(reader.read(), None);
     }
 
+    #[test]
+    fn test_read_big_integers_and_ratios() {
+        let mut reader = Reader::new(
+            "_test_.tiny",
+            "9223372036854775808 -9223372036854775809 2/4 -6/3 1/0",
+        );
+        let big = |s: &str| Value::BigInt(s.parse().unwrap());
+        assert_eq!(reader.read(), Some(Ok(big("9223372036854775808"))));
+        assert_eq!(reader.read(), Some(Ok(big("-9223372036854775809"))));
+        assert_eq!(reader.read(), Some(Ok(Value::Rational(Ratio::new(1, 2)))));
+        assert_eq!(reader.read(), Some(Ok(Value::Int(-2))));
+        assert_eq!(
+            reader.read().unwrap().unwrap_err().message,
+            "invalid rational"
+        );
+    }
+
+    #[test]
+    fn test_line_col() {
+        let content = "(a\n  é b)\n";
+        assert_eq!(line_col(content, 0), (1, 1));
+        assert_eq!(line_col(content, 3), (2, 1));
+        assert_eq!(line_col(content, 8), (2, 5));
+        assert_eq!(line_col(content, 100), (3, 1));
+    }
+
+    #[test]
+    fn test_tokens() {
+        let content = "(def x ~@'[1 :k \"s\\\"\"]) ; note\n#\"r\" } \"open";
+        let kinds: Vec<(TokenKind, &str)> = tokens(content)
+            .into_iter()
+            .map(|token| (token.kind, &content[token.start..token.end]))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (TokenKind::Open, "("),
+                (TokenKind::Symbol, "def"),
+                (TokenKind::Symbol, "x"),
+                (TokenKind::Prefix, "~@"),
+                (TokenKind::Prefix, "'"),
+                (TokenKind::Open, "["),
+                (TokenKind::Number, "1"),
+                (TokenKind::Keyword, ":k"),
+                (TokenKind::String, "\"s\\\"\""),
+                (TokenKind::Close, "]"),
+                (TokenKind::Close, ")"),
+                (TokenKind::Comment, "; note"),
+                (TokenKind::Regex, "#\"r\""),
+                (TokenKind::Close, "}"),
+                (TokenKind::String, "\"open"),
+            ]
+        );
+        let errors = tokens("1/0 §");
+        assert_eq!(errors[0].kind, TokenKind::Error);
+        assert_eq!((errors[1].kind, errors[1].end), (TokenKind::Error, 6));
+    }
+
+    #[test]
+    fn test_read_string_ending_in_backslash() {
+        let mut reader = Reader::new("_test_.tiny", "\"a\\");
+        let err = reader.read().unwrap().unwrap_err();
+        assert_eq!(err.message, "expected closing `\"`, found EOF");
+        assert!(err.is_unterminated());
+    }
+
     #[test]
     fn test_read_floats() {
         let mut reader = Reader::new("_test_.tiny", "0. 0.0 -0.0 +0.0 1.23 +1.23 -1.23 0.125");
@@ -65,6 +131,7 @@ quux"
                 name: reader.name.into(),
                 start: 4,
                 end: 6,
+                kind: ReadErrorKind::InvalidEscape,
                 message: "invalid string escape `\\x`".into(),
             }))
         );
@@ -76,11 +143,32 @@ quux"
                 name: reader.name.into(),
                 start: 3,
                 end: 7,
+                kind: ReadErrorKind::UnterminatedString,
                 message: "expected closing `\"`, found EOF".into(),
             }))
         );
     }
 
+    #[test]
+    fn test_read_regexes() {
+        let mut reader = Reader::new("_test_.tiny", r#"#"\d+" #"a\"b" #"é|\\""#);
+        let regex = |pattern: &str| Some(Ok(Value::Regex(Regex::new(pattern).unwrap())));
+        assert_eq!(reader.read(), regex(r"\d+"));
+        assert_eq!(reader.read(), regex(r#"a\"b"#));
+        assert_eq!(reader.read(), regex(r"é|\\"));
+        assert_eq!(reader.read(), None);
+        assert_eq!(regex(r#"a\"b"#).unwrap().unwrap().to_string(), r#"#"a\"b""#);
+
+        let mut reader = Reader::new("_test_.tiny", r#" #"(a" "#);
+        match reader.read() {
+            Some(Err(err)) => {
+                assert_eq!((err.start, err.end), (1, 6));
+                assert!(err.message.starts_with("invalid regex:"), "{}", err.message);
+            }
+            result => panic!("{:?}", result),
+        }
+    }
+
     #[test]
     fn test_read_symbols() {
         let mut reader = Reader::new(
@@ -255,6 +343,7 @@ namespaced/symbol
                 name: reader.name.into(),
                 start: 2,
                 end: 10,
+                kind: ReadErrorKind::Unclosed { close: ')' },
                 message: "unclosed `(`".into(),
             }))
         );
@@ -307,6 +396,7 @@ namespaced/symbol
                 name: reader.name.into(),
                 start: 2,
                 end: 10,
+                kind: ReadErrorKind::Unclosed { close: ']' },
                 message: "unclosed `[`".into(),
             }))
         );
@@ -338,4 +428,108 @@ namespaced/symbol
         assert_eq!(reader.read(), Some(Ok(Value::ListBrace(Vec::new()))));
         assert_eq!(reader.read(), None);
     }
+
+    #[test]
+    fn test_read_spans() {
+        let mut reader = Reader::new("_test_.tiny", " foo (a [b 12]) ");
+        assert_eq!(
+            reader.read_spanned(),
+            Some(Ok((
+                Value::Symbol("".into(), "foo".into()),
+                Span {
+                    start: 1,
+                    end: 4,
+                    items: vec![],
+                }
+            )))
+        );
+        let (_, span) = reader.read_spanned().unwrap().unwrap();
+        assert_eq!(
+            span,
+            Span {
+                start: 5,
+                end: 15,
+                items: vec![
+                    Span {
+                        start: 6,
+                        end: 7,
+                        items: vec![],
+                    },
+                    Span {
+                        start: 8,
+                        end: 14,
+                        items: vec![
+                            Span {
+                                start: 9,
+                                end: 10,
+                                items: vec![],
+                            },
+                            Span {
+                                start: 11,
+                                end: 13,
+                                items: vec![],
+                            },
+                        ],
+                    },
+                ],
+            }
+        );
+        assert_eq!(reader.read_spanned(), None);
+    }
+
+    #[test]
+    fn test_read_quotes() {
+        let mut reader = Reader::new("_test_.tiny", "'a `(b ~c ~@d) ' [1] quote' `");
+        let sym = |name: &str| Value::Symbol("".into(), name.into());
+        assert_eq!(
+            reader.read(),
+            Some(Ok(Value::ListParen(vec![sym("quote"), sym("a")])))
+        );
+        assert_eq!(
+            reader.read(),
+            Some(Ok(Value::ListParen(vec![
+                sym("syntax-quote"),
+                Value::ListParen(vec![
+                    sym("b"),
+                    Value::ListParen(vec![sym("unquote"), sym("c")]),
+                    Value::ListParen(vec![sym("unquote-splicing"), sym("d")]),
+                ])
+            ])))
+        );
+        let (value, span) = reader.read_spanned().unwrap().unwrap();
+        assert_eq!(
+            value,
+            Value::ListParen(vec![sym("quote"), Value::ListBracket(vec![Value::Int(1)])])
+        );
+        assert_eq!((span.start, span.end, span.items[1].start), (15, 20, 17));
+        assert_eq!(reader.read(), Some(Ok(sym("quote'"))));
+        assert_eq!(
+            reader.read(),
+            Some(Err(ReadError {
+                name: reader.name.into(),
+                start: 28,
+                end: 29,
+                kind: ReadErrorKind::MissingForm { len: 1 },
+                message: "expected a form after ```".into(),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_read_deref() {
+        let mut reader = Reader::new("_test_.tiny", "@state @(:a m) a@b");
+        let sym = |name: &str| Value::Symbol("".into(), name.into());
+        assert_eq!(
+            reader.read(),
+            Some(Ok(Value::ListParen(vec![sym("deref"), sym("state")])))
+        );
+        assert_eq!(
+            reader.read(),
+            Some(Ok(Value::ListParen(vec![
+                sym("deref"),
+                Value::ListParen(vec![Value::Keyword("".into(), "a".into()), sym("m")])
+            ])))
+        );
+        assert_eq!(reader.read(), Some(Ok(sym("a@b"))));
+    }
 }
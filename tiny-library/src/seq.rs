@@ -0,0 +1,614 @@
+//! Sequences: every collection seen as a first item and the rest, and lazy
+//! sequences that compute their items a chunk at a time, when first needed.
+
+use crate::eval::EvalError;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, ThreadId};
+
+/// How many items lazy sequences like `range` compute at once.
+pub const CHUNK_SIZE: usize = 32;
+
+/// Computes the rest of a lazy sequence: any value `seq` accepts.
+pub type Thunk = Box<dyn FnOnce() -> Result<Value, EvalError> + Send>;
+
+/// A function a lazy sequence calls as it is realized.
+pub type Call = Arc<dyn Fn(Vec<Value>) -> Result<Value, EvalError> + Send + Sync>;
+
+/// Makes the items of a new chunk from the items of an old one.
+pub type ChunkFn = Arc<dyn Fn(&[Value]) -> Result<Vec<Value>, EvalError> + Send + Sync>;
+
+/// A sequence computed on first use and cached after that.
+pub struct LazySeq {
+    state: Mutex<State>,
+    realized: Condvar,
+}
+
+enum State {
+    Pending(Thunk),
+    Realizing(ThreadId),
+    Done(Option<Chunk>),
+    Failed(EvalError),
+}
+
+/// Some items of a sequence, and the sequence of the items after them.
+#[derive(Clone)]
+pub struct Chunk {
+    items: Arc<Vec<Value>>,
+    offset: usize,
+    more: Value,
+}
+
+impl Chunk {
+    /// A chunk of `items` followed by `more`, or `None` if there are no items.
+    pub fn new(items: Vec<Value>, more: Value) -> Option<Chunk> {
+        if items.is_empty() {
+            return None;
+        }
+        Some(Chunk {
+            items: Arc::new(items),
+            offset: 0,
+            more,
+        })
+    }
+
+    pub fn items(&self) -> &[Value] {
+        &self.items[self.offset..]
+    }
+
+    pub fn first(&self) -> &Value {
+        &self.items[self.offset]
+    }
+
+    /// The sequence without the first item.
+    pub fn rest(&self) -> Value {
+        self.skip(1)
+    }
+
+    /// The sequence after the items of this chunk.
+    pub fn more(&self) -> &Value {
+        &self.more
+    }
+
+    /// The sequence without the first `n` items of this chunk.
+    fn skip(&self, n: usize) -> Value {
+        if self.offset + n >= self.items.len() {
+            return self.more.clone();
+        }
+        let chunk = Chunk {
+            items: self.items.clone(),
+            offset: self.offset + n,
+            more: self.more.clone(),
+        };
+        Value::LazySeq(Arc::new(LazySeq::realized(Some(chunk))))
+    }
+}
+
+impl LazySeq {
+    pub fn new(thunk: Thunk) -> LazySeq {
+        LazySeq {
+            state: Mutex::new(State::Pending(thunk)),
+            realized: Condvar::new(),
+        }
+    }
+
+    /// A lazy sequence that is already computed.
+    pub fn realized(chunk: Option<Chunk>) -> LazySeq {
+        LazySeq {
+            state: Mutex::new(State::Done(chunk)),
+            realized: Condvar::new(),
+        }
+    }
+
+    pub fn is_realized(&self) -> bool {
+        matches!(
+            *self.state.lock().unwrap(),
+            State::Done(_) | State::Failed(_)
+        )
+    }
+
+    /// Computes the sequence the first time, or waits for the thread that
+    /// is computing it, and returns its first chunk.
+    pub fn realize(&self) -> Result<Option<Chunk>, EvalError> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            match &*state {
+                State::Done(chunk) => return Ok(chunk.clone()),
+                State::Failed(err) => return Err(err.clone()),
+                State::Realizing(id) if *id == thread::current().id() => {
+                    return Err(EvalError::new("lazy seq depends on itself"))
+                }
+                State::Realizing(_) => state = self.realized.wait(state).unwrap(),
+                State::Pending(_) => break,
+            }
+        }
+        let thunk = match std::mem::replace(&mut *state, State::Realizing(thread::current().id())) {
+            State::Pending(thunk) => thunk,
+            _ => unreachable!(),
+        };
+        std::mem::drop(state);
+        let result = thunk().and_then(|value| chunk(&value));
+        let mut state = self.state.lock().unwrap();
+        *state = match &result {
+            Ok(chunk) => State::Done(chunk.clone()),
+            Err(err) => State::Failed(err.clone()),
+        };
+        self.realized.notify_all();
+        result
+    }
+
+    fn iter(&self) -> Result<Iter, EvalError> {
+        Ok(Iter {
+            chunk: self.realize()?,
+            index: 0,
+            pending: None,
+        })
+    }
+
+    fn take_more(&mut self) -> Option<Value> {
+        match self.state.get_mut() {
+            Ok(State::Done(Some(chunk))) => Some(std::mem::replace(&mut chunk.more, Value::Nil)),
+            _ => None,
+        }
+    }
+}
+
+/// Unlinks the realized chunks after this one a seq at a time, as dropping
+/// them recursively would overflow the stack on long sequences.
+impl Drop for LazySeq {
+    fn drop(&mut self) {
+        let mut more = self.take_more();
+        while let Some(Value::LazySeq(seq)) = more {
+            more = match Arc::try_unwrap(seq) {
+                Ok(mut seq) => seq.take_more(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+impl fmt::Debug for LazySeq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.iter() {
+            Ok(items) => write_items(f, items),
+            Err(err) => write!(f, "#<error {}>", err.message),
+        }
+    }
+}
+
+/// Lazy sequences are equal when they have the same items, so comparing
+/// realizes both.
+impl PartialEq for LazySeq {
+    fn eq(&self, other: &LazySeq) -> bool {
+        if std::ptr::eq(self, other) {
+            return true;
+        }
+        let items = |seq: &LazySeq| seq.iter()?.collect::<Result<Vec<Value>, EvalError>>();
+        matches!((items(self), items(other)), (Ok(a), Ok(b)) if a == b)
+    }
+}
+
+impl PartialOrd for LazySeq {
+    fn partial_cmp(&self, other: &LazySeq) -> Option<Ordering> {
+        if self == other {
+            Some(Ordering::Equal)
+        } else {
+            None
+        }
+    }
+}
+
+/// Makes a lazy sequence of what `f` returns.
+pub fn lazy<F>(f: F) -> Value
+where
+    F: FnOnce() -> Result<Value, EvalError> + Send + 'static,
+{
+    Value::LazySeq(Arc::new(LazySeq::new(Box::new(f))))
+}
+
+/// A realized lazy sequence starting with `chunk`, or an empty one.
+pub fn from_chunk(chunk: Option<Chunk>) -> Value {
+    Value::LazySeq(Arc::new(LazySeq::realized(chunk)))
+}
+
+pub fn is_seqable(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Nil
+            | Value::String(_)
+            | Value::ListParen(_)
+            | Value::ListBracket(_)
+            | Value::ListBrace(_)
+            | Value::LazySeq(_)
+    )
+}
+
+/// The first chunk of `value` seen as a sequence, or `None` if it is empty.
+/// Maps are sequences of `[key value]` entries and strings of characters.
+pub fn chunk(value: &Value) -> Result<Option<Chunk>, EvalError> {
+    Ok(match value {
+        Value::Nil => None,
+        Value::ListParen(items) | Value::ListBracket(items) => {
+            Chunk::new(items.clone(), Value::Nil)
+        }
+        Value::ListBrace(items) => Chunk::new(
+            (items.chunks(2))
+                .map(|entry| Value::ListBracket(entry.to_vec()))
+                .collect(),
+            Value::Nil,
+        ),
+        Value::String(s) => Chunk::new(
+            s.chars().map(|ch| Value::String(ch.into())).collect(),
+            Value::Nil,
+        ),
+        Value::LazySeq(seq) => seq.realize()?,
+        value => {
+            return Err(EvalError::new(format!(
+                "don't know how to create a seq from {value}"
+            )))
+        }
+    })
+}
+
+/// `value` as a sequence, or nil if it is empty.
+pub fn seq(value: &Value) -> Result<Value, EvalError> {
+    match value {
+        Value::ListParen(items) if !items.is_empty() => Ok(value.clone()),
+        Value::LazySeq(_) => Ok(match chunk(value)? {
+            Some(_) => value.clone(),
+            None => Value::Nil,
+        }),
+        value => Ok(match chunk(value)? {
+            Some(chunk) => from_chunk(Some(chunk)),
+            None => Value::Nil,
+        }),
+    }
+}
+
+pub fn first(value: &Value) -> Result<Value, EvalError> {
+    match value {
+        Value::ListParen(items) | Value::ListBracket(items) => {
+            Ok(items.first().cloned().unwrap_or(Value::Nil))
+        }
+        value => Ok(chunk(value)?.map_or(Value::Nil, |chunk| chunk.first().clone())),
+    }
+}
+
+/// The items after the first, as an empty list if there are none.
+pub fn rest(value: &Value) -> Result<Value, EvalError> {
+    let rest = match chunk(value)? {
+        Some(chunk) => chunk.rest(),
+        None => Value::Nil,
+    };
+    Ok(match rest {
+        Value::Nil => Value::ListParen(vec![]),
+        rest => rest,
+    })
+}
+
+/// The items after the first, or nil if there are none.
+pub fn next(value: &Value) -> Result<Value, EvalError> {
+    match chunk(value)? {
+        Some(chunk) => seq(&chunk.rest()),
+        None => Ok(Value::Nil),
+    }
+}
+
+pub fn is_empty(value: &Value) -> Result<bool, EvalError> {
+    Ok(chunk(value)?.is_none())
+}
+
+/// `coll` with `value` in front, without realizing `coll`.
+pub fn cons(value: Value, coll: &Value) -> Result<Value, EvalError> {
+    match coll {
+        Value::Nil => Ok(Value::ListParen(vec![value])),
+        Value::ListParen(items) | Value::ListBracket(items) => {
+            let mut list = Vec::with_capacity(items.len() + 1);
+            list.push(value);
+            list.extend(items.iter().cloned());
+            Ok(Value::ListParen(list))
+        }
+        coll if is_seqable(coll) => Ok(from_chunk(Chunk::new(vec![value], coll.clone()))),
+        coll => Err(EvalError::new(format!(
+            "don't know how to create a seq from {coll}"
+        ))),
+    }
+}
+
+/// Iterates over the items of a sequence, realizing it as it goes.
+pub struct Iter {
+    chunk: Option<Chunk>,
+    index: usize,
+    pending: Option<Value>,
+}
+
+pub fn iter(value: &Value) -> Iter {
+    Iter {
+        chunk: None,
+        index: 0,
+        pending: Some(value.clone()),
+    }
+}
+
+impl Iterator for Iter {
+    type Item = Result<Value, EvalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(chunk) = &self.chunk {
+                if let Some(item) = chunk.items().get(self.index) {
+                    self.index += 1;
+                    return Some(Ok(item.clone()));
+                }
+                self.pending = Some(chunk.more().clone());
+            }
+            let pending = self.pending.take()?;
+            self.index = 0;
+            self.chunk = match chunk(&pending) {
+                Ok(Some(chunk)) => Some(chunk),
+                Ok(None) => return None,
+                Err(err) => {
+                    self.chunk = None;
+                    return Some(Err(err));
+                }
+            };
+        }
+    }
+}
+
+pub fn to_vec(value: &Value) -> Result<Vec<Value>, EvalError> {
+    match value {
+        Value::ListParen(items) | Value::ListBracket(items) => Ok(items.clone()),
+        value => iter(value).collect(),
+    }
+}
+
+/// The first `n` items of a sequence, and the sequence after them.
+pub fn split(value: &Value, n: usize) -> Result<(Vec<Value>, Value), EvalError> {
+    let mut items = vec![];
+    let mut rest = value.clone();
+    while items.len() < n {
+        let chunk = match chunk(&rest)? {
+            Some(chunk) => chunk,
+            None => return Ok((items, Value::Nil)),
+        };
+        let wanted = n - items.len();
+        if chunk.items().len() > wanted {
+            items.extend_from_slice(&chunk.items()[..wanted]);
+            return Ok((items, chunk.skip(wanted)));
+        }
+        items.extend_from_slice(chunk.items());
+        rest = chunk.more().clone();
+    }
+    Ok((items, rest))
+}
+
+/// The integers from `start` up to, not including, `end`, or forever if
+/// there is no end.
+pub fn range(start: i64, end: Option<i64>, step: i64) -> Value {
+    lazy(move || {
+        let mut items = Vec::with_capacity(CHUNK_SIZE);
+        let mut next = Some(start);
+        while let Some(n) = next {
+            let done = match end {
+                Some(end) => {
+                    (step > 0 && n >= end) || (step < 0 && n <= end) || (step == 0 && n == end)
+                }
+                None => false,
+            };
+            if done || items.len() == CHUNK_SIZE {
+                break;
+            }
+            items.push(Value::Int(n));
+            next = n.checked_add(step);
+        }
+        Ok(match next {
+            Some(n) if items.len() == CHUNK_SIZE => {
+                from_chunk(Chunk::new(items, range(n, end, step)))
+            }
+            _ => from_chunk(Chunk::new(items, Value::Nil)),
+        })
+    })
+}
+
+/// The first `n` items of `coll`, lazily.
+pub fn take(n: usize, coll: Value) -> Value {
+    lazy(move || {
+        if n == 0 {
+            return Ok(Value::Nil);
+        }
+        Ok(match chunk(&coll)? {
+            Some(chunk) if chunk.items().len() >= n => {
+                from_chunk(Chunk::new(chunk.items()[..n].to_vec(), Value::Nil))
+            }
+            Some(chunk) => {
+                let count = chunk.items().len();
+                let more = take(n - count, chunk.more().clone());
+                from_chunk(Chunk::new(chunk.items().to_vec(), more))
+            }
+            None => Value::Nil,
+        })
+    })
+}
+
+/// `coll` without its first `n` items, lazily.
+pub fn drop(n: usize, coll: Value) -> Value {
+    lazy(move || Ok(split(&coll, n)?.1))
+}
+
+/// Writes the items of a sequence as a list, realizing all of them.
+pub fn write_items(f: &mut fmt::Formatter, items: Iter) -> fmt::Result {
+    write!(f, "(")?;
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        match item {
+            Ok(item) => write!(f, "{item}")?,
+            Err(err) => write!(f, "#<error {}>", err.message)?,
+        }
+    }
+    write!(f, ")")
+}
+
+/// Lazily replaces each chunk of `coll` with what `f` makes of its items,
+/// leaving out the chunks it makes nothing of.
+pub fn map_chunks(coll: Value, f: ChunkFn) -> Value {
+    lazy(move || {
+        let mut coll = coll;
+        while let Some(chunk) = chunk(&coll)? {
+            let items = f(chunk.items())?;
+            if !items.is_empty() {
+                let more = map_chunks(chunk.more().clone(), f);
+                return Ok(from_chunk(Chunk::new(items, more)));
+            }
+            coll = chunk.more().clone();
+        }
+        Ok(Value::Nil)
+    })
+}
+
+/// Calls `f` with the first items of all `colls`, then the second ones and
+/// so on, until one runs out.
+pub fn map_many(f: Call, colls: Vec<Value>) -> Value {
+    lazy(move || {
+        let mut firsts = vec![];
+        let mut rests = vec![];
+        for coll in &colls {
+            match chunk(coll)? {
+                Some(chunk) => {
+                    firsts.push(chunk.first().clone());
+                    rests.push(chunk.rest());
+                }
+                None => return Ok(Value::Nil),
+            }
+        }
+        let value = f(firsts)?;
+        Ok(from_chunk(Chunk::new(vec![value], map_many(f, rests))))
+    })
+}
+
+/// The items of `inner` followed by the items of each sequence in `outer`.
+pub fn join(inner: Value, outer: Value) -> Value {
+    lazy(move || {
+        let (mut inner, mut outer) = (inner, outer);
+        loop {
+            if let Some(chunk) = chunk(&inner)? {
+                let more = join(chunk.more().clone(), outer);
+                return Ok(from_chunk(Chunk::new(chunk.items().to_vec(), more)));
+            }
+            match chunk(&outer)? {
+                Some(chunk) => {
+                    inner = chunk.first().clone();
+                    outer = chunk.rest();
+                }
+                None => return Ok(Value::Nil),
+            }
+        }
+    })
+}
+
+pub fn concat(colls: Vec<Value>) -> Value {
+    join(Value::Nil, Value::ListParen(colls))
+}
+
+/// The items of `coll` up to the first that `pred` is false for.
+pub fn take_while(pred: Call, coll: Value) -> Value {
+    lazy(move || {
+        let chunk = match chunk(&coll)? {
+            Some(chunk) => chunk,
+            None => return Ok(Value::Nil),
+        };
+        let mut items = vec![];
+        for item in chunk.items() {
+            if !pred(vec![item.clone()])?.is_truthy() {
+                return Ok(from_chunk(Chunk::new(items, Value::Nil)));
+            }
+            items.push(item.clone());
+        }
+        let more = take_while(pred, chunk.more().clone());
+        Ok(from_chunk(Chunk::new(items, more)))
+    })
+}
+
+/// The items of `coll` from the first that `pred` is false for.
+pub fn drop_while(pred: Call, coll: Value) -> Value {
+    lazy(move || {
+        let mut coll = coll;
+        while let Some(chunk) = chunk(&coll)? {
+            for (i, item) in chunk.items().iter().enumerate() {
+                if !pred(vec![item.clone()])?.is_truthy() {
+                    return Ok(chunk.skip(i));
+                }
+            }
+            coll = chunk.more().clone();
+        }
+        Ok(Value::Nil)
+    })
+}
+
+/// `value` forever.
+pub fn repeat(value: Value) -> Value {
+    lazy(move || {
+        let items = vec![value.clone(); CHUNK_SIZE];
+        Ok(from_chunk(Chunk::new(items, repeat(value))))
+    })
+}
+
+/// `x`, `(f x)`, `(f (f x))` and so on, calling `f` as items are needed.
+pub fn iterate(f: Call, x: Value) -> Value {
+    let next = x.clone();
+    let more = lazy(move || Ok(iterate(f.clone(), f(vec![next])?)));
+    from_chunk(Chunk::new(vec![x], more))
+}
+
+/// The items of `coll` over and over.
+pub fn cycle(coll: Value) -> Value {
+    lazy(move || {
+        if is_empty(&coll)? {
+            return Ok(Value::Nil);
+        }
+        Ok(join(coll.clone(), Value::ListParen(vec![cycle(coll)])))
+    })
+}
+
+/// Lists of `n` items of `coll`, each starting `step` items after the one
+/// before. Lists that run short are padded from `pad` and end the sequence,
+/// or are left out unless `all` is set.
+pub fn partition(n: usize, step: usize, pad: Option<Value>, all: bool, coll: Value) -> Value {
+    lazy(move || {
+        let (mut items, _) = split(&coll, n)?;
+        if items.is_empty() {
+            return Ok(Value::Nil);
+        }
+        if items.len() < n && !all {
+            return Ok(match &pad {
+                Some(pad) => {
+                    items.extend(split(pad, n - items.len())?.0);
+                    Value::ListParen(vec![Value::ListParen(items)])
+                }
+                None => Value::Nil,
+            });
+        }
+        let (_, more) = split(&coll, step)?;
+        let more = partition(n, step, pad, all, more);
+        Ok(from_chunk(Chunk::new(vec![Value::ListParen(items)], more)))
+    })
+}
+
+/// `acc`, then each value `f` reduces it to with the items of `coll`.
+pub fn reductions(f: Call, acc: Value, coll: Value) -> Value {
+    let more = {
+        let acc = acc.clone();
+        lazy(move || match chunk(&coll)? {
+            Some(chunk) => {
+                let acc = f(vec![acc, chunk.first().clone()])?;
+                Ok(reductions(f, acc, chunk.rest()))
+            }
+            None => Ok(Value::Nil),
+        })
+    };
+    from_chunk(Chunk::new(vec![acc], more))
+}
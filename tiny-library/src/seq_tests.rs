@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use seq::{self, CHUNK_SIZE};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use value::Value;
+
+    fn ints(values: &[i64]) -> Vec<Value> {
+        values.iter().map(|n| Value::Int(*n)).collect()
+    }
+
+    #[test]
+    fn test_seq_of_collections() {
+        let vector = Value::ListBracket(ints(&[1, 2, 3]));
+        assert_eq!(seq::first(&vector), Ok(Value::Int(1)));
+        assert_eq!(seq::to_vec(&seq::rest(&vector).unwrap()), Ok(ints(&[2, 3])));
+        assert_eq!(seq::next(&Value::ListParen(ints(&[1]))), Ok(Value::Nil));
+        assert_eq!(seq::rest(&Value::Nil), Ok(Value::ListParen(vec![])));
+        assert_eq!(
+            seq::to_vec(&Value::ListBrace(vec![Value::Int(1), Value::Int(2)])),
+            Ok(vec![Value::ListBracket(ints(&[1, 2]))])
+        );
+        assert_eq!(
+            seq::to_vec(&Value::String("añb".into())),
+            Ok(vec![
+                Value::String("a".into()),
+                Value::String("ñ".into()),
+                Value::String("b".into())
+            ])
+        );
+        assert_eq!(
+            seq::first(&Value::Int(1)).map_err(|err| err.message),
+            Err("don't know how to create a seq from 1".into())
+        );
+    }
+
+    #[test]
+    fn test_lazy_seq_is_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let lazy = seq::lazy(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(Value::ListBracket(ints(&[1, 2])))
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(seq::first(&lazy), Ok(Value::Int(1)));
+        assert_eq!(seq::to_vec(&lazy), Ok(ints(&[1, 2])));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_range_is_chunked() {
+        let range = seq::range(0, None, 1);
+        let chunk = seq::chunk(&range).unwrap().unwrap();
+        assert_eq!(chunk.items().len(), CHUNK_SIZE);
+        match chunk.more() {
+            Value::LazySeq(more) => assert!(!more.is_realized()),
+            more => panic!("{:?}", more),
+        }
+        assert_eq!(
+            seq::to_vec(&seq::range(10, Some(0), -3)),
+            Ok(ints(&[10, 7, 4, 1]))
+        );
+        assert_eq!(seq::to_vec(&seq::range(0, Some(0), 1)), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_take_and_drop() {
+        let range = seq::range(0, None, 1);
+        assert_eq!(
+            seq::to_vec(&seq::take(3, range.clone())),
+            Ok(ints(&[0, 1, 2]))
+        );
+        assert_eq!(
+            seq::to_vec(&seq::take(3, seq::drop(40, range))),
+            Ok(ints(&[40, 41, 42]))
+        );
+        let (items, rest) = seq::split(&seq::range(0, Some(5), 1), 2).unwrap();
+        assert_eq!(items, ints(&[0, 1]));
+        assert_eq!(seq::to_vec(&rest), Ok(ints(&[2, 3, 4])));
+    }
+
+    #[test]
+    fn test_long_seq_drops() {
+        let range = seq::range(0, None, 1);
+        let (items, _) = seq::split(&range, 1_000_000).unwrap();
+        assert_eq!(items.len(), 1_000_000);
+        drop(range);
+    }
+}
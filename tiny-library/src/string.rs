@@ -0,0 +1,527 @@
+//! The `std.string` namespace, and the regex functions of `tiny.core`.
+//!
+//! Strings are UTF-8 and every index here counts chars rather than bytes, so
+//! `index-of` and `subs` agree with `count` and `seq`.
+
+extern crate regex;
+use crate::core::{arity, to_str, Doc};
+use crate::eval::{Env, EvalError};
+use crate::math;
+use crate::seq;
+use crate::value::{NativeFn, Regex, Value};
+use std::convert::TryFrom;
+use std::iter::Peekable;
+use std::str::Chars;
+
+pub const NATIVES: &[(&str, NativeFn, Doc)] = &[
+    ("join", join, "Returns the text of the items of coll, separated by sep if given."),
+    ("split", split, "Splits s around matches of a string or regex, into at most limit parts if given."),
+    ("lines", lines, "Splits s into lines, dropping the \\n or \\r\\n ends."),
+    ("trim", trim, "Removes whitespace from both ends of s."),
+    ("triml", triml, "Removes whitespace from the start of s."),
+    ("trimr", trimr, "Removes whitespace from the end of s."),
+    ("trim-newline", trim_newline, "Removes every \\n and \\r from the end of s."),
+    ("replace", replace, "Replaces every match of a string or regex in s; a regex replacement may use $1 or be a function of the match."),
+    ("replace-first", replace_first, "Like replace, but only replaces the first match."),
+    ("starts-with?", starts_with, "Returns true if s starts with substr."),
+    ("ends-with?", ends_with, "Returns true if s ends with substr."),
+    ("includes?", includes, "Returns true if s contains substr."),
+    ("blank?", blank, "Returns true if s is nil, empty or only whitespace."),
+    ("upper-case", upper_case, "Returns s in upper case."),
+    ("lower-case", lower_case, "Returns s in lower case."),
+    ("capitalize", capitalize, "Returns s with its first char in upper case and the rest in lower case."),
+    ("reverse", reverse, "Returns the chars of s in reverse order."),
+    ("index-of", index_of, "Returns the index of the first substr in s at or after from, or nil."),
+    ("last-index-of", last_index_of, "Returns the index of the last substr in s at or before from, or nil."),
+    ("format", format, "Formats args printf style: %s, %d, %f, %e, %x, %o, %c, %b, %n and %% with flags -, +, 0 and a width and precision."),
+];
+
+pub const CORE_NATIVES: &[(&str, NativeFn, Doc)] = &[
+    ("subs", subs, "Returns the chars of s from start up to end, or its end."),
+    ("re-pattern", re_pattern, "Returns the regex that the string s is the pattern of."),
+    ("re-find", re_find, "Returns the first match of re in s, as a vector of it and its groups if re has any, or nil."),
+    ("re-matches", re_matches, "Returns the match of re if it matches all of s, like re-find, or nil."),
+    ("re-seq", re_seq, "Returns every match of re in s, like re-find, or nil if there is none."),
+    ("regex?", is_regex, "Returns true if x is a regex."),
+];
+
+fn string_arg<'a>(name: &str, x: &'a Value) -> Result<&'a str, EvalError> {
+    match x {
+        Value::String(s) => Ok(s),
+        x => Err(EvalError::new(format!("{name} expects a string, got {x}"))),
+    }
+}
+
+fn regex_arg<'a>(name: &str, x: &'a Value) -> Result<&'a Regex, EvalError> {
+    match x {
+        Value::Regex(re) => Ok(re),
+        x => Err(EvalError::new(format!("{name} expects a regex, got {x}"))),
+    }
+}
+
+/// The byte offset of the char at `index`, which may be one past the end.
+fn byte_offset(s: &str, index: i64) -> Option<usize> {
+    let index = usize::try_from(index).ok()?;
+    match s.char_indices().nth(index) {
+        Some((offset, _)) => Some(offset),
+        None if s.chars().count() == index => Some(s.len()),
+        None => None,
+    }
+}
+
+fn char_index(s: &str, offset: usize) -> Value {
+    Value::Int(s[..offset].chars().count() as i64)
+}
+
+fn index_arg(name: &str, s: &str, x: &Value) -> Result<usize, EvalError> {
+    match x {
+        Value::Int(n) => {
+            byte_offset(s, *n).ok_or_else(|| EvalError::new(format!("index out of bounds: {n}")))
+        }
+        x => Err(EvalError::new(format!("{name} expects an index, got {x}"))),
+    }
+}
+
+/// A match as the regex functions return it: the matched text, or a vector
+/// of it and each group, with nil for groups that did not take part.
+fn found(captures: &regex::Captures) -> Value {
+    if captures.len() == 1 {
+        return Value::String(captures[0].into());
+    }
+    Value::ListBracket(
+        captures
+            .iter()
+            .map(|group| match group {
+                Some(group) => Value::String(group.as_str().into()),
+                None => Value::Nil,
+            })
+            .collect(),
+    )
+}
+
+fn join(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (sep, coll) = match &args[..] {
+        [coll] => (String::new(), coll),
+        [sep, coll] => (to_str(sep), coll),
+        _ => return Err(arity("join", args.len())),
+    };
+    let items = seq::iter(coll)
+        .map(|item| item.map(|item| to_str(&item)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::String(items.join(&sep)))
+}
+
+fn split(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (s, sep, limit) = match &args[..] {
+        [s, sep] => (string_arg("split", s)?, sep, 0),
+        [s, sep, Value::Int(limit)] => (string_arg("split", s)?, sep, (*limit).max(0) as usize),
+        [_, _, limit] => {
+            return Err(EvalError::new(format!(
+                "split expects a limit, got {limit}"
+            )))
+        }
+        _ => return Err(arity("split", args.len())),
+    };
+    let mut parts: Vec<&str> = match (sep, limit) {
+        (Value::String(sep), 0) => s.split(sep.as_str()).collect(),
+        (Value::String(sep), limit) => s.splitn(limit, sep.as_str()).collect(),
+        (Value::Regex(re), 0) => re.split(s).collect(),
+        (Value::Regex(re), limit) => re.splitn(s, limit).collect(),
+        (sep, _) => {
+            return Err(EvalError::new(format!(
+                "split expects a string or regex separator, got {sep}"
+            )))
+        }
+    };
+    if limit == 0 {
+        while parts.len() > 1 && parts.last() == Some(&"") {
+            parts.pop();
+        }
+    }
+    Ok(Value::ListBracket(
+        parts
+            .into_iter()
+            .map(|part| Value::String(part.into()))
+            .collect(),
+    ))
+}
+
+fn lines(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [s] => Ok(Value::ListBracket(
+            (string_arg("lines", s)?.lines())
+                .map(|line| Value::String(line.into()))
+                .collect(),
+        )),
+        _ => Err(arity("lines", args.len())),
+    }
+}
+
+/// Defines a native taking one string `$s` and returning `$result`.
+macro_rules! string_fn {
+    ($fn_name:ident, $name:expr, |$s:ident| $result:expr) => {
+        fn $fn_name(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+            match &args[..] {
+                [s] => {
+                    let $s = string_arg($name, s)?;
+                    Ok($result)
+                }
+                _ => Err(arity($name, args.len())),
+            }
+        }
+    };
+}
+
+string_fn!(trim, "trim", |s| Value::String(s.trim().into()));
+string_fn!(triml, "triml", |s| Value::String(s.trim_start().into()));
+string_fn!(trimr, "trimr", |s| Value::String(s.trim_end().into()));
+string_fn!(trim_newline, "trim-newline", |s| Value::String(
+    s.trim_end_matches(['\n', '\r']).into()
+));
+string_fn!(upper_case, "upper-case", |s| Value::String(
+    s.to_uppercase()
+));
+string_fn!(lower_case, "lower-case", |s| Value::String(
+    s.to_lowercase()
+));
+string_fn!(reverse, "reverse", |s| Value::String(
+    s.chars().rev().collect()
+));
+string_fn!(capitalize, "capitalize", |s| {
+    let mut chars = s.chars();
+    Value::String(match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.as_str().to_lowercase().chars())
+            .collect(),
+        None => String::new(),
+    })
+});
+
+fn blank(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [Value::Nil] => Ok(Value::Bool(true)),
+        [s] => Ok(Value::Bool(string_arg("blank?", s)?.trim().is_empty())),
+        _ => Err(arity("blank?", args.len())),
+    }
+}
+
+fn replace_n(name: &str, args: Vec<Value>, env: &Env, limit: usize) -> Result<Value, EvalError> {
+    let (s, pattern, replacement) = match &args[..] {
+        [s, pattern, replacement] => (string_arg(name, s)?, pattern, replacement),
+        _ => return Err(arity(name, args.len())),
+    };
+    let replaced = match (pattern, replacement) {
+        (Value::String(from), Value::String(to)) if limit == 0 => s.replace(from.as_str(), to),
+        (Value::String(from), Value::String(to)) => s.replacen(from.as_str(), to, limit),
+        (Value::Regex(re), Value::String(to)) => re.replacen(s, limit, to.as_str()).into_owned(),
+        (Value::Regex(re), f @ Value::Fn(_)) => {
+            let mut replaced = String::new();
+            let mut last = 0;
+            for (i, captures) in re.captures_iter(s).enumerate() {
+                if limit > 0 && i == limit {
+                    break;
+                }
+                let whole = captures.get(0).unwrap();
+                replaced.push_str(&s[last..whole.start()]);
+                replaced.push_str(&to_str(&env.apply(f, vec![found(&captures)])?));
+                last = whole.end();
+            }
+            replaced.push_str(&s[last..]);
+            replaced
+        }
+        (pattern, replacement) => {
+            return Err(EvalError::new(format!(
+                "{name} cannot replace {pattern} with {replacement}"
+            )))
+        }
+    };
+    Ok(Value::String(replaced))
+}
+
+fn replace(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    replace_n("replace", args, env, 0)
+}
+
+fn replace_first(env: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    replace_n("replace-first", args, env, 1)
+}
+
+/// Defines a native testing a string against a substring.
+macro_rules! substring_fn {
+    ($fn_name:ident, $name:expr, $method:ident) => {
+        fn $fn_name(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+            match &args[..] {
+                [s, substr] => {
+                    let substr = string_arg($name, substr)?;
+                    Ok(Value::Bool(string_arg($name, s)?.$method(substr)))
+                }
+                _ => Err(arity($name, args.len())),
+            }
+        }
+    };
+}
+
+substring_fn!(starts_with, "starts-with?", starts_with);
+substring_fn!(ends_with, "ends-with?", ends_with);
+substring_fn!(includes, "includes?", contains);
+
+fn index_of(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (s, substr, from) = match &args[..] {
+        [s, substr] => (string_arg("index-of", s)?, substr, 0),
+        [s, substr, from] => {
+            let s = string_arg("index-of", s)?;
+            (s, substr, index_arg("index-of", s, from)?)
+        }
+        _ => return Err(arity("index-of", args.len())),
+    };
+    let substr = string_arg("index-of", substr)?;
+    Ok(match s[from..].find(substr) {
+        Some(offset) => char_index(s, from + offset),
+        None => Value::Nil,
+    })
+}
+
+fn last_index_of(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (s, substr, from) = match &args[..] {
+        [s, substr] => (string_arg("last-index-of", s)?, substr, None),
+        [s, substr, from] => {
+            let s = string_arg("last-index-of", s)?;
+            (s, substr, Some(index_arg("last-index-of", s, from)?))
+        }
+        _ => return Err(arity("last-index-of", args.len())),
+    };
+    let substr = string_arg("last-index-of", substr)?;
+    let end = match from {
+        Some(from) => (from + substr.len()).min(s.len()),
+        None => s.len(),
+    };
+    let end = (0..=end)
+        .rev()
+        .find(|end| s.is_char_boundary(*end))
+        .unwrap();
+    Ok(match s[..end].rfind(substr) {
+        Some(offset) => char_index(s, offset),
+        None => Value::Nil,
+    })
+}
+
+/// A `%` directive of `format`: its flags, width and precision.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// Pads `text` to the width, putting zeros after any sign.
+    fn pad(&self, text: String) -> String {
+        let len = text.chars().count();
+        if len >= self.width {
+            return text;
+        }
+        let fill = self.width - len;
+        if self.left {
+            format!("{text}{}", " ".repeat(fill))
+        } else if self.zero {
+            let digits = text.trim_start_matches(['-', '+']);
+            let sign = &text[..text.len() - digits.len()];
+            format!("{sign}{}{digits}", "0".repeat(fill))
+        } else {
+            format!("{}{text}", " ".repeat(fill))
+        }
+    }
+
+    fn sign(&self, text: String) -> String {
+        if self.plus && !text.starts_with('-') {
+            format!("+{text}")
+        } else {
+            text
+        }
+    }
+}
+
+fn format(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (template, args) = match &args[..] {
+        [template, args @ ..] => (string_arg("format", template)?, args),
+        [] => return Err(arity("format", 0)),
+    };
+    let mut args = args.iter();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            text.push(ch);
+            continue;
+        }
+        let mut spec = Spec::default();
+        while let Some(flag) = chars.next_if(|ch| matches!(ch, '-' | '0' | '+')) {
+            match flag {
+                '-' => spec.left = true,
+                '0' => spec.zero = true,
+                _ => spec.plus = true,
+            }
+        }
+        spec.width = format_number(&mut chars, "width")?;
+        if chars.next_if_eq(&'.').is_some() {
+            spec.precision = Some(format_number(&mut chars, "precision")?);
+        }
+        let conversion = chars
+            .next()
+            .ok_or_else(|| EvalError::new("format string ends in the middle of a %"))?;
+        match conversion {
+            '%' => {
+                text.push('%');
+                continue;
+            }
+            'n' => {
+                text.push('\n');
+                continue;
+            }
+            _ => {}
+        }
+        let arg = args.next().ok_or_else(|| {
+            EvalError::new(format!("format is missing an argument for %{conversion}"))
+        })?;
+        let formatted = match (conversion, arg) {
+            ('s', arg) => {
+                let s = to_str(arg);
+                match spec.precision {
+                    Some(precision) => s.chars().take(precision).collect(),
+                    None => s,
+                }
+            }
+            ('d', Value::Int(_) | Value::BigInt(_)) => spec.sign(arg.to_string()),
+            ('x', Value::Int(n)) => format!("{n:x}"),
+            ('X', Value::Int(n)) => format!("{n:X}"),
+            ('o', Value::Int(n)) => format!("{n:o}"),
+            ('b', arg) => arg.is_truthy().to_string(),
+            ('c', Value::String(s)) if s.chars().count() == 1 => s.clone(),
+            (
+                'f' | 'e',
+                Value::Int(_) | Value::BigInt(_) | Value::Float(_) | Value::Rational(_),
+            ) => float(conversion, &spec, math::num(arg)?.to_f64()),
+            ('d' | 'x' | 'X' | 'o' | 'c' | 'f' | 'e', arg) => {
+                return Err(EvalError::new(format!("%{conversion} cannot format {arg}")))
+            }
+            (conversion, _) => {
+                return Err(EvalError::new(format!(
+                    "unknown format conversion %{conversion}"
+                )))
+            }
+        };
+        text.push_str(&spec.pad(formatted));
+    }
+    Ok(Value::String(text))
+}
+
+/// The largest width or precision `format` takes.
+const MAX_FORMAT_NUMBER: usize = 10_000;
+
+/// Reads the digits of a width or precision, 0 if there are none.
+fn format_number(chars: &mut Peekable<Chars>, what: &str) -> Result<usize, EvalError> {
+    let mut n: usize = 0;
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        n = (n.checked_mul(10))
+            .and_then(|n| n.checked_add(digit.to_digit(10).unwrap() as usize))
+            .filter(|&n| n <= MAX_FORMAT_NUMBER)
+            .ok_or_else(|| EvalError::new(format!("format {what} is above {MAX_FORMAT_NUMBER}")))?;
+    }
+    Ok(n)
+}
+
+fn float(conversion: char, spec: &Spec, n: f64) -> String {
+    let precision = spec.precision.unwrap_or(6);
+    spec.sign(match conversion {
+        'e' => {
+            let text = format!("{n:.precision$e}");
+            let (mantissa, exponent) = text.split_once('e').unwrap();
+            let exponent: i32 = exponent.parse().unwrap();
+            let sign = if exponent < 0 { '-' } else { '+' };
+            format!("{mantissa}e{sign}{:02}", exponent.abs())
+        }
+        _ => format!("{n:.precision$}"),
+    })
+}
+
+fn subs(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    let (s, start, end) = match &args[..] {
+        [s, start] => {
+            let s = string_arg("subs", s)?;
+            (s, index_arg("subs", s, start)?, s.len())
+        }
+        [s, start, end] => {
+            let s = string_arg("subs", s)?;
+            (s, index_arg("subs", s, start)?, index_arg("subs", s, end)?)
+        }
+        _ => return Err(arity("subs", args.len())),
+    };
+    match s.get(start..end) {
+        Some(sub) => Ok(Value::String(sub.into())),
+        None => Err(EvalError::new(format!(
+            "subs expects start <= end, got {} and {}",
+            char_index(s, start),
+            char_index(s, end)
+        ))),
+    }
+}
+
+fn re_pattern(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [re @ Value::Regex(_)] => Ok(re.clone()),
+        [pattern] => Regex::new(string_arg("re-pattern", pattern)?)
+            .map(Value::Regex)
+            .map_err(|message| EvalError::new(format!("invalid regex: {message}"))),
+        _ => Err(arity("re-pattern", args.len())),
+    }
+}
+
+fn re_find(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [re, s] => {
+            let captures = regex_arg("re-find", re)?.captures(string_arg("re-find", s)?);
+            Ok(captures.as_ref().map_or(Value::Nil, found))
+        }
+        _ => Err(arity("re-find", args.len())),
+    }
+}
+
+fn re_matches(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [re, s] => {
+            let re = regex_arg("re-matches", re)?;
+            let whole = Regex::new(&format!(r"\A(?:{})\z", re.as_str())).map_err(EvalError::new)?;
+            let captures = whole.captures(string_arg("re-matches", s)?);
+            Ok(captures.as_ref().map_or(Value::Nil, found))
+        }
+        _ => Err(arity("re-matches", args.len())),
+    }
+}
+
+fn re_seq(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [re, s] => {
+            let re = regex_arg("re-seq", re)?;
+            let matches: Vec<Value> = re
+                .captures_iter(string_arg("re-seq", s)?)
+                .map(|c| found(&c))
+                .collect();
+            Ok(if matches.is_empty() {
+                Value::Nil
+            } else {
+                Value::ListParen(matches)
+            })
+        }
+        _ => Err(arity("re-seq", args.len())),
+    }
+}
+
+fn is_regex(_: &Env, args: Vec<Value>) -> Result<Value, EvalError> {
+    match &args[..] {
+        [x] => Ok(Value::Bool(matches!(x, Value::Regex(_)))),
+        _ => Err(arity("regex?", args.len())),
+    }
+}
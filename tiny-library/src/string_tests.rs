@@ -0,0 +1,225 @@
+#[cfg(test)]
+mod tests {
+    use eval::Env;
+
+    /// Evaluates `content` with `std.string` imported as `s` and prints the
+    /// result, or the error message.
+    fn show(content: &str) -> String {
+        let content = format!("(import [std.string :as s]) {content}");
+        match Env::new().eval_str("_test_.tiny", &content) {
+            Ok(value) => value.to_string(),
+            Err(err) => format!("error: {}", err.message),
+        }
+    }
+
+    #[test]
+    fn test_import() {
+        assert_eq!(show("(s/trim \" a \")"), "\"a\"");
+        let env = Env::new();
+        env.eval_str("_test_.tiny", "(import [std.string :refer [upper-case]])")
+            .unwrap();
+        assert_eq!(
+            env.eval_str("_test_.tiny", "(upper-case \"a\")")
+                .unwrap()
+                .to_string(),
+            "\"A\""
+        );
+        assert!(env.doc("std.string/join").unwrap().contains("separated"));
+    }
+
+    #[test]
+    fn test_join() {
+        assert_eq!(show("(s/join [1 \"a\" nil :b])"), "\"1a:b\"");
+        assert_eq!(show("(s/join \", \" (range 3))"), "\"0, 1, 2\"");
+        assert_eq!(show("(s/join \"-\" [])"), "\"\"");
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            show("(s/split \"a,b,,c,,\" #\",\")"),
+            "[\"a\" \"b\" \"\" \"c\"]"
+        );
+        assert_eq!(show("(s/split \"a b  c\" #\"\\s+\" 2)"), "[\"a\" \"b  c\"]");
+        assert_eq!(show("(s/split \"añbñc\" \"ñ\")"), "[\"a\" \"b\" \"c\"]");
+        assert_eq!(show("(s/split \"\" #\",\")"), "[\"\"]");
+        assert_eq!(
+            show("(s/split \"a\" 1)"),
+            "error: split expects a string or regex separator, got 1"
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        assert_eq!(
+            show("(s/lines \"a\\r\\nb\\n\\nc\\n\")"),
+            "[\"a\" \"b\" \"\" \"c\"]"
+        );
+        assert_eq!(show("(s/lines \"\")"), "[]");
+    }
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(
+            show(
+                "(s/trim \" \\t héllo\\u3000\\n\")"
+                    .replace("\\u3000", "\u{3000}")
+                    .as_str()
+            ),
+            "\"héllo\""
+        );
+        assert_eq!(
+            show("[(s/triml \" a \") (s/trimr \" a \")]"),
+            "[\"a \" \" a\"]"
+        );
+        assert_eq!(show("(s/trim-newline \"a\\r\\n\\n\")"), "\"a\"");
+        assert_eq!(show("(s/trim 1)"), "error: trim expects a string, got 1");
+    }
+
+    #[test]
+    fn test_replace() {
+        assert_eq!(show("(s/replace \"a.b.c\" \".\" \"-\")"), "\"a-b-c\"");
+        assert_eq!(
+            show("(s/replace \"hello world\" #\"o(\\w?)\" \"0$1\")"),
+            "\"hell0 w0rld\""
+        );
+        assert_eq!(
+            show("(s/replace \"hi bob\" #\"(\\w)(\\w+)\" (fn [[_ a b]] (str (s/upper-case a) b)))"),
+            "\"Hi Bob\""
+        );
+        assert_eq!(show("(s/replace-first \"aaa\" \"a\" \"b\")"), "\"baa\"");
+        assert_eq!(
+            show("(s/replace-first \"aaa\" #\"a\" s/upper-case)"),
+            "\"Aaa\""
+        );
+        assert_eq!(
+            show("(s/replace \"a\" \"a\" 1)"),
+            "error: replace cannot replace \"a\" with 1"
+        );
+    }
+
+    #[test]
+    fn test_substrings() {
+        assert_eq!(
+            show("[(s/starts-with? \"héllo\" \"hé\") (s/ends-with? \"héllo\" \"lo\") (s/includes? \"héllo\" \"x\")]"),
+            "[true true false]"
+        );
+        assert_eq!(
+            show("[(s/blank? nil) (s/blank? \" \\n\") (s/blank? \" a\")]"),
+            "[true true false]"
+        );
+    }
+
+    #[test]
+    fn test_case() {
+        assert_eq!(show("(s/upper-case \"straße\")"), "\"STRASSE\"");
+        assert_eq!(show("(s/lower-case \"ÉCOLE\")"), "\"école\"");
+        assert_eq!(show("(s/capitalize \"éCOLE\")"), "\"École\"");
+        assert_eq!(show("(s/capitalize \"\")"), "\"\"");
+    }
+
+    #[test]
+    fn test_reverse() {
+        assert_eq!(show("(s/reverse \"añb\")"), "\"bña\"");
+    }
+
+    #[test]
+    fn test_index_of() {
+        assert_eq!(show("(s/index-of \"héllo wörld\" \"wö\")"), "6");
+        assert_eq!(show("(s/index-of \"abcabc\" \"b\" 2)"), "4");
+        assert_eq!(show("(s/index-of \"abc\" \"z\")"), "nil");
+        assert_eq!(show("(s/last-index-of \"ébcébc\" \"é\")"), "3");
+        assert_eq!(show("(s/last-index-of \"ébcébc\" \"é\" 2)"), "0");
+        assert_eq!(
+            show("(s/index-of \"abc\" \"a\" 4)"),
+            "error: index out of bounds: 4"
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            show("(s/format \"%-5s|%5s|%05d|%+d|%.2f|%x|%e|%%|%s|%.2s\" \"ab\" \"cd\" -42 7 3.14159 255 12345.678 nil \"héllo\")"),
+            "\"ab   |   cd|-0042|+7|3.14|ff|1.234568e+04|%||hé\""
+        );
+        assert_eq!(show("(s/format \"%5s|\" \"é\")"), "\"    é|\"");
+        assert_eq!(show("(s/format \"%d\" 1.5)"), "error: %d cannot format 1.5");
+        assert_eq!(
+            show("(s/format \"%d %d\" 1)"),
+            "error: format is missing an argument for %d"
+        );
+        assert_eq!(
+            show("(s/format \"%q\" 1)"),
+            "error: unknown format conversion %q"
+        );
+        assert_eq!(
+            show("(s/format \"%99999999999999999999s\" 1)"),
+            "error: format width is above 10000"
+        );
+        assert_eq!(
+            show("(s/format \"%.99999999999f\" 1.0)"),
+            "error: format precision is above 10000"
+        );
+        assert_eq!(show("(count (s/format \"%10000s\" 1))"), "10000");
+    }
+
+    #[test]
+    fn test_subs() {
+        assert_eq!(
+            show("[(subs \"héllo\" 1 3) (subs \"héllo\" 2) (subs \"é\" 1)]"),
+            "[\"él\" \"llo\" \"\"]"
+        );
+        assert_eq!(show("(subs \"abc\" 4)"), "error: index out of bounds: 4");
+        assert_eq!(
+            show("(subs \"abc\" 2 1)"),
+            "error: subs expects start <= end, got 2 and 1"
+        );
+    }
+
+    #[test]
+    fn test_regex_values() {
+        assert_eq!(
+            show("[(regex? #\"a\") (regex? \"a\") (= #\"a\" (re-pattern \"a\"))]"),
+            "[true false true]"
+        );
+        assert_eq!(show("(re-pattern \"a\\\"b\")"), "#\"a\\\"b\"");
+        assert!(show("(re-pattern \"(\")").starts_with("error: invalid regex:"));
+    }
+
+    #[test]
+    fn test_re_find() {
+        assert_eq!(show("(re-find #\"\\d+\" \"ab 12 34\")"), "\"12\"");
+        assert_eq!(
+            show("(re-find #\"(\\d+)-(\\d+)?\" \"tel 12- x\")"),
+            "[\"12-\" \"12\" nil]"
+        );
+        assert_eq!(show("(re-find #\"\\d\" \"abc\")"), "nil");
+    }
+
+    #[test]
+    fn test_re_matches() {
+        assert_eq!(show("(re-matches #\"a|ab\" \"ab\")"), "\"ab\"");
+        assert_eq!(show("(re-matches #\"\\d+\" \"12a\")"), "nil");
+        assert_eq!(
+            show("(re-matches #\"(\\w)(\\w)\" \"añ\")"),
+            "[\"añ\" \"a\" \"ñ\"]"
+        );
+    }
+
+    #[test]
+    fn test_re_seq() {
+        assert_eq!(
+            show("(re-seq #\"\\p{L}+\" \"héllo, wörld 42\")"),
+            "(\"héllo\" \"wörld\")"
+        );
+        assert_eq!(
+            show("(re-seq #\"(\\d)\" \"a1b2\")"),
+            "([\"1\" \"1\"] [\"2\" \"2\"])"
+        );
+        assert_eq!(show("(re-seq #\"\\d\" \"ab\")"), "nil");
+        assert_eq!(
+            show("(re-seq \"a\" \"ab\")"),
+            "error: re-seq expects a regex, got \"a\""
+        );
+    }
+}
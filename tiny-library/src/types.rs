@@ -0,0 +1,269 @@
+use crate::eval::EvalError;
+use crate::value::Value;
+use std::fmt;
+use std::sync::Arc;
+
+/// The declared type of a struct field, checked whenever the field is set.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum Type {
+    Any,
+    Int,
+    Float,
+    Number,
+    String,
+    Bool,
+    Keyword,
+    Symbol,
+    List,
+    Vector,
+    Map,
+    Fn,
+    ListOf(Box<Type>),
+    Maybe(Box<Type>),
+    /// A user-defined type, by qualified name and as written.
+    Named(String, String),
+}
+
+impl Type {
+    /// Parses a type form such as `Int`, `lib/string` or `(List Todo)`;
+    /// `resolve` qualifies the names of user-defined types.
+    pub fn parse(
+        form: &Value,
+        resolve: &dyn Fn(&str) -> Result<String, String>,
+    ) -> Result<Type, String> {
+        match form {
+            Value::Symbol(_, name) => {
+                let base = name.rsplit('/').next().unwrap_or(name);
+                Ok(match base.to_lowercase().as_str() {
+                    "any" => Type::Any,
+                    "int" => Type::Int,
+                    "float" => Type::Float,
+                    "number" => Type::Number,
+                    "string" | "str" => Type::String,
+                    "bool" | "boolean" => Type::Bool,
+                    "keyword" => Type::Keyword,
+                    "symbol" => Type::Symbol,
+                    "list" => Type::List,
+                    "vector" => Type::Vector,
+                    "map" => Type::Map,
+                    "fn" => Type::Fn,
+                    _ => Type::Named(resolve(name)?, name.clone()),
+                })
+            }
+            Value::ListParen(items) => match &items[..] {
+                [Value::Symbol(_, head), inner] if head == "List" => {
+                    Ok(Type::ListOf(Box::new(Type::parse(inner, resolve)?)))
+                }
+                [Value::Symbol(_, head), inner] if head == "Maybe" => {
+                    Ok(Type::Maybe(Box::new(Type::parse(inner, resolve)?)))
+                }
+                _ => Err(format!("unsupported type: {form}")),
+            },
+            form => Err(format!("unsupported type: {form}")),
+        }
+    }
+
+    pub fn check(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Type::Any, _)
+            | (Type::Int, Value::Int(_))
+            | (Type::Float, Value::Float(_))
+            | (
+                Type::Number,
+                Value::Int(_) | Value::BigInt(_) | Value::Float(_) | Value::Rational(_),
+            )
+            | (Type::String, Value::String(_))
+            | (Type::Bool, Value::Bool(_))
+            | (Type::Keyword, Value::Keyword(_, _))
+            | (Type::Symbol, Value::Symbol(_, _))
+            | (Type::List, Value::ListParen(_) | Value::ListBracket(_) | Value::LazySeq(_))
+            | (Type::Vector, Value::ListBracket(_))
+            | (Type::Map, Value::ListBrace(_))
+            | (Type::Fn, Value::Fn(_))
+            | (Type::Maybe(_), Value::Nil) => true,
+            (Type::ListOf(item), Value::ListParen(items) | Value::ListBracket(items)) => {
+                items.iter().all(|value| item.check(value))
+            }
+            (Type::Maybe(inner), value) => inner.check(value),
+            (Type::Named(key, _), Value::Struct(instance)) => instance.ty.key == *key,
+            (Type::Named(key, _), Value::Variant(variant)) => variant.ty.key == *key,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::ListOf(item) => write!(f, "(List {item})"),
+            Type::Maybe(inner) => write!(f, "(Maybe {inner})"),
+            Type::Named(_, name) => write!(f, "{name}"),
+            ty => write!(f, "{ty:?}"),
+        }
+    }
+}
+
+/// A type defined by `defstruct`.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct StructType {
+    pub name: String,
+    /// The qualified name of the constructor, which identifies the type.
+    pub key: String,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct Field {
+    pub name: String,
+    pub ty: Option<Type>,
+    pub default: Option<Value>,
+    /// The name of the accessor function, `Type-field`.
+    pub accessor: String,
+}
+
+impl StructType {
+    /// Builds an instance from positional values, one per field, or from
+    /// `:field value` pairs with defaults for the fields left out.
+    pub fn construct(ty: &Arc<StructType>, args: Vec<Value>) -> Result<Value, EvalError> {
+        let by_keyword = args.len().is_multiple_of(2)
+            && matches!(args.first(), Some(Value::Keyword(_, name)) if ty.index(name).is_some());
+        let values = if by_keyword {
+            let mut values: Vec<Value> = (ty.fields.iter())
+                .map(|field| field.default.clone().unwrap_or(Value::Nil))
+                .collect();
+            for pair in args.chunks(2) {
+                match &pair[0] {
+                    Value::Keyword(_, name) if ty.index(name).is_some() => {
+                        values[ty.index(name).unwrap()] = pair[1].clone();
+                    }
+                    key => return Err(EvalError::new(format!("{} has no field {key}", ty.name))),
+                }
+            }
+            values
+        } else if args.len() == ty.fields.len() {
+            args
+        } else {
+            return Err(EvalError::new(format!(
+                "wrong number of args ({}) passed to {}",
+                args.len(),
+                ty.name
+            )));
+        };
+        for (field, value) in ty.fields.iter().zip(&values) {
+            ty.check(field, value)?;
+        }
+        Ok(Value::Struct(Arc::new(Struct {
+            ty: ty.clone(),
+            values,
+        })))
+    }
+
+    pub fn index(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f.name == field)
+    }
+
+    pub fn check(&self, field: &Field, value: &Value) -> Result<(), EvalError> {
+        match &field.ty {
+            Some(ty) if !ty.check(value) => Err(EvalError::new(format!(
+                "field {} of {} expects {ty}, got {value}",
+                field.name, self.name
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An instance of a struct type, with one value per field.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct Struct {
+    pub ty: Arc<StructType>,
+    pub values: Vec<Value>,
+}
+
+impl Struct {
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.ty.index(field).map(|i| &self.values[i])
+    }
+}
+
+impl fmt::Display for Struct {
+    /// Prints as a call to the constructor, which reads back as the same value.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}", self.ty.name)?;
+        for (field, value) in self.ty.fields.iter().zip(&self.values) {
+            write!(f, " :{} {value}", field.name)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// A tagged union defined by `defenum`.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct EnumType {
+    pub name: String,
+    /// The qualified name of the enum, which identifies the type.
+    pub key: String,
+    pub variants: Vec<VariantType>,
+}
+
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct VariantType {
+    pub name: String,
+    pub fields: Vec<Type>,
+}
+
+impl EnumType {
+    /// Builds the `tag`th variant, checking each value against its field type.
+    pub fn construct(
+        ty: &Arc<EnumType>,
+        tag: usize,
+        values: Vec<Value>,
+    ) -> Result<Value, EvalError> {
+        let variant = &ty.variants[tag];
+        if values.len() != variant.fields.len() {
+            return Err(EvalError::new(format!(
+                "wrong number of args ({}) passed to {}",
+                values.len(),
+                variant.name
+            )));
+        }
+        for (i, (field, value)) in variant.fields.iter().zip(&values).enumerate() {
+            if !field.check(value) {
+                return Err(EvalError::new(format!(
+                    "argument {} of {} expects {field}, got {value}",
+                    i + 1,
+                    variant.name
+                )));
+            }
+        }
+        Ok(Value::Variant(Arc::new(Variant {
+            ty: ty.clone(),
+            tag,
+            values,
+        })))
+    }
+}
+
+/// A value of an enum type: one of its variants and the values it holds.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct Variant {
+    pub ty: Arc<EnumType>,
+    pub tag: usize,
+    pub values: Vec<Value>,
+}
+
+impl fmt::Display for Variant {
+    /// Prints as a call to the variant's constructor, or just its name for
+    /// variants without fields.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = &self.ty.variants[self.tag].name;
+        if self.values.is_empty() {
+            return write!(f, "{name}");
+        }
+        write!(f, "({name}")?;
+        for value in &self.values {
+            write!(f, " {value}")?;
+        }
+        write!(f, ")")
+    }
+}
@@ -1,6 +1,21 @@
+extern crate num_bigint;
+extern crate regex;
+use self::num_bigint::BigInt;
+use crate::csp::Channel;
+use crate::eval::{Env, EvalError, Lambda};
+use crate::io::Handle;
+use crate::seq::LazySeq;
+use crate::types::{EnumType, Struct, StructType, Variant};
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum Value {
+    Nil,
+    Bool(bool),
     Int(i64),
+    BigInt(BigInt),
     Float(f64),
     Rational(Ratio),
     String(String),
@@ -9,22 +24,114 @@ pub enum Value {
     ListParen(Vec<Value>),
     ListBracket(Vec<Value>),
     ListBrace(Vec<Value>),
+    Fn(Function),
+    Struct(Arc<Struct>),
+    Variant(Arc<Variant>),
+    Error(Arc<ExInfo>),
+    Atom(Arc<Atom>),
+    Chan(Arc<Channel>),
+    LazySeq(Arc<LazySeq>),
+    Regex(Regex),
+    Handle(Arc<Handle>),
 }
 
 impl Value {
     pub fn dump(&self, ident: &str) {
         match self {
+            Value::Nil => println!("{ident}'nil' nil"),
+            Value::Bool(v) => println!("{ident}'{v}' bool"),
+
             Value::String(v) => println!("{ident}'{v}' string"),
             Value::Symbol(_, v) => println!("{ident}'{v}' symbol"),
             Value::Keyword(_, v) => println!("{ident}'{v}' keyword"),
 
             Value::Int(v) => println!("{ident}'{v}' int"),
+            Value::BigInt(v) => println!("{ident}'{v}' bigint"),
             Value::Float(v) => println!("{ident}'{v}' float"),
             Value::Rational(rat) => println!("{ident}'{}/{}' rational", rat.numer, rat.denom),
 
             Value::ListParen(list) => dump_list(list, ident, '(', ')'),
             Value::ListBracket(list) => dump_list(list, ident, '[', ']'),
             Value::ListBrace(list) => dump_list(list, ident, '{', '}'),
+
+            Value::Fn(f) => println!("{ident}'{}' fn", f.name()),
+            Value::Struct(s) => println!("{ident}'{s}' struct"),
+            Value::Variant(v) => println!("{ident}'{v}' variant"),
+            Value::Error(ex) => println!("{ident}'{}' error", ex.message),
+            Value::Atom(atom) => println!("{ident}'{:?}' atom", atom),
+            Value::Chan(_) => println!("{ident}'chan' chan"),
+            Value::LazySeq(seq) => println!("{ident}'{:?}' lazy-seq", seq),
+            Value::Regex(re) => println!("{ident}'{}' regex", re.as_str()),
+            Value::Handle(handle) => println!("{ident}'{:?}' handle", handle),
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(v) => write!(f, "{v}"),
+            Value::Int(v) => write!(f, "{v}"),
+            Value::BigInt(v) => write!(f, "{v}"),
+            Value::Float(v) => write!(f, "{v:?}"),
+            Value::Rational(rat) => write!(f, "{}/{}", rat.numer, rat.denom),
+            Value::String(v) => {
+                write!(f, "\"")?;
+                for ch in v.chars() {
+                    match ch {
+                        '\t' => write!(f, "\\t")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '"' => write!(f, "\\\"")?,
+                        ch => write!(f, "{ch}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Value::Symbol(ns, name) if ns.is_empty() => write!(f, "{name}"),
+            Value::Symbol(ns, name) => write!(f, "{ns}/{name}"),
+            Value::Keyword(ns, name) if ns.is_empty() => write!(f, ":{name}"),
+            Value::Keyword(ns, name) => write!(f, ":{ns}/{name}"),
+            Value::ListParen(list) => write_list(f, list, '(', ')'),
+            Value::ListBracket(list) => write_list(f, list, '[', ']'),
+            Value::ListBrace(list) => write_list(f, list, '{', '}'),
+            Value::Fn(func) => write!(f, "#<fn {}>", func.name()),
+            Value::Struct(s) => write!(f, "{s}"),
+            Value::Variant(v) => write!(f, "{v}"),
+            Value::Error(ex) => {
+                write!(
+                    f,
+                    "(ex-info {} {}",
+                    Value::String(ex.message.clone()),
+                    ex.data
+                )?;
+                if let Some(cause) = &ex.cause {
+                    write!(f, " {cause}")?;
+                }
+                write!(f, ")")
+            }
+            Value::Atom(atom) => write!(f, "#<atom {}>", atom.deref()),
+            Value::Chan(_) => write!(f, "#<chan>"),
+            Value::LazySeq(seq) => write!(f, "{:?}", seq),
+            Value::Regex(re) => {
+                write!(f, "#\"")?;
+                let mut escaped = false;
+                for ch in re.as_str().chars() {
+                    if ch == '"' && !escaped {
+                        write!(f, "\\")?;
+                    }
+                    escaped = ch == '\\' && !escaped;
+                    write!(f, "{ch}")?;
+                }
+                write!(f, "\"")
+            }
+            Value::Handle(handle) => write!(f, "{:?}", handle),
         }
     }
 }
@@ -39,6 +146,178 @@ impl Ratio {
     pub fn new(numer: i64, denom: i64) -> Ratio {
         Ratio { numer, denom }
     }
+
+    pub fn numer(&self) -> i64 {
+        self.numer
+    }
+
+    pub fn denom(&self) -> i64 {
+        self.denom
+    }
+}
+
+/// An error value made by `ex-info`: a message, a map of data and maybe
+/// the error that caused it.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct ExInfo {
+    pub message: String,
+    pub data: Value,
+    pub cause: Option<Value>,
+}
+
+/// A reference to a value that threads can share and update atomically.
+pub struct Atom {
+    /// The value and how many times it was set, so `swap!` can tell
+    /// whether another thread set it in between.
+    state: Mutex<(u64, Value)>,
+    pub validator: RwLock<Option<Value>>,
+    pub watches: RwLock<Vec<(Value, Value)>>,
+}
+
+impl Atom {
+    pub fn new(value: Value) -> Atom {
+        Atom {
+            state: Mutex::new((0, value)),
+            validator: RwLock::new(None),
+            watches: RwLock::new(vec![]),
+        }
+    }
+
+    pub fn deref(&self) -> Value {
+        self.state.lock().unwrap().1.clone()
+    }
+
+    /// The current value with its version, to pass to `compare_and_set`.
+    pub fn load(&self) -> (u64, Value) {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Sets the value if it wasn't set since `version` was loaded.
+    pub fn compare_and_set(&self, version: u64, value: Value) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.0 != version {
+            return false;
+        }
+        *state = (version + 1, value);
+        true
+    }
+
+    /// Sets the value, returning the old one.
+    pub fn set(&self, value: Value) -> Value {
+        let mut state = self.state.lock().unwrap();
+        state.0 += 1;
+        std::mem::replace(&mut state.1, value)
+    }
+}
+
+impl fmt::Debug for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#<atom {:?}>", self.deref())
+    }
+}
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Atom) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Atom {
+    fn partial_cmp(&self, other: &Atom) -> Option<Ordering> {
+        if self == other {
+            Some(Ordering::Equal)
+        } else {
+            None
+        }
+    }
+}
+
+/// A compiled regular expression. Regexes with the same pattern are equal.
+#[derive(Clone, Debug)]
+pub struct Regex(regex::Regex);
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        regex::Regex::new(pattern)
+            .map(Regex)
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl std::ops::Deref for Regex {
+    type Target = regex::Regex;
+
+    fn deref(&self) -> &regex::Regex {
+        &self.0
+    }
+}
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Regex) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialOrd for Regex {
+    fn partial_cmp(&self, other: &Regex) -> Option<Ordering> {
+        self.as_str().partial_cmp(other.as_str())
+    }
+}
+
+pub type NativeFn = fn(&Env, Vec<Value>) -> Result<Value, EvalError>;
+
+/// A callable value. Functions compare equal only to themselves.
+#[derive(Clone)]
+pub enum Function {
+    Native(&'static str, NativeFn),
+    Lambda(Arc<Lambda>),
+    /// The constructor of a struct type.
+    Struct(Arc<StructType>),
+    /// The accessor of the `i`th field of a struct type.
+    Field(Arc<StructType>, usize),
+    /// The constructor of the `i`th variant of an enum type.
+    Variant(Arc<EnumType>, usize),
+}
+
+impl Function {
+    pub fn name(&self) -> &str {
+        match self {
+            Function::Native(name, _) => name,
+            Function::Lambda(lambda) => lambda.name(),
+            Function::Struct(ty) => &ty.name,
+            Function::Field(ty, i) => &ty.fields[*i].accessor,
+            Function::Variant(ty, i) => &ty.variants[*i].name,
+        }
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#<fn {}>", self.name())
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        match (self, other) {
+            (Function::Native(a, _), Function::Native(b, _)) => a == b,
+            (Function::Lambda(a), Function::Lambda(b)) => Arc::ptr_eq(a, b),
+            (Function::Struct(a), Function::Struct(b)) => Arc::ptr_eq(a, b),
+            (Function::Field(a, i), Function::Field(b, j)) => Arc::ptr_eq(a, b) && i == j,
+            (Function::Variant(a, i), Function::Variant(b, j)) => Arc::ptr_eq(a, b) && i == j,
+            _ => false,
+        }
+    }
+}
+
+impl PartialOrd for Function {
+    fn partial_cmp(&self, other: &Function) -> Option<Ordering> {
+        if self == other {
+            Some(Ordering::Equal)
+        } else {
+            None
+        }
+    }
 }
 
 fn dump_list(list: &Vec<Value>, ident: &str, left: char, right: char) {
@@ -48,3 +327,14 @@ fn dump_list(list: &Vec<Value>, ident: &str, left: char, right: char) {
     }
     println!("{ident}{right}");
 }
+
+fn write_list(f: &mut fmt::Formatter, list: &[Value], left: char, right: char) -> fmt::Result {
+    write!(f, "{left}")?;
+    for (i, item) in list.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{item}")?;
+    }
+    write!(f, "{right}")
+}